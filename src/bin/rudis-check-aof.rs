@@ -0,0 +1,160 @@
+// rudis-check-aof - validate (and optionally repair) an AOF file or manifest
+
+use rudis::persistence::{
+    aof::{self, AofManifest, AofReport},
+    rdb,
+};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+fn usage() -> ! {
+    eprintln!("Usage: rudis-check-aof [--fix] <file.aof|file.manifest>");
+    process::exit(1);
+}
+
+fn print_report(path: &Path, report: &AofReport) {
+    if let Some(len) = report.rdb_preamble {
+        println!(
+            "RDB preamble is OK, proceeding with AOF tail ({} bytes)",
+            len
+        );
+    }
+    if let Some(error) = &report.error {
+        println!("0x{:>8x}: {}", report.ok_up_to, error);
+    }
+    println!(
+        "AOF analyzed: filename={}, size={}, ok_up_to={}, ok_up_to_line={}, diff={}",
+        path.display(),
+        report.size,
+        report.ok_up_to,
+        report.ok_up_to_line,
+        report.diff()
+    );
+}
+
+fn confirm(question: &str) -> bool {
+    print!("{} [y/N]: ", question);
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim(), "y" | "Y")
+}
+
+/// Check a single AOF file, truncating its corrupt tail when `fix` is set.
+/// Returns whether the file is valid afterwards.
+fn check_file(path: &Path, fix: bool) -> io::Result<bool> {
+    let data = fs::read(path)?;
+    let report = aof::check(&data);
+    print_report(path, &report);
+    if report.is_ok() {
+        println!("AOF {} is valid", path.display());
+        return Ok(true);
+    }
+    if !fix {
+        println!(
+            "AOF {} is not valid. Use the --fix option to try fixing it.",
+            path.display()
+        );
+        return Ok(false);
+    }
+    println!(
+        "This will shrink the AOF {} from {} bytes, with {} bytes, to {} bytes",
+        path.display(),
+        report.size,
+        report.diff(),
+        report.ok_up_to
+    );
+    if !confirm("Continue?") {
+        println!("Aborting...");
+        return Ok(false);
+    }
+    OpenOptions::new()
+        .write(true)
+        .open(path)?
+        .set_len(report.ok_up_to as u64)?;
+    println!("Successfully truncated AOF {}", path.display());
+    Ok(true)
+}
+
+fn check_manifest(path: &Path, data: &[u8], fix: bool) -> io::Result<bool> {
+    let manifest = match AofManifest::parse(&String::from_utf8_lossy(data)) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            println!("{}", e);
+            return Ok(false);
+        }
+    };
+    println!("Start checking Multi Part AOF");
+    let dir = path.parent().map(PathBuf::from).unwrap_or_default();
+
+    if let Some(base) = manifest.base() {
+        let base_path = dir.join(&base.name);
+        let base_data = fs::read(&base_path)?;
+        if base_data.starts_with(b"REDIS") {
+            println!("Start to check BASE AOF (RDB format).");
+            if let Err(e) = rdb::parse(&base_data, |_| Ok(())) {
+                println!("RDB {} is not valid: {}", base_path.display(), e);
+                return Ok(false);
+            }
+            println!("RDB {} is valid", base_path.display());
+        } else {
+            println!("Start to check BASE AOF (AOF format).");
+            // Only the last file of the chain may be truncated
+            if !check_file(&base_path, false)? {
+                return Ok(false);
+            }
+        }
+    }
+
+    let incrs = manifest.incrs();
+    for (i, incr) in incrs.iter().enumerate() {
+        println!("Start to check INCR files.");
+        let last = i + 1 == incrs.len();
+        if !check_file(&dir.join(&incr.name), fix && last)? {
+            if !last {
+                println!(
+                    "Only the last INCR AOF can be truncated; fix the manifest chain by hand."
+                );
+            }
+            return Ok(false);
+        }
+    }
+    println!("All AOF files and manifest are valid");
+    Ok(true)
+}
+
+fn main() {
+    let mut fix = false;
+    let mut file = None;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--fix" => fix = true,
+            _ if arg.starts_with("--") => usage(),
+            _ if file.is_none() => file = Some(PathBuf::from(arg)),
+            _ => usage(),
+        }
+    }
+    let Some(path) = file else { usage() };
+
+    let result = fs::read(&path).and_then(|data| {
+        if AofManifest::is_manifest(&data) {
+            check_manifest(&path, &data, fix)
+        } else {
+            check_file(&path, fix)
+        }
+    });
+    match result {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("Cannot check {}: {}", path.display(), e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,192 @@
+// rudis-check-rdb - verify an RDB snapshot and summarise its contents
+
+use rudis::database::RedisValue;
+use rudis::persistence::rdb::{self, RdbWriter};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::PathBuf,
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const DEFAULT_LARGEST: usize = 10;
+
+fn usage() -> ! {
+    eprintln!("Usage: rudis-check-rdb <file.rdb> [--largest <count>]");
+    process::exit(1);
+}
+
+/// Printable form of a binary-safe key
+fn repr(key: &[u8]) -> String {
+    let mut out = String::with_capacity(key.len() + 2);
+    out.push('"');
+    for &b in key {
+        match b {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            0x20..=0x7e => out.push(b as char),
+            _ => out.push_str(&format!("\\x{:02x}", b)),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn element_count(value: &RedisValue) -> usize {
+    match value {
+        RedisValue::String(s) => s.get().len(),
+        RedisValue::List(l) => l.len(),
+        RedisValue::Set(s) => s.scard(),
+        RedisValue::SortedSet(z) => z.zcard(),
+        RedisValue::Hash(h) => h.len(),
+        RedisValue::Module(_) => 1,
+        // Never produced by the RDB reader
+        RedisValue::Spilled(_) => 0,
+    }
+}
+
+struct KeyInfo {
+    db: u64,
+    key: String,
+    type_name: &'static str,
+    size: usize,
+    elements: usize,
+}
+
+#[derive(Default)]
+struct DbStats {
+    by_type: BTreeMap<&'static str, usize>,
+    keys: usize,
+    with_expiry: usize,
+    expired: usize,
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut file = None;
+    let mut largest = DEFAULT_LARGEST;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--largest" => {
+                largest = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or_else(|| usage())
+            }
+            _ if arg.starts_with("--") => usage(),
+            _ if file.is_none() => file = Some(PathBuf::from(arg)),
+            _ => usage(),
+        }
+    }
+    let Some(path) = file else { usage() };
+
+    println!("[offset 0] Checking RDB file {}", path.display());
+    let data = match fs::read(&path) {
+        Ok(data) => data,
+        Err(e) => {
+            println!("Cannot open {}: {}", path.display(), e);
+            process::exit(1);
+        }
+    };
+
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    let mut dbs: BTreeMap<u64, DbStats> = BTreeMap::new();
+    let mut keys: Vec<KeyInfo> = Vec::new();
+
+    let result = rdb::parse(&data, |entry| {
+        let stats = dbs.entry(entry.db).or_default();
+        let type_name = entry.value.type_name();
+        *stats.by_type.entry(type_name).or_default() += 1;
+        stats.keys += 1;
+        if let Some(at) = entry.expire_at_ms {
+            stats.with_expiry += 1;
+            if at <= now_ms {
+                stats.expired += 1;
+            }
+        }
+
+        let mut writer = RdbWriter::new();
+        writer.write_object(&entry.value);
+        keys.push(KeyInfo {
+            db: entry.db,
+            key: repr(&entry.key),
+            type_name,
+            size: writer.into_inner().len(),
+            elements: element_count(&entry.value),
+        });
+        Ok(())
+    });
+
+    let summary = match result {
+        Ok(summary) => summary,
+        Err(e) => {
+            println!("--- RDB ERROR DETECTED ---");
+            println!("{}", e);
+            println!(
+                "[additional info] {} keys read before the error",
+                keys.len()
+            );
+            process::exit(1);
+        }
+    };
+
+    println!("[offset 9] RDB version {}", summary.version);
+    for (key, value) in &summary.aux {
+        println!(
+            "[info] {} = '{}'",
+            String::from_utf8_lossy(key),
+            String::from_utf8_lossy(value)
+        );
+    }
+    match summary.checksum {
+        Some(crc) => println!("[offset {}] Checksum OK ({:016x})", summary.length, crc),
+        None => println!("[offset {}] Checksum disabled", summary.length),
+    }
+
+    println!();
+    println!("Keyspace:");
+    let mut total = DbStats::default();
+    for (db, stats) in &dbs {
+        let types = stats
+            .by_type
+            .iter()
+            .map(|(t, n)| format!("{}={}", t, n))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!(
+            "  db{}: keys={} ({}), expires={}, already_expired={}",
+            db, stats.keys, types, stats.with_expiry, stats.expired
+        );
+        total.keys += stats.keys;
+        total.with_expiry += stats.with_expiry;
+        total.expired += stats.expired;
+        for (t, n) in &stats.by_type {
+            *total.by_type.entry(t).or_default() += n;
+        }
+    }
+    println!("Keys by type:");
+    for (t, n) in &total.by_type {
+        println!("  {}: {}", t, n);
+    }
+    println!(
+        "Expiry: {} of {} keys have a TTL, {} already expired",
+        total.with_expiry, total.keys, total.expired
+    );
+
+    keys.sort_by_key(|info| std::cmp::Reverse(info.size));
+    if largest > 0 && !keys.is_empty() {
+        println!("Largest keys (serialized size):");
+        for info in keys.iter().take(largest) {
+            println!(
+                "  db{} {} {} bytes={} elements={}",
+                info.db, info.key, info.type_name, info.size, info.elements
+            );
+        }
+    }
+
+    println!("\\o/ RDB looks OK! \\o/");
+}
@@ -0,0 +1,80 @@
+// rudis-jsonl - convert between RDB snapshots and the JSON lines keyspace format
+
+use rudis::database::Database;
+use rudis::error::{AppError, Result};
+use rudis::persistence::jsonl::{self, JsonRecord};
+use rudis::persistence::rdb;
+use std::{
+    fs,
+    io::{self, BufWriter, Write},
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const DEFAULT_DATABASES: usize = 16;
+
+fn usage() -> ! {
+    eprintln!("Usage: rudis-jsonl export <dump.rdb> [out.jsonl]");
+    eprintln!("       rudis-jsonl import <in.jsonl> <dump.rdb>");
+    process::exit(1);
+}
+
+/// Stream every live key of an RDB file as JSON lines
+fn export(rdb_path: &str, out: &mut impl Write) -> Result<usize> {
+    let data = fs::read(rdb_path)?;
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    let mut written = 0;
+    rdb::parse(&data, |entry| {
+        let db = u8::try_from(entry.db)
+            .map_err(|_| AppError::Serialization(format!("DB index {} too large", entry.db)))?;
+        let pttl = match entry.expire_at_ms {
+            Some(at) if at <= now_ms => return Ok(()),
+            Some(at) => Some(at - now_ms),
+            None => None,
+        };
+        let record = JsonRecord::new(db, &entry.key, &entry.value, pttl);
+        serde_json::to_writer(&mut *out, &record)?;
+        out.write_all(b"\n")?;
+        written += 1;
+        Ok(())
+    })?;
+    out.flush()?;
+    Ok(written)
+}
+
+/// Build an RDB file from JSON lines
+fn import(jsonl_path: &str, rdb_path: &str) -> Result<usize> {
+    let records = jsonl::parse(&fs::read_to_string(jsonl_path)?)?;
+    let databases = records
+        .iter()
+        .map(|r| r.db as usize + 1)
+        .max()
+        .unwrap_or(0)
+        .max(DEFAULT_DATABASES);
+    let db = Database::new(databases);
+    let count = jsonl::import(&db, records, false)?;
+    fs::write(rdb_path, rdb::dump(&db)?)?;
+    Ok(count)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["export", rdb_path] => export(rdb_path, &mut BufWriter::new(io::stdout().lock())),
+        ["export", rdb_path, out] => fs::File::create(out)
+            .map_err(AppError::from)
+            .and_then(|f| export(rdb_path, &mut BufWriter::new(f))),
+        ["import", jsonl_path, rdb_path] => import(jsonl_path, rdb_path),
+        _ => usage(),
+    };
+    match result {
+        Ok(count) => eprintln!("{} keys converted", count),
+        Err(e) => {
+            eprintln!("rudis-jsonl: {}", e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,235 @@
+// A multiplexed client connection
+// Requests from every clone of a `Connection` are queued to one writer task,
+// which sends whatever has queued up in a single write, so concurrent callers
+// are pipelined without asking for it. A reader task matches replies to
+// requests in order.
+
+use super::{Addr, Error, Options, Pipeline, Result};
+use crate::commands::reply::FromReply;
+use crate::commands::{CommandError, Reply};
+use crate::networking::resp::{self, Protocol};
+use bytes::{Buf, Bytes, BytesMut};
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot};
+
+struct Request {
+    /// One or more encoded commands
+    data: Bytes,
+    replies: usize,
+    tx: oneshot::Sender<Result<Vec<Reply>>>,
+}
+
+struct Pending {
+    replies: Vec<Reply>,
+    expected: usize,
+    tx: oneshot::Sender<Result<Vec<Reply>>>,
+}
+
+/// Requests written and waiting for replies; `None` once the connection is
+/// gone, so nothing new waits forever
+type Queue = Arc<Mutex<Option<VecDeque<Pending>>>>;
+
+#[derive(Clone)]
+pub struct Connection {
+    requests: mpsc::UnboundedSender<Request>,
+    protocol: Protocol,
+}
+
+impl Connection {
+    /// Connect to `options.addr`, then authenticate, negotiate the protocol
+    /// and select the database
+    pub async fn connect(options: &Options) -> Result<Connection> {
+        let connection = match &options.addr {
+            Addr::Tcp(addr) => {
+                let stream = TcpStream::connect(addr).await?;
+                stream.set_nodelay(true)?;
+                Connection::spawn(stream, options.protocol)
+            }
+            #[cfg(unix)]
+            Addr::Unix(path) => {
+                let stream = tokio::net::UnixStream::connect(path).await?;
+                Connection::spawn(stream, options.protocol)
+            }
+        };
+        connection.handshake(options).await?;
+        Ok(connection)
+    }
+
+    /// Like `connect`, retrying with `options.backoff` while the server is
+    /// unreachable
+    pub async fn connect_with_backoff(options: &Options) -> Result<Connection> {
+        options.backoff.retry(|| Connection::connect(options)).await
+    }
+
+    /// Use a stream the caller opened, such as a TLS session over TCP.
+    /// `options.addr` is ignored.
+    pub async fn from_stream<S>(stream: S, options: &Options) -> Result<Connection>
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let connection = Connection::spawn(stream, options.protocol);
+        connection.handshake(options).await?;
+        Ok(connection)
+    }
+
+    fn spawn<S>(stream: S, protocol: Protocol) -> Connection
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (reader, writer) = tokio::io::split(stream);
+        let (requests, rx) = mpsc::unbounded_channel();
+        let queue: Queue = Arc::new(Mutex::new(Some(VecDeque::new())));
+        let writer = tokio::spawn(write_loop(writer, rx, Arc::clone(&queue)));
+        tokio::spawn(async move {
+            read_loop(reader, &queue).await;
+            // Fail whatever is still waiting, and make `is_closed` true
+            writer.abort();
+            if let Some(pending) = queue.lock().take() {
+                for request in pending {
+                    let _ = request.tx.send(Err(Error::Closed));
+                }
+            }
+        });
+        Connection { requests, protocol }
+    }
+
+    async fn handshake(&self, options: &Options) -> Result<()> {
+        if let Some(password) = &options.password {
+            self.query::<(), _>(&["AUTH", password.as_str()]).await?;
+        }
+        if options.protocol == Protocol::Resp3 {
+            self.query::<Reply, _>(&["HELLO", "3"]).await?;
+        }
+        if options.db != 0 {
+            let db = options.db.to_string();
+            self.query::<(), _>(&["SELECT", db.as_str()]).await?;
+        }
+        Ok(())
+    }
+
+    /// The protocol replies arrive in
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// Whether the connection has been lost; a `Pool` replaces such
+    /// connections
+    pub fn is_closed(&self) -> bool {
+        self.requests.is_closed()
+    }
+
+    /// Send raw encoded commands and wait for their replies
+    async fn send(&self, data: Bytes, replies: usize) -> Result<Vec<Reply>> {
+        let (tx, rx) = oneshot::channel();
+        let request = Request { data, replies, tx };
+        self.requests.send(request).map_err(|_| Error::Closed)?;
+        rx.await.map_err(|_| Error::Closed)?
+    }
+
+    /// Run a command, given as its name followed by its arguments. Error
+    /// replies are returned as `Reply::Error`.
+    pub async fn call<A: AsRef<[u8]>>(&self, args: &[A]) -> Result<Reply> {
+        let mut replies = self.send(encode_command(args), 1).await?;
+        replies.pop().ok_or(Error::Closed)
+    }
+
+    /// Run a command and convert its reply, e.g. `query::<Option<Bytes>, _>`
+    /// for GET. Error replies become `Error::Server`.
+    pub async fn query<T: FromReply, A: AsRef<[u8]>>(&self, args: &[A]) -> Result<T> {
+        match self.call(args).await? {
+            Reply::Error(message) => Err(CommandError::from_reply(message).into()),
+            reply => Ok(T::from_reply(reply)?),
+        }
+    }
+
+    /// Send every command of `pipeline` in one write; returns one reply per
+    /// command, errors included
+    pub async fn pipeline(&self, pipeline: &Pipeline) -> Result<Vec<Reply>> {
+        if pipeline.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.send(pipeline.data(), pipeline.len()).await
+    }
+}
+
+/// A command as a RESP array of bulk strings
+pub(super) fn encode_command<A: AsRef<[u8]>>(args: &[A]) -> Bytes {
+    let args = args
+        .iter()
+        .map(|arg| Bytes::copy_from_slice(arg.as_ref()))
+        .collect();
+    resp::encode(&Reply::bulk_array(args), Protocol::Resp2)
+}
+
+async fn write_loop<W: AsyncWrite + Unpin>(
+    mut writer: W,
+    mut requests: mpsc::UnboundedReceiver<Request>,
+    queue: Queue,
+) {
+    let mut buf = BytesMut::new();
+    while let Some(first) = requests.recv().await {
+        // Everything queued up meanwhile goes out in the same write
+        let mut next = Some(first);
+        while let Some(request) = next {
+            match queue.lock().as_mut() {
+                Some(pending) => {
+                    buf.extend_from_slice(&request.data);
+                    pending.push_back(Pending {
+                        replies: Vec::with_capacity(request.replies),
+                        expected: request.replies,
+                        tx: request.tx,
+                    });
+                }
+                None => return,
+            }
+            next = requests.try_recv().ok();
+        }
+        if writer.write_all(&buf).await.is_err() || writer.flush().await.is_err() {
+            break;
+        }
+        buf.clear();
+    }
+    // Every handle is gone: let the server close its side, ending the reader
+    let _ = writer.shutdown().await;
+}
+
+async fn read_loop<R: AsyncRead + Unpin>(mut reader: R, queue: &Queue) {
+    let mut buf = BytesMut::with_capacity(4096);
+    loop {
+        loop {
+            match resp::decode(&buf) {
+                Ok(Some((reply, used))) => {
+                    buf.advance(used);
+                    deliver(reply, queue);
+                }
+                Ok(None) => break,
+                Err(_) => return,
+            }
+        }
+        match reader.read_buf(&mut buf).await {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+    }
+}
+
+fn deliver(reply: Reply, queue: &Queue) {
+    // Out-of-band pushes, e.g. invalidations, answer no request
+    if matches!(reply, Reply::Push(_)) {
+        return;
+    }
+    let mut queue = queue.lock();
+    let Some(front) = queue.as_mut().and_then(|pending| pending.front_mut()) else {
+        return;
+    };
+    front.replies.push(reply);
+    if front.replies.len() == front.expected {
+        if let Some(done) = queue.as_mut().and_then(|pending| pending.pop_front()) {
+            let _ = done.tx.send(Ok(done.replies));
+        }
+    }
+}
@@ -0,0 +1,128 @@
+// Network client for Rudis and other Redis servers
+// Speaks RESP2 or RESP3 over TCP, Unix sockets or any stream the caller
+// opens (such as a TLS session). A `Connection` is shared by cloning and
+// pipelines concurrent requests automatically; a `Pool` spreads requests over
+// several connections and reconnects with backoff.
+
+pub mod connection;
+pub mod pipeline;
+pub mod pool;
+
+pub use connection::Connection;
+pub use pipeline::Pipeline;
+pub use pool::Pool;
+
+use crate::commands::CommandError;
+pub use crate::networking::resp::Protocol;
+use std::future::Future;
+use std::io;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::time::Duration;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+
+    /// An error reply; well-known ones map to their `CommandError` variant
+    #[error("{0}")]
+    Server(#[from] CommandError),
+
+    #[error("Connection closed")]
+    Closed,
+}
+
+/// Where to connect
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Addr {
+    /// `host:port`
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+/// Delays between reconnection attempts: `initial`, doubling up to `max`,
+/// for at most `retries` retries after the first attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    pub retries: u32,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            initial: Duration::from_millis(50),
+            max: Duration::from_secs(2),
+            retries: 6,
+        }
+    }
+}
+
+impl Backoff {
+    fn delay(&self, attempt: u32) -> Duration {
+        self.initial
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max)
+    }
+
+    /// Run `f` until it succeeds, waiting between attempts. Only I/O errors
+    /// are retried; an error reply means the server is up and said no.
+    pub(crate) async fn retry<T, F, Fut>(&self, mut f: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match f().await {
+                Err(Error::Io(_) | Error::Closed) if attempt < self.retries => {
+                    tokio::time::sleep(self.delay(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub addr: Addr,
+    /// RESP3 is negotiated with HELLO when the connection opens
+    pub protocol: Protocol,
+    /// Database to SELECT on every new connection
+    pub db: u8,
+    /// Sent with AUTH on every new connection
+    pub password: Option<String>,
+    /// Connections a `Pool` keeps open
+    pub pool_size: usize,
+    pub backoff: Backoff,
+}
+
+impl Options {
+    pub fn tcp(addr: impl Into<String>) -> Self {
+        Options::new(Addr::Tcp(addr.into()))
+    }
+
+    #[cfg(unix)]
+    pub fn unix(path: impl Into<PathBuf>) -> Self {
+        Options::new(Addr::Unix(path.into()))
+    }
+
+    fn new(addr: Addr) -> Self {
+        Options {
+            addr,
+            protocol: Protocol::Resp2,
+            db: 0,
+            password: None,
+            pool_size: 4,
+            backoff: Backoff::default(),
+        }
+    }
+}
@@ -0,0 +1,36 @@
+// Batches of commands sent in one write
+
+use super::connection::encode_command;
+use bytes::{Bytes, BytesMut};
+
+/// Commands to send together with `Connection::pipeline`
+#[derive(Debug, Clone, Default)]
+pub struct Pipeline {
+    data: BytesMut,
+    len: usize,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Pipeline::default()
+    }
+
+    /// Add a command, given as its name followed by its arguments
+    pub fn cmd<A: AsRef<[u8]>>(&mut self, args: &[A]) -> &mut Self {
+        self.data.extend_from_slice(&encode_command(args));
+        self.len += 1;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub(super) fn data(&self) -> Bytes {
+        Bytes::copy_from_slice(&self.data)
+    }
+}
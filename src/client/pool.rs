@@ -0,0 +1,63 @@
+// A pool of client connections
+
+use super::{Connection, Options, Pipeline, Result};
+use crate::commands::reply::FromReply;
+use crate::commands::Reply;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::Mutex;
+
+/// `options.pool_size` connections, opened on first use and handed out in
+/// turn. Each connection is shared and pipelines its callers' requests. A
+/// connection found closed is replaced, retrying with `options.backoff`.
+///
+/// Connections handed out are shared, so per-connection state such as
+/// WATCH needs a dedicated `Connection::connect` instead.
+pub struct Pool {
+    options: Options,
+    slots: Vec<Mutex<Option<Connection>>>,
+    next: AtomicUsize,
+}
+
+impl Pool {
+    pub fn new(options: Options) -> Self {
+        let slots = (0..options.pool_size.max(1))
+            .map(|_| Mutex::new(None))
+            .collect();
+        Pool {
+            options,
+            slots,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// The next connection in turn, reconnected if it was lost
+    pub async fn get(&self) -> Result<Connection> {
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.slots.len();
+        let mut slot = self.slots[index].lock().await;
+        if let Some(connection) = slot.as_ref().filter(|c| !c.is_closed()) {
+            return Ok(connection.clone());
+        }
+        let connection = Connection::connect_with_backoff(&self.options).await?;
+        *slot = Some(connection.clone());
+        Ok(connection)
+    }
+
+    /// See `Connection::call`
+    pub async fn call<A: AsRef<[u8]>>(&self, args: &[A]) -> Result<Reply> {
+        self.get().await?.call(args).await
+    }
+
+    /// See `Connection::query`
+    pub async fn query<T: FromReply, A: AsRef<[u8]>>(&self, args: &[A]) -> Result<T> {
+        self.get().await?.query(args).await
+    }
+
+    /// See `Connection::pipeline`
+    pub async fn pipeline(&self, pipeline: &Pipeline) -> Result<Vec<Reply>> {
+        self.get().await?.pipeline(pipeline).await
+    }
+}
@@ -0,0 +1,197 @@
+use crate::{
+    commands::{strings::parse_integer, CommandError, Reply, Result},
+    data_structures::bitmap::{
+        BitOperation, BitUnit, BitfieldOp, BitfieldType, Overflow, MAX_BIT_OFFSET,
+    },
+    database::traits::BitmapOp,
+    database::SharedDatabase,
+};
+use bytes::Bytes;
+
+fn check_offset(offset: Option<i64>) -> Result<u64> {
+    offset
+        .and_then(|offset| u64::try_from(offset).ok())
+        .filter(|offset| *offset <= MAX_BIT_OFFSET)
+        .ok_or(CommandError::InvalidBitOffset)
+}
+
+fn parse_offset(offset: &Bytes) -> Result<u64> {
+    check_offset(parse_integer(offset).ok())
+}
+
+pub fn setbit(db: &SharedDatabase, key: Bytes, offset: Bytes, value: Bytes) -> Reply {
+    let offset = match parse_offset(&offset) {
+        Ok(offset) => offset,
+        Err(e) => return e.into(),
+    };
+    let bit = match value.as_ref() {
+        b"0" => 0,
+        b"1" => 1,
+        _ => return CommandError::InvalidBit.into(),
+    };
+    db.setbit(&key, offset, bit)
+        .map(|old| Reply::Integer(old as i64))
+        .into()
+}
+
+pub fn getbit(db: &SharedDatabase, key: Bytes, offset: Bytes) -> Reply {
+    parse_offset(&offset)
+        .and_then(|offset| db.getbit(&key, offset))
+        .map(|bit| Reply::Integer(bit as i64))
+        .into()
+}
+
+pub fn bitcount(
+    db: &SharedDatabase,
+    key: Bytes,
+    range: Option<(Bytes, Bytes)>,
+    unit: BitUnit,
+) -> Reply {
+    let range = match range {
+        Some((start, end)) => match (parse_integer(&start), parse_integer(&end)) {
+            (Ok(start), Ok(end)) => Some((start, end)),
+            _ => return CommandError::InvalidInteger.into(),
+        },
+        None => None,
+    };
+    db.bitcount(&key, range, unit)
+        .map(|count| Reply::Integer(count as i64))
+        .into()
+}
+
+pub fn bitpos(
+    db: &SharedDatabase,
+    key: Bytes,
+    bit: Bytes,
+    start: Option<Bytes>,
+    end: Option<Bytes>,
+    unit: BitUnit,
+) -> Reply {
+    bitpos_reply(db, &key, &bit, start, end, unit).into()
+}
+
+fn bitpos_reply(
+    db: &SharedDatabase,
+    key: &Bytes,
+    bit: &Bytes,
+    start: Option<Bytes>,
+    end: Option<Bytes>,
+    unit: BitUnit,
+) -> Result<i64> {
+    let bit = match parse_integer(bit)? {
+        bit @ (0 | 1) => bit as u8,
+        _ => return Err(CommandError::InvalidBitArgument),
+    };
+    let start = start.as_ref().map(parse_integer).transpose()?;
+    let end = end.as_ref().map(parse_integer).transpose()?;
+    db.bitpos(key, bit, start, end, unit)
+}
+
+pub fn bitop(db: &SharedDatabase, op: BitOperation, dest: Bytes, keys: Vec<Bytes>) -> Reply {
+    match op {
+        BitOperation::Not if keys.len() != 1 => {
+            CommandError::Custom("BITOP NOT must be called with a single source key.".to_string())
+                .into()
+        }
+        BitOperation::Diff if keys.len() < 2 => CommandError::Custom(
+            "BITOP DIFF must be called with at least two source keys.".to_string(),
+        )
+        .into(),
+        _ => db.bitop(op, &dest, &keys).into(),
+    }
+}
+
+pub fn bitfield(db: &SharedDatabase, key: Bytes, args: Vec<Bytes>) -> Reply {
+    bitfield_reply(db, &key, &args, false).into()
+}
+
+pub fn bitfield_ro(db: &SharedDatabase, key: Bytes, args: Vec<Bytes>) -> Reply {
+    bitfield_reply(db, &key, &args, true).into()
+}
+
+fn bitfield_reply(
+    db: &SharedDatabase,
+    key: &Bytes,
+    args: &[Bytes],
+    read_only: bool,
+) -> Result<Reply> {
+    let ops = parse_bitfield_ops(args, read_only)?;
+    let values = db.bitfield(key, &ops)?;
+    Ok(Reply::Array(values.into_iter().map(Reply::from).collect()))
+}
+
+/// A BITFIELD type and offset; `#N` offsets count in fields of that type
+fn parse_field(ty: &Bytes, offset: &Bytes) -> Result<(BitfieldType, u64)> {
+    let ty = BitfieldType::parse(ty).ok_or(CommandError::InvalidBitfieldType)?;
+    let offset = match offset.first() {
+        Some(b'#') => parse_integer(&offset.slice(1..))
+            .ok()
+            .and_then(|index| index.checked_mul(ty.bits as i64)),
+        _ => parse_integer(offset).ok(),
+    };
+    let offset = check_offset(offset)?;
+    // The field's last bit has to fit in the string limit too
+    if offset + u64::from(ty.bits) - 1 > MAX_BIT_OFFSET {
+        return Err(CommandError::InvalidBitOffset);
+    }
+    Ok((ty, offset))
+}
+
+/// The operations of a BITFIELD call. OVERFLOW applies to the SET and INCRBY
+/// operations after it.
+fn parse_bitfield_ops(args: &[Bytes], read_only: bool) -> Result<Vec<BitfieldOp>> {
+    let mut ops = Vec::new();
+    let mut overflow = Overflow::default();
+    let mut i = 0;
+    while i < args.len() {
+        let subcommand = String::from_utf8_lossy(&args[i]).to_uppercase();
+        let remaining = &args[i + 1..];
+        match (subcommand.as_str(), remaining) {
+            ("GET", [ty, offset, ..]) => {
+                let (ty, offset) = parse_field(ty, offset)?;
+                ops.push(BitfieldOp::Get { ty, offset });
+                i += 3;
+            }
+            ("SET" | "INCRBY", [ty, offset, value, ..]) => {
+                if read_only {
+                    return Err(CommandError::Custom(
+                        "BITFIELD_RO only supports the GET subcommand".to_string(),
+                    ));
+                }
+                let (ty, offset) = parse_field(ty, offset)?;
+                let value = parse_integer(value)?;
+                ops.push(if subcommand == "SET" {
+                    BitfieldOp::Set {
+                        ty,
+                        offset,
+                        value,
+                        overflow,
+                    }
+                } else {
+                    BitfieldOp::IncrBy {
+                        ty,
+                        offset,
+                        increment: value,
+                        overflow,
+                    }
+                });
+                i += 4;
+            }
+            ("OVERFLOW", [kind, ..]) => {
+                overflow = match String::from_utf8_lossy(kind).to_uppercase().as_str() {
+                    "WRAP" => Overflow::Wrap,
+                    "SAT" => Overflow::Sat,
+                    "FAIL" => Overflow::Fail,
+                    _ => {
+                        return Err(CommandError::Custom(
+                            "Invalid OVERFLOW type specified".to_string(),
+                        ))
+                    }
+                };
+                i += 2;
+            }
+            _ => return Err(CommandError::SyntaxError),
+        }
+    }
+    Ok(ops)
+}
@@ -1,5 +1,14 @@
-use crate::{commands::SetOptions, networking::resp::RespValue};
-use bytes::{BufMut, Bytes, BytesMut};
+use crate::{
+    commands::{
+        Command, GetExOption, LcsOptions, MigrateOptions, RestoreOptions, ScanOptions, SetOptions,
+    },
+    data_structures::{
+        bitmap::{BitOperation, BitUnit},
+        ListEnd,
+    },
+    networking::resp::RespValue,
+};
+use bytes::Bytes;
 
 // Helper function to extract BulkString value
 pub fn extract_bulk_string(resp_value: &RespValue) -> Option<Bytes> {
@@ -81,7 +90,8 @@ pub fn parse_keys_values_command(
     elements: &[RespValue],
     min_required_len: usize,
 ) -> Option<Vec<(Bytes, Bytes)>> {
-    if elements.len() >= min_required_len && elements.len() % 2 == 1 {
+    // The name plus whole pairs: an odd count
+    if elements.len() >= min_required_len && !elements.len().is_multiple_of(2) {
         extract_key_value_strings(&elements[1..])
     } else {
         None
@@ -91,7 +101,7 @@ pub fn parse_key_pair_values_command(
     elements: &[RespValue],
     min_required_len: usize,
 ) -> Option<(Bytes, Vec<(Bytes, Bytes)>)> {
-    if elements.len() >= min_required_len && elements.len() % 2 == 1 {
+    if elements.len() >= min_required_len && elements.len().is_multiple_of(2) {
         let key = extract_bulk_string(&elements[1])?;
         let pairs = extract_key_value_strings(&elements[2..])?;
         Some((key, pairs))
@@ -123,27 +133,26 @@ pub fn parse_key_value_options_command(
                 match opt.as_str() {
                     "NX" => opts.nx = true,
                     "XX" => opts.xx = true,
-                    "EX" => {
-                        if i + 1 < elements.len() {
-                            let val_bytes = extract_bulk_string(&elements[i + 1])?;
-                            let val_str = String::from_utf8_lossy(&val_bytes);
-                            opts.ex = val_str.parse().ok();
-                            i += 1;
-                        }
+                    "EX" if i + 1 < elements.len() => {
+                        let val_bytes = extract_bulk_string(&elements[i + 1])?;
+                        let val_str = String::from_utf8_lossy(&val_bytes);
+                        opts.ex = val_str.parse().ok();
+                        i += 1;
                     }
-                    "PX" => {
-                        if i + 1 < elements.len() {
-                            let val_bytes = extract_bulk_string(&elements[i + 1])?;
-                            let val_str = String::from_utf8_lossy(&val_bytes);
-                            opts.px = val_str.parse().ok();
-                            i += 1;
-                        }
+                    "PX" if i + 1 < elements.len() => {
+                        let val_bytes = extract_bulk_string(&elements[i + 1])?;
+                        let val_str = String::from_utf8_lossy(&val_bytes);
+                        opts.px = val_str.parse().ok();
+                        i += 1;
                     }
                     "KEEPTTL" => opts.keepttl = true,
-                    _ => {}
+                    _ => return None,
                 }
                 i += 1;
             }
+            if opts.nx && opts.xx {
+                return None;
+            }
             options = Some(opts);
         }
         Some((key, value, options))
@@ -166,84 +175,461 @@ pub fn parse_key_ord_pivot_value_command(
         None
     }
 }
-// Helper function to extract key-value pairs from bulk strings
-pub fn extract_key_value_strings(elements: &[RespValue]) -> Option<Vec<(Bytes, Bytes)>> {
-    elements
-        .chunks(2)
-        .into_iter()
-        .map(|value| {
-            if value.len() == 2 {
-                // Adapt to Frame variants
-                let key = extract_bulk_string(&value[0])?;
-                let val = extract_bulk_string(&value[1])?;
-                Some((key, val))
-            } else {
-                None
+// KEYSPACE EXPORT [DB index] | KEYSPACE IMPORT payload [REPLACE]
+pub fn parse_keyspace_command(elements: &[RespValue]) -> Option<Command> {
+    let args = extract_bulk_strings(elements.get(1..)?)?;
+    let subcommand = String::from_utf8_lossy(args.first()?).to_uppercase();
+    let option = args
+        .get(2)
+        .map(|o| String::from_utf8_lossy(o).to_uppercase());
+    match (subcommand.as_str(), args.len(), option.as_deref()) {
+        ("EXPORT", 1, None) => Some(Command::KeyspaceExport(None)),
+        ("EXPORT", 3, _) if args[1].eq_ignore_ascii_case(b"DB") => {
+            Some(Command::KeyspaceExport(Some(args[2].clone())))
+        }
+        ("IMPORT", 2, None) => Some(Command::KeyspaceImport(args[1].clone(), false)),
+        ("IMPORT", 3, Some("REPLACE")) => Some(Command::KeyspaceImport(args[1].clone(), true)),
+        _ => None,
+    }
+}
+
+// CONFIG GET pattern [pattern ...] | SET parameter value [parameter value ...] | RESETSTAT | REWRITE
+pub fn parse_config_command(elements: &[RespValue]) -> Option<Command> {
+    let args = extract_bulk_strings(elements.get(1..)?)?;
+    let (subcommand, rest) = args.split_first()?;
+    match String::from_utf8_lossy(subcommand).to_uppercase().as_str() {
+        "GET" if !rest.is_empty() => Some(Command::ConfigGet(rest.to_vec())),
+        "SET" if !rest.is_empty() && rest.len() % 2 == 0 => Some(Command::ConfigSet(
+            rest.chunks(2)
+                .map(|pair| (pair[0].clone(), pair[1].clone()))
+                .collect(),
+        )),
+        "RESETSTAT" if rest.is_empty() => Some(Command::ConfigResetStat),
+        "REWRITE" if rest.is_empty() => Some(Command::ConfigRewrite),
+        _ => None,
+    }
+}
+
+// GETEX key [EX seconds | PX milliseconds | EXAT unix-time-seconds | PXAT unix-time-milliseconds | PERSIST]
+pub fn parse_getex_command(elements: &[RespValue]) -> Option<Command> {
+    let args = extract_bulk_strings(elements.get(1..)?)?;
+    let (key, rest) = args.split_first()?;
+    let option = match rest {
+        [] => None,
+        [persist] if persist.eq_ignore_ascii_case(b"PERSIST") => Some(GetExOption::Persist),
+        [unit, time] => {
+            let time = time.clone();
+            Some(
+                match String::from_utf8_lossy(unit).to_uppercase().as_str() {
+                    "EX" => GetExOption::Ex(time),
+                    "PX" => GetExOption::Px(time),
+                    "EXAT" => GetExOption::ExAt(time),
+                    "PXAT" => GetExOption::PxAt(time),
+                    _ => return None,
+                },
+            )
+        }
+        _ => return None,
+    };
+    Some(Command::GetEx(key.clone(), option))
+}
+
+// LCS key1 key2 [LEN] [IDX] [MINMATCHLEN len] [WITHMATCHLEN]
+pub fn parse_lcs_command(elements: &[RespValue]) -> Option<Command> {
+    let args = extract_bulk_strings(elements.get(1..)?)?;
+    let mut options = LcsOptions::default();
+    let mut i = 2;
+    while i < args.len() {
+        match String::from_utf8_lossy(&args[i]).to_uppercase().as_str() {
+            "LEN" => options.len = true,
+            "IDX" => options.idx = true,
+            "WITHMATCHLEN" => options.with_match_len = true,
+            "MINMATCHLEN" if i + 1 < args.len() => {
+                options.min_match_len = Some(args[i + 1].clone());
+                i += 1;
             }
-        })
-        .collect::<Option<Vec<_>>>()
+            _ => return None,
+        }
+        i += 1;
+    }
+    Some(Command::Lcs(args[0].clone(), args[1].clone(), options))
+}
+
+// LPOP key [count], RPOP key [count]
+pub fn parse_key_count_command(elements: &[RespValue]) -> Option<(Bytes, Option<Bytes>)> {
+    let args = extract_bulk_strings(elements.get(1..)?)?;
+    match args.as_slice() {
+        [key] => Some((key.clone(), None)),
+        [key, count] => Some((key.clone(), Some(count.clone()))),
+        _ => None,
+    }
+}
+
+// LPOS key element [RANK rank] [COUNT num-matches] [MAXLEN len]
+pub fn parse_lpos_command(elements: &[RespValue]) -> Option<Command> {
+    let args = extract_bulk_strings(elements.get(1..)?)?;
+    let [key, element, options @ ..] = args.as_slice() else {
+        return None;
+    };
+    Some(Command::LPos(
+        key.clone(),
+        element.clone(),
+        options.to_vec(),
+    ))
+}
+
+// LMOVE source destination LEFT|RIGHT LEFT|RIGHT
+pub fn parse_lmove_command(elements: &[RespValue]) -> Option<Command> {
+    let args = extract_bulk_strings(elements.get(1..)?)?;
+    let [source, destination, from, to] = args.as_slice() else {
+        return None;
+    };
+    Some(Command::LMove(
+        source.clone(),
+        destination.clone(),
+        ListEnd::parse(from)?,
+        ListEnd::parse(to)?,
+    ))
+}
+
+// BLPOP key [key ...] timeout, BRPOP key [key ...] timeout
+pub fn parse_blocking_pop_command(elements: &[RespValue]) -> Option<(Vec<Bytes>, Bytes)> {
+    let args = extract_bulk_strings(elements.get(1..)?)?;
+    let (timeout, keys) = args.split_last()?;
+    (!keys.is_empty()).then(|| (keys.to_vec(), timeout.clone()))
+}
+
+// BLMOVE source destination LEFT|RIGHT LEFT|RIGHT timeout
+pub fn parse_blmove_command(elements: &[RespValue]) -> Option<Command> {
+    let args = extract_bulk_strings(elements.get(1..)?)?;
+    let [source, destination, from, to, timeout] = args.as_slice() else {
+        return None;
+    };
+    Some(Command::BLMove(
+        source.clone(),
+        destination.clone(),
+        ListEnd::parse(from)?,
+        ListEnd::parse(to)?,
+        timeout.clone(),
+    ))
+}
+
+// HRANDFIELD key [count [WITHVALUES]]
+pub fn parse_hrandfield_command(elements: &[RespValue]) -> Option<Command> {
+    let args = extract_bulk_strings(elements.get(1..)?)?;
+    match args.as_slice() {
+        [key] => Some(Command::HRandField(key.clone(), None, false)),
+        [key, count] => Some(Command::HRandField(key.clone(), Some(count.clone()), false)),
+        [key, count, option] if option.eq_ignore_ascii_case(b"WITHVALUES") => {
+            Some(Command::HRandField(key.clone(), Some(count.clone()), true))
+        }
+        _ => None,
+    }
+}
+
+// [MATCH pattern] [COUNT count] [NOVALUES], after the cursor of a SCAN command
+fn parse_scan_options(args: &[Bytes], novalues: bool) -> Option<ScanOptions> {
+    let mut options = ScanOptions::default();
+    let mut i = 0;
+    while i < args.len() {
+        match String::from_utf8_lossy(&args[i]).to_uppercase().as_str() {
+            "MATCH" if i + 1 < args.len() => {
+                options.pattern = Some(args[i + 1].clone());
+                i += 1;
+            }
+            "COUNT" if i + 1 < args.len() => {
+                options.count = Some(args[i + 1].clone());
+                i += 1;
+            }
+            "NOVALUES" if novalues => options.novalues = true,
+            _ => return None,
+        }
+        i += 1;
+    }
+    Some(options)
+}
+
+// HSCAN key cursor [MATCH pattern] [COUNT count] [NOVALUES]
+pub fn parse_hscan_command(elements: &[RespValue]) -> Option<Command> {
+    let args = extract_bulk_strings(elements.get(1..)?)?;
+    let [key, cursor, options @ ..] = args.as_slice() else {
+        return None;
+    };
+    let options = parse_scan_options(options, true)?;
+    Some(Command::HScan(key.clone(), cursor.clone(), options))
+}
+
+// SSCAN key cursor [MATCH pattern] [COUNT count]
+pub fn parse_sscan_command(elements: &[RespValue]) -> Option<Command> {
+    let args = extract_bulk_strings(elements.get(1..)?)?;
+    let [key, cursor, options @ ..] = args.as_slice() else {
+        return None;
+    };
+    let options = parse_scan_options(options, false)?;
+    Some(Command::SScan(key.clone(), cursor.clone(), options))
+}
+
+// BYTE | BIT, defaulting to BYTE
+fn parse_bit_unit(unit: Option<&Bytes>) -> Option<BitUnit> {
+    match unit {
+        None => Some(BitUnit::Byte),
+        Some(unit) if unit.eq_ignore_ascii_case(b"BYTE") => Some(BitUnit::Byte),
+        Some(unit) if unit.eq_ignore_ascii_case(b"BIT") => Some(BitUnit::Bit),
+        Some(_) => None,
+    }
 }
 
-// Helper functions for response formatting
-pub fn format_integer(value: i64) -> Bytes {
-    Bytes::from(format!(":{}\r\n", value))
+// BITCOUNT key [start end [BYTE | BIT]]
+pub fn parse_bitcount_command(elements: &[RespValue]) -> Option<Command> {
+    let args = extract_bulk_strings(elements.get(1..)?)?;
+    match args.as_slice() {
+        [key] => Some(Command::BitCount(key.clone(), None, BitUnit::Byte)),
+        [key, start, end, unit @ ..] if unit.len() <= 1 => Some(Command::BitCount(
+            key.clone(),
+            Some((start.clone(), end.clone())),
+            parse_bit_unit(unit.first())?,
+        )),
+        _ => None,
+    }
 }
 
-pub fn format_array(elements: Vec<String>) -> Bytes {
-    // This function still takes Vec<String> which is suboptimal, but we'll adapt it for now.
-    // The callers (like keys handler) construct Vec<String> from format_bulk_string (which we will change to return Bytes).
-    // So we should change this to take Vec<Bytes>.
-    let mut buf = BytesMut::new();
-    buf.put_slice(format!("*{}\r\n", elements.len()).as_bytes());
-    for element in elements {
-        buf.put_slice(element.as_bytes());
+// BITPOS key bit [start [end [BYTE | BIT]]]
+pub fn parse_bitpos_command(elements: &[RespValue]) -> Option<Command> {
+    let args = extract_bulk_strings(elements.get(1..)?)?;
+    match args.as_slice() {
+        [key, bit, range @ ..] if range.len() <= 3 => Some(Command::BitPos(
+            key.clone(),
+            bit.clone(),
+            range.first().cloned(),
+            range.get(1).cloned(),
+            parse_bit_unit(range.get(2))?,
+        )),
+        _ => None,
     }
-    buf.freeze()
 }
 
-// New signature for format_array taking Bytes
-pub fn format_array_bytes(elements: Vec<Bytes>) -> Bytes {
-    let mut buf = BytesMut::new();
-    buf.put_slice(format!("*{}\r\n", elements.len()).as_bytes());
-    for element in elements {
-        buf.put_slice(&element);
+// BITOP AND | OR | XOR | NOT | DIFF | ONE destkey key [key ...]
+pub fn parse_bitop_command(elements: &[RespValue]) -> Option<Command> {
+    let args = extract_bulk_strings(elements.get(1..)?)?;
+    let [op, dest, keys @ ..] = args.as_slice() else {
+        return None;
+    };
+    let op = match String::from_utf8_lossy(op).to_uppercase().as_str() {
+        "AND" => BitOperation::And,
+        "OR" => BitOperation::Or,
+        "XOR" => BitOperation::Xor,
+        "NOT" => BitOperation::Not,
+        "DIFF" => BitOperation::Diff,
+        "ONE" => BitOperation::One,
+        _ => return None,
+    };
+    Some(Command::BitOp(op, dest.clone(), keys.to_vec()))
+}
+
+// OBJECT ENCODING key
+pub fn parse_object_command(elements: &[RespValue]) -> Option<Command> {
+    let args = extract_bulk_strings(elements.get(1..)?)?;
+    match args.as_slice() {
+        [subcommand, key] if subcommand.eq_ignore_ascii_case(b"ENCODING") => {
+            Some(Command::ObjectEncoding(key.clone()))
+        }
+        _ => None,
     }
-    buf.freeze()
 }
 
-pub fn format_error(error: impl std::fmt::Display) -> Bytes {
-    Bytes::from(format!("-ERR {}\r\n", error))
+// MODULE LIST
+pub fn parse_module_command(elements: &[RespValue]) -> Option<Command> {
+    let args = extract_bulk_strings(elements.get(1..)?)?;
+    match args.as_slice() {
+        [subcommand] if subcommand.eq_ignore_ascii_case(b"LIST") => Some(Command::ModuleList),
+        _ => None,
+    }
+}
+
+// COMMAND [COUNT | INFO [name ...] | DOCS [name ...] | LIST [FILTERBY MODULE|ACLCAT|PATTERN value] | GETKEYS command [arg ...]]
+pub fn parse_command_command(elements: &[RespValue]) -> Option<Command> {
+    let args = extract_bulk_strings(elements.get(1..)?)?;
+    let Some((subcommand, rest)) = args.split_first() else {
+        return Some(Command::CommandAll);
+    };
+    match String::from_utf8_lossy(subcommand).to_uppercase().as_str() {
+        "COUNT" if rest.is_empty() => Some(Command::CommandCount),
+        "INFO" => Some(Command::CommandInfo(rest.to_vec())),
+        "DOCS" => Some(Command::CommandDocs(rest.to_vec())),
+        "LIST" => match rest {
+            [] => Some(Command::CommandList(None)),
+            [filterby, kind, value] if filterby.eq_ignore_ascii_case(b"FILTERBY") => {
+                let kind = Bytes::from(String::from_utf8_lossy(kind).to_uppercase());
+                match kind.as_ref() {
+                    b"MODULE" | b"ACLCAT" | b"PATTERN" => {
+                        Some(Command::CommandList(Some((kind, value.clone()))))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        },
+        "GETKEYS" if !rest.is_empty() => Some(Command::CommandGetKeys(rest.to_vec())),
+        _ => None,
+    }
 }
 
-pub fn format_bulk_string(value: &Bytes) -> Bytes {
-    let mut buf = BytesMut::with_capacity(value.len() + 20);
-    buf.put_u8(b'$');
-    buf.put_slice(value.len().to_string().as_bytes());
-    buf.put_slice(b"\r\n");
-    buf.put_slice(value);
-    buf.put_slice(b"\r\n");
-    buf.freeze()
+// RESTORE key ttl serialized-value [REPLACE] [ABSTTL] [IDLETIME seconds] [FREQ frequency]
+pub fn parse_restore_command(elements: &[RespValue]) -> Option<Command> {
+    if elements.len() < 4 {
+        return None;
+    }
+    let args = extract_bulk_strings(&elements[1..])?;
+    let mut options = RestoreOptions::default();
+    let mut i = 3;
+    while i < args.len() {
+        match String::from_utf8_lossy(&args[i]).to_uppercase().as_str() {
+            "REPLACE" => options.replace = true,
+            "ABSTTL" => options.absttl = true,
+            "IDLETIME" if i + 1 < args.len() && options.freq.is_none() => {
+                options.idletime = Some(args[i + 1].clone());
+                i += 1;
+            }
+            "FREQ" if i + 1 < args.len() && options.idletime.is_none() => {
+                options.freq = Some(args[i + 1].clone());
+                i += 1;
+            }
+            _ => return None,
+        }
+        i += 1;
+    }
+    Some(Command::Restore(
+        args[0].clone(),
+        args[1].clone(),
+        args[2].clone(),
+        options,
+    ))
 }
 
-pub fn format_null() -> Bytes {
-    Bytes::from_static(b"$-1\r\n")
+// MIGRATE host port key|"" destination-db timeout [COPY] [REPLACE] [KEYS key ...]
+pub fn parse_migrate_command(elements: &[RespValue]) -> Option<Command> {
+    if elements.len() < 6 {
+        return None;
+    }
+    let args = extract_bulk_strings(&elements[1..])?;
+    let mut options = MigrateOptions {
+        host: args[0].clone(),
+        port: args[1].clone(),
+        keys: vec![args[2].clone()],
+        db: args[3].clone(),
+        timeout: args[4].clone(),
+        copy: false,
+        replace: false,
+    };
+    let mut i = 5;
+    while i < args.len() {
+        match String::from_utf8_lossy(&args[i]).to_uppercase().as_str() {
+            "COPY" => options.copy = true,
+            "REPLACE" => options.replace = true,
+            // KEYS takes the rest of the arguments and needs an empty key argument
+            "KEYS" if args[2].is_empty() && i + 1 < args.len() => {
+                options.keys = args[i + 1..].to_vec();
+                break;
+            }
+            _ => return None,
+        }
+        i += 1;
+    }
+    if options.keys.iter().all(|k| k.is_empty()) {
+        return None;
+    }
+    Some(Command::Migrate(options))
 }
 
-pub fn format_simple_string(value: &str) -> Bytes {
-    Bytes::from(format!("+{}\r\n", value))
+// Helper function to extract key-value pairs from bulk strings
+pub fn extract_key_value_strings(elements: &[RespValue]) -> Option<Vec<(Bytes, Bytes)>> {
+    elements
+        .chunks(2)
+        .map(|value| {
+            if value.len() == 2 {
+                // Adapt to Frame variants
+                let key = extract_bulk_string(&value[0])?;
+                let val = extract_bulk_string(&value[1])?;
+                Some((key, val))
+            } else {
+                None
+            }
+        })
+        .collect::<Option<Vec<_>>>()
 }
 
-pub fn format_hash_response(value: Vec<Bytes>) -> Bytes {
-    let mut buf = BytesMut::new();
-    buf.put_slice(format!("*{}\r\n", value.len()).as_bytes());
-    for item in value {
-        // Reuse format_bulk_string logic or inline it to avoid excessive allocation if convenient,
-        // but format_bulk_string returns Bytes which might be zero-copy from BytesMut if well optimized?
-        // Actually format_bulk_string creates a new Bytes.
-        // Better to inline the writing here for performance?
-        // For simplicity let's append.
-        let bulk = format_bulk_string(&item);
-        buf.put_slice(&bulk);
+/// Redis glob-style matching (`*`, `?`, `[abc]`, `[^a-z]`, `\` escapes), as
+/// used by CONFIG GET
+pub fn glob_match(pattern: &[u8], string: &[u8], nocase: bool) -> bool {
+    let eq = |a: u8, b: u8| {
+        if nocase {
+            a.eq_ignore_ascii_case(&b)
+        } else {
+            a == b
+        }
+    };
+    match pattern.split_first() {
+        None => string.is_empty(),
+        Some((b'*', rest)) => {
+            (0..=string.len()).any(|skip| glob_match(rest, &string[skip..], nocase))
+        }
+        Some((b'?', rest)) => !string.is_empty() && glob_match(rest, &string[1..], nocase),
+        Some((b'[', rest)) => {
+            let Some((&c, tail)) = string.split_first() else {
+                return false;
+            };
+            let (negate, mut class) = match rest.split_first() {
+                Some((b'^', class)) => (true, class),
+                _ => (false, rest),
+            };
+            let mut matched = false;
+            loop {
+                match class {
+                    [] => break,
+                    [b']', ..] => {
+                        class = &class[1..];
+                        break;
+                    }
+                    [b'\\', escaped, ..] => {
+                        matched |= eq(*escaped, c);
+                        class = &class[2..];
+                    }
+                    [start, b'-', end, ..] if *end != b']' => {
+                        let (lo, hi) = if start <= end {
+                            (*start, *end)
+                        } else {
+                            (*end, *start)
+                        };
+                        let c = if nocase { c.to_ascii_lowercase() } else { c };
+                        let (lo, hi) = if nocase {
+                            (lo.to_ascii_lowercase(), hi.to_ascii_lowercase())
+                        } else {
+                            (lo, hi)
+                        };
+                        matched |= lo <= c && c <= hi;
+                        class = &class[3..];
+                    }
+                    [other, ..] => {
+                        matched |= eq(*other, c);
+                        class = &class[1..];
+                    }
+                }
+            }
+            matched != negate && glob_match(class, tail, nocase)
+        }
+        Some((b'\\', [escaped, rest @ ..])) => {
+            string.first().is_some_and(|&c| eq(*escaped, c))
+                && glob_match(rest, &string[1..], nocase)
+        }
+        Some((&p, rest)) => {
+            string.first().is_some_and(|&c| eq(p, c)) && glob_match(rest, &string[1..], nocase)
+        }
     }
-    buf.freeze()
+}
+
+/// Whether an error message already starts with an error code such as
+/// `ERR`, `WRONGTYPE` or `OOM`
+pub fn has_error_code(message: &str) -> bool {
+    let code = message.split(' ').next().unwrap_or_default();
+    code.len() > 1 && code.bytes().all(|b| b.is_ascii_uppercase())
 }
@@ -1,45 +1,164 @@
-use crate::commands::command_helper::{format_bulk_string, format_error, format_simple_string};
+use crate::commands::{CommandError, Reply};
 use crate::database::traits::KeyOp;
-use crate::database::SharedDatabase;
+use crate::database::{Session, SharedDatabase};
+use crate::modules;
+use crate::networking::resp::Protocol;
 use bytes::Bytes;
+use std::sync::atomic::Ordering;
 
-pub fn ping(msg: Option<Bytes>) -> Bytes {
+pub fn ping(msg: Option<Bytes>) -> Reply {
     match msg {
-        None => format_simple_string("PONG"),
-        Some(msg) => {
-            // Redis PING returns the argument as bulk string if present
-            format_bulk_string(&msg)
-        }
+        None => Reply::status("PONG"),
+        // Redis PING returns the argument as bulk string if present
+        Some(msg) => Reply::Bulk(msg),
     }
 }
 
-pub fn echo(msg: Bytes) -> Bytes {
-    format_bulk_string(&msg)
+pub fn echo(msg: Bytes) -> Reply {
+    Reply::Bulk(msg)
 }
 
-pub fn select(db: &SharedDatabase, db_index: Bytes) -> Bytes {
-    let db_idx_str = match std::str::from_utf8(&db_index) {
-        Ok(s) => s,
-        Err(_) => return format_error("ERR invalid DB index"),
+pub fn select(db: &SharedDatabase, db_index: Bytes) -> Reply {
+    let db_idx = match std::str::from_utf8(&db_index).map(|s| s.parse::<i64>()) {
+        Ok(Ok(index)) => index,
+        _ => return CommandError::InvalidInteger.into(),
     };
 
-    match db_idx_str.parse::<u8>() {
+    match u8::try_from(db_idx) {
         Ok(db_num) if db_num <= 15 => {
             db.select(db_num);
-            format_simple_string("OK")
+            Reply::ok()
+        }
+        _ => Reply::error("ERR DB index is out of range"),
+    }
+}
+
+/// Switch the session to the requested protocol and describe the server
+pub fn hello(version: Option<Bytes>) -> Reply {
+    let session = Session::current();
+    let protocol = match version.as_deref() {
+        None => session.as_ref().map(|s| s.protocol()).unwrap_or_default(),
+        Some(b"2") => Protocol::Resp2,
+        Some(b"3") => Protocol::Resp3,
+        Some(v) if std::str::from_utf8(v).is_ok_and(|v| v.parse::<i64>().is_ok()) => {
+            return Reply::error("NOPROTO unsupported protocol version");
         }
-        _ => format_error("ERR invalid DB index"),
+        Some(_) => return Reply::error("ERR Protocol version is not an integer or out of range"),
+    };
+    if let Some(session) = session {
+        session.set_protocol(protocol);
     }
+    let field = |name: &'static str| Reply::Bulk(Bytes::from_static(name.as_bytes()));
+    Reply::Map(vec![
+        (field("server"), field("redis")),
+        (field("version"), field("6.0.0")),
+        (
+            field("proto"),
+            Reply::Integer(if protocol == Protocol::Resp3 { 3 } else { 2 }),
+        ),
+        (field("mode"), field("standalone")),
+        (field("role"), field("master")),
+        (field("modules"), modules::list()),
+    ])
 }
 
-pub fn auth(_: Bytes) -> Bytes {
-    format_simple_string("OK")
+pub fn auth(_: Bytes) -> Reply {
+    Reply::ok()
 }
 
-pub fn info(_: Option<Bytes>) -> Bytes {
-    format_bulk_string(&Bytes::from("# Server\r\nredis_version:6.0.0\r\n"))
+pub fn info(db: &SharedDatabase, section: Option<Bytes>) -> Reply {
+    let section = section
+        .map(|s| String::from_utf8_lossy(&s).to_lowercase())
+        .unwrap_or_else(|| "default".to_string());
+    let all = matches!(section.as_str(), "default" | "all" | "everything");
+
+    let mut info = String::new();
+    if all || section == "server" {
+        info.push_str("# Server\r\nredis_version:6.0.0\r\n");
+    }
+    if all || section == "clients" {
+        if !info.is_empty() {
+            info.push_str("\r\n");
+        }
+        info.push_str("# Clients\r\n");
+        info.push_str(&format!(
+            "connected_clients:{}\r\n",
+            db.stats().connected_clients.load(Ordering::Relaxed)
+        ));
+        info.push_str(&format!("maxclients:{}\r\n", db.config().max_connections));
+        info.push_str(&format!("blocked_clients:{}\r\n", db.blocked_clients()));
+    }
+    if all || section == "memory" {
+        if !info.is_empty() {
+            info.push_str("\r\n");
+        }
+        info.push_str("# Memory\r\n");
+        info.push_str(&format!("used_memory:{}\r\n", db.refresh_used_memory()));
+        info.push_str(&format!("maxmemory:{}\r\n", db.config().maxmemory));
+        info.push_str("maxmemory_policy:noeviction\r\n");
+    }
+    if all || section == "persistence" {
+        let state = db.save_state();
+        if !info.is_empty() {
+            info.push_str("\r\n");
+        }
+        info.push_str("# Persistence\r\n");
+        info.push_str("loading:0\r\n");
+        info.push_str(&format!(
+            "rdb_changes_since_last_save:{}\r\n",
+            state.dirty()
+        ));
+        info.push_str(&format!(
+            "rdb_bgsave_in_progress:{}\r\n",
+            state.bgsave_in_progress() as u8
+        ));
+        info.push_str(&format!("rdb_last_save_time:{}\r\n", state.last_save()));
+        info.push_str(&format!(
+            "rdb_last_bgsave_status:{}\r\n",
+            if state.last_bgsave_ok() { "ok" } else { "err" }
+        ));
+    }
+    if all || section == "stats" {
+        let stats = db.stats();
+        if !info.is_empty() {
+            info.push_str("\r\n");
+        }
+        info.push_str("# Stats\r\n");
+        for (name, value) in [
+            ("total_connections_received", &stats.connections_received),
+            ("total_commands_processed", &stats.commands_processed),
+            ("rejected_connections", &stats.rejected_connections),
+        ] {
+            info.push_str(&format!("{}:{}\r\n", name, value.load(Ordering::Relaxed)));
+        }
+    }
+    if all || section == "tiered" {
+        if !info.is_empty() {
+            info.push_str("\r\n");
+        }
+        info.push_str("# Tiered\r\n");
+        match db.tiered() {
+            None => info.push_str("tiered_enabled:0\r\n"),
+            Some(store) => {
+                let stats = store.stats();
+                info.push_str("tiered_enabled:1\r\n");
+                info.push_str(&format!("tiered_max_memory:{}\r\n", store.max_memory()));
+                for (name, value) in [
+                    ("tiered_resident_bytes", &stats.resident_bytes),
+                    ("tiered_spilled_keys", &stats.spilled_keys),
+                    ("tiered_spilled_bytes", &stats.spilled_bytes),
+                    ("tiered_disk_hits", &stats.disk_hits),
+                    ("tiered_spills", &stats.spills),
+                    ("tiered_segments", &stats.segments),
+                ] {
+                    info.push_str(&format!("{}:{}\r\n", name, value.load(Ordering::Relaxed)));
+                }
+            }
+        }
+    }
+    Reply::bulk(info)
 }
 
-pub fn quit() -> Bytes {
-    Bytes::from_static(b"#QUIT")
+pub fn quit() -> Reply {
+    Reply::ok()
 }
@@ -1,5 +1,10 @@
+use bytes::Bytes;
+
 pub type Result<T> = std::result::Result<T, CommandError>;
 
+/// Errors sent back to clients. Every message starts with its error code
+/// (`ERR`, `WRONGTYPE`, `NOAUTH`, ...) exactly as Redis sends it, since
+/// client libraries match on these strings.
 #[derive(Debug, PartialEq, thiserror::Error)]
 pub enum CommandError {
     #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
@@ -14,19 +19,31 @@ pub enum CommandError {
     #[error("ERR syntax error")]
     SyntaxError,
 
-    #[error("ERR command not found")]
-    CommandNotFound,
+    #[error("ERR increment or decrement would overflow")]
+    Overflow,
+
+    #[error("ERR decrement would overflow")]
+    DecrementOverflow,
+
+    #[error("ERR increment would produce NaN or Infinity")]
+    NanOrInfinity,
+
+    #[error("ERR invalid expire time in '{0}' command")]
+    InvalidExpireTime(&'static str),
+
+    #[error("ERR wrong number of arguments for '{0}' command")]
+    WrongArity(String),
 
-    #[error("ERR wrong number of arguments for command")]
-    WrongNumberOfArguments,
+    #[error("ERR unknown command '{name}', with args beginning with: {args}")]
+    UnknownCommand { name: String, args: String },
 
-    #[error("ERR unknown command")]
-    UnknownCommand,
+    #[error("ERR unknown subcommand '{subcommand}'. Try {command} HELP.")]
+    UnknownSubcommand { command: String, subcommand: String },
 
-    #[error("ERR invalid command format")]
+    #[error("ERR Protocol error: expected an array of bulk strings")]
     InvalidCommandFormat,
 
-    #[error("ERR key not found")]
+    #[error("ERR no such key")]
     KeyNotFound,
 
     #[error("ERR index out of range")]
@@ -35,35 +52,92 @@ pub enum CommandError {
     #[error("ERR invalid range")]
     InvalidRange,
 
+    #[error("ERR offset is out of range")]
+    OffsetOutOfRange,
+
+    #[error("ERR string exceeds maximum allowed size (proto-max-bulk-len)")]
+    StringTooLong,
+
+    #[error("ERR value is out of range, value must between {0} and {1}")]
+    OutOfRange(i64, i64),
+
+    #[error("ERR invalid cursor")]
+    InvalidCursor,
+
+    // Bitmap errors
+    #[error("ERR bit offset is not an integer or out of range")]
+    InvalidBitOffset,
+
+    #[error("ERR bit is not an integer or out of range")]
+    InvalidBit,
+
+    #[error("ERR The bit argument must be 1 or 0.")]
+    InvalidBitArgument,
+
+    #[error("ERR Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is.")]
+    InvalidBitfieldType,
+
     #[error("ERR operation not permitted")]
     OperationNotPermitted,
 
-    #[error("ERR maximum number of clients reached")]
+    #[error("ERR max number of clients reached")]
     MaxClientsReached,
 
     #[error("ERR command disabled")]
     CommandDisabled,
 
-    #[error("ERR readonly mode")]
+    #[error("READONLY You can't write against a read only replica.")]
     ReadOnly,
 
-    #[error("ERR out of memory")]
+    #[error("NOAUTH Authentication required.")]
+    NoAuth,
+
+    #[error("WRONGPASS invalid username-password pair or user is disabled.")]
+    WrongPass,
+
+    #[error("LOADING Redis is loading the dataset in memory")]
+    Loading,
+
+    #[error(
+        "BUSY Redis is busy running a script. You can only call SCRIPT KILL or SHUTDOWN NOSCRIPT."
+    )]
+    Busy,
+
+    #[error("NOSCRIPT No matching script. Please use EVAL.")]
+    NoScript,
+
+    #[error("EXECABORT Transaction discarded because of previous errors.")]
+    ExecAbort,
+
+    #[error("OOM command not allowed when used memory > 'maxmemory'.")]
     OutOfMemory,
 
     #[error("ERR internal error")]
     InternalError,
 
     // List-specific errors
-    #[error("ERR only BEFORE|AFTER allowed for LINSERT")]
+    #[error("ERR syntax error")]
     InvalidInsertDirection,
 
     #[error("ERR pivot not found in list")]
     PivotNotFound,
 
+    #[error("ERR timeout is not a float or out of range")]
+    InvalidTimeout,
+
+    #[error("ERR timeout is negative")]
+    NegativeTimeout,
+
     // Hash-specific errors
     #[error("ERR hash field not found")]
     FieldNotFound,
 
+    #[error("ERR hash value is not an integer")]
+    HashValueNotInteger,
+
+    #[error("ERR hash value is not a float")]
+    HashValueNotFloat,
+
     // Set-specific errors
     #[error("ERR member not found in set")]
     MemberNotFound,
@@ -72,6 +146,19 @@ pub enum CommandError {
     #[error("ERR member not found in sorted set")]
     SortedSetMemberNotFound,
 
+    // DUMP/RESTORE/MIGRATE errors
+    #[error("BUSYKEY Target key name already exists.")]
+    BusyKey,
+
+    #[error("ERR DUMP payload version or checksum are wrong")]
+    BadDumpPayload,
+
+    #[error("ERR Bad data format")]
+    BadDataFormat,
+
+    #[error("IOERR error or timeout {0}")]
+    MigrateIo(&'static str),
+
     // Pattern matching error
     #[error("ERR invalid pattern")]
     InvalidPattern,
@@ -83,10 +170,79 @@ pub enum CommandError {
     // Generic error with custom message
     #[error("ERR {0}")]
     Custom(String),
+
+    // Error reply passed on as received, code included
+    #[error("{0}")]
+    Reply(String),
 }
 
 impl CommandError {
+    /// The reply Redis sends for an unknown command: the name and as many
+    /// arguments as fit in 128 bytes, each quoted and followed by a space
+    pub fn unknown_command(name: &[u8], args: &[Bytes]) -> Self {
+        let mut quoted = String::new();
+        for arg in args {
+            if quoted.len() >= 128 {
+                break;
+            }
+            let budget = 128 - quoted.len();
+            let arg = String::from_utf8_lossy(&arg[..arg.len().min(budget)]).into_owned();
+            quoted.push_str(&format!("'{}' ", arg));
+        }
+        let name = String::from_utf8_lossy(&name[..name.len().min(128)]).into_owned();
+        CommandError::UnknownCommand { name, args: quoted }
+    }
+
+    /// The error behind an error reply. Replies of variants without fields
+    /// map back to that variant, so callers can match on `WrongType` and the
+    /// like; anything else is kept as a `Reply`.
+    pub fn from_reply(message: String) -> Self {
+        use CommandError::*;
+        [
+            WrongType,
+            InvalidInteger,
+            InvalidFloat,
+            SyntaxError,
+            Overflow,
+            DecrementOverflow,
+            NanOrInfinity,
+            KeyNotFound,
+            IndexOutOfRange,
+            InvalidRange,
+            OffsetOutOfRange,
+            StringTooLong,
+            InvalidCursor,
+            InvalidBitOffset,
+            InvalidBit,
+            InvalidBitArgument,
+            InvalidBitfieldType,
+            OperationNotPermitted,
+            ReadOnly,
+            NoAuth,
+            WrongPass,
+            ExecAbort,
+            OutOfMemory,
+            PivotNotFound,
+            InvalidTimeout,
+            NegativeTimeout,
+            HashValueNotInteger,
+            HashValueNotFloat,
+            BusyKey,
+            BadDumpPayload,
+            InvalidPattern,
+        ]
+        .into_iter()
+        .find(|error| error.to_string() == message)
+        .unwrap_or(Reply(message))
+    }
+
+    /// The error code, e.g. `ERR` or `WRONGTYPE`
+    pub fn code(&self) -> String {
+        let message = self.to_string();
+        message.split(' ').next().unwrap_or_default().to_string()
+    }
+
     pub fn to_redis_error(&self) -> String {
-        format!("-ERR {}\r\n", self)
+        format!("-{}\r\n", self)
     }
 }
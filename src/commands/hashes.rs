@@ -1,91 +1,157 @@
-use crate::commands::command_helper::{
-    format_bulk_string, format_error, format_hash_response, format_integer, format_null,
-};
+use super::keys::{scan_arguments, scan_matches};
+use super::strings::parse_integer;
+use crate::commands::{CommandError, Reply, Result, ScanOptions};
 use crate::database::traits::HashOp;
 use crate::database::SharedDatabase;
 use bytes::Bytes;
 
-pub fn hset(db: &SharedDatabase, hash: Bytes, field: Bytes, value: Bytes) -> Bytes {
-    match db.hset(&hash, field, value) {
-        Ok(result) => format_integer(result),
-        Err(e) => format_error(e),
+/// The field/value pairs of HSET and HMSET. An odd number of arguments is
+/// an arity error, which the arity in the command table cannot express.
+fn field_value_pairs(fields: Vec<Bytes>, command: &str) -> Result<Vec<(Bytes, Bytes)>> {
+    if !fields.len().is_multiple_of(2) {
+        return Err(CommandError::WrongArity(command.to_string()));
     }
+    Ok(fields
+        .chunks_exact(2)
+        .map(|pair| (pair[0].clone(), pair[1].clone()))
+        .collect())
 }
 
-pub fn hget(db: &SharedDatabase, hash: Bytes, field: Bytes) -> Bytes {
-    match db.hget(&hash, &field) {
-        Ok(Some(result)) => format_bulk_string(&result),
-        Ok(None) => format_null(),
-        Err(e) => format_error(e),
-    }
+pub fn hset(db: &SharedDatabase, hash: Bytes, fields: Vec<Bytes>) -> Reply {
+    field_value_pairs(fields, "hset")
+        .and_then(|pairs| db.hset_multiple(&hash, &pairs))
+        .into()
+}
+
+pub fn hmset(db: &SharedDatabase, hash: Bytes, fields: Vec<Bytes>) -> Reply {
+    field_value_pairs(fields, "hmset")
+        .and_then(|pairs| db.hset_multiple(&hash, &pairs))
+        .map(|_| Reply::ok())
+        .into()
 }
 
-pub fn hdel(db: &SharedDatabase, hash: Bytes, fields: Vec<Bytes>) -> Bytes {
-    format_integer(db.hdel_multiple(&hash, &fields) as i64)
+pub fn hsetnx(db: &SharedDatabase, hash: Bytes, field: Bytes, value: Bytes) -> Reply {
+    db.hsetnx(&hash, field, value).into()
 }
 
-pub fn hgetall(db: &SharedDatabase, key: Bytes) -> Bytes {
+pub fn hget(db: &SharedDatabase, hash: Bytes, field: Bytes) -> Reply {
+    db.hget(&hash, &field).into()
+}
+
+pub fn hdel(db: &SharedDatabase, hash: Bytes, fields: Vec<Bytes>) -> Reply {
+    db.hdel_multiple(&hash, &fields).into()
+}
+
+pub fn hgetall(db: &SharedDatabase, key: Bytes) -> Reply {
     match db.hget_all(&key) {
-        Ok(value) => format_hash_response(value),
-        Err(e) => format_error(e),
+        Ok(value) => Reply::Map(
+            value
+                .chunks_exact(2)
+                .map(|pair| (Reply::Bulk(pair[0].clone()), Reply::Bulk(pair[1].clone())))
+                .collect(),
+        ),
+        Err(e) => e.into(),
     }
 }
 
-pub fn hkeys(db: &SharedDatabase, key: Bytes) -> Bytes {
-    match db.hkeys(&key) {
-        Ok(value) => format_hash_response(value),
-        Err(e) => format_error(e),
-    }
+pub fn hkeys(db: &SharedDatabase, key: Bytes) -> Reply {
+    db.hkeys(&key).map(Reply::bulk_array).into()
 }
 
-pub fn hvals(db: &SharedDatabase, key: Bytes) -> Bytes {
-    match db.hvals(&key) {
-        Ok(value) => format_hash_response(value),
-        Err(e) => format_error(e),
-    }
+pub fn hvals(db: &SharedDatabase, key: Bytes) -> Reply {
+    db.hvals(&key).map(Reply::bulk_array).into()
 }
 
-pub fn hlen(db: &SharedDatabase, key: Bytes) -> Bytes {
-    match db.hlen(&key) {
-        Ok(value) => format_integer(value as i64),
-        Err(e) => format_error(e),
-    }
+pub fn hlen(db: &SharedDatabase, key: Bytes) -> Reply {
+    db.hlen(&key).into()
 }
 
-pub fn hexists(db: &SharedDatabase, hash: Bytes, field: Bytes) -> Bytes {
-    match db.hexists(&hash, &field) {
-        Ok(value) => format_integer(if value { 1 } else { 0 }),
-        Err(e) => format_error(e),
-    }
+pub fn hexists(db: &SharedDatabase, hash: Bytes, field: Bytes) -> Reply {
+    db.hexists(&hash, &field).into()
 }
 
-pub fn hincrby(db: &SharedDatabase, hash: Bytes, field: Bytes, value: Bytes) -> Bytes {
-    // Parsing should happen here or in db?
-    // Database::hincrby expects value: i64.
-    // So we must parse Bytes -> i64 here.
-    let val_str = match std::str::from_utf8(&value) {
-        Ok(s) => s,
-        Err(_) => return format_error(crate::commands::CommandError::InvalidInteger),
+pub fn hincrby(db: &SharedDatabase, hash: Bytes, field: Bytes, value: Bytes) -> Reply {
+    parse_integer(&value)
+        .and_then(|increment| db.hincrby(&hash, &field, increment))
+        .into()
+}
+
+pub fn hincrbyfloat(db: &SharedDatabase, hash: Bytes, field: Bytes, value: Bytes) -> Reply {
+    let increment = std::str::from_utf8(&value)
+        .ok()
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|v| !v.is_nan())
+        .ok_or(CommandError::InvalidFloat);
+    increment
+        .and_then(|increment| db.hincrbyfloat(&hash, &field, increment))
+        // Sent as a bulk string, not a double, like Redis does
+        .map(|result| Reply::bulk(result.to_string()))
+        .into()
+}
+
+pub fn hmget(db: &SharedDatabase, hash: Bytes, fields: Vec<Bytes>) -> Reply {
+    db.hmget(&hash, &fields)
+        .map(|values| Reply::Array(values.into_iter().map(Reply::from).collect()))
+        .into()
+}
+
+pub fn hstrlen(db: &SharedDatabase, hash: Bytes, field: Bytes) -> Reply {
+    db.hstrlen(&hash, &field).into()
+}
+
+pub fn hrandfield(
+    db: &SharedDatabase,
+    hash: Bytes,
+    count: Option<Bytes>,
+    with_values: bool,
+) -> Reply {
+    let Some(count) = count else {
+        // A single field, or nil for a missing key
+        return db
+            .hrandfield(&hash, 1)
+            .map(|pairs| pairs.into_iter().next().map(|(field, _)| field))
+            .into();
+    };
+    let count = match parse_integer(&count) {
+        Ok(i64::MIN) => return CommandError::OutOfRange(-i64::MAX, i64::MAX).into(),
+        Ok(count) => count,
+        Err(e) => return e.into(),
     };
-    match val_str.parse::<i64>() {
-        Ok(val) => match db.hincrby(&hash, &field, val) {
-            Ok(result) => format_integer(result),
-            Err(e) => format_error(e),
-        },
-        Err(_) => format_error(crate::commands::CommandError::InvalidInteger),
+    // Twice as many replies as fields must still be countable
+    if with_values && count.unsigned_abs() > (i64::MAX / 2) as u64 {
+        return CommandError::Custom("value is out of range".to_string()).into();
+    }
+    match db.hrandfield(&hash, count) {
+        Ok(pairs) if with_values => Reply::Pairs(
+            pairs
+                .into_iter()
+                .map(|(field, value)| (Reply::Bulk(field), Reply::Bulk(value)))
+                .collect(),
+        ),
+        Ok(pairs) => Reply::bulk_array(pairs.into_iter().map(|(field, _)| field).collect()),
+        Err(e) => e.into(),
     }
 }
 
-pub fn hincrbyfloat(db: &SharedDatabase, hash: Bytes, field: Bytes, value: Bytes) -> Bytes {
-    let val_str = match std::str::from_utf8(&value) {
-        Ok(s) => s,
-        Err(_) => return format_error(crate::commands::CommandError::InvalidFloat),
-    };
-    match val_str.parse::<f64>() {
-        Ok(val) => match db.hincrbyfloat(&hash, &field, val) {
-            Ok(result) => format_bulk_string(&Bytes::from(result.to_string())),
-            Err(e) => format_error(e),
-        },
-        Err(_) => format_error(crate::commands::CommandError::InvalidFloat),
+pub fn hscan(db: &SharedDatabase, hash: Bytes, cursor: Bytes, options: ScanOptions) -> Reply {
+    let scanned = scan_arguments(&cursor, &options)
+        .and_then(|(cursor, count)| db.hscan(&hash, cursor, count));
+    match scanned {
+        Ok((next, pairs)) => {
+            let mut items = Vec::new();
+            for (field, value) in pairs {
+                if scan_matches(&options, &field) {
+                    items.push(field);
+                    if !options.novalues {
+                        items.push(value);
+                    }
+                }
+            }
+            Reply::Array(vec![
+                Reply::bulk(next.to_string()),
+                Reply::bulk_array(items),
+            ])
+        }
+        Err(e) => e.into(),
     }
 }
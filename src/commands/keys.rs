@@ -1,52 +1,280 @@
-use crate::commands::command_helper::{
-    format_array_bytes, format_bulk_string, format_error, format_integer, format_simple_string,
-};
+use crate::commands::command_helper::glob_match;
+use crate::commands::{CommandError, MigrateOptions, Reply, RestoreOptions, Result, ScanOptions};
 use crate::database::traits::KeyOp;
 use crate::database::SharedDatabase;
-use bytes::Bytes;
+use crate::networking::resp::{self, Protocol, RespValue};
+use crate::persistence::rdb;
+use bytes::{Bytes, BytesMut};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::timeout,
+};
+
+// Default MIGRATE timeout when the caller passes 0 or less
+const MIGRATE_DEFAULT_TIMEOUT_MS: u64 = 1000;
 
-pub fn exists(db: &SharedDatabase, keys: Vec<Bytes>) -> Bytes {
-    format_integer(db.exist(&keys) as i64)
+fn parse_i64(value: &Bytes) -> Option<i64> {
+    std::str::from_utf8(value).ok()?.parse().ok()
 }
 
-pub fn expire(db: &SharedDatabase, key: Bytes, seconds: Bytes) -> Bytes {
+pub fn exists(db: &SharedDatabase, keys: Vec<Bytes>) -> Reply {
+    db.exist(&keys).into()
+}
+
+pub fn expire(db: &SharedDatabase, key: Bytes, seconds: Bytes) -> Reply {
     // Parse seconds from Bytes
     let secs_str = match std::str::from_utf8(&seconds) {
         Ok(s) => s,
-        Err(_) => return format_error(crate::commands::CommandError::InvalidInteger),
+        Err(_) => return CommandError::InvalidInteger.into(),
     };
 
     match secs_str.parse::<u64>() {
-        Ok(s) => match db.expire(&key, s) {
-            Ok(()) => format_simple_string("OK"),
-            Err(e) => format_error(e),
-        },
-        Err(_) => format_error(crate::commands::CommandError::InvalidInteger),
+        Ok(s) => db.expire(&key, s).into(),
+        Err(_) => CommandError::InvalidInteger.into(),
     }
 }
 
-pub fn ttl(db: &SharedDatabase, key: Bytes) -> Bytes {
-    format_integer(db.ttl(&key))
+pub fn ttl(db: &SharedDatabase, key: Bytes) -> Reply {
+    Reply::Integer(db.ttl(&key))
 }
 
-pub fn type_(db: &SharedDatabase, key: Bytes) -> Bytes {
+pub fn type_(db: &SharedDatabase, key: Bytes) -> Reply {
     // db.data_type now accepts &Bytes
-    format_simple_string(&db.data_type(&key))
+    Reply::status(db.data_type(&key))
 }
 
-pub fn keys(db: &SharedDatabase, pattern: Bytes) -> Bytes {
-    match db.keys(&pattern) {
-        Ok(keys) => format_array_bytes(keys.into_iter().map(|k| format_bulk_string(&k)).collect()),
-        Err(e) => format_error(e),
+pub fn object_encoding(db: &SharedDatabase, key: Bytes) -> Reply {
+    db.object_encoding(&key).map(Reply::bulk).into()
+}
+
+pub fn keys(db: &SharedDatabase, pattern: Bytes) -> Reply {
+    db.keys(&pattern).map(Reply::bulk_array).into()
+}
+
+/// Cursor and COUNT of a command of the SCAN family, COUNT defaulting to 10
+pub(super) fn scan_arguments(cursor: &Bytes, options: &ScanOptions) -> Result<(u64, usize)> {
+    let cursor = std::str::from_utf8(cursor)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or(CommandError::InvalidCursor)?;
+    let count = match &options.count {
+        Some(count) => parse_i64(count).ok_or(CommandError::InvalidInteger)?,
+        None => 10,
+    };
+    if count < 1 {
+        return Err(CommandError::SyntaxError);
     }
+    Ok((cursor, count as usize))
+}
+
+/// Whether a scanned element matches the MATCH pattern, if there is one
+pub(super) fn scan_matches(options: &ScanOptions, element: &[u8]) -> bool {
+    options
+        .pattern
+        .as_ref()
+        .is_none_or(|pattern| glob_match(pattern, element, false))
 }
 
-pub fn flushall(db: &SharedDatabase) -> Bytes {
+pub fn flushall(db: &SharedDatabase) -> Reply {
     db.flush_all();
-    format_simple_string("OK")
+    Reply::ok()
 }
 
-pub fn flushdb(db: &SharedDatabase) -> Bytes {
+pub fn flushdb(db: &SharedDatabase) -> Reply {
     db.flush_db();
-    format_simple_string("OK")
+    Reply::ok()
+}
+
+pub fn dump(db: &SharedDatabase, key: Bytes) -> Reply {
+    db.dump(&key).map(Reply::bulk).into()
+}
+
+pub fn restore(
+    db: &SharedDatabase,
+    key: Bytes,
+    ttl: Bytes,
+    payload: Bytes,
+    options: RestoreOptions,
+) -> Reply {
+    let ttl = match parse_i64(&ttl) {
+        Some(ttl) if ttl >= 0 => ttl as u64,
+        Some(_) => {
+            return Reply::from(CommandError::Custom(
+                "Invalid TTL value, must be >= 0".to_string(),
+            ))
+        }
+        None => return Reply::from(CommandError::InvalidInteger),
+    };
+    // Rudis keeps no LRU/LFU metadata, so IDLETIME and FREQ are only validated
+    if let Some(idletime) = &options.idletime {
+        match parse_i64(idletime) {
+            Some(n) if n >= 0 => {}
+            Some(_) => {
+                return Reply::from(CommandError::Custom(
+                    "Invalid IDLETIME value, must be >= 0".to_string(),
+                ))
+            }
+            None => return Reply::from(CommandError::InvalidInteger),
+        }
+    }
+    if let Some(freq) = &options.freq {
+        match parse_i64(freq) {
+            Some(n) if (0..=255).contains(&n) => {}
+            Some(_) => {
+                return Reply::from(CommandError::Custom(
+                    "Invalid FREQ value, must be >= 0 and <= 255".to_string(),
+                ))
+            }
+            None => return Reply::from(CommandError::InvalidInteger),
+        }
+    }
+
+    if !rdb::check_dump_payload(&payload) {
+        return CommandError::BadDumpPayload.into();
+    }
+    let value = match rdb::restore_value(&payload) {
+        Ok(value) => value,
+        Err(_) => return Reply::from(CommandError::BadDataFormat),
+    };
+    let expire_at = match (ttl, options.absttl) {
+        (0, _) => None,
+        (ms, true) => Some(UNIX_EPOCH + Duration::from_millis(ms)),
+        (ms, false) => match SystemTime::now().checked_add(Duration::from_millis(ms)) {
+            Some(at) => Some(at),
+            None => return Reply::from(CommandError::InvalidRange),
+        },
+    };
+    match db.restore(&key, value, expire_at, options.replace) {
+        Ok(()) => Reply::ok(),
+        Err(e) => e.into(),
+    }
+}
+
+/// A request to another server, encoded like a client would send it
+fn format_command(args: &[Bytes]) -> Bytes {
+    resp::encode(&Reply::bulk_array(args.to_vec()), Protocol::Resp2)
+}
+
+/// Send a pipelined request to the target and read `expected` replies
+async fn migrate_exchange(
+    addr: (&str, u16),
+    request: &[u8],
+    expected: usize,
+    limit: Duration,
+) -> std::result::Result<Vec<RespValue>, CommandError> {
+    let mut stream = match timeout(limit, TcpStream::connect(addr)).await {
+        Ok(Ok(stream)) => stream,
+        _ => return Err(CommandError::MigrateIo("connecting to the client")),
+    };
+    if !matches!(timeout(limit, stream.write_all(request)).await, Ok(Ok(()))) {
+        return Err(CommandError::MigrateIo("writing to target instance"));
+    }
+
+    let mut buffer = BytesMut::with_capacity(64);
+    let mut replies = Vec::with_capacity(expected);
+    while replies.len() < expected {
+        let peek_bytes = Bytes::copy_from_slice(&buffer);
+        match redis_protocol::resp2::decode::decode(&peek_bytes) {
+            Ok(Some((frame, consumed))) => {
+                let _ = buffer.split_to(consumed);
+                replies.push(frame);
+            }
+            Ok(None) => match timeout(limit, stream.read_buf(&mut buffer)).await {
+                Ok(Ok(n)) if n > 0 => {}
+                _ => return Err(CommandError::MigrateIo("reading to target instance")),
+            },
+            Err(_) => return Err(CommandError::MigrateIo("reading to target instance")),
+        }
+    }
+    Ok(replies)
+}
+
+fn target_error(reply: &RespValue) -> Option<CommandError> {
+    match reply {
+        RespValue::Error(msg) => Some(CommandError::Custom(format!(
+            "Target instance replied with error: {}",
+            msg
+        ))),
+        _ => None,
+    }
+}
+
+pub async fn migrate(db: &SharedDatabase, options: MigrateOptions) -> Reply {
+    let port = parse_i64(&options.port).filter(|p| (0..=65535).contains(p));
+    let (Some(port), Some(dest_db), Some(limit)) =
+        (port, parse_i64(&options.db), parse_i64(&options.timeout))
+    else {
+        return CommandError::InvalidInteger.into();
+    };
+    let limit = Duration::from_millis(if limit <= 0 {
+        MIGRATE_DEFAULT_TIMEOUT_MS
+    } else {
+        limit as u64
+    });
+
+    // Serialize the keys that exist; missing ones are skipped
+    let mut request = BytesMut::new();
+    request.extend_from_slice(&format_command(&[
+        Bytes::from_static(b"SELECT"),
+        Bytes::from(dest_db.to_string()),
+    ]));
+    let mut dumped = Vec::new();
+    for key in options.keys {
+        let Some(payload) = db.dump(&key).map(Bytes::from) else {
+            continue;
+        };
+        // 0 asks the target for no expiry; a key that expired since the
+        // DUMP is skipped rather than sent without its TTL
+        let ttl = match db.pttl(&key) {
+            -2 => continue,
+            -1 => 0,
+            ms => ms,
+        };
+        let mut args = vec![
+            Bytes::from_static(b"RESTORE"),
+            key.clone(),
+            Bytes::from(ttl.to_string()),
+            payload.clone(),
+        ];
+        if options.replace {
+            args.push(Bytes::from_static(b"REPLACE"));
+        }
+        request.extend_from_slice(&format_command(&args));
+        dumped.push((key, payload));
+    }
+    if dumped.is_empty() {
+        return Reply::status("NOKEY");
+    }
+
+    let host = String::from_utf8_lossy(&options.host);
+    let replies =
+        match migrate_exchange((&host, port as u16), &request, dumped.len() + 1, limit).await {
+            Ok(replies) => replies,
+            Err(e) => return Reply::from(e),
+        };
+    if let Some(e) = target_error(&replies[0]) {
+        return e.into();
+    }
+
+    // Keys the target accepted are removed locally unless COPY was given,
+    // even when some other key failed. A key written to since its DUMP
+    // keeps the newer value.
+    let mut error = None;
+    for ((key, payload), reply) in dumped.iter().zip(&replies[1..]) {
+        match target_error(reply) {
+            Some(e) => error = Some(e),
+            None if options.copy => {}
+            None => {
+                if let Err(e) = db.del_if_unchanged(key, payload) {
+                    error = Some(e);
+                }
+            }
+        }
+    }
+    match error {
+        Some(e) => e.into(),
+        None => Reply::ok(),
+    }
 }
@@ -1,109 +1,330 @@
-use crate::commands::command_helper::{
-    format_array_bytes, format_bulk_string, format_error, format_integer, format_null,
-    format_simple_string,
-};
+use super::strings::parse_integer;
+use crate::commands::{CommandError, Reply, Result};
+use crate::data_structures::ListEnd;
+use crate::database::blocking::BlockedOp;
 use crate::database::traits::ListOp;
 use crate::database::SharedDatabase;
 use bytes::Bytes;
+use std::time::Duration;
 
-pub fn lpush(db: &SharedDatabase, key: Bytes, values: Vec<Bytes>) -> Bytes {
-    format_integer(db.lpush(&key, &values) as i64)
+pub fn lpush(db: &SharedDatabase, key: Bytes, values: Vec<Bytes>) -> Reply {
+    db.lpush(&key, &values).into()
 }
 
-pub fn rpush(db: &SharedDatabase, key: Bytes, values: Vec<Bytes>) -> Bytes {
-    format_integer(db.rpush(&key, &values) as i64)
+pub fn rpush(db: &SharedDatabase, key: Bytes, values: Vec<Bytes>) -> Reply {
+    db.rpush(&key, &values).into()
 }
 
-pub fn lpop(db: &SharedDatabase, key: Bytes) -> Bytes {
-    match db.lpop(&key) {
-        Some(result) => format_bulk_string(&result),
-        None => format_null(),
-    }
+pub fn lpop(db: &SharedDatabase, key: Bytes, count: Option<Bytes>) -> Reply {
+    pop(db, key, count, ListEnd::Left)
+}
+
+pub fn rpop(db: &SharedDatabase, key: Bytes, count: Option<Bytes>) -> Reply {
+    pop(db, key, count, ListEnd::Right)
 }
 
-pub fn rpop(db: &SharedDatabase, key: Bytes) -> Bytes {
-    match db.rpop(&key) {
-        Some(result) => format_bulk_string(&result),
-        None => format_null(),
+/// LPOP and RPOP. Without a count they reply with one element; with one,
+/// with an array, which is a null array for a missing key.
+fn pop(db: &SharedDatabase, key: Bytes, count: Option<Bytes>, end: ListEnd) -> Reply {
+    let Some(count) = count else {
+        return match end {
+            ListEnd::Left => db.lpop(&key),
+            ListEnd::Right => db.rpop(&key),
+        }
+        .into();
+    };
+    let count = match parse_integer(&count) {
+        Ok(count) if count >= 0 => count as usize,
+        Ok(_) => {
+            return CommandError::Custom("value is out of range, must be positive".to_string())
+                .into()
+        }
+        Err(e) => return e.into(),
+    };
+    match db.pop_many(&key, end, count) {
+        Ok(Some(elements)) => Reply::bulk_array(elements),
+        Ok(None) => Reply::NullArray,
+        Err(e) => e.into(),
     }
 }
 
-pub fn llen(db: &SharedDatabase, key: Bytes) -> Bytes {
-    format_integer(db.llen(&key) as i64)
+pub fn llen(db: &SharedDatabase, key: Bytes) -> Reply {
+    db.llen(&key).into()
 }
 
-pub fn lindex(db: &SharedDatabase, key: Bytes, index: Bytes) -> Bytes {
+pub fn lindex(db: &SharedDatabase, key: Bytes, index: Bytes) -> Reply {
     // Parse index
     let index_str = match std::str::from_utf8(&index) {
         Ok(s) => s,
-        Err(_) => return format_error(crate::commands::CommandError::InvalidInteger),
+        Err(_) => return CommandError::InvalidInteger.into(),
     };
     match index_str.parse::<i64>() {
-        Ok(idx) => match db.lindex(&key, idx) {
-            Some(val) => format_bulk_string(&val),
-            None => format_null(),
-        },
-        Err(_) => format_error(crate::commands::CommandError::InvalidInteger),
+        Ok(idx) => db.lindex(&key, idx).into(),
+        Err(_) => CommandError::InvalidInteger.into(),
     }
 }
 
-pub fn lrange(db: &SharedDatabase, key: Bytes, start: Bytes, end: Bytes) -> Bytes {
+pub fn lrange(db: &SharedDatabase, key: Bytes, start: Bytes, end: Bytes) -> Reply {
     let start_str = match std::str::from_utf8(&start) {
         Ok(s) => s,
-        Err(_) => return format_error(crate::commands::CommandError::InvalidInteger),
+        Err(_) => return CommandError::InvalidInteger.into(),
     };
     let end_str = match std::str::from_utf8(&end) {
         Ok(s) => s,
-        Err(_) => return format_error(crate::commands::CommandError::InvalidInteger),
+        Err(_) => return CommandError::InvalidInteger.into(),
     };
 
     match (start_str.parse::<i64>(), end_str.parse::<i64>()) {
-        (Ok(s), Ok(e)) => match db.lrange(&key, s, e) {
-            Ok(val) => format_array_bytes(val),
-            Err(e) => format_error(e),
-        },
-        _ => format_error(crate::commands::CommandError::InvalidInteger),
+        (Ok(s), Ok(e)) => db.lrange(&key, s, e).map(Reply::bulk_array).into(),
+        _ => CommandError::InvalidInteger.into(),
     }
 }
 
-pub fn ltrim(db: &SharedDatabase, key: Bytes, start: Bytes, end: Bytes) -> Bytes {
+pub fn ltrim(db: &SharedDatabase, key: Bytes, start: Bytes, end: Bytes) -> Reply {
     let start_str = match std::str::from_utf8(&start) {
         Ok(s) => s,
-        Err(_) => return format_error(crate::commands::CommandError::InvalidInteger),
+        Err(_) => return CommandError::InvalidInteger.into(),
     };
     let end_str = match std::str::from_utf8(&end) {
         Ok(s) => s,
-        Err(_) => return format_error(crate::commands::CommandError::InvalidInteger),
+        Err(_) => return CommandError::InvalidInteger.into(),
     };
 
     match (start_str.parse::<i64>(), end_str.parse::<i64>()) {
-        (Ok(s), Ok(e)) => match db.ltrim(&key, s, e) {
-            Ok(_) => format_simple_string("OK"),
-            Err(e) => format_error(e),
-        },
-        _ => format_error(crate::commands::CommandError::InvalidInteger),
+        (Ok(s), Ok(e)) => db.ltrim(&key, s, e).map(|_| Reply::ok()).into(),
+        _ => CommandError::InvalidInteger.into(),
     }
 }
 
-pub fn lset(db: &SharedDatabase, key: Bytes, index: Bytes, value: Bytes) -> Bytes {
+pub fn lset(db: &SharedDatabase, key: Bytes, index: Bytes, value: Bytes) -> Reply {
     let index_str = match std::str::from_utf8(&index) {
         Ok(s) => s,
-        Err(_) => return format_error(crate::commands::CommandError::InvalidInteger),
+        Err(_) => return CommandError::InvalidInteger.into(),
     };
 
     match index_str.parse::<i64>() {
-        Ok(idx) => match db.lset(&key, idx, value) {
-            Ok(_) => format_simple_string("OK"),
-            Err(e) => format_error(e),
-        },
-        Err(_) => format_error(crate::commands::CommandError::InvalidInteger),
+        Ok(idx) => db.lset(&key, idx, value).map(|_| Reply::ok()).into(),
+        Err(_) => CommandError::InvalidInteger.into(),
+    }
+}
+
+pub fn linsert(db: &SharedDatabase, key: Bytes, ord: Bytes, pivot: Bytes, value: Bytes) -> Reply {
+    let ord_str = String::from_utf8_lossy(&ord).to_uppercase();
+    db.linsert(&key, &ord_str, &pivot, value).into()
+}
+
+pub fn lpushx(db: &SharedDatabase, key: Bytes, values: Vec<Bytes>) -> Reply {
+    db.lpushx(&key, &values).into()
+}
+
+pub fn rpushx(db: &SharedDatabase, key: Bytes, values: Vec<Bytes>) -> Reply {
+    db.rpushx(&key, &values).into()
+}
+
+/// RANK, COUNT and MAXLEN of LPOS, checked in the order given
+fn lpos_options(options: &[Bytes]) -> Result<(i64, Option<usize>, usize)> {
+    let (mut rank, mut count, mut maxlen) = (1, None, 0);
+    for pair in options.chunks(2) {
+        let [option, value] = pair else {
+            return Err(CommandError::SyntaxError);
+        };
+        match String::from_utf8_lossy(option).to_uppercase().as_str() {
+            "RANK" => {
+                rank = parse_integer(value)?;
+                if rank == i64::MIN {
+                    return Err(CommandError::OutOfRange(-i64::MAX, i64::MAX));
+                }
+                if rank == 0 {
+                    return Err(CommandError::Custom(
+                        "RANK can't be zero: use 1 to start from the first match, 2 from the second ... or use negative to start from the end of the list".to_string(),
+                    ));
+                }
+            }
+            "COUNT" => {
+                let value = parse_integer(value)?;
+                if value < 0 {
+                    return Err(CommandError::Custom("COUNT can't be negative".to_string()));
+                }
+                count = Some(value as usize);
+            }
+            "MAXLEN" => {
+                let value = parse_integer(value)?;
+                if value < 0 {
+                    return Err(CommandError::Custom("MAXLEN can't be negative".to_string()));
+                }
+                maxlen = value as usize;
+            }
+            _ => return Err(CommandError::SyntaxError),
+        }
+    }
+    Ok((rank, count, maxlen))
+}
+
+pub fn lpos(db: &SharedDatabase, key: Bytes, element: Bytes, options: Vec<Bytes>) -> Reply {
+    let (rank, count, maxlen) = match lpos_options(&options) {
+        Ok(options) => options,
+        Err(e) => return e.into(),
+    };
+    // Without COUNT the reply is the first match alone
+    match db.lpos(&key, &element, rank, count.unwrap_or(1), maxlen) {
+        Ok(positions) if count.is_some() => Reply::Array(
+            positions
+                .into_iter()
+                .map(|position| Reply::Integer(position as i64))
+                .collect(),
+        ),
+        Ok(positions) => positions
+            .first()
+            .map(|&position| Reply::Integer(position as i64))
+            .into(),
+        Err(e) => e.into(),
+    }
+}
+
+pub fn lrem(db: &SharedDatabase, key: Bytes, count: Bytes, element: Bytes) -> Reply {
+    parse_integer(&count)
+        .and_then(|count| db.lrem(&key, count, &element))
+        .into()
+}
+
+pub fn lmove(
+    db: &SharedDatabase,
+    source: Bytes,
+    destination: Bytes,
+    from: ListEnd,
+    to: ListEnd,
+) -> Reply {
+    db.lmove(&source, &destination, from, to).into()
+}
+
+/// The keys, end and count of `numkeys key [key ...] LEFT|RIGHT [COUNT count]`
+pub(super) fn lmpop_args(args: &[Bytes]) -> Result<(Vec<Bytes>, ListEnd, usize)> {
+    let (numkeys, rest) = args.split_first().ok_or(CommandError::SyntaxError)?;
+    let numkeys = parse_integer(numkeys)?;
+    if numkeys <= 0 {
+        return Err(CommandError::Custom(
+            "numkeys should be greater than 0".to_string(),
+        ));
+    }
+    if rest.len() as u64 <= numkeys as u64 {
+        return Err(CommandError::SyntaxError);
+    }
+    let (keys, rest) = rest.split_at(numkeys as usize);
+    let end = ListEnd::parse(&rest[0]).ok_or(CommandError::SyntaxError)?;
+    let count = match &rest[1..] {
+        [] => 1,
+        [option, count] if option.eq_ignore_ascii_case(b"COUNT") => {
+            let count = parse_integer(count)?;
+            if count <= 0 {
+                return Err(CommandError::Custom(
+                    "count should be greater than 0".to_string(),
+                ));
+            }
+            count as usize
+        }
+        _ => return Err(CommandError::SyntaxError),
+    };
+    Ok((keys.to_vec(), end, count))
+}
+
+/// The reply of LMPOP: the key popped from and its elements, or a null
+/// array when every list was empty
+pub(super) fn popped_reply(popped: Option<(Bytes, Vec<Bytes>)>) -> Reply {
+    match popped {
+        Some((key, elements)) => Reply::Array(vec![Reply::Bulk(key), Reply::bulk_array(elements)]),
+        None => Reply::NullArray,
+    }
+}
+
+pub fn lmpop(db: &SharedDatabase, args: Vec<Bytes>) -> Reply {
+    lmpop_args(&args)
+        .and_then(|(keys, end, count)| db.lmpop(&keys, end, count))
+        .map(popped_reply)
+        .into()
+}
+
+/// The timeout of a blocking command, in seconds with an optional fraction;
+/// 0 waits forever
+fn parse_timeout(timeout: &Bytes) -> Result<Option<Duration>> {
+    let seconds = std::str::from_utf8(timeout)
+        .ok()
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|seconds| seconds.is_finite())
+        .ok_or(CommandError::InvalidTimeout)?;
+    if seconds < 0.0 {
+        return Err(CommandError::NegativeTimeout);
+    }
+    if seconds == 0.0 {
+        return Ok(None);
     }
+    Duration::try_from_secs_f64(seconds)
+        .map(Some)
+        .map_err(|_| CommandError::InvalidTimeout)
 }
 
-pub fn linsert(db: &SharedDatabase, key: Bytes, ord: Bytes, pivot: Bytes, value: Bytes) -> Bytes {
-    let ord_str = String::from_utf8_lossy(&ord);
-    match db.linsert(&key, &ord_str, &pivot, value) {
-        Ok(val) => format_integer(val),
-        Err(e) => format_error(e),
+pub async fn blpop(db: &SharedDatabase, keys: Vec<Bytes>, timeout: Bytes) -> Reply {
+    blocking_pop(db, keys, timeout, ListEnd::Left).await
+}
+
+pub async fn brpop(db: &SharedDatabase, keys: Vec<Bytes>, timeout: Bytes) -> Reply {
+    blocking_pop(db, keys, timeout, ListEnd::Right).await
+}
+
+/// BLPOP and BRPOP: the key and the element popped from it, or a null array
+/// when the timeout passed
+async fn blocking_pop(
+    db: &SharedDatabase,
+    keys: Vec<Bytes>,
+    timeout: Bytes,
+    end: ListEnd,
+) -> Reply {
+    let timeout = match parse_timeout(&timeout) {
+        Ok(timeout) => timeout,
+        Err(e) => return e.into(),
+    };
+    match db
+        .block_on_lists(&keys, BlockedOp::Pop { end, count: 1 }, timeout)
+        .await
+    {
+        Ok(Some((key, mut elements))) => {
+            Reply::Array(vec![Reply::Bulk(key), Reply::Bulk(elements.remove(0))])
+        }
+        Ok(None) => Reply::NullArray,
+        Err(e) => e.into(),
     }
 }
+
+pub async fn blmove(
+    db: &SharedDatabase,
+    source: Bytes,
+    destination: Bytes,
+    from: ListEnd,
+    to: ListEnd,
+    timeout: Bytes,
+) -> Reply {
+    let timeout = match parse_timeout(&timeout) {
+        Ok(timeout) => timeout,
+        Err(e) => return e.into(),
+    };
+    let op = BlockedOp::Move {
+        destination,
+        from,
+        to,
+    };
+    db.block_on_lists(&[source], op, timeout)
+        .await
+        .map(|moved| moved.map(|(_, mut elements)| elements.remove(0)))
+        .into()
+}
+
+pub async fn blmpop(db: &SharedDatabase, timeout: Bytes, args: Vec<Bytes>) -> Reply {
+    let parsed = parse_timeout(&timeout).and_then(|timeout| Ok((timeout, lmpop_args(&args)?)));
+    let (timeout, (keys, end, count)) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => return e.into(),
+    };
+    db.block_on_lists(&keys, BlockedOp::Pop { end, count }, timeout)
+        .await
+        .map(popped_reply)
+        .into()
+}
@@ -2,25 +2,31 @@
 // Handles parsing and executing Redis commands
 
 use crate::{
-    commands::command_helper::{
-        format_array_bytes, format_bulk_string, format_error, format_hash_response, format_integer,
-        format_null, format_simple_string,
+    data_structures::{
+        bitmap::{BitOperation, BitUnit},
+        ListEnd,
     },
     database::SharedDatabase,
+    modules,
     networking::resp::RespValue,
 };
 use bytes::Bytes;
+use std::sync::atomic::Ordering;
 
+pub mod bitmaps;
 pub mod connection;
 pub mod errors;
 pub mod hashes;
 pub mod keys;
 pub mod lists;
+pub mod reply;
+pub mod server;
 pub mod sets;
 pub mod strings;
 pub mod zsets;
 
 pub use errors::*;
+pub use reply::Reply;
 
 #[derive(Debug, PartialEq)]
 pub enum Command {
@@ -41,28 +47,46 @@ pub enum Command {
     MSet(Vec<(Bytes, Bytes)>), // MSET key value [key value ...] - Set multiple keys to multiple values
 
     // Hash Commands
-    HSet(Bytes, Bytes, Bytes), // HSET key field value - Set field in hash stored at key to value
-    HGet(Bytes, Bytes),        // HGET key field - Get value of field in hash stored at key
-    HDel(Bytes, Vec<Bytes>),   // HDEL key field [field ...] - Delete one or more hash fields
-    HGetAll(Bytes),            // HGETALL key - Get all fields and values in hash
-    HKeys(Bytes),              // HKEYS key - Get all field names in hash
-    HVals(Bytes),              // HVALS key - Get all values in hash
-    HLen(Bytes),               // HLEN key - Get number of fields in hash
-    HExists(Bytes, Bytes),     // HEXISTS key field - Check if field exists in hash
+    HSet(Bytes, Vec<Bytes>), // HSET key field value [field value ...] - Set fields in hash stored at key
+    HGet(Bytes, Bytes),      // HGET key field - Get value of field in hash stored at key
+    HDel(Bytes, Vec<Bytes>), // HDEL key field [field ...] - Delete one or more hash fields
+    HGetAll(Bytes),          // HGETALL key - Get all fields and values in hash
+    HKeys(Bytes),            // HKEYS key - Get all field names in hash
+    HVals(Bytes),            // HVALS key - Get all values in hash
+    HLen(Bytes),             // HLEN key - Get number of fields in hash
+    HExists(Bytes, Bytes),   // HEXISTS key field - Check if field exists in hash
     HIncrBy(Bytes, Bytes, Bytes), // HINCRBY key field increment - Increment integer value of hash field
     HIncrByFloat(Bytes, Bytes, Bytes), // HINCRBYFLOAT key field increment - Increment float value of hash field
+    HSetNx(Bytes, Bytes, Bytes), // HSETNX key field value - Set field only if it does not exist
+    HMSet(Bytes, Vec<Bytes>), // HMSET key field value [field value ...] - Set multiple hash fields
+    HMGet(Bytes, Vec<Bytes>), // HMGET key field [field ...] - Get values of multiple hash fields
+    HStrLen(Bytes, Bytes),    // HSTRLEN key field - Get length of the value of a hash field
+    HRandField(Bytes, Option<Bytes>, bool), // HRANDFIELD key [count [WITHVALUES]] - Get random fields from hash
+    HScan(Bytes, Bytes, ScanOptions), // HSCAN key cursor [MATCH pattern] [COUNT count] [NOVALUES] - Iterate hash fields
 
     // List Commands
     LPush(Bytes, Vec<Bytes>), // LPUSH key element [element ...] - Insert elements at head of list
     RPush(Bytes, Vec<Bytes>), // RPUSH key element [element ...] - Insert elements at tail of list
-    LPop(Bytes),              // LPOP key - Remove and return first element of list
-    RPop(Bytes),              // RPOP key - Remove and return last element of list
+    LPop(Bytes, Option<Bytes>), // LPOP key [count] - Remove and return first elements of list
+    RPop(Bytes, Option<Bytes>), // RPOP key [count] - Remove and return last elements of list
     LLen(Bytes),              // LLEN key - Get length of list
     LIndex(Bytes, Bytes),     // LINDEX key index - Get element at index in list
     LRange(Bytes, Bytes, Bytes), // LRANGE key start stop - Get range of elements from list
     LTrim(Bytes, Bytes, Bytes), // LTRIM key start stop - Trim list to specified range
     LSet(Bytes, Bytes, Bytes), // LSET key index element - Set element at index in list
     LInsert(Bytes, Bytes, Bytes, Bytes), // LINSERT key BEFORE|AFTER pivot element - Insert element before/after pivot
+    LPushX(Bytes, Vec<Bytes>), // LPUSHX key element [element ...] - Prepend elements only if the list exists
+    RPushX(Bytes, Vec<Bytes>), // RPUSHX key element [element ...] - Append elements only if the list exists
+    LPos(Bytes, Bytes, Vec<Bytes>), // LPOS key element [RANK rank] [COUNT num-matches] [MAXLEN len] - Find element indexes
+    LRem(Bytes, Bytes, Bytes), // LREM key count element - Remove occurrences of element from list
+    LMove(Bytes, Bytes, ListEnd, ListEnd), // LMOVE source destination LEFT|RIGHT LEFT|RIGHT - Move element between lists
+    RPopLPush(Bytes, Bytes), // RPOPLPUSH source destination - Move last element of a list to the head of another
+    LMPop(Vec<Bytes>), // LMPOP numkeys key [key ...] LEFT|RIGHT [COUNT count] - Pop from the first non-empty list
+    BLPop(Vec<Bytes>, Bytes), // BLPOP key [key ...] timeout - Pop the first element of the first non-empty list, blocking until there is one
+    BRPop(Vec<Bytes>, Bytes), // BRPOP key [key ...] timeout - Pop the last element of the first non-empty list, blocking until there is one
+    BLMove(Bytes, Bytes, ListEnd, ListEnd, Bytes), // BLMOVE source destination LEFT|RIGHT LEFT|RIGHT timeout - LMOVE, blocking until the source has elements
+    BRPopLPush(Bytes, Bytes, Bytes), // BRPOPLPUSH source destination timeout - RPOPLPUSH, blocking until the source has elements
+    BLMPop(Bytes, Vec<Bytes>), // BLMPOP timeout numkeys key [key ...] LEFT|RIGHT [COUNT count] - LMPOP, blocking until a list has elements
 
     // Set Commands
     SAdd(Bytes, Vec<Bytes>), // SADD key member [member ...] - Add members to set
@@ -73,6 +97,15 @@ pub enum Command {
     SInter(Vec<Bytes>),      // SINTER key [key ...] - Intersect multiple sets
     SUnion(Vec<Bytes>),      // SUNION key [key ...] - Union multiple sets
     SDiff(Vec<Bytes>),       // SDIFF key [key ...] - Subtract multiple sets
+    SInterStore(Bytes, Vec<Bytes>), // SINTERSTORE destination key [key ...] - Store the intersection of sets
+    SUnionStore(Bytes, Vec<Bytes>), // SUNIONSTORE destination key [key ...] - Store the union of sets
+    SDiffStore(Bytes, Vec<Bytes>), // SDIFFSTORE destination key [key ...] - Store the difference of sets
+    SInterCard(Vec<Bytes>), // SINTERCARD numkeys key [key ...] [LIMIT limit] - Count the members of an intersection
+    SPop(Bytes, Option<Bytes>), // SPOP key [count] - Remove and return random members of set
+    SRandMember(Bytes, Option<Bytes>), // SRANDMEMBER key [count] - Get random members of set
+    SMove(Bytes, Bytes, Bytes), // SMOVE source destination member - Move a member between sets
+    SMIsMember(Bytes, Vec<Bytes>), // SMISMEMBER key member [member ...] - Check if members exist in set
+    SScan(Bytes, Bytes, ScanOptions), // SSCAN key cursor [MATCH pattern] [COUNT count] - Iterate set members
 
     // Sorted Set Commands
     ZAdd(Bytes, Vec<(Bytes, Bytes)>), // ZADD key score member [score member ...] - Add members to sorted set
@@ -84,24 +117,66 @@ pub enum Command {
     ZRank(Bytes, Bytes),     // ZRANK key member - Get rank of member in sorted set
 
     // Key Commands
-    Exists(Vec<Bytes>),   // EXISTS key [key ...] - Check if keys exist
-    Expire(Bytes, Bytes), // EXPIRE key seconds - Set key expiration time
-    Ttl(Bytes),           // TTL key - Get remaining time to live of key
-    Type(Bytes),          // TYPE key - Get type of key
-    Keys(Bytes),          // KEYS pattern - Find keys matching pattern
-    FlushAll,             // FLUSHALL - Remove all keys from all databases
-    FlushDB,              // FLUSHDB - Remove all keys from current database
+    Exists(Vec<Bytes>),    // EXISTS key [key ...] - Check if keys exist
+    Expire(Bytes, Bytes),  // EXPIRE key seconds - Set key expiration time
+    Ttl(Bytes),            // TTL key - Get remaining time to live of key
+    Type(Bytes),           // TYPE key - Get type of key
+    ObjectEncoding(Bytes), // OBJECT ENCODING key - Get the internal encoding of the value of key
+    Keys(Bytes),           // KEYS pattern - Find keys matching pattern
+    FlushAll,              // FLUSHALL - Remove all keys from all databases
+    FlushDB,               // FLUSHDB - Remove all keys from current database
+    Dump(Bytes),           // DUMP key - Serialize the value stored at key
+    Restore(Bytes, Bytes, Bytes, RestoreOptions), // RESTORE key ttl serialized-value [REPLACE] [ABSTTL] [IDLETIME seconds] [FREQ frequency]
+    Migrate(MigrateOptions), // MIGRATE host port key|"" destination-db timeout [COPY] [REPLACE] [KEYS key ...]
 
     // Connection/Server Commands
     Echo(Bytes),         // ECHO message - Echo the given string
     Auth(Bytes),         // AUTH password - Authenticate to server
     Select(Bytes),       // SELECT index - Change selected database
     Info(Option<Bytes>), // INFO [section] - Get server information
+    // HELLO [protover] - Switch protocol, describe the server
+    Hello(Option<Bytes>),
 
     // Additional String Commands
     SetNX(Bytes, Bytes), // SETNX key value - Set key only if it doesn't exist
     SetEX(Bytes, Bytes, Bytes), // SETEX key seconds value - Set key with expiration
     GetSet(Bytes, Bytes), // GETSET key value - Set key and return old value
+    GetRange(Bytes, Bytes, Bytes), // GETRANGE key start end - Get a substring of the value of key
+    SubStr(Bytes, Bytes, Bytes), // SUBSTR key start end - Get a substring of the value of key
+    SetRange(Bytes, Bytes, Bytes), // SETRANGE key offset value - Overwrite part of the value of key
+    IncrByFloat(Bytes, Bytes), // INCRBYFLOAT key increment - Increment float value of key by increment
+    MSetNX(Vec<(Bytes, Bytes)>), // MSETNX key value [key value ...] - Set keys only if none of them exists
+    GetDel(Bytes),               // GETDEL key - Get value of key and delete it
+    GetEx(Bytes, Option<GetExOption>), // GETEX key [EX seconds|PX ms|EXAT time|PXAT time|PERSIST] - Get value of key and change its expiration
+    PSetEX(Bytes, Bytes, Bytes), // PSETEX key milliseconds value - Set key with expiration in milliseconds
+    Lcs(Bytes, Bytes, LcsOptions), // LCS key1 key2 [LEN] [IDX] [MINMATCHLEN len] [WITHMATCHLEN] - Longest common subsequence
+
+    // Bitmap Commands
+    SetBit(Bytes, Bytes, Bytes), // SETBIT key offset value - Set or clear the bit at offset
+    GetBit(Bytes, Bytes),        // GETBIT key offset - Get the bit at offset
+    BitCount(Bytes, Option<(Bytes, Bytes)>, BitUnit), // BITCOUNT key [start end [BYTE|BIT]] - Count set bits
+    BitPos(Bytes, Bytes, Option<Bytes>, Option<Bytes>, BitUnit), // BITPOS key bit [start [end [BYTE|BIT]]] - Find the first set or clear bit
+    BitOp(BitOperation, Bytes, Vec<Bytes>), // BITOP AND|OR|XOR|NOT|DIFF|ONE destkey key [key ...] - Combine bitmaps into destkey
+    BitField(Bytes, Vec<Bytes>), // BITFIELD key [GET type offset] [SET type offset value] [INCRBY type offset increment] [OVERFLOW WRAP|SAT|FAIL] ... - Integer fields of a bitmap
+    BitFieldRo(Bytes, Vec<Bytes>), // BITFIELD_RO key [GET type offset ...] - Read integer fields of a bitmap
+
+    // Admin Commands
+    KeyspaceExport(Option<Bytes>), // KEYSPACE EXPORT [DB index] - Dump keys as JSON lines
+    KeyspaceImport(Bytes, bool),   // KEYSPACE IMPORT payload [REPLACE] - Load keys from JSON lines
+    ConfigGet(Vec<Bytes>), // CONFIG GET pattern [pattern ...] - Read configuration parameters
+    ConfigSet(Vec<(Bytes, Bytes)>), // CONFIG SET parameter value [parameter value ...] - Change configuration at runtime
+    ConfigResetStat,                // CONFIG RESETSTAT - Reset the INFO counters
+    ConfigRewrite, // CONFIG REWRITE - Write the running configuration to the config file
+    CommandAll,    // COMMAND - Describe every command
+    CommandCount,  // COMMAND COUNT - Number of commands
+    CommandInfo(Vec<Bytes>), // COMMAND INFO [command-name ...] - Describe the given commands
+    CommandDocs(Vec<Bytes>), // COMMAND DOCS [command-name ...] - Documentation of the given commands
+    CommandList(Option<(Bytes, Bytes)>), // COMMAND LIST [FILTERBY MODULE|ACLCAT|PATTERN value] - Command names
+    CommandGetKeys(Vec<Bytes>), // COMMAND GETKEYS command [arg ...] - Keys of an arbitrary command
+    ModuleList,                 // MODULE LIST - Describe the loaded modules
+
+    // A command added by a module: its table name and arguments
+    Module(&'static str, Vec<Bytes>),
 }
 #[derive(Debug, PartialEq)]
 pub struct SetOptions {
@@ -111,6 +186,46 @@ pub struct SetOptions {
     pub px: Option<u64>, // milliseconds
     pub keepttl: bool,
 }
+#[derive(Debug, PartialEq, Default)]
+pub struct RestoreOptions {
+    pub replace: bool,
+    pub absttl: bool,
+    pub idletime: Option<Bytes>, // seconds
+    pub freq: Option<Bytes>,
+}
+/// GETEX expiration change; times are validated when the command runs
+#[derive(Debug, PartialEq)]
+pub enum GetExOption {
+    Ex(Bytes),
+    Px(Bytes),
+    ExAt(Bytes),
+    PxAt(Bytes),
+    Persist,
+}
+#[derive(Debug, PartialEq, Default)]
+pub struct LcsOptions {
+    pub len: bool,
+    pub idx: bool,
+    pub min_match_len: Option<Bytes>,
+    pub with_match_len: bool,
+}
+/// Options of the SCAN family; COUNT is validated when the command runs
+#[derive(Debug, PartialEq, Default)]
+pub struct ScanOptions {
+    pub pattern: Option<Bytes>,
+    pub count: Option<Bytes>,
+    pub novalues: bool,
+}
+#[derive(Debug, PartialEq)]
+pub struct MigrateOptions {
+    pub host: Bytes,
+    pub port: Bytes,
+    pub keys: Vec<Bytes>,
+    pub db: Bytes,
+    pub timeout: Bytes, // milliseconds
+    pub copy: bool,
+    pub replace: bool,
+}
 pub mod command_helper;
 
 macro_rules! parse_command {
@@ -174,87 +289,202 @@ macro_rules! parse_command {
     };
 }
 
+pub mod table;
+
 impl Command {
     pub fn parse(resp_value: &RespValue) -> Option<Self> {
-        match resp_value {
-            RespValue::Array(elements) if !elements.is_empty() => {
-                let command_name_bytes = command_helper::extract_bulk_string(&elements[0])?;
-                // Convert command name to uppercase string for matching (commands are ASCII usually)
-                let command_name = String::from_utf8_lossy(&command_name_bytes).to_uppercase();
+        Self::try_parse(resp_value).ok()
+    }
 
-                match command_name.as_str() {
-                    "PING" => parse_command!(option, elements, Ping),
-                    "QUIT" => parse_command!(none, elements, Quit),
-                    "GET" => parse_command!(single_key, elements, Get),
-                    "SET" => {
-                        parse_command!(key_value_options, elements, Set)
-                    }
-                    "DEL" => parse_command!(keys, elements, Del),
-                    "INCR" => parse_command!(single_key, elements, Incr),
-                    "DECR" => parse_command!(single_key, elements, Decr),
-                    "INCRBY" => parse_command!(key_value, elements, IncrBy),
-                    "DECRBY" => parse_command!(key_value, elements, DecrBy),
-                    "APPEND" => parse_command!(key_value, elements, Append),
-                    "STRLEN" => parse_command!(single_key, elements, Strlen),
-                    "MGET" => parse_command!(keys, elements, MGet),
-                    "MSET" => parse_command!(key_value_pairs, elements, MSet),
-                    "HSET" => parse_command!(key_field_value, elements, HSet),
-                    "HGET" => parse_command!(key_value, elements, HGet),
-                    "HDEL" => parse_command!(key_fields, elements, HDel),
-                    "HGETALL" => parse_command!(single_key, elements, HGetAll),
-                    "HKEYS" => parse_command!(single_key, elements, HKeys),
-                    "HVALS" => parse_command!(single_key, elements, HVals),
-                    "HLEN" => parse_command!(single_key, elements, HLen),
-                    "HEXISTS" => parse_command!(key_value, elements, HExists),
-                    "HINCRBY" => parse_command!(key_field_value, elements, HIncrBy),
-                    "HINCRBYFLOAT" => parse_command!(key_field_value, elements, HIncrByFloat),
-                    "LPUSH" => parse_command!(key_fields, elements, LPush),
-                    "RPUSH" => parse_command!(key_fields, elements, RPush),
-                    "LPOP" => parse_command!(single_key, elements, LPop),
-                    "RPOP" => parse_command!(single_key, elements, RPop),
-                    "LLen" => parse_command!(single_key, elements, LLen),
-                    "LINDEX" => parse_command!(key_value, elements, LIndex),
-                    "LRANGE" => parse_command!(key_field_value, elements, LRange),
-                    "LTRIM" => parse_command!(key_field_value, elements, LTrim),
-                    "LSET" => parse_command!(key_field_value, elements, LSet),
-                    "LINSERT" => parse_command!(key_ord_pivot_value, elements, LInsert),
-                    "SADD" => parse_command!(key_fields, elements, SAdd),
-                    "SREM" => parse_command!(key_fields, elements, SRem),
-                    "SMEMBERS" => parse_command!(single_key, elements, SMembers),
-                    "SCard" => parse_command!(single_key, elements, SCard),
-                    "SISMEMBER" => parse_command!(key_value, elements, SIsMember),
-                    "SINTER" => parse_command!(keys, elements, SInter),
-                    "SUNION" => parse_command!(keys, elements, SUnion),
-                    "SDiff" => parse_command!(keys, elements, SDiff),
-                    "ZADD" => parse_command!(key_pair_values, elements, ZAdd),
-                    "ZREM" => parse_command!(key_fields, elements, ZRem),
-                    "ZRANGE" => parse_command!(key_field_value, elements, ZRange),
-                    "ZRANGEBYSCORE" => parse_command!(key_field_value, elements, ZRangeByScore),
-                    "ZCARD" => parse_command!(single_key, elements, ZCard),
-                    "ZSCORE" => parse_command!(key_value, elements, ZScore),
-                    "ZRANK" => parse_command!(key_value, elements, ZRank),
-                    "EXISTS" => parse_command!(keys, elements, Exists),
-                    "EXPIRE" => parse_command!(key_value, elements, Expire),
-                    "TTL" => parse_command!(single_key, elements, Ttl),
-                    "TYPE" => parse_command!(single_key, elements, Type),
-                    "KEYS" => parse_command!(single_key, elements, Keys),
-                    "FLUSHALL" => parse_command!(none, elements, FlushAll),
-                    "FLUSHDB" => parse_command!(none, elements, FlushDB),
-                    "ECHO" => parse_command!(single_key, elements, Echo),
-                    "AUTH" => parse_command!(single_key, elements, Auth),
-                    "SELECT" => parse_command!(single_key, elements, Select),
-                    "INFO" => parse_command!(option, elements, Info),
-                    "SETNX" => parse_command!(key_value, elements, SetNX),
-                    "SETEX" => parse_command!(key_field_value, elements, SetEX),
-                    "GETSET" => parse_command!(key_value, elements, GetSet),
-                    _ => None,
-                }
-            }
-            _ => None,
+    /// Parse a request, reporting why it was rejected the way Redis does
+    pub fn try_parse(resp_value: &RespValue) -> Result<Self> {
+        let RespValue::Array(elements) = resp_value else {
+            return Err(CommandError::InvalidCommandFormat);
+        };
+        let args = command_helper::extract_bulk_strings(elements)
+            .filter(|args| !args.is_empty())
+            .ok_or(CommandError::InvalidCommandFormat)?;
+        let Some(mut spec) = table::lookup(&String::from_utf8_lossy(&args[0])) else {
+            return Err(CommandError::unknown_command(&args[0], &args[1..]));
+        };
+        // Container commands take their arity and parser from the subcommand
+        if !spec.subcommands.is_empty() && args.len() > 1 {
+            let subcommand = String::from_utf8_lossy(&args[1]);
+            spec = table::lookup(&format!("{}|{}", spec.name, subcommand.to_lowercase()))
+                .ok_or_else(|| CommandError::UnknownSubcommand {
+                    command: spec.name.to_uppercase(),
+                    subcommand: subcommand.to_string(),
+                })?;
+        }
+        if !spec.arity_ok(args.len()) {
+            return Err(CommandError::WrongArity(spec.name.to_string()));
+        }
+        (spec.parse)(elements).ok_or(CommandError::SyntaxError)
+    }
+
+    /// The table name of the command, `container|subcommand` for subcommands
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Ping(_) => "ping",
+            Command::Quit => "quit",
+            Command::Get(_) => "get",
+            Command::Set(..) => "set",
+            Command::Del(_) => "del",
+            Command::Incr(_) => "incr",
+            Command::Decr(_) => "decr",
+            Command::IncrBy(..) => "incrby",
+            Command::DecrBy(..) => "decrby",
+            Command::Append(..) => "append",
+            Command::Strlen(_) => "strlen",
+            Command::MGet(_) => "mget",
+            Command::MSet(_) => "mset",
+            Command::HSet(..) => "hset",
+            Command::HGet(..) => "hget",
+            Command::HDel(..) => "hdel",
+            Command::HGetAll(_) => "hgetall",
+            Command::HKeys(_) => "hkeys",
+            Command::HVals(_) => "hvals",
+            Command::HLen(_) => "hlen",
+            Command::HExists(..) => "hexists",
+            Command::HIncrBy(..) => "hincrby",
+            Command::HIncrByFloat(..) => "hincrbyfloat",
+            Command::HSetNx(..) => "hsetnx",
+            Command::HMSet(..) => "hmset",
+            Command::HMGet(..) => "hmget",
+            Command::HStrLen(..) => "hstrlen",
+            Command::HRandField(..) => "hrandfield",
+            Command::HScan(..) => "hscan",
+            Command::LPush(..) => "lpush",
+            Command::RPush(..) => "rpush",
+            Command::LPop(..) => "lpop",
+            Command::RPop(..) => "rpop",
+            Command::LLen(_) => "llen",
+            Command::LIndex(..) => "lindex",
+            Command::LRange(..) => "lrange",
+            Command::LTrim(..) => "ltrim",
+            Command::LSet(..) => "lset",
+            Command::LInsert(..) => "linsert",
+            Command::LPushX(..) => "lpushx",
+            Command::RPushX(..) => "rpushx",
+            Command::LPos(..) => "lpos",
+            Command::LRem(..) => "lrem",
+            Command::LMove(..) => "lmove",
+            Command::RPopLPush(..) => "rpoplpush",
+            Command::LMPop(_) => "lmpop",
+            Command::BLPop(..) => "blpop",
+            Command::BRPop(..) => "brpop",
+            Command::BLMove(..) => "blmove",
+            Command::BRPopLPush(..) => "brpoplpush",
+            Command::BLMPop(..) => "blmpop",
+            Command::SAdd(..) => "sadd",
+            Command::SRem(..) => "srem",
+            Command::SMembers(_) => "smembers",
+            Command::SCard(_) => "scard",
+            Command::SIsMember(..) => "sismember",
+            Command::SInter(_) => "sinter",
+            Command::SUnion(_) => "sunion",
+            Command::SDiff(_) => "sdiff",
+            Command::SInterStore(..) => "sinterstore",
+            Command::SUnionStore(..) => "sunionstore",
+            Command::SDiffStore(..) => "sdiffstore",
+            Command::SInterCard(_) => "sintercard",
+            Command::SPop(..) => "spop",
+            Command::SRandMember(..) => "srandmember",
+            Command::SMove(..) => "smove",
+            Command::SMIsMember(..) => "smismember",
+            Command::SScan(..) => "sscan",
+            Command::ZAdd(..) => "zadd",
+            Command::ZRem(..) => "zrem",
+            Command::ZRange(..) => "zrange",
+            Command::ZRangeByScore(..) => "zrangebyscore",
+            Command::ZCard(_) => "zcard",
+            Command::ZScore(..) => "zscore",
+            Command::ZRank(..) => "zrank",
+            Command::Exists(_) => "exists",
+            Command::Expire(..) => "expire",
+            Command::Ttl(_) => "ttl",
+            Command::Type(_) => "type",
+            Command::ObjectEncoding(_) => "object|encoding",
+            Command::Keys(_) => "keys",
+            Command::FlushAll => "flushall",
+            Command::FlushDB => "flushdb",
+            Command::Dump(_) => "dump",
+            Command::Restore(..) => "restore",
+            Command::Migrate(_) => "migrate",
+            Command::Echo(_) => "echo",
+            Command::Auth(_) => "auth",
+            Command::Select(_) => "select",
+            Command::Hello(_) => "hello",
+            Command::Info(_) => "info",
+            Command::SetNX(..) => "setnx",
+            Command::SetEX(..) => "setex",
+            Command::GetSet(..) => "getset",
+            Command::GetRange(..) => "getrange",
+            Command::SubStr(..) => "substr",
+            Command::SetRange(..) => "setrange",
+            Command::IncrByFloat(..) => "incrbyfloat",
+            Command::MSetNX(_) => "msetnx",
+            Command::GetDel(_) => "getdel",
+            Command::GetEx(..) => "getex",
+            Command::PSetEX(..) => "psetex",
+            Command::Lcs(..) => "lcs",
+            Command::SetBit(..) => "setbit",
+            Command::GetBit(..) => "getbit",
+            Command::BitCount(..) => "bitcount",
+            Command::BitPos(..) => "bitpos",
+            Command::BitOp(..) => "bitop",
+            Command::BitField(..) => "bitfield",
+            Command::BitFieldRo(..) => "bitfield_ro",
+            Command::KeyspaceExport(_) => "keyspace|export",
+            Command::KeyspaceImport(..) => "keyspace|import",
+            Command::ConfigGet(_) => "config|get",
+            Command::ConfigSet(_) => "config|set",
+            Command::ConfigResetStat => "config|resetstat",
+            Command::ConfigRewrite => "config|rewrite",
+            Command::CommandAll => "command",
+            Command::CommandCount => "command|count",
+            Command::CommandInfo(_) => "command|info",
+            Command::CommandDocs(_) => "command|docs",
+            Command::CommandList(_) => "command|list",
+            Command::CommandGetKeys(_) => "command|getkeys",
+            Command::ModuleList => "module|list",
+            Command::Module(name, _) => name,
+        }
+    }
+
+    pub fn spec(&self) -> &'static table::CommandSpec {
+        table::lookup(self.name()).expect("every command is in the command table")
+    }
+
+    /// Commands that may modify the keyspace
+    pub fn is_write(&self) -> bool {
+        self.spec().has_flag(table::Flag::Write)
+    }
+
+    /// Commands that may wait for another client to push onto a list
+    pub fn is_blocking(&self) -> bool {
+        self.spec().has_flag(table::Flag::Blocking)
+    }
+
+    /// Writes that can grow the dataset, refused while over `maxmemory`
+    pub fn is_denied_oom(&self) -> bool {
+        self.spec().has_flag(table::Flag::DenyOom)
+    }
+
+    /// Run the command. The reply is encoded by the caller for whatever
+    /// protocol its client speaks.
+    pub async fn execute(self, db: &SharedDatabase) -> Reply {
+        db.stats()
+            .commands_processed
+            .fetch_add(1, Ordering::Relaxed);
+        if self.is_denied_oom() && db.over_maxmemory() {
+            return CommandError::OutOfMemory.into();
         }
+        // Writes count what they change towards the `save` points themselves
+        self.dispatch(db).await
     }
 
-    pub async fn execute(self, db: &SharedDatabase) -> Bytes {
+    async fn dispatch(self, db: &SharedDatabase) -> Reply {
         match self {
             Command::Ping(msg) => connection::ping(msg),
             Command::Quit => connection::quit(),
@@ -269,7 +499,7 @@ impl Command {
             Command::Strlen(key) => strings::strlen(db, key),
             Command::MGet(keys) => strings::mget(db, keys),
             Command::MSet(key_values) => strings::mset(db, key_values),
-            Command::HSet(hash, field, value) => hashes::hset(db, hash, field, value),
+            Command::HSet(hash, fields) => hashes::hset(db, hash, fields),
             Command::HGet(hash, field) => hashes::hget(db, hash, field),
             Command::HDel(hash, fields) => hashes::hdel(db, hash, fields),
             Command::HGetAll(key) => hashes::hgetall(db, key),
@@ -281,16 +511,45 @@ impl Command {
             Command::HIncrByFloat(hash, field, value) => {
                 hashes::hincrbyfloat(db, hash, field, value)
             }
+            Command::HSetNx(hash, field, value) => hashes::hsetnx(db, hash, field, value),
+            Command::HMSet(hash, fields) => hashes::hmset(db, hash, fields),
+            Command::HMGet(hash, fields) => hashes::hmget(db, hash, fields),
+            Command::HStrLen(hash, field) => hashes::hstrlen(db, hash, field),
+            Command::HRandField(hash, count, with_values) => {
+                hashes::hrandfield(db, hash, count, with_values)
+            }
+            Command::HScan(hash, cursor, options) => hashes::hscan(db, hash, cursor, options),
             Command::LPush(key, value) => lists::lpush(db, key, value),
             Command::RPush(key, value) => lists::rpush(db, key, value),
-            Command::LPop(key) => lists::lpop(db, key),
-            Command::RPop(key) => lists::rpop(db, key),
+            Command::LPop(key, count) => lists::lpop(db, key, count),
+            Command::RPop(key, count) => lists::rpop(db, key, count),
             Command::LLen(key) => lists::llen(db, key),
             Command::LIndex(key, index) => lists::lindex(db, key, index),
             Command::LRange(key, start, end) => lists::lrange(db, key, start, end),
             Command::LTrim(key, start, end) => lists::ltrim(db, key, start, end),
             Command::LSet(key, index, value) => lists::lset(db, key, index, value),
             Command::LInsert(key, ord, pivot, value) => lists::linsert(db, key, ord, pivot, value),
+            Command::LPushX(key, values) => lists::lpushx(db, key, values),
+            Command::RPushX(key, values) => lists::rpushx(db, key, values),
+            Command::LPos(key, element, options) => lists::lpos(db, key, element, options),
+            Command::LRem(key, count, element) => lists::lrem(db, key, count, element),
+            Command::LMove(source, destination, from, to) => {
+                lists::lmove(db, source, destination, from, to)
+            }
+            Command::RPopLPush(source, destination) => {
+                lists::lmove(db, source, destination, ListEnd::Right, ListEnd::Left)
+            }
+            Command::LMPop(args) => lists::lmpop(db, args),
+            Command::BLPop(keys, timeout) => lists::blpop(db, keys, timeout).await,
+            Command::BRPop(keys, timeout) => lists::brpop(db, keys, timeout).await,
+            Command::BLMove(source, destination, from, to, timeout) => {
+                lists::blmove(db, source, destination, from, to, timeout).await
+            }
+            Command::BRPopLPush(source, destination, timeout) => {
+                let (from, to) = (ListEnd::Right, ListEnd::Left);
+                lists::blmove(db, source, destination, from, to, timeout).await
+            }
+            Command::BLMPop(timeout, args) => lists::blmpop(db, timeout, args).await,
             Command::SAdd(key, values) => sets::sadd(db, key, values),
             Command::SRem(key, values) => sets::srem(db, key, values),
             Command::SMembers(key) => sets::smembers(db, key),
@@ -299,6 +558,17 @@ impl Command {
             Command::SInter(items) => sets::sinter(db, items),
             Command::SUnion(items) => sets::sunion(db, items),
             Command::SDiff(items) => sets::sdiff(db, items),
+            Command::SInterStore(destination, keys) => sets::sinterstore(db, destination, keys),
+            Command::SUnionStore(destination, keys) => sets::sunionstore(db, destination, keys),
+            Command::SDiffStore(destination, keys) => sets::sdiffstore(db, destination, keys),
+            Command::SInterCard(args) => sets::sintercard(db, args),
+            Command::SPop(key, count) => sets::spop(db, key, count),
+            Command::SRandMember(key, count) => sets::srandmember(db, key, count),
+            Command::SMove(source, destination, member) => {
+                sets::smove(db, source, destination, member)
+            }
+            Command::SMIsMember(key, members) => sets::smismember(db, key, members),
+            Command::SScan(key, cursor, options) => sets::sscan(db, key, cursor, options),
             Command::ZAdd(key, pairs) => zsets::zadd(db, key, pairs),
             Command::ZRem(key, members) => zsets::zrem(db, key, members),
             Command::ZRange(key, start, stop) => zsets::zrange(db, key, start, stop),
@@ -310,16 +580,58 @@ impl Command {
             Command::Expire(key, seconds) => keys::expire(db, key, seconds),
             Command::Ttl(key) => keys::ttl(db, key),
             Command::Type(key) => keys::type_(db, key),
+            Command::ObjectEncoding(key) => keys::object_encoding(db, key),
             Command::Keys(pattern) => keys::keys(db, pattern),
             Command::FlushAll => keys::flushall(db),
             Command::FlushDB => keys::flushdb(db),
+            Command::Dump(key) => keys::dump(db, key),
+            Command::Restore(key, ttl, payload, options) => {
+                keys::restore(db, key, ttl, payload, options)
+            }
+            Command::Migrate(options) => keys::migrate(db, options).await,
             Command::Echo(msg) => connection::echo(msg),
             Command::Auth(msg) => connection::auth(msg),
             Command::Select(db_index) => connection::select(db, db_index),
-            Command::Info(section) => connection::info(section),
+            Command::Hello(version) => connection::hello(version),
+            Command::Info(section) => connection::info(db, section),
             Command::SetNX(key, value) => strings::setnx(db, key, value),
             Command::SetEX(key, seconds, value) => strings::setex(db, key, seconds, value),
             Command::GetSet(key, value) => strings::getset(db, key, value),
+            Command::GetRange(key, start, end) | Command::SubStr(key, start, end) => {
+                strings::getrange(db, key, start, end)
+            }
+            Command::SetRange(key, offset, value) => strings::setrange(db, key, offset, value),
+            Command::IncrByFloat(key, value) => strings::incr_by_float(db, key, value),
+            Command::MSetNX(pairs) => strings::msetnx(db, pairs),
+            Command::GetDel(key) => strings::getdel(db, key),
+            Command::GetEx(key, option) => strings::getex(db, key, option),
+            Command::PSetEX(key, millis, value) => strings::psetex(db, key, millis, value),
+            Command::Lcs(key1, key2, options) => strings::lcs(db, key1, key2, options),
+            Command::SetBit(key, offset, value) => bitmaps::setbit(db, key, offset, value),
+            Command::GetBit(key, offset) => bitmaps::getbit(db, key, offset),
+            Command::BitCount(key, range, unit) => bitmaps::bitcount(db, key, range, unit),
+            Command::BitPos(key, bit, start, end, unit) => {
+                bitmaps::bitpos(db, key, bit, start, end, unit)
+            }
+            Command::BitOp(op, dest, keys) => bitmaps::bitop(db, op, dest, keys),
+            Command::BitField(key, args) => bitmaps::bitfield(db, key, args),
+            Command::BitFieldRo(key, args) => bitmaps::bitfield_ro(db, key, args),
+            Command::KeyspaceExport(index) => server::keyspace_export(db, index),
+            Command::KeyspaceImport(payload, replace) => {
+                server::keyspace_import(db, payload, replace)
+            }
+            Command::ConfigGet(patterns) => server::config_get(db, patterns),
+            Command::ConfigSet(pairs) => server::config_set(db, pairs),
+            Command::ConfigResetStat => server::config_resetstat(db),
+            Command::ConfigRewrite => server::config_rewrite(db),
+            Command::CommandAll => server::command_info(Vec::new()),
+            Command::CommandCount => server::command_count(),
+            Command::CommandInfo(names) => server::command_info(names),
+            Command::CommandDocs(names) => server::command_docs(names),
+            Command::CommandList(filter) => server::command_list(filter),
+            Command::CommandGetKeys(args) => server::command_getkeys(args),
+            Command::ModuleList => modules::list(),
+            Command::Module(name, args) => modules::call(name, db, &args),
         }
     }
 }
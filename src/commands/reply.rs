@@ -0,0 +1,213 @@
+// Typed command replies
+// Handlers build a `Reply`; the networking layer encodes it for the
+// protocol the connection speaks (see `networking::resp::encode`).
+
+use crate::commands::command_helper::has_error_code;
+use crate::commands::CommandError;
+use bytes::Bytes;
+use std::borrow::Cow;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    /// Simple string such as `OK` or `PONG`
+    Status(Cow<'static, str>),
+    /// Error message, starting with its code (`ERR`, `WRONGTYPE`, ...)
+    Error(String),
+    Integer(i64),
+    Bulk(Bytes),
+    /// Missing value, e.g. GET on a missing key
+    Null,
+    /// Missing array; RESP2 tells it apart from a missing bulk string
+    NullArray,
+    Array(Vec<Reply>),
+    /// Field/value pairs, sent as a flat array to RESP2 clients
+    Map(Vec<(Reply, Reply)>),
+    /// Pairs that may repeat, e.g. HRANDFIELD with a negative count, sent as
+    /// two-element arrays to RESP3 clients and flattened for RESP2 ones
+    Pairs(Vec<(Reply, Reply)>),
+    /// Unordered members, sent as an array to RESP2 clients
+    Set(Vec<Reply>),
+    /// Floating point number, sent as a bulk string to RESP2 clients
+    Double(f64),
+    /// Out-of-band message such as a pub/sub notification
+    Push(Vec<Reply>),
+}
+
+impl Reply {
+    pub const fn ok() -> Reply {
+        Reply::Status(Cow::Borrowed("OK"))
+    }
+
+    pub const fn status(value: &'static str) -> Reply {
+        Reply::Status(Cow::Borrowed(value))
+    }
+
+    /// An error reply. Messages carry their own error code; one without a
+    /// code is sent as a generic `ERR`. Line breaks would end the reply
+    /// early, so they become spaces.
+    pub fn error(error: impl std::fmt::Display) -> Reply {
+        let message = error.to_string().replace(['\r', '\n'], " ");
+        if has_error_code(&message) {
+            Reply::Error(message)
+        } else {
+            Reply::Error(format!("ERR {}", message))
+        }
+    }
+
+    pub fn bulk(value: impl Into<Bytes>) -> Reply {
+        Reply::Bulk(value.into())
+    }
+
+    /// An array of bulk strings, e.g. the elements returned by LRANGE
+    pub fn bulk_array(values: Vec<Bytes>) -> Reply {
+        Reply::Array(values.into_iter().map(Reply::Bulk).collect())
+    }
+
+    /// A set of bulk strings, e.g. the members returned by SMEMBERS
+    pub fn bulk_set(values: Vec<Bytes>) -> Reply {
+        Reply::Set(values.into_iter().map(Reply::Bulk).collect())
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, Reply::Error(_))
+    }
+}
+
+impl From<CommandError> for Reply {
+    fn from(error: CommandError) -> Reply {
+        Reply::error(error)
+    }
+}
+
+impl From<i64> for Reply {
+    fn from(value: i64) -> Reply {
+        Reply::Integer(value)
+    }
+}
+
+impl From<usize> for Reply {
+    fn from(value: usize) -> Reply {
+        Reply::Integer(value as i64)
+    }
+}
+
+impl From<bool> for Reply {
+    fn from(value: bool) -> Reply {
+        Reply::Integer(value as i64)
+    }
+}
+
+impl From<Bytes> for Reply {
+    fn from(value: Bytes) -> Reply {
+        Reply::Bulk(value)
+    }
+}
+
+impl<T: Into<Reply>> From<Option<T>> for Reply {
+    fn from(value: Option<T>) -> Reply {
+        value.map_or(Reply::Null, Into::into)
+    }
+}
+
+impl<T: Into<Reply>> From<Result<T, CommandError>> for Reply {
+    fn from(result: Result<T, CommandError>) -> Reply {
+        result.map_or_else(Reply::from, Into::into)
+    }
+}
+
+/// Conversion of a successful reply into a typed value, for clients.
+/// Error replies are handled by the caller.
+pub trait FromReply: Sized {
+    fn from_reply(reply: Reply) -> Result<Self, CommandError>;
+}
+
+fn unexpected<T>(reply: Reply) -> Result<T, CommandError> {
+    Err(CommandError::Custom(format!(
+        "unexpected reply {:?}",
+        reply
+    )))
+}
+
+impl FromReply for Reply {
+    fn from_reply(reply: Reply) -> Result<Self, CommandError> {
+        Ok(reply)
+    }
+}
+
+impl FromReply for () {
+    fn from_reply(reply: Reply) -> Result<Self, CommandError> {
+        match reply {
+            Reply::Status(_) => Ok(()),
+            reply => unexpected(reply),
+        }
+    }
+}
+
+impl FromReply for i64 {
+    fn from_reply(reply: Reply) -> Result<Self, CommandError> {
+        match reply {
+            Reply::Integer(value) => Ok(value),
+            reply => unexpected(reply),
+        }
+    }
+}
+
+impl FromReply for bool {
+    fn from_reply(reply: Reply) -> Result<Self, CommandError> {
+        i64::from_reply(reply).map(|value| value > 0)
+    }
+}
+
+impl FromReply for Bytes {
+    fn from_reply(reply: Reply) -> Result<Self, CommandError> {
+        match reply {
+            Reply::Bulk(value) => Ok(value),
+            reply => unexpected(reply),
+        }
+    }
+}
+
+impl FromReply for f64 {
+    fn from_reply(reply: Reply) -> Result<Self, CommandError> {
+        match reply {
+            Reply::Double(value) => Ok(value),
+            Reply::Bulk(value) => std::str::from_utf8(&value)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .ok_or(CommandError::InvalidFloat),
+            reply => unexpected(reply),
+        }
+    }
+}
+
+impl<T: FromReply> FromReply for Option<T> {
+    fn from_reply(reply: Reply) -> Result<Self, CommandError> {
+        match reply {
+            Reply::Null | Reply::NullArray => Ok(None),
+            reply => T::from_reply(reply).map(Some),
+        }
+    }
+}
+
+impl FromReply for Vec<Bytes> {
+    fn from_reply(reply: Reply) -> Result<Self, CommandError> {
+        match reply {
+            Reply::Array(items) | Reply::Set(items) => {
+                items.into_iter().map(Bytes::from_reply).collect()
+            }
+            reply => unexpected(reply),
+        }
+    }
+}
+
+impl FromReply for Vec<(Bytes, Bytes)> {
+    fn from_reply(reply: Reply) -> Result<Self, CommandError> {
+        match reply {
+            Reply::Map(pairs) | Reply::Pairs(pairs) => pairs
+                .into_iter()
+                .map(|(field, value)| Ok((Bytes::from_reply(field)?, Bytes::from_reply(value)?)))
+                .collect(),
+            reply => unexpected(reply),
+        }
+    }
+}
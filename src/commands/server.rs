@@ -0,0 +1,225 @@
+use crate::commands::command_helper::glob_match;
+use crate::commands::table::{self, CommandSpec};
+use crate::commands::{CommandError, Reply};
+use crate::config::registry;
+use crate::database::SharedDatabase;
+use crate::modules;
+use crate::persistence::jsonl;
+use bytes::Bytes;
+
+fn bulk(value: &'static str) -> Reply {
+    Reply::Bulk(Bytes::from_static(value.as_bytes()))
+}
+
+pub fn keyspace_export(db: &SharedDatabase, index: Option<Bytes>) -> Reply {
+    let index = match index {
+        Some(index) => match std::str::from_utf8(&index)
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            Some(i) => Some(i),
+            None => return Reply::error("ERR invalid DB index"),
+        },
+        None => None,
+    };
+    let mut out = Vec::new();
+    match jsonl::export(db, index, &mut out) {
+        Ok(_) => Reply::bulk(out),
+        Err(e) => CommandError::Custom(e.to_string()).into(),
+    }
+}
+
+pub fn keyspace_import(db: &SharedDatabase, payload: Bytes, replace: bool) -> Reply {
+    let records = match std::str::from_utf8(&payload) {
+        Ok(text) => jsonl::parse(text),
+        Err(_) => return Reply::error("ERR payload is not valid UTF-8"),
+    };
+    match records.and_then(|records| jsonl::import(db, records, replace)) {
+        Ok(count) => count.into(),
+        Err(e) => CommandError::Custom(e.to_string()).into(),
+    }
+}
+
+pub fn config_get(db: &SharedDatabase, patterns: Vec<Bytes>) -> Reply {
+    let config = db.config();
+    let mut reply = Vec::new();
+    for param in registry::PARAMS {
+        if patterns
+            .iter()
+            .any(|p| glob_match(p, param.name.as_bytes(), true))
+        {
+            reply.push((bulk(param.name), Reply::bulk((param.get)(&config))));
+        }
+    }
+    Reply::Map(reply)
+}
+
+pub fn config_set(db: &SharedDatabase, pairs: Vec<(Bytes, Bytes)>) -> Reply {
+    // Apply every change to a copy first so a failure leaves nothing changed
+    let mut current = db.config.write();
+    let mut config = current.clone();
+    let mut changed: Vec<&registry::ConfigParam> = Vec::new();
+    for (name, value) in pairs {
+        let name = String::from_utf8_lossy(&name).to_lowercase();
+        let failed = |reason: &str| {
+            Reply::error(format!(
+                "ERR CONFIG SET failed (possibly related to argument '{}') - {}",
+                name, reason
+            ))
+        };
+        let Some(param) = registry::lookup(&name) else {
+            return Reply::error(format!(
+                "ERR Unknown option or number of arguments for CONFIG SET - '{}'",
+                name
+            ));
+        };
+        if !param.mutable {
+            return failed("can't set immutable config");
+        }
+        if changed.iter().any(|p| p.name == param.name) {
+            return failed("duplicate parameter");
+        }
+        let value = String::from_utf8_lossy(&value).to_string();
+        if let Err(reason) = config.set_directive(param.name, &[value]) {
+            return failed(&reason);
+        }
+        changed.push(param);
+    }
+    if let Err(e) = config.validate() {
+        return CommandError::Custom(e.to_string()).into();
+    }
+    *current = config.clone();
+    drop(current);
+    for param in changed {
+        if let Some(apply) = param.apply {
+            apply(db, &config);
+        }
+    }
+    Reply::ok()
+}
+
+pub fn config_resetstat(db: &SharedDatabase) -> Reply {
+    db.reset_stats();
+    Reply::ok()
+}
+
+pub fn config_rewrite(db: &SharedDatabase) -> Reply {
+    let config = db.config().clone();
+    let Some(path) = &config.config_file else {
+        return Reply::error("ERR The server is running without a config file");
+    };
+    match registry::rewrite(&config, path) {
+        Ok(()) => Reply::ok(),
+        Err(e) => Reply::error(format!("ERR Rewriting config file: {}", e)),
+    }
+}
+
+/// One COMMAND INFO entry: name, arity, flags, first key, last key, step,
+/// ACL categories, tips, key specs and subcommands
+fn command_entry(spec: &CommandSpec) -> Reply {
+    Reply::Array(vec![
+        bulk(spec.name),
+        Reply::Integer(spec.arity as i64),
+        Reply::Set(spec.flags.iter().map(|f| Reply::status(f.name())).collect()),
+        Reply::Integer(spec.first_key as i64),
+        Reply::Integer(spec.last_key as i64),
+        Reply::Integer(spec.step as i64),
+        Reply::Set(spec.categories.iter().map(|c| Reply::status(c)).collect()),
+        Reply::Set(Vec::new()),
+        Reply::Array(Vec::new()),
+        Reply::Array(spec.subcommands.iter().map(command_entry).collect()),
+    ])
+}
+
+pub fn command_info(names: Vec<Bytes>) -> Reply {
+    if names.is_empty() {
+        return Reply::Array(table::all().into_iter().map(command_entry).collect());
+    }
+    Reply::Array(
+        names
+            .iter()
+            .map(|name| match table::lookup(&String::from_utf8_lossy(name)) {
+                Some(spec) => command_entry(spec),
+                None => Reply::NullArray,
+            })
+            .collect(),
+    )
+}
+
+pub fn command_count() -> Reply {
+    table::all().len().into()
+}
+
+fn command_doc(spec: &CommandSpec) -> Reply {
+    let mut doc = vec![
+        (bulk("summary"), bulk(spec.summary)),
+        (bulk("group"), bulk(spec.group)),
+    ];
+    if !spec.subcommands.is_empty() {
+        doc.push((
+            bulk("subcommands"),
+            Reply::Map(
+                spec.subcommands
+                    .iter()
+                    .map(|sub| (bulk(sub.name), command_doc(sub)))
+                    .collect(),
+            ),
+        ));
+    }
+    Reply::Map(doc)
+}
+
+pub fn command_docs(names: Vec<Bytes>) -> Reply {
+    let specs: Vec<&CommandSpec> = if names.is_empty() {
+        table::all()
+    } else {
+        names
+            .iter()
+            .filter_map(|name| table::lookup(&String::from_utf8_lossy(name)))
+            .collect()
+    };
+    Reply::Map(
+        specs
+            .into_iter()
+            .map(|spec| (bulk(spec.name), command_doc(spec)))
+            .collect(),
+    )
+}
+
+pub fn command_list(filter: Option<(Bytes, Bytes)>) -> Reply {
+    let all = table::all()
+        .into_iter()
+        .flat_map(|spec| std::iter::once(spec).chain(spec.subcommands.iter()));
+    let names: Vec<Reply> = all
+        .filter(|spec| match &filter {
+            None => true,
+            Some((kind, value)) => match kind.as_ref() {
+                b"ACLCAT" => spec.categories.iter().any(|c| {
+                    c.trim_start_matches('@')
+                        .as_bytes()
+                        .eq_ignore_ascii_case(value)
+                }),
+                b"PATTERN" => glob_match(value, spec.name.as_bytes(), true),
+                b"MODULE" => modules::module_of(spec.name)
+                    .is_some_and(|module| module.as_bytes().eq_ignore_ascii_case(value)),
+                _ => false,
+            },
+        })
+        .map(|spec| bulk(spec.name))
+        .collect();
+    Reply::Array(names)
+}
+
+pub fn command_getkeys(args: Vec<Bytes>) -> Reply {
+    let Some(spec) = table::lookup_args(&args) else {
+        return Reply::error("ERR Invalid command specified");
+    };
+    if !spec.arity_ok(args.len()) {
+        return Reply::error("ERR Invalid number of arguments specified for command");
+    }
+    let keys = spec.keys(&args);
+    if keys.is_empty() {
+        return Reply::error("ERR The command has no key arguments");
+    }
+    Reply::bulk_array(keys)
+}
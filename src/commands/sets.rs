@@ -1,48 +1,143 @@
-use crate::commands::command_helper::{format_array_bytes, format_error, format_integer};
+use super::keys::{scan_arguments, scan_matches};
+use super::strings::parse_integer;
+use crate::commands::{CommandError, Reply, Result, ScanOptions};
 use crate::database::traits::SetOp;
 use crate::database::SharedDatabase;
 use bytes::Bytes;
 
-pub fn sadd(db: &SharedDatabase, key: Bytes, values: Vec<Bytes>) -> Bytes {
-    format_integer(db.sadd(&key, &values) as i64)
+pub fn sadd(db: &SharedDatabase, key: Bytes, values: Vec<Bytes>) -> Reply {
+    db.sadd(&key, &values).into()
 }
 
-pub fn srem(db: &SharedDatabase, key: Bytes, values: Vec<Bytes>) -> Bytes {
-    format_integer(db.srem(&key, &values) as i64)
+pub fn srem(db: &SharedDatabase, key: Bytes, values: Vec<Bytes>) -> Reply {
+    db.srem(&key, &values).into()
 }
 
-pub fn smembers(db: &SharedDatabase, key: Bytes) -> Bytes {
-    match db.smembers(&key) {
-        Ok(value) => format_array_bytes(value),
-        Err(e) => format_error(e),
-    }
+pub fn smembers(db: &SharedDatabase, key: Bytes) -> Reply {
+    db.smembers(&key).map(Reply::bulk_set).into()
+}
+
+pub fn scard(db: &SharedDatabase, key: Bytes) -> Reply {
+    db.scard(&key).into()
+}
+
+pub fn sismember(db: &SharedDatabase, key: Bytes, member: Bytes) -> Reply {
+    db.sismember(&key, &member).into()
+}
+
+pub fn sinter(db: &SharedDatabase, keys: Vec<Bytes>) -> Reply {
+    db.sinter(&keys).map(Reply::bulk_set).into()
+}
+
+pub fn sunion(db: &SharedDatabase, keys: Vec<Bytes>) -> Reply {
+    db.sunion(&keys).map(Reply::bulk_set).into()
 }
 
-pub fn scard(db: &SharedDatabase, key: Bytes) -> Bytes {
-    format_integer(db.scard(&key) as i64)
+pub fn sdiff(db: &SharedDatabase, keys: Vec<Bytes>) -> Reply {
+    db.sdiff(&keys).map(Reply::bulk_set).into()
 }
 
-pub fn sismember(db: &SharedDatabase, key: Bytes, member: Bytes) -> Bytes {
-    format_integer(db.sismember(&key, &member) as i64)
+pub fn sinterstore(db: &SharedDatabase, destination: Bytes, keys: Vec<Bytes>) -> Reply {
+    db.sinterstore(&destination, &keys).into()
 }
 
-pub fn sinter(db: &SharedDatabase, keys: Vec<Bytes>) -> Bytes {
-    match db.sinter(&keys) {
-        Ok(res) => format_array_bytes(res),
-        Err(e) => format_error(e),
+pub fn sunionstore(db: &SharedDatabase, destination: Bytes, keys: Vec<Bytes>) -> Reply {
+    db.sunionstore(&destination, &keys).into()
+}
+
+pub fn sdiffstore(db: &SharedDatabase, destination: Bytes, keys: Vec<Bytes>) -> Reply {
+    db.sdiffstore(&destination, &keys).into()
+}
+
+/// The keys and limit of `numkeys key [key ...] [LIMIT limit]`
+fn sintercard_args(args: &[Bytes]) -> Result<(&[Bytes], usize)> {
+    let (numkeys, rest) = args.split_first().ok_or(CommandError::SyntaxError)?;
+    let numkeys = parse_integer(numkeys)?;
+    if numkeys <= 0 {
+        return Err(CommandError::Custom(
+            "numkeys should be greater than 0".to_string(),
+        ));
+    }
+    if numkeys as u64 > rest.len() as u64 {
+        return Err(CommandError::Custom(
+            "Number of keys can't be greater than number of args".to_string(),
+        ));
     }
+    let (keys, rest) = rest.split_at(numkeys as usize);
+    let limit = match rest {
+        [] => 0,
+        [option, limit] if option.eq_ignore_ascii_case(b"LIMIT") => {
+            let limit = parse_integer(limit)?;
+            if limit < 0 {
+                return Err(CommandError::Custom("LIMIT can't be negative".to_string()));
+            }
+            limit as usize
+        }
+        _ => return Err(CommandError::SyntaxError),
+    };
+    Ok((keys, limit))
+}
+
+pub fn sintercard(db: &SharedDatabase, args: Vec<Bytes>) -> Reply {
+    sintercard_args(&args)
+        .and_then(|(keys, limit)| db.sintercard(keys, limit))
+        .into()
 }
 
-pub fn sunion(db: &SharedDatabase, keys: Vec<Bytes>) -> Bytes {
-    match db.sunion(&keys) {
-        Ok(res) => format_array_bytes(res),
-        Err(e) => format_error(e),
+/// Without a count SPOP replies with one member; with one, with a set,
+/// which is empty for a missing key
+pub fn spop(db: &SharedDatabase, key: Bytes, count: Option<Bytes>) -> Reply {
+    let Some(count) = count else {
+        return db
+            .spop(&key, 1)
+            .map(|members| members.into_iter().next())
+            .into();
+    };
+    match parse_integer(&count) {
+        Ok(count) if count >= 0 => db.spop(&key, count as usize).map(Reply::bulk_set).into(),
+        Ok(_) => CommandError::Custom("value is out of range, must be positive".to_string()).into(),
+        Err(e) => e.into(),
     }
 }
 
-pub fn sdiff(db: &SharedDatabase, keys: Vec<Bytes>) -> Bytes {
-    match db.sdiff(&keys) {
-        Ok(res) => format_array_bytes(res),
-        Err(e) => format_error(e),
+pub fn srandmember(db: &SharedDatabase, key: Bytes, count: Option<Bytes>) -> Reply {
+    let Some(count) = count else {
+        // A single member, or nil for a missing key
+        return db
+            .srandmember(&key, 1)
+            .map(|members| members.into_iter().next())
+            .into();
+    };
+    match parse_integer(&count) {
+        Ok(i64::MIN) => CommandError::OutOfRange(-i64::MAX, i64::MAX).into(),
+        Ok(count) => db.srandmember(&key, count).map(Reply::bulk_array).into(),
+        Err(e) => e.into(),
+    }
+}
+
+pub fn smove(db: &SharedDatabase, source: Bytes, destination: Bytes, member: Bytes) -> Reply {
+    db.smove(&source, &destination, &member).into()
+}
+
+pub fn smismember(db: &SharedDatabase, key: Bytes, members: Vec<Bytes>) -> Reply {
+    db.smismember(&key, &members)
+        .map(|found| Reply::Array(found.into_iter().map(Reply::from).collect()))
+        .into()
+}
+
+pub fn sscan(db: &SharedDatabase, key: Bytes, cursor: Bytes, options: ScanOptions) -> Reply {
+    let scanned =
+        scan_arguments(&cursor, &options).and_then(|(cursor, count)| db.sscan(&key, cursor, count));
+    match scanned {
+        Ok((next, members)) => Reply::Array(vec![
+            Reply::bulk(next.to_string()),
+            Reply::bulk_array(
+                members
+                    .into_iter()
+                    .filter(|member| scan_matches(&options, member))
+                    .collect(),
+            ),
+        ]),
+        Err(e) => e.into(),
     }
 }
@@ -1,149 +1,345 @@
 use crate::{
-    commands::{
-        command_helper::{
-            format_array_bytes, format_bulk_string, format_error, format_integer, format_null,
-            format_simple_string,
-        },
-        SetOptions,
-    },
+    commands::{CommandError, GetExOption, LcsOptions, Reply, Result, SetOptions},
     database::traits::{KeyOp, StringOp},
     database::SharedDatabase,
 };
 use bytes::Bytes;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-pub fn get(db: &SharedDatabase, key: Bytes) -> Bytes {
-    match db.get(&key) {
-        Some(value) => format_bulk_string(&value),
-        None => format_null(),
+/// Largest table LCS may allocate, the same limit as a string value
+const MAX_LCS_MEMORY: usize = 512 * 1024 * 1024;
+
+pub(super) fn parse_integer(value: &Bytes) -> Result<i64> {
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or(CommandError::InvalidInteger)
+}
+
+/// The moment an expiration argument names: a positive count of seconds or
+/// milliseconds, from now or (`absolute`) from the Unix epoch
+fn expire_time(
+    value: &Bytes,
+    millis: bool,
+    absolute: bool,
+    command: &'static str,
+) -> Result<SystemTime> {
+    let time = parse_integer(value)?;
+    let invalid = || CommandError::InvalidExpireTime(command);
+    if time <= 0 {
+        return Err(invalid());
+    }
+    let time = if millis {
+        Some(time)
+    } else {
+        time.checked_mul(1000)
+    }
+    .ok_or_else(invalid)?;
+    let base = if absolute {
+        0
+    } else {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as i64
+    };
+    let at = base.checked_add(time).ok_or_else(invalid)?;
+    Ok(UNIX_EPOCH + Duration::from_millis(at as u64))
+}
+
+/// SET followed by an expiration, for SETEX and PSETEX
+fn set_expiring(
+    db: &SharedDatabase,
+    key: Bytes,
+    time: &Bytes,
+    millis: bool,
+    command: &'static str,
+    value: Bytes,
+) -> Reply {
+    match expire_time(time, millis, false, command) {
+        Ok(at) => {
+            db.set_with_expiry(&key, value, at);
+            Reply::ok()
+        }
+        Err(e) => e.into(),
     }
 }
 
-pub fn set(db: &SharedDatabase, key: Bytes, value: Bytes, options: Option<SetOptions>) -> Bytes {
+pub fn get(db: &SharedDatabase, key: Bytes) -> Reply {
+    db.get(&key).into()
+}
+
+pub fn set(db: &SharedDatabase, key: Bytes, value: Bytes, options: Option<SetOptions>) -> Reply {
     // Check options
     if let Some(opts) = options {
+        // NX and XX look at the key whatever its type
+        let exists = db.exist(std::slice::from_ref(&key)) > 0;
         // Handle NX: set only if not exists
-        if opts.nx {
-            if db.get(&key).is_some() {
-                return format_null();
-            }
+        if opts.nx && exists {
+            return Reply::Null;
         }
         // Handle XX: set only if exists
-        if opts.xx {
-            if db.get(&key).is_none() {
-                return format_null();
-            }
+        if opts.xx && !exists {
+            return Reply::Null;
         }
 
-        // Value must be set before expiration
-        // But wait, if we set then fail expiration?
-        // Ideally we'd have a set_ex in db.
-        // For now:
-        db.set(&key, value);
-
         // Handle expiration
-        if let Some(ex) = opts.ex {
-            let _ = db.expire(&key, ex);
-        } else if let Some(px) = opts.px {
-            let _ = db.expire(&key, px / 1000);
+        let millis = opts.ex.map(|ex| ex.saturating_mul(1000)).or(opts.px);
+        let at = millis.and_then(|ms| SystemTime::now().checked_add(Duration::from_millis(ms)));
+        match at {
+            Some(at) => db.set_with_expiry(&key, value, at),
+            None => db.set(&key, value),
         }
     } else {
         db.set(&key, value);
     }
 
-    format_simple_string("OK")
+    Reply::ok()
 }
 
-pub fn del(db: &SharedDatabase, keys: Vec<Bytes>) -> Bytes {
-    let count = db.del(&keys);
-    format_integer(count as i64)
+pub fn del(db: &SharedDatabase, keys: Vec<Bytes>) -> Reply {
+    db.del(&keys).into()
 }
 
-pub fn incr(db: &SharedDatabase, key: Bytes) -> Bytes {
-    match db.incr(&key) {
-        Ok(val) => format_integer(val),
-        Err(_) => format_error("ERR value is not an integer or out of range"),
-    }
+pub fn incr(db: &SharedDatabase, key: Bytes) -> Reply {
+    db.incr(&key).into()
 }
 
-pub fn decr(db: &SharedDatabase, key: Bytes) -> Bytes {
-    match db.decr(&key) {
-        Ok(val) => format_integer(val),
-        Err(_) => format_error("ERR value is not an integer or out of range"),
-    }
+pub fn decr(db: &SharedDatabase, key: Bytes) -> Reply {
+    db.decr(&key).into()
 }
 
-pub fn incr_by(db: &SharedDatabase, key: Bytes, value: Bytes) -> Bytes {
-    match db.incr_by(&key, value) {
-        Ok(val) => format_integer(val),
-        Err(_) => format_error("ERR value is not an integer or out of range"),
-    }
+pub fn incr_by(db: &SharedDatabase, key: Bytes, value: Bytes) -> Reply {
+    db.incr_by(&key, value).into()
 }
 
-pub fn decr_by(db: &SharedDatabase, key: Bytes, value: Bytes) -> Bytes {
-    match db.decr_by(&key, value) {
-        Ok(val) => format_integer(val),
-        Err(_) => format_error("ERR value is not an integer or out of range"),
-    }
+pub fn decr_by(db: &SharedDatabase, key: Bytes, value: Bytes) -> Reply {
+    db.decr_by(&key, value).into()
 }
 
-pub fn append(db: &SharedDatabase, key: Bytes, value: Bytes) -> Bytes {
-    let len = db.append(&key, value);
-    format_integer(len as i64)
+pub fn append(db: &SharedDatabase, key: Bytes, value: Bytes) -> Reply {
+    db.append(&key, value).into()
 }
 
-pub fn strlen(db: &SharedDatabase, key: Bytes) -> Bytes {
-    let len = db.str_len(&key);
-    format_integer(len as i64)
+pub fn strlen(db: &SharedDatabase, key: Bytes) -> Reply {
+    db.str_len(&key).into()
 }
 
-pub fn mget(db: &SharedDatabase, keys: Vec<Bytes>) -> Bytes {
-    let mut response = Vec::new();
-    for key in keys {
-        match db.get(&key) {
-            Some(val) => response.push(format_bulk_string(&val)),
-            None => response.push(format_null()),
-        }
-    }
-    format_array_bytes(response)
+pub fn mget(db: &SharedDatabase, keys: Vec<Bytes>) -> Reply {
+    // Keys holding other types read as nil rather than failing the batch
+    Reply::Array(db.mget(&keys).into_iter().map(Reply::from).collect())
 }
 
-pub fn mset(db: &SharedDatabase, pairs: Vec<(Bytes, Bytes)>) -> Bytes {
-    for (key, value) in pairs {
-        db.set(&key, value);
-    }
-    format_simple_string("OK")
+pub fn mset(db: &SharedDatabase, pairs: Vec<(Bytes, Bytes)>) -> Reply {
+    db.mset(&pairs);
+    Reply::ok()
+}
+
+pub fn msetnx(db: &SharedDatabase, pairs: Vec<(Bytes, Bytes)>) -> Reply {
+    db.msetnx(&pairs).into()
 }
 
-pub fn setnx(db: &SharedDatabase, key: Bytes, value: Bytes) -> Bytes {
-    if db.get(&key).is_some() {
-        format_integer(0)
+pub fn setnx(db: &SharedDatabase, key: Bytes, value: Bytes) -> Reply {
+    if db.exist(std::slice::from_ref(&key)) > 0 {
+        Reply::Integer(0)
     } else {
         db.set(&key, value);
-        format_integer(1)
+        Reply::Integer(1)
     }
 }
 
-pub fn setex(db: &SharedDatabase, key: Bytes, seconds: Bytes, value: Bytes) -> Bytes {
-    let seconds_str = String::from_utf8_lossy(&seconds);
-    match seconds_str.parse::<u64>() {
-        Ok(s) => {
+pub fn setex(db: &SharedDatabase, key: Bytes, seconds: Bytes, value: Bytes) -> Reply {
+    set_expiring(db, key, &seconds, false, "setex", value)
+}
+
+pub fn psetex(db: &SharedDatabase, key: Bytes, millis: Bytes, value: Bytes) -> Reply {
+    set_expiring(db, key, &millis, true, "psetex", value)
+}
+
+pub fn getset(db: &SharedDatabase, key: Bytes, value: Bytes) -> Reply {
+    match db.get(&key) {
+        Ok(Some(old_val)) => {
             db.set(&key, value);
-            let _ = db.expire(&key, s);
-            format_simple_string("OK")
+            Reply::Bulk(old_val)
         }
-        Err(_) => format_error("ERR value is not an integer or out of range"),
+        Ok(None) => {
+            db.set(&key, value);
+            Reply::Null
+        }
+        Err(e) => e.into(),
     }
 }
 
-pub fn getset(db: &SharedDatabase, key: Bytes, value: Bytes) -> Bytes {
+pub fn getdel(db: &SharedDatabase, key: Bytes) -> Reply {
+    db.get_del(&key).into()
+}
+
+pub fn getex(db: &SharedDatabase, key: Bytes, option: Option<GetExOption>) -> Reply {
+    // The new expiration is checked before the key is looked at
+    let at = match &option {
+        Some(GetExOption::Ex(time)) => expire_time(time, false, false, "getex").map(Some),
+        Some(GetExOption::Px(time)) => expire_time(time, true, false, "getex").map(Some),
+        Some(GetExOption::ExAt(time)) => expire_time(time, false, true, "getex").map(Some),
+        Some(GetExOption::PxAt(time)) => expire_time(time, true, true, "getex").map(Some),
+        Some(GetExOption::Persist) | None => Ok(None),
+    };
+    let at = match at {
+        Ok(at) => at,
+        Err(e) => return e.into(),
+    };
     match db.get(&key) {
-        Some(old_val) => {
-            db.set(&key, value);
-            format_bulk_string(&old_val)
+        Ok(Some(value)) => {
+            if let Some(at) = at {
+                db.pexpire_at(&key, at);
+            } else if option == Some(GetExOption::Persist) {
+                db.persist(&key);
+            }
+            Reply::Bulk(value)
         }
-        None => {
-            db.set(&key, value);
-            format_null()
+        Ok(None) => Reply::Null,
+        Err(e) => e.into(),
+    }
+}
+
+pub fn getrange(db: &SharedDatabase, key: Bytes, start: Bytes, end: Bytes) -> Reply {
+    let range = parse_integer(&start).and_then(|start| Ok((start, parse_integer(&end)?)));
+    range
+        .and_then(|(start, end)| db.get_range(&key, start, end))
+        .into()
+}
+
+pub fn setrange(db: &SharedDatabase, key: Bytes, offset: Bytes, value: Bytes) -> Reply {
+    parse_integer(&offset)
+        .and_then(|offset| db.set_range(&key, offset, value))
+        .into()
+}
+
+pub fn incr_by_float(db: &SharedDatabase, key: Bytes, value: Bytes) -> Reply {
+    let increment = std::str::from_utf8(&value)
+        .ok()
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|v| !v.is_nan())
+        .ok_or(CommandError::InvalidFloat);
+    increment
+        .and_then(|increment| db.incr_by_float(&key, increment))
+        .map(|result| Reply::bulk(result.to_string()))
+        .into()
+}
+
+pub fn lcs(db: &SharedDatabase, key1: Bytes, key2: Bytes, options: LcsOptions) -> Reply {
+    lcs_reply(db, &key1, &key2, &options).into()
+}
+
+fn lcs_reply(
+    db: &SharedDatabase,
+    key1: &Bytes,
+    key2: &Bytes,
+    options: &LcsOptions,
+) -> Result<Reply> {
+    let string = |key| match db.get(key) {
+        Ok(value) => Ok(value.unwrap_or_default()),
+        Err(CommandError::WrongType) => Err(CommandError::Custom(
+            "The specified keys must contain string values".to_string(),
+        )),
+        Err(e) => Err(e),
+    };
+    let (a, b) = (string(key1)?, string(key2)?);
+    let min_match_len = match &options.min_match_len {
+        Some(len) => parse_integer(len)?.max(0) as usize,
+        None => 0,
+    };
+    if options.len && options.idx {
+        return Err(CommandError::Custom(
+            "If you want both the length and indexes, please just use IDX.".to_string(),
+        ));
+    }
+
+    // lcs[i][j] is the length of the LCS of the first i bytes of `a` and the
+    // first j bytes of `b`
+    let (alen, blen) = (a.len(), b.len());
+    let cells = (alen + 1).checked_mul(blen + 1);
+    if cells.is_none_or(|cells| cells.saturating_mul(4) > MAX_LCS_MEMORY) {
+        return Err(CommandError::Custom(
+            "Insufficient memory, transient memory for LCS exceeds proto-max-bulk-len".to_string(),
+        ));
+    }
+    let at = |i: usize, j: usize| i * (blen + 1) + j;
+    let mut lcs = vec![0u32; (alen + 1) * (blen + 1)];
+    for i in 1..=alen {
+        for j in 1..=blen {
+            lcs[at(i, j)] = if a[i - 1] == b[j - 1] {
+                lcs[at(i - 1, j - 1)] + 1
+            } else {
+                lcs[at(i - 1, j)].max(lcs[at(i, j - 1)])
+            };
         }
     }
+    let len = lcs[at(alen, blen)] as usize;
+    if options.len {
+        return Ok(Reply::Integer(len as i64));
+    }
+
+    // Walk back from the end, collecting the common bytes and, for IDX, the
+    // ranges they form in both strings
+    let mut result = vec![0u8; len];
+    let mut idx = len;
+    let mut matches = Vec::new();
+    let mut range: Option<((usize, usize), (usize, usize))> = None;
+    let (mut i, mut j) = (alen, blen);
+    let mut emit = |range: ((usize, usize), (usize, usize))| {
+        let ((a_start, a_end), (b_start, b_end)) = range;
+        let match_len = a_end - a_start + 1;
+        if match_len >= min_match_len {
+            let mut entry = vec![
+                Reply::Array(vec![
+                    Reply::Integer(a_start as i64),
+                    Reply::Integer(a_end as i64),
+                ]),
+                Reply::Array(vec![
+                    Reply::Integer(b_start as i64),
+                    Reply::Integer(b_end as i64),
+                ]),
+            ];
+            if options.with_match_len {
+                entry.push(Reply::Integer(match_len as i64));
+            }
+            matches.push(Reply::Array(entry));
+        }
+    };
+    while i > 0 && j > 0 {
+        if a[i - 1] == b[j - 1] {
+            idx -= 1;
+            result[idx] = a[i - 1];
+            // Extend the current range if this byte is adjacent to it in both
+            // strings, otherwise start a new one
+            range = match range {
+                Some(((a_start, a_end), (b_start, b_end))) if a_start == i && b_start == j => {
+                    Some(((i - 1, a_end), (j - 1, b_end)))
+                }
+                Some(current) => {
+                    emit(current);
+                    Some(((i - 1, i - 1), (j - 1, j - 1)))
+                }
+                None => Some(((i - 1, i - 1), (j - 1, j - 1))),
+            };
+            i -= 1;
+            j -= 1;
+        } else if lcs[at(i - 1, j)] > lcs[at(i, j - 1)] {
+            i -= 1;
+        } else {
+            j -= 1;
+        }
+    }
+    if let Some(current) = range {
+        emit(current);
+    }
+
+    if options.idx {
+        Ok(Reply::Map(vec![
+            (Reply::bulk("matches"), Reply::Array(matches)),
+            (Reply::bulk("len"), Reply::Integer(len as i64)),
+        ]))
+    } else {
+        Ok(Reply::Bulk(Bytes::from(result)))
+    }
 }
@@ -0,0 +1,1547 @@
+// Command table for Rudis
+// One entry per command with its arity, flags, key positions and ACL
+// categories. Parsing is routed through the table, and COMMAND reports it.
+
+use super::{command_helper, Command};
+use crate::modules;
+use crate::networking::resp::RespValue;
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+pub type ParseFn = fn(&[RespValue]) -> Option<Command>;
+pub type KeyFn = fn(&[Bytes]) -> Vec<Bytes>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flag {
+    /// May modify the keyspace
+    Write,
+    /// Never modifies the keyspace
+    ReadOnly,
+    /// Refused while over `maxmemory`
+    DenyOom,
+    /// O(1) or O(log N)
+    Fast,
+    Admin,
+    PubSub,
+    NoScript,
+    /// Allowed while the dataset is loading
+    Loading,
+    /// Allowed on a replica with stale data
+    Stale,
+    /// Keys can't be found from first/last/step alone
+    MovableKeys,
+    /// May block the client until a key changes
+    Blocking,
+}
+
+impl Flag {
+    pub fn name(self) -> &'static str {
+        match self {
+            Flag::Write => "write",
+            Flag::ReadOnly => "readonly",
+            Flag::DenyOom => "denyoom",
+            Flag::Fast => "fast",
+            Flag::Admin => "admin",
+            Flag::PubSub => "pubsub",
+            Flag::NoScript => "noscript",
+            Flag::Loading => "loading",
+            Flag::Stale => "stale",
+            Flag::MovableKeys => "movablekeys",
+            Flag::Blocking => "blocking",
+        }
+    }
+}
+
+pub struct CommandSpec {
+    /// Lower case; subcommands are named `container|subcommand`
+    pub name: &'static str,
+    /// Number of arguments including the name; negative means "at least"
+    pub arity: i32,
+    pub flags: &'static [Flag],
+    /// Position of the first and last key (negative counts from the end) and
+    /// the step between keys; all 0 for commands without keys
+    pub first_key: i32,
+    pub last_key: i32,
+    pub step: i32,
+    pub categories: &'static [&'static str],
+    /// Documentation group reported by COMMAND DOCS
+    pub group: &'static str,
+    pub summary: &'static str,
+    pub parse: ParseFn,
+    /// Finds the keys of commands flagged `movablekeys`
+    pub key_fn: Option<KeyFn>,
+    pub subcommands: &'static [CommandSpec],
+}
+
+#[allow(clippy::too_many_arguments)]
+const fn spec(
+    name: &'static str,
+    arity: i32,
+    flags: &'static [Flag],
+    (first_key, last_key, step): (i32, i32, i32),
+    categories: &'static [&'static str],
+    group: &'static str,
+    summary: &'static str,
+    parse: ParseFn,
+) -> CommandSpec {
+    CommandSpec {
+        name,
+        arity,
+        flags,
+        first_key,
+        last_key,
+        step,
+        categories,
+        group,
+        summary,
+        parse,
+        key_fn: None,
+        subcommands: &[],
+    }
+}
+
+impl CommandSpec {
+    const fn with_subcommands(mut self, subcommands: &'static [CommandSpec]) -> Self {
+        self.subcommands = subcommands;
+        self
+    }
+
+    const fn with_key_fn(mut self, key_fn: KeyFn) -> Self {
+        self.key_fn = Some(key_fn);
+        self
+    }
+
+    pub fn has_flag(&self, flag: Flag) -> bool {
+        self.flags.contains(&flag)
+    }
+
+    /// Whether `argc` arguments (including the name) satisfy the arity
+    pub fn arity_ok(&self, argc: usize) -> bool {
+        let argc = argc as i64;
+        let arity = self.arity as i64;
+        if arity >= 0 {
+            argc == arity
+        } else {
+            argc >= -arity
+        }
+    }
+
+    /// The keys in a full argument vector (name included)
+    pub fn keys(&self, args: &[Bytes]) -> Vec<Bytes> {
+        if let Some(key_fn) = self.key_fn {
+            return key_fn(args);
+        }
+        if self.first_key <= 0 || self.step <= 0 {
+            return Vec::new();
+        }
+        let last = if self.last_key < 0 {
+            args.len() as i64 + self.last_key as i64
+        } else {
+            self.last_key as i64
+        };
+        (self.first_key as i64..=last)
+            .step_by(self.step as usize)
+            .filter_map(|i| args.get(i as usize).cloned())
+            .collect()
+    }
+}
+
+/// The `numkeys key [key ...]` at `position`; none if numkeys is invalid
+fn numkeys_keys(args: &[Bytes], position: usize) -> Vec<Bytes> {
+    let numkeys = args
+        .get(position)
+        .and_then(|n| std::str::from_utf8(n).ok()?.parse::<usize>().ok())
+        .unwrap_or(0);
+    args.get(position + 1..)
+        .and_then(|rest| rest.get(..numkeys))
+        .map(<[Bytes]>::to_vec)
+        .unwrap_or_default()
+}
+
+/// LMPOP numkeys key [key ...] LEFT|RIGHT [COUNT count]
+fn lmpop_keys(args: &[Bytes]) -> Vec<Bytes> {
+    numkeys_keys(args, 1)
+}
+
+/// BLMPOP timeout numkeys key [key ...] LEFT|RIGHT [COUNT count]
+fn blmpop_keys(args: &[Bytes]) -> Vec<Bytes> {
+    numkeys_keys(args, 2)
+}
+
+/// SINTERCARD numkeys key [key ...] [LIMIT limit]
+fn sintercard_keys(args: &[Bytes]) -> Vec<Bytes> {
+    numkeys_keys(args, 1)
+}
+
+/// MIGRATE host port key|"" db timeout [COPY] [REPLACE] [KEYS key ...]
+fn migrate_keys(args: &[Bytes]) -> Vec<Bytes> {
+    if let Some(pos) = args
+        .iter()
+        .skip(6)
+        .position(|a| a.eq_ignore_ascii_case(b"KEYS"))
+    {
+        return args[6 + pos + 1..].to_vec();
+    }
+    args.get(3)
+        .filter(|k| !k.is_empty())
+        .cloned()
+        .into_iter()
+        .collect()
+}
+
+use Flag::*;
+
+const NO_KEYS: (i32, i32, i32) = (0, 0, 0);
+const ONE_KEY: (i32, i32, i32) = (1, 1, 1);
+const ALL_KEYS: (i32, i32, i32) = (1, -1, 1);
+
+const CONFIG_SUBCOMMANDS: &[CommandSpec] = &[
+    spec(
+        "config|get",
+        -3,
+        &[Admin, NoScript, Loading, Stale],
+        NO_KEYS,
+        &["@admin", "@slow", "@dangerous"],
+        "server",
+        "Returns the effective values of configuration parameters.",
+        command_helper::parse_config_command,
+    ),
+    spec(
+        "config|set",
+        -4,
+        &[Admin, NoScript, Loading, Stale],
+        NO_KEYS,
+        &["@admin", "@slow", "@dangerous"],
+        "server",
+        "Sets configuration parameters in-flight.",
+        command_helper::parse_config_command,
+    ),
+    spec(
+        "config|resetstat",
+        2,
+        &[Admin, NoScript, Loading, Stale],
+        NO_KEYS,
+        &["@admin", "@slow", "@dangerous"],
+        "server",
+        "Resets the server's statistics.",
+        command_helper::parse_config_command,
+    ),
+    spec(
+        "config|rewrite",
+        2,
+        &[Admin, NoScript, Loading, Stale],
+        NO_KEYS,
+        &["@admin", "@slow", "@dangerous"],
+        "server",
+        "Persists the effective configuration to file.",
+        command_helper::parse_config_command,
+    ),
+];
+
+const KEYSPACE_SUBCOMMANDS: &[CommandSpec] = &[
+    spec(
+        "keyspace|export",
+        -2,
+        &[Admin, ReadOnly],
+        NO_KEYS,
+        &["@admin", "@keyspace", "@read", "@slow", "@dangerous"],
+        "server",
+        "Returns the keys of one or all databases as JSON lines.",
+        command_helper::parse_keyspace_command,
+    ),
+    spec(
+        "keyspace|import",
+        -3,
+        &[Admin, Write, DenyOom],
+        NO_KEYS,
+        &["@admin", "@keyspace", "@write", "@slow", "@dangerous"],
+        "server",
+        "Loads keys from JSON lines.",
+        command_helper::parse_keyspace_command,
+    ),
+];
+
+const MODULE_SUBCOMMANDS: &[CommandSpec] = &[spec(
+    "module|list",
+    2,
+    &[Admin, NoScript],
+    NO_KEYS,
+    &["@admin", "@slow", "@dangerous"],
+    "server",
+    "Returns all loaded modules.",
+    command_helper::parse_module_command,
+)];
+
+const OBJECT_SUBCOMMANDS: &[CommandSpec] = &[spec(
+    "object|encoding",
+    3,
+    &[ReadOnly],
+    (2, 2, 1),
+    &["@keyspace", "@read", "@slow"],
+    "generic",
+    "Returns the internal encoding of a Redis object.",
+    command_helper::parse_object_command,
+)];
+
+const COMMAND_SUBCOMMANDS: &[CommandSpec] = &[
+    spec(
+        "command|count",
+        2,
+        &[Loading, Stale],
+        NO_KEYS,
+        &["@slow", "@connection"],
+        "server",
+        "Returns a count of commands.",
+        command_helper::parse_command_command,
+    ),
+    spec(
+        "command|docs",
+        -2,
+        &[Loading, Stale],
+        NO_KEYS,
+        &["@slow", "@connection"],
+        "server",
+        "Returns documentary information about one, multiple or all commands.",
+        command_helper::parse_command_command,
+    ),
+    spec(
+        "command|getkeys",
+        -3,
+        &[Loading, Stale],
+        NO_KEYS,
+        &["@slow", "@connection"],
+        "server",
+        "Extracts the key names from an arbitrary command.",
+        command_helper::parse_command_command,
+    ),
+    spec(
+        "command|info",
+        -2,
+        &[Loading, Stale],
+        NO_KEYS,
+        &["@slow", "@connection"],
+        "server",
+        "Returns information about one, multiple or all commands.",
+        command_helper::parse_command_command,
+    ),
+    spec(
+        "command|list",
+        -2,
+        &[Loading, Stale],
+        NO_KEYS,
+        &["@slow", "@connection"],
+        "server",
+        "Returns a list of command names.",
+        command_helper::parse_command_command,
+    ),
+];
+
+pub static COMMANDS: &[CommandSpec] = &[
+    // Connection
+    spec(
+        "ping",
+        -1,
+        &[Fast],
+        NO_KEYS,
+        &["@fast", "@connection"],
+        "connection",
+        "Returns the server's liveliness response.",
+        |e| parse_command!(option, e, Ping),
+    ),
+    spec(
+        "quit",
+        -1,
+        &[Fast, NoScript, Loading, Stale],
+        NO_KEYS,
+        &["@fast", "@connection"],
+        "connection",
+        "Closes the connection.",
+        |e| parse_command!(none, e, Quit),
+    ),
+    spec(
+        "echo",
+        2,
+        &[Fast],
+        NO_KEYS,
+        &["@fast", "@connection"],
+        "connection",
+        "Returns the given string.",
+        |e| parse_command!(single_key, e, Echo),
+    ),
+    spec(
+        "auth",
+        -2,
+        &[NoScript, Loading, Stale, Fast],
+        NO_KEYS,
+        &["@fast", "@connection"],
+        "connection",
+        "Authenticates the connection.",
+        |e| parse_command!(single_key, e, Auth),
+    ),
+    spec(
+        "select",
+        2,
+        &[Loading, Stale, Fast],
+        NO_KEYS,
+        &["@fast", "@connection"],
+        "connection",
+        "Changes the selected database.",
+        |e| parse_command!(single_key, e, Select),
+    ),
+    spec(
+        "hello",
+        -1,
+        &[NoScript, Loading, Stale, Fast],
+        NO_KEYS,
+        &["@fast", "@connection"],
+        "connection",
+        "Handshakes with the Redis server.",
+        |e| parse_command!(option, e, Hello),
+    ),
+    // Strings
+    spec(
+        "get",
+        2,
+        &[ReadOnly, Fast],
+        ONE_KEY,
+        &["@read", "@string", "@fast"],
+        "string",
+        "Returns the string value of a key.",
+        |e| parse_command!(single_key, e, Get),
+    ),
+    spec(
+        "set",
+        -3,
+        &[Write, DenyOom],
+        ONE_KEY,
+        &["@write", "@string", "@slow"],
+        "string",
+        "Sets the string value of a key, ignoring its type.",
+        |e| parse_command!(key_value_options, e, Set),
+    ),
+    spec(
+        "setnx",
+        3,
+        &[Write, DenyOom, Fast],
+        ONE_KEY,
+        &["@write", "@string", "@fast"],
+        "string",
+        "Set the string value of a key only when the key doesn't exist.",
+        |e| parse_command!(key_value, e, SetNX),
+    ),
+    spec(
+        "setex",
+        4,
+        &[Write, DenyOom],
+        ONE_KEY,
+        &["@write", "@string", "@slow"],
+        "string",
+        "Sets the string value and expiration time of a key.",
+        |e| parse_command!(key_field_value, e, SetEX),
+    ),
+    spec(
+        "getset",
+        3,
+        &[Write, DenyOom, Fast],
+        ONE_KEY,
+        &["@write", "@string", "@fast"],
+        "string",
+        "Returns the previous string value of a key after setting it to a new value.",
+        |e| parse_command!(key_value, e, GetSet),
+    ),
+    spec(
+        "getrange",
+        4,
+        &[ReadOnly],
+        ONE_KEY,
+        &["@read", "@string", "@slow"],
+        "string",
+        "Returns a substring of the string stored at a key.",
+        |e| parse_command!(key_field_value, e, GetRange),
+    ),
+    spec(
+        "substr",
+        4,
+        &[ReadOnly],
+        ONE_KEY,
+        &["@read", "@string", "@slow"],
+        "string",
+        "Returns a substring from a string value.",
+        |e| parse_command!(key_field_value, e, SubStr),
+    ),
+    spec(
+        "setrange",
+        4,
+        &[Write, DenyOom],
+        ONE_KEY,
+        &["@write", "@string", "@slow"],
+        "string",
+        "Overwrites a part of a string value with another by an offset. Creates the key if it doesn't exist.",
+        |e| parse_command!(key_field_value, e, SetRange),
+    ),
+    spec(
+        "getdel",
+        2,
+        &[Write, Fast],
+        ONE_KEY,
+        &["@write", "@string", "@fast"],
+        "string",
+        "Returns the string value of a key after deleting the key.",
+        |e| parse_command!(single_key, e, GetDel),
+    ),
+    spec(
+        "getex",
+        -2,
+        &[Write, Fast],
+        ONE_KEY,
+        &["@write", "@string", "@fast"],
+        "string",
+        "Returns the string value of a key after setting its expiration time.",
+        command_helper::parse_getex_command,
+    ),
+    spec(
+        "psetex",
+        4,
+        &[Write, DenyOom],
+        ONE_KEY,
+        &["@write", "@string", "@slow"],
+        "string",
+        "Sets both string value and expiration time in milliseconds of a key. The key is created if it doesn't exist.",
+        |e| parse_command!(key_field_value, e, PSetEX),
+    ),
+    spec(
+        "lcs",
+        -3,
+        &[ReadOnly],
+        (1, 2, 1),
+        &["@read", "@string", "@slow"],
+        "string",
+        "Finds the longest common substring.",
+        command_helper::parse_lcs_command,
+    ),
+    spec(
+        "incr",
+        2,
+        &[Write, DenyOom, Fast],
+        ONE_KEY,
+        &["@write", "@string", "@fast"],
+        "string",
+        "Increments the integer value of a key by one.",
+        |e| parse_command!(single_key, e, Incr),
+    ),
+    spec(
+        "decr",
+        2,
+        &[Write, DenyOom, Fast],
+        ONE_KEY,
+        &["@write", "@string", "@fast"],
+        "string",
+        "Decrements the integer value of a key by one.",
+        |e| parse_command!(single_key, e, Decr),
+    ),
+    spec(
+        "incrby",
+        3,
+        &[Write, DenyOom, Fast],
+        ONE_KEY,
+        &["@write", "@string", "@fast"],
+        "string",
+        "Increments the integer value of a key by a number.",
+        |e| parse_command!(key_value, e, IncrBy),
+    ),
+    spec(
+        "decrby",
+        3,
+        &[Write, DenyOom, Fast],
+        ONE_KEY,
+        &["@write", "@string", "@fast"],
+        "string",
+        "Decrements a number from the integer value of a key.",
+        |e| parse_command!(key_value, e, DecrBy),
+    ),
+    spec(
+        "incrbyfloat",
+        3,
+        &[Write, DenyOom, Fast],
+        ONE_KEY,
+        &["@write", "@string", "@fast"],
+        "string",
+        "Increment the floating point value of a key by a number. Uses 0 as initial value if the key doesn't exist.",
+        |e| parse_command!(key_value, e, IncrByFloat),
+    ),
+    spec(
+        "append",
+        3,
+        &[Write, DenyOom, Fast],
+        ONE_KEY,
+        &["@write", "@string", "@fast"],
+        "string",
+        "Appends a string to the value of a key.",
+        |e| parse_command!(key_value, e, Append),
+    ),
+    spec(
+        "strlen",
+        2,
+        &[ReadOnly, Fast],
+        ONE_KEY,
+        &["@read", "@string", "@fast"],
+        "string",
+        "Returns the length of a string value.",
+        |e| parse_command!(single_key, e, Strlen),
+    ),
+    spec(
+        "mget",
+        -2,
+        &[ReadOnly, Fast],
+        ALL_KEYS,
+        &["@read", "@string", "@fast"],
+        "string",
+        "Atomically returns the string values of one or more keys.",
+        |e| parse_command!(keys, e, MGet),
+    ),
+    spec(
+        "mset",
+        -3,
+        &[Write, DenyOom],
+        (1, -1, 2),
+        &["@write", "@string", "@slow"],
+        "string",
+        "Atomically creates or modifies the string values of one or more keys.",
+        |e| parse_command!(key_value_pairs, e, MSet),
+    ),
+    spec(
+        "msetnx",
+        -3,
+        &[Write, DenyOom],
+        (1, -1, 2),
+        &["@write", "@string", "@slow"],
+        "string",
+        "Atomically modifies the string values of one or more keys only when all keys don't exist.",
+        |e| parse_command!(key_value_pairs, e, MSetNX),
+    ),
+    // Bitmaps
+    spec(
+        "setbit",
+        4,
+        &[Write, DenyOom],
+        ONE_KEY,
+        &["@write", "@bitmap", "@slow"],
+        "bitmap",
+        "Sets or clears the bit at offset of the string value. Creates the key if it doesn't exist.",
+        |e| parse_command!(key_field_value, e, SetBit),
+    ),
+    spec(
+        "getbit",
+        3,
+        &[ReadOnly, Fast],
+        ONE_KEY,
+        &["@read", "@bitmap", "@fast"],
+        "bitmap",
+        "Returns a bit value by offset.",
+        |e| parse_command!(key_value, e, GetBit),
+    ),
+    spec(
+        "bitcount",
+        -2,
+        &[ReadOnly],
+        ONE_KEY,
+        &["@read", "@bitmap", "@slow"],
+        "bitmap",
+        "Counts the number of set bits (population counting) in a string.",
+        command_helper::parse_bitcount_command,
+    ),
+    spec(
+        "bitpos",
+        -3,
+        &[ReadOnly],
+        ONE_KEY,
+        &["@read", "@bitmap", "@slow"],
+        "bitmap",
+        "Finds the first set (1) or clear (0) bit in a string.",
+        command_helper::parse_bitpos_command,
+    ),
+    spec(
+        "bitop",
+        -4,
+        &[Write, DenyOom],
+        (2, -1, 1),
+        &["@write", "@bitmap", "@slow"],
+        "bitmap",
+        "Performs bitwise operations on multiple strings, and stores the result.",
+        command_helper::parse_bitop_command,
+    ),
+    spec(
+        "bitfield",
+        -2,
+        &[Write, DenyOom],
+        ONE_KEY,
+        &["@write", "@bitmap", "@slow"],
+        "bitmap",
+        "Performs arbitrary bitfield integer operations on strings.",
+        |e| command_helper::parse_key_fields_command(e, 2).map(|(k, f)| Command::BitField(k, f)),
+    ),
+    spec(
+        "bitfield_ro",
+        -2,
+        &[ReadOnly, Fast],
+        ONE_KEY,
+        &["@read", "@bitmap", "@fast"],
+        "bitmap",
+        "Performs arbitrary read-only bitfield integer operations on strings.",
+        |e| command_helper::parse_key_fields_command(e, 2).map(|(k, f)| Command::BitFieldRo(k, f)),
+    ),
+    // Hashes
+    spec(
+        "hset",
+        -4,
+        &[Write, DenyOom, Fast],
+        ONE_KEY,
+        &["@write", "@hash", "@fast"],
+        "hash",
+        "Creates or modifies the value of a field in a hash.",
+        |e| command_helper::parse_key_fields_command(e, 4).map(|(k, f)| Command::HSet(k, f)),
+    ),
+    spec(
+        "hget",
+        3,
+        &[ReadOnly, Fast],
+        ONE_KEY,
+        &["@read", "@hash", "@fast"],
+        "hash",
+        "Returns the value of a field in a hash.",
+        |e| parse_command!(key_value, e, HGet),
+    ),
+    spec(
+        "hdel",
+        -3,
+        &[Write, Fast],
+        ONE_KEY,
+        &["@write", "@hash", "@fast"],
+        "hash",
+        "Deletes one or more fields and their values from a hash.",
+        |e| parse_command!(key_fields, e, HDel),
+    ),
+    spec(
+        "hgetall",
+        2,
+        &[ReadOnly],
+        ONE_KEY,
+        &["@read", "@hash", "@slow"],
+        "hash",
+        "Returns all fields and values in a hash.",
+        |e| parse_command!(single_key, e, HGetAll),
+    ),
+    spec(
+        "hkeys",
+        2,
+        &[ReadOnly],
+        ONE_KEY,
+        &["@read", "@hash", "@slow"],
+        "hash",
+        "Returns all fields in a hash.",
+        |e| parse_command!(single_key, e, HKeys),
+    ),
+    spec(
+        "hvals",
+        2,
+        &[ReadOnly],
+        ONE_KEY,
+        &["@read", "@hash", "@slow"],
+        "hash",
+        "Returns all values in a hash.",
+        |e| parse_command!(single_key, e, HVals),
+    ),
+    spec(
+        "hlen",
+        2,
+        &[ReadOnly, Fast],
+        ONE_KEY,
+        &["@read", "@hash", "@fast"],
+        "hash",
+        "Returns the number of fields in a hash.",
+        |e| parse_command!(single_key, e, HLen),
+    ),
+    spec(
+        "hexists",
+        3,
+        &[ReadOnly, Fast],
+        ONE_KEY,
+        &["@read", "@hash", "@fast"],
+        "hash",
+        "Determines whether a field exists in a hash.",
+        |e| parse_command!(key_value, e, HExists),
+    ),
+    spec(
+        "hincrby",
+        4,
+        &[Write, DenyOom, Fast],
+        ONE_KEY,
+        &["@write", "@hash", "@fast"],
+        "hash",
+        "Increments the integer value of a field in a hash by a number.",
+        |e| parse_command!(key_field_value, e, HIncrBy),
+    ),
+    spec(
+        "hincrbyfloat",
+        4,
+        &[Write, DenyOom, Fast],
+        ONE_KEY,
+        &["@write", "@hash", "@fast"],
+        "hash",
+        "Increments the floating point value of a field by a number.",
+        |e| parse_command!(key_field_value, e, HIncrByFloat),
+    ),
+    spec(
+        "hsetnx",
+        4,
+        &[Write, DenyOom, Fast],
+        ONE_KEY,
+        &["@write", "@hash", "@fast"],
+        "hash",
+        "Sets the value of a field in a hash only when the field doesn't exist.",
+        |e| parse_command!(key_field_value, e, HSetNx),
+    ),
+    spec(
+        "hmset",
+        -4,
+        &[Write, DenyOom, Fast],
+        ONE_KEY,
+        &["@write", "@hash", "@fast"],
+        "hash",
+        "Sets the values of multiple fields.",
+        |e| command_helper::parse_key_fields_command(e, 4).map(|(k, f)| Command::HMSet(k, f)),
+    ),
+    spec(
+        "hmget",
+        -3,
+        &[ReadOnly, Fast],
+        ONE_KEY,
+        &["@read", "@hash", "@fast"],
+        "hash",
+        "Returns the values of all fields in a hash.",
+        |e| parse_command!(key_fields, e, HMGet),
+    ),
+    spec(
+        "hstrlen",
+        3,
+        &[ReadOnly, Fast],
+        ONE_KEY,
+        &["@read", "@hash", "@fast"],
+        "hash",
+        "Returns the length of the value of a field.",
+        |e| parse_command!(key_value, e, HStrLen),
+    ),
+    spec(
+        "hrandfield",
+        -2,
+        &[ReadOnly],
+        ONE_KEY,
+        &["@read", "@hash", "@slow"],
+        "hash",
+        "Returns one or more random fields from a hash.",
+        command_helper::parse_hrandfield_command,
+    ),
+    spec(
+        "hscan",
+        -3,
+        &[ReadOnly],
+        ONE_KEY,
+        &["@read", "@hash", "@slow"],
+        "hash",
+        "Iterates over fields and values of a hash.",
+        command_helper::parse_hscan_command,
+    ),
+    // Lists
+    spec(
+        "lpush",
+        -3,
+        &[Write, DenyOom, Fast],
+        ONE_KEY,
+        &["@write", "@list", "@fast"],
+        "list",
+        "Prepends one or more elements to a list.",
+        |e| parse_command!(key_fields, e, LPush),
+    ),
+    spec(
+        "rpush",
+        -3,
+        &[Write, DenyOom, Fast],
+        ONE_KEY,
+        &["@write", "@list", "@fast"],
+        "list",
+        "Appends one or more elements to a list.",
+        |e| parse_command!(key_fields, e, RPush),
+    ),
+    spec(
+        "lpop",
+        -2,
+        &[Write, Fast],
+        ONE_KEY,
+        &["@write", "@list", "@fast"],
+        "list",
+        "Returns the first elements in a list after removing it. Deletes the list if the last element was popped.",
+        |e| command_helper::parse_key_count_command(e).map(|(k, c)| Command::LPop(k, c)),
+    ),
+    spec(
+        "rpop",
+        -2,
+        &[Write, Fast],
+        ONE_KEY,
+        &["@write", "@list", "@fast"],
+        "list",
+        "Returns and removes the last elements of a list. Deletes the list if the last element was popped.",
+        |e| command_helper::parse_key_count_command(e).map(|(k, c)| Command::RPop(k, c)),
+    ),
+    spec(
+        "llen",
+        2,
+        &[ReadOnly, Fast],
+        ONE_KEY,
+        &["@read", "@list", "@fast"],
+        "list",
+        "Returns the length of a list.",
+        |e| parse_command!(single_key, e, LLen),
+    ),
+    spec(
+        "lindex",
+        3,
+        &[ReadOnly],
+        ONE_KEY,
+        &["@read", "@list", "@slow"],
+        "list",
+        "Returns an element from a list by its index.",
+        |e| parse_command!(key_value, e, LIndex),
+    ),
+    spec(
+        "lrange",
+        4,
+        &[ReadOnly],
+        ONE_KEY,
+        &["@read", "@list", "@slow"],
+        "list",
+        "Returns a range of elements from a list.",
+        |e| parse_command!(key_field_value, e, LRange),
+    ),
+    spec(
+        "ltrim",
+        4,
+        &[Write],
+        ONE_KEY,
+        &["@write", "@list", "@slow"],
+        "list",
+        "Removes elements from both ends a list.",
+        |e| parse_command!(key_field_value, e, LTrim),
+    ),
+    spec(
+        "lset",
+        4,
+        &[Write, DenyOom],
+        ONE_KEY,
+        &["@write", "@list", "@slow"],
+        "list",
+        "Sets the value of an element in a list by its index.",
+        |e| parse_command!(key_field_value, e, LSet),
+    ),
+    spec(
+        "linsert",
+        5,
+        &[Write, DenyOom],
+        ONE_KEY,
+        &["@write", "@list", "@slow"],
+        "list",
+        "Inserts an element before or after another element in a list.",
+        |e| parse_command!(key_ord_pivot_value, e, LInsert),
+    ),
+    spec(
+        "lpushx",
+        -3,
+        &[Write, DenyOom, Fast],
+        ONE_KEY,
+        &["@write", "@list", "@fast"],
+        "list",
+        "Prepends one or more elements to a list only when the list exists.",
+        |e| parse_command!(key_fields, e, LPushX),
+    ),
+    spec(
+        "rpushx",
+        -3,
+        &[Write, DenyOom, Fast],
+        ONE_KEY,
+        &["@write", "@list", "@fast"],
+        "list",
+        "Appends an element to a list only when the list exists.",
+        |e| parse_command!(key_fields, e, RPushX),
+    ),
+    spec(
+        "lpos",
+        -3,
+        &[ReadOnly],
+        ONE_KEY,
+        &["@read", "@list", "@slow"],
+        "list",
+        "Returns the index of matching elements in a list.",
+        command_helper::parse_lpos_command,
+    ),
+    spec(
+        "lrem",
+        4,
+        &[Write],
+        ONE_KEY,
+        &["@write", "@list", "@slow"],
+        "list",
+        "Removes elements from a list. Deletes the list if the last element was removed.",
+        |e| parse_command!(key_field_value, e, LRem),
+    ),
+    spec(
+        "lmove",
+        5,
+        &[Write, DenyOom],
+        (1, 2, 1),
+        &["@write", "@list", "@slow"],
+        "list",
+        "Returns an element after popping it from one list and pushing it to another. Deletes the list if the last element was moved.",
+        command_helper::parse_lmove_command,
+    ),
+    spec(
+        "rpoplpush",
+        3,
+        &[Write, DenyOom],
+        (1, 2, 1),
+        &["@write", "@list", "@slow"],
+        "list",
+        "Returns the last element of a list after removing and pushing it to another list. Deletes the list if the last element was popped.",
+        |e| parse_command!(key_value, e, RPopLPush),
+    ),
+    spec(
+        "lmpop",
+        -4,
+        &[Write, MovableKeys],
+        NO_KEYS,
+        &["@write", "@list", "@slow"],
+        "list",
+        "Returns multiple elements from a list after removing them. Deletes the list if the last element was popped.",
+        |e| parse_command!(keys, e, LMPop),
+    )
+    .with_key_fn(lmpop_keys),
+    spec(
+        "blpop",
+        -3,
+        &[Write, Blocking],
+        (1, -2, 1),
+        &["@write", "@list", "@slow", "@blocking"],
+        "list",
+        "Removes and returns the first element in a list. Blocks until an element is available otherwise. Deletes the list if the last element was popped.",
+        |e| command_helper::parse_blocking_pop_command(e).map(|(k, t)| Command::BLPop(k, t)),
+    ),
+    spec(
+        "brpop",
+        -3,
+        &[Write, Blocking],
+        (1, -2, 1),
+        &["@write", "@list", "@slow", "@blocking"],
+        "list",
+        "Removes and returns the last element in a list. Blocks until an element is available otherwise. Deletes the list if the last element was popped.",
+        |e| command_helper::parse_blocking_pop_command(e).map(|(k, t)| Command::BRPop(k, t)),
+    ),
+    spec(
+        "blmove",
+        6,
+        &[Write, DenyOom, Blocking],
+        (1, 2, 1),
+        &["@write", "@list", "@slow", "@blocking"],
+        "list",
+        "Pops an element from a list, pushes it to another list and returns it. Blocks until an element is available otherwise. Deletes the list if the last element was moved.",
+        command_helper::parse_blmove_command,
+    ),
+    spec(
+        "brpoplpush",
+        4,
+        &[Write, DenyOom, Blocking],
+        (1, 2, 1),
+        &["@write", "@list", "@slow", "@blocking"],
+        "list",
+        "Pops an element from a list, pushes it to another list and returns it. Blocks until an element is available otherwise. Deletes the list if the last element was popped.",
+        |e| parse_command!(key_field_value, e, BRPopLPush),
+    ),
+    spec(
+        "blmpop",
+        -5,
+        &[Write, Blocking, MovableKeys],
+        NO_KEYS,
+        &["@write", "@list", "@slow", "@blocking"],
+        "list",
+        "Pops the first element from one of multiple lists. Blocks until an element is available otherwise. Deletes the list if the last element was popped.",
+        |e| parse_command!(key_fields, e, BLMPop),
+    )
+    .with_key_fn(blmpop_keys),
+    // Sets
+    spec(
+        "sadd",
+        -3,
+        &[Write, DenyOom, Fast],
+        ONE_KEY,
+        &["@write", "@set", "@fast"],
+        "set",
+        "Adds one or more members to a set.",
+        |e| parse_command!(key_fields, e, SAdd),
+    ),
+    spec(
+        "srem",
+        -3,
+        &[Write, Fast],
+        ONE_KEY,
+        &["@write", "@set", "@fast"],
+        "set",
+        "Removes one or more members from a set.",
+        |e| parse_command!(key_fields, e, SRem),
+    ),
+    spec(
+        "smembers",
+        2,
+        &[ReadOnly],
+        ONE_KEY,
+        &["@read", "@set", "@slow"],
+        "set",
+        "Returns all members of a set.",
+        |e| parse_command!(single_key, e, SMembers),
+    ),
+    spec(
+        "scard",
+        2,
+        &[ReadOnly, Fast],
+        ONE_KEY,
+        &["@read", "@set", "@fast"],
+        "set",
+        "Returns the number of members in a set.",
+        |e| parse_command!(single_key, e, SCard),
+    ),
+    spec(
+        "sismember",
+        3,
+        &[ReadOnly, Fast],
+        ONE_KEY,
+        &["@read", "@set", "@fast"],
+        "set",
+        "Determines whether a member belongs to a set.",
+        |e| parse_command!(key_value, e, SIsMember),
+    ),
+    spec(
+        "sinter",
+        -2,
+        &[ReadOnly],
+        ALL_KEYS,
+        &["@read", "@set", "@slow"],
+        "set",
+        "Returns the intersect of multiple sets.",
+        |e| parse_command!(keys, e, SInter),
+    ),
+    spec(
+        "sunion",
+        -2,
+        &[ReadOnly],
+        ALL_KEYS,
+        &["@read", "@set", "@slow"],
+        "set",
+        "Returns the union of multiple sets.",
+        |e| parse_command!(keys, e, SUnion),
+    ),
+    spec(
+        "sdiff",
+        -2,
+        &[ReadOnly],
+        ALL_KEYS,
+        &["@read", "@set", "@slow"],
+        "set",
+        "Returns the difference of multiple sets.",
+        |e| parse_command!(keys, e, SDiff),
+    ),
+    spec(
+        "sinterstore",
+        -3,
+        &[Write, DenyOom],
+        ALL_KEYS,
+        &["@write", "@set", "@slow"],
+        "set",
+        "Stores the intersect of multiple sets in a key.",
+        |e| parse_command!(key_fields, e, SInterStore),
+    ),
+    spec(
+        "sunionstore",
+        -3,
+        &[Write, DenyOom],
+        ALL_KEYS,
+        &["@write", "@set", "@slow"],
+        "set",
+        "Stores the union of multiple sets in a key.",
+        |e| parse_command!(key_fields, e, SUnionStore),
+    ),
+    spec(
+        "sdiffstore",
+        -3,
+        &[Write, DenyOom],
+        ALL_KEYS,
+        &["@write", "@set", "@slow"],
+        "set",
+        "Stores the difference of multiple sets in a key.",
+        |e| parse_command!(key_fields, e, SDiffStore),
+    ),
+    spec(
+        "sintercard",
+        -3,
+        &[ReadOnly, MovableKeys],
+        NO_KEYS,
+        &["@read", "@set", "@slow"],
+        "set",
+        "Returns the number of members of the intersect of multiple sets.",
+        |e| parse_command!(keys, e, SInterCard),
+    )
+    .with_key_fn(sintercard_keys),
+    spec(
+        "spop",
+        -2,
+        &[Write, Fast],
+        ONE_KEY,
+        &["@write", "@set", "@fast"],
+        "set",
+        "Returns one or more random members from a set after removing them. Deletes the set if the last member was popped.",
+        |e| command_helper::parse_key_count_command(e).map(|(k, c)| Command::SPop(k, c)),
+    ),
+    spec(
+        "srandmember",
+        -2,
+        &[ReadOnly],
+        ONE_KEY,
+        &["@read", "@set", "@slow"],
+        "set",
+        "Returns one or more random members from a set.",
+        |e| command_helper::parse_key_count_command(e).map(|(k, c)| Command::SRandMember(k, c)),
+    ),
+    spec(
+        "smove",
+        4,
+        &[Write, Fast],
+        (1, 2, 1),
+        &["@write", "@set", "@fast"],
+        "set",
+        "Moves a member from one set to another.",
+        |e| parse_command!(key_field_value, e, SMove),
+    ),
+    spec(
+        "smismember",
+        -3,
+        &[ReadOnly, Fast],
+        ONE_KEY,
+        &["@read", "@set", "@fast"],
+        "set",
+        "Determines whether multiple members belong to a set.",
+        |e| parse_command!(key_fields, e, SMIsMember),
+    ),
+    spec(
+        "sscan",
+        -3,
+        &[ReadOnly],
+        ONE_KEY,
+        &["@read", "@set", "@slow"],
+        "set",
+        "Iterates over members of a set.",
+        command_helper::parse_sscan_command,
+    ),
+    // Sorted sets
+    spec(
+        "zadd",
+        -4,
+        &[Write, DenyOom, Fast],
+        ONE_KEY,
+        &["@write", "@sortedset", "@fast"],
+        "sorted-set",
+        "Adds one or more members to a sorted set, or updates their scores.",
+        |e| parse_command!(key_pair_values, e, ZAdd),
+    ),
+    spec(
+        "zrem",
+        -3,
+        &[Write, Fast],
+        ONE_KEY,
+        &["@write", "@sortedset", "@fast"],
+        "sorted-set",
+        "Removes one or more members from a sorted set.",
+        |e| parse_command!(key_fields, e, ZRem),
+    ),
+    spec(
+        "zrange",
+        4,
+        &[ReadOnly],
+        ONE_KEY,
+        &["@read", "@sortedset", "@slow"],
+        "sorted-set",
+        "Returns members in a sorted set within a range of indexes.",
+        |e| parse_command!(key_field_value, e, ZRange),
+    ),
+    spec(
+        "zrangebyscore",
+        4,
+        &[ReadOnly],
+        ONE_KEY,
+        &["@read", "@sortedset", "@slow"],
+        "sorted-set",
+        "Returns members in a sorted set within a range of scores.",
+        |e| parse_command!(key_field_value, e, ZRangeByScore),
+    ),
+    spec(
+        "zcard",
+        2,
+        &[ReadOnly, Fast],
+        ONE_KEY,
+        &["@read", "@sortedset", "@fast"],
+        "sorted-set",
+        "Returns the number of members in a sorted set.",
+        |e| parse_command!(single_key, e, ZCard),
+    ),
+    spec(
+        "zscore",
+        3,
+        &[ReadOnly, Fast],
+        ONE_KEY,
+        &["@read", "@sortedset", "@fast"],
+        "sorted-set",
+        "Returns the score of a member in a sorted set.",
+        |e| parse_command!(key_value, e, ZScore),
+    ),
+    spec(
+        "zrank",
+        3,
+        &[ReadOnly, Fast],
+        ONE_KEY,
+        &["@read", "@sortedset", "@fast"],
+        "sorted-set",
+        "Returns the index of a member in a sorted set ordered by ascending scores.",
+        |e| parse_command!(key_value, e, ZRank),
+    ),
+    // Keys
+    spec(
+        "del",
+        -2,
+        &[Write],
+        ALL_KEYS,
+        &["@keyspace", "@write", "@slow"],
+        "generic",
+        "Deletes one or more keys.",
+        |e| parse_command!(keys, e, Del),
+    ),
+    spec(
+        "exists",
+        -2,
+        &[ReadOnly, Fast],
+        ALL_KEYS,
+        &["@keyspace", "@read", "@fast"],
+        "generic",
+        "Determines whether one or more keys exist.",
+        |e| parse_command!(keys, e, Exists),
+    ),
+    spec(
+        "expire",
+        3,
+        &[Write, Fast],
+        ONE_KEY,
+        &["@keyspace", "@write", "@fast"],
+        "generic",
+        "Sets the expiration time of a key in seconds.",
+        |e| parse_command!(key_value, e, Expire),
+    ),
+    spec(
+        "ttl",
+        2,
+        &[ReadOnly, Fast],
+        ONE_KEY,
+        &["@keyspace", "@read", "@fast"],
+        "generic",
+        "Returns the expiration time in seconds of a key.",
+        |e| parse_command!(single_key, e, Ttl),
+    ),
+    spec(
+        "type",
+        2,
+        &[ReadOnly, Fast],
+        ONE_KEY,
+        &["@keyspace", "@read", "@fast"],
+        "generic",
+        "Determines the type of value stored at a key.",
+        |e| parse_command!(single_key, e, Type),
+    ),
+    spec(
+        "object",
+        -2,
+        &[],
+        NO_KEYS,
+        &["@slow"],
+        "generic",
+        "A container for object introspection commands.",
+        command_helper::parse_object_command,
+    )
+    .with_subcommands(OBJECT_SUBCOMMANDS),
+    spec(
+        "keys",
+        2,
+        &[ReadOnly],
+        NO_KEYS,
+        &["@keyspace", "@read", "@slow", "@dangerous"],
+        "generic",
+        "Returns all key names that match a pattern.",
+        |e| parse_command!(single_key, e, Keys),
+    ),
+    spec(
+        "flushall",
+        -1,
+        &[Write],
+        NO_KEYS,
+        &["@keyspace", "@write", "@slow", "@dangerous"],
+        "server",
+        "Removes all keys from all databases.",
+        |e| parse_command!(none, e, FlushAll),
+    ),
+    spec(
+        "flushdb",
+        -1,
+        &[Write],
+        NO_KEYS,
+        &["@keyspace", "@write", "@slow", "@dangerous"],
+        "server",
+        "Remove all keys from the current database.",
+        |e| parse_command!(none, e, FlushDB),
+    ),
+    spec(
+        "dump",
+        2,
+        &[ReadOnly],
+        ONE_KEY,
+        &["@keyspace", "@read", "@slow"],
+        "generic",
+        "Returns a serialized representation of the value stored at a key.",
+        |e| parse_command!(single_key, e, Dump),
+    ),
+    spec(
+        "restore",
+        -4,
+        &[Write, DenyOom],
+        ONE_KEY,
+        &["@keyspace", "@write", "@slow", "@dangerous"],
+        "generic",
+        "Creates a key from the serialized representation of a value.",
+        command_helper::parse_restore_command,
+    ),
+    spec(
+        "migrate",
+        -6,
+        &[Write, MovableKeys],
+        (3, 3, 1),
+        &["@keyspace", "@write", "@slow", "@dangerous"],
+        "generic",
+        "Atomically transfers a key from one Redis instance to another.",
+        command_helper::parse_migrate_command,
+    )
+    .with_key_fn(migrate_keys),
+    // Server
+    spec(
+        "info",
+        -1,
+        &[Loading, Stale],
+        NO_KEYS,
+        &["@slow", "@dangerous"],
+        "server",
+        "Returns information and statistics about the server.",
+        |e| parse_command!(option, e, Info),
+    ),
+    spec(
+        "config",
+        -2,
+        &[],
+        NO_KEYS,
+        &["@slow"],
+        "server",
+        "A container for server configuration commands.",
+        command_helper::parse_config_command,
+    )
+    .with_subcommands(CONFIG_SUBCOMMANDS),
+    spec(
+        "command",
+        -1,
+        &[Loading, Stale],
+        NO_KEYS,
+        &["@slow", "@connection"],
+        "server",
+        "Returns detailed information about all commands.",
+        command_helper::parse_command_command,
+    )
+    .with_subcommands(COMMAND_SUBCOMMANDS),
+    spec(
+        "keyspace",
+        -2,
+        &[],
+        NO_KEYS,
+        &["@slow"],
+        "server",
+        "A container for keyspace export and import commands.",
+        command_helper::parse_keyspace_command,
+    )
+    .with_subcommands(KEYSPACE_SUBCOMMANDS),
+    spec(
+        "module",
+        -2,
+        &[],
+        NO_KEYS,
+        &["@slow"],
+        "server",
+        "A container for module commands.",
+        command_helper::parse_module_command,
+    )
+    .with_subcommands(MODULE_SUBCOMMANDS),
+];
+
+fn index() -> &'static HashMap<&'static str, &'static CommandSpec> {
+    static INDEX: OnceLock<HashMap<&'static str, &'static CommandSpec>> = OnceLock::new();
+    INDEX.get_or_init(|| {
+        let mut index = HashMap::new();
+        for spec in COMMANDS {
+            index.insert(spec.name, spec);
+            for sub in spec.subcommands {
+                index.insert(sub.name, sub);
+            }
+        }
+        index
+    })
+}
+
+/// Find a command (`get`) or subcommand (`config|get`), case-insensitively.
+/// Commands added by modules are found after the builtin ones.
+pub fn lookup(name: &str) -> Option<&'static CommandSpec> {
+    let index = index();
+    if let Some(spec) = index.get(name) {
+        return Some(spec);
+    }
+    let name = name.to_ascii_lowercase();
+    index
+        .get(name.as_str())
+        .copied()
+        .or_else(|| modules::lookup(&name))
+}
+
+/// Every top-level command: the builtin table followed by module commands
+pub fn all() -> Vec<&'static CommandSpec> {
+    COMMANDS.iter().chain(modules::specs()).collect()
+}
+
+/// The spec that applies to a full argument vector: the subcommand's for
+/// container commands, otherwise the command's own
+pub fn lookup_args(args: &[Bytes]) -> Option<&'static CommandSpec> {
+    let spec = lookup(&String::from_utf8_lossy(args.first()?))?;
+    if spec.subcommands.is_empty() {
+        return Some(spec);
+    }
+    let sub = args.get(1)?;
+    lookup(&format!(
+        "{}|{}",
+        spec.name,
+        String::from_utf8_lossy(sub).to_ascii_lowercase()
+    ))
+}
@@ -1,85 +1,74 @@
-use crate::commands::command_helper::{
-    format_array_bytes, format_bulk_string, format_error, format_integer, format_null,
-};
+use crate::commands::{CommandError, Reply};
 use crate::database::traits::SortedSetOp;
 use crate::database::SharedDatabase;
 use bytes::Bytes;
 
-pub fn zadd(db: &SharedDatabase, key: Bytes, pairs: Vec<(Bytes, Bytes)>) -> Bytes {
+pub fn zadd(db: &SharedDatabase, key: Bytes, pairs: Vec<(Bytes, Bytes)>) -> Reply {
     // Parse scores from Bytes to f64
     let mut parsed_pairs = Vec::with_capacity(pairs.len());
     for (score_bytes, member) in pairs {
         let score_str = match std::str::from_utf8(&score_bytes) {
             Ok(s) => s,
-            Err(_) => return format_error(crate::commands::CommandError::InvalidFloat),
+            Err(_) => return CommandError::InvalidFloat.into(),
         };
         match score_str.parse::<f64>() {
             Ok(score) => parsed_pairs.push((score, member)),
-            Err(_) => return format_error(crate::commands::CommandError::InvalidFloat),
+            Err(_) => return CommandError::InvalidFloat.into(),
         }
     }
 
-    let added = db.zadd(&key, &parsed_pairs);
-    format_integer(added as i64)
+    db.zadd(&key, &parsed_pairs).into()
 }
 
-pub fn zrem(db: &SharedDatabase, key: Bytes, members: Vec<Bytes>) -> Bytes {
-    let removed = db.zrem(&key, &members);
-    format_integer(removed as i64)
+pub fn zrem(db: &SharedDatabase, key: Bytes, members: Vec<Bytes>) -> Reply {
+    db.zrem(&key, &members).into()
 }
 
-pub fn zrange(db: &SharedDatabase, key: Bytes, start: Bytes, stop: Bytes) -> Bytes {
+pub fn zrange(db: &SharedDatabase, key: Bytes, start: Bytes, stop: Bytes) -> Reply {
     let start_str = match std::str::from_utf8(&start) {
         Ok(s) => s,
-        Err(_) => return format_error(crate::commands::CommandError::InvalidInteger),
+        Err(_) => return CommandError::InvalidInteger.into(),
     };
     let stop_str = match std::str::from_utf8(&stop) {
         Ok(s) => s,
-        Err(_) => return format_error(crate::commands::CommandError::InvalidInteger),
+        Err(_) => return CommandError::InvalidInteger.into(),
     };
 
     match (start_str.parse::<i64>(), stop_str.parse::<i64>()) {
-        (Ok(s), Ok(e)) => match db.zrange(&key, s, e) {
-            Ok(members) => format_array_bytes(members),
-            Err(e) => format_error(e),
-        },
-        _ => format_error(crate::commands::CommandError::InvalidInteger),
+        (Ok(s), Ok(e)) => db.zrange(&key, s, e).map(Reply::bulk_array).into(),
+        _ => CommandError::InvalidInteger.into(),
     }
 }
 
-pub fn zrangebyscore(db: &SharedDatabase, key: Bytes, min: Bytes, max: Bytes) -> Bytes {
+pub fn zrangebyscore(db: &SharedDatabase, key: Bytes, min: Bytes, max: Bytes) -> Reply {
     let min_str = match std::str::from_utf8(&min) {
         Ok(s) => s,
-        Err(_) => return format_error(crate::commands::CommandError::InvalidFloat),
+        Err(_) => return CommandError::InvalidFloat.into(),
     };
     let max_str = match std::str::from_utf8(&max) {
         Ok(s) => s,
-        Err(_) => return format_error(crate::commands::CommandError::InvalidFloat),
+        Err(_) => return CommandError::InvalidFloat.into(),
     };
 
     match (min_str.parse::<f64>(), max_str.parse::<f64>()) {
-        (Ok(mn), Ok(mx)) => match db.zrange_by_score(&key, mn, mx) {
-            Ok(members) => format_array_bytes(members),
-            Err(e) => format_error(e),
-        },
-        _ => format_error(crate::commands::CommandError::InvalidFloat),
+        (Ok(mn), Ok(mx)) => db
+            .zrange_by_score(&key, mn, mx)
+            .map(Reply::bulk_array)
+            .into(),
+        _ => CommandError::InvalidFloat.into(),
     }
 }
 
-pub fn zcard(db: &SharedDatabase, key: Bytes) -> Bytes {
-    format_integer(db.zcard(&key) as i64)
+pub fn zcard(db: &SharedDatabase, key: Bytes) -> Reply {
+    db.zcard(&key).into()
 }
 
-pub fn zscore(db: &SharedDatabase, key: Bytes, member: Bytes) -> Bytes {
-    match db.zscore(&key, &member) {
-        Some(score) => format_bulk_string(&Bytes::from(score.to_string())),
-        None => format_null(),
-    }
+pub fn zscore(db: &SharedDatabase, key: Bytes, member: Bytes) -> Reply {
+    db.zscore(&key, &member)
+        .map(|score| score.map(Reply::Double))
+        .into()
 }
 
-pub fn zrank(db: &SharedDatabase, key: Bytes, member: Bytes) -> Bytes {
-    match db.zrank(&key, &member) {
-        Some(rank) => format_integer(rank as i64),
-        None => format_null(),
-    }
+pub fn zrank(db: &SharedDatabase, key: Bytes, member: Bytes) -> Reply {
+    db.zrank(&key, &member).into()
 }
@@ -1,20 +1,92 @@
 // Config module for Rudis
 // Configuration management
 
+pub mod parser;
+pub mod registry;
+
+use crate::error::{AppError, Result};
+use std::net::IpAddr;
+use tracing::warn;
+
+/// Accepted values of the `loglevel` directive, most verbose first
+pub const LOG_LEVELS: &[&str] = &["debug", "verbose", "notice", "warning", "nothing"];
+
+/// Nested `include` directives deeper than this are treated as a cycle
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// A single `save <seconds> <changes>` rule: snapshot once at least `changes`
+/// writes happened and `seconds` elapsed since the last successful save.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SavePoint {
+    pub seconds: u64,
+    pub changes: u64,
+}
+
+#[derive(Debug, Clone)]
 pub struct Config {
+    /// TCP port, 0 disables TCP listening
     pub port: u16,
-    pub host: String,
+    /// Addresses to listen on; a leading `-` marks an address as optional
+    pub bind: Vec<String>,
+    /// `protected-mode yes`: without passwords this only allows loopback
+    /// bind addresses
+    pub protected_mode: bool,
     pub max_connections: usize,
     pub db_num: usize,
+    pub dir: String,
+    pub dbfilename: String,
+    pub save: Vec<SavePoint>,
+    /// Spill cold values to segment files under `<dir>/tiered`
+    pub tiered_storage: bool,
+    /// Bytes of values kept in memory before spilling starts
+    pub tiered_max_memory: u64,
+    /// Values smaller than this always stay in memory
+    pub tiered_min_value_size: usize,
+    /// Close connections idle for this many seconds (0 = never)
+    pub timeout: u64,
+    /// Redis log level: debug, verbose, notice, warning or nothing
+    pub loglevel: String,
+    /// Refuse writes once the estimated dataset size exceeds this (0 = no limit)
+    pub maxmemory: u64,
+    /// The file the configuration was loaded from, updated by CONFIG REWRITE
+    pub config_file: Option<String>,
+    /// Command-line arguments the configuration was built from, replayed
+    /// when the file is reloaded so overrides keep taking precedence
+    pub args: Vec<String>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
             port: 6379,
-            host: "127.0.0.1".to_string(),
+            bind: vec!["127.0.0.1".to_string()],
+            protected_mode: false,
             max_connections: 1000,
             db_num: 16,
+            dir: ".".to_string(),
+            dbfilename: "dump.rdb".to_string(),
+            save: vec![
+                SavePoint {
+                    seconds: 3600,
+                    changes: 1,
+                },
+                SavePoint {
+                    seconds: 300,
+                    changes: 100,
+                },
+                SavePoint {
+                    seconds: 60,
+                    changes: 10000,
+                },
+            ],
+            tiered_storage: false,
+            tiered_max_memory: 0,
+            tiered_min_value_size: 1024,
+            timeout: 0,
+            loglevel: "notice".to_string(),
+            maxmemory: 0,
+            config_file: None,
+            args: Vec::new(),
         }
     }
 }
@@ -24,12 +96,305 @@ impl Config {
         Default::default()
     }
 
-    pub fn load() -> Self {
-        // TODO: Implement config file loading with fallback to defaults
-        Self::new()
+    /// Build the configuration from redis-server style arguments: an optional
+    /// config file path followed by `--name value...` overrides, which are
+    /// applied after the file as if they were appended to it. Returns the
+    /// warnings for ignored directives along with the configuration.
+    pub fn from_args(args: &[String]) -> Result<(Self, Vec<String>)> {
+        let mut config = Self::new();
+        config.args = args.to_vec();
+        let mut state = LoadState::default();
+        let mut rest = args;
+        if let Some((first, tail)) = args.split_first() {
+            if !first.starts_with("--") {
+                config.load_file(first, &mut state)?;
+                let absolute = std::fs::canonicalize(first).map(|p| p.display().to_string());
+                let path = absolute.unwrap_or_else(|_| first.clone());
+                config.args[0] = path.clone();
+                config.config_file = Some(path);
+                rest = tail;
+            }
+        }
+
+        let mut directives: Vec<Vec<String>> = Vec::new();
+        for arg in rest {
+            match arg.strip_prefix("--") {
+                Some(name) if !name.is_empty() => directives.push(vec![name.to_string()]),
+                _ => match directives.last_mut() {
+                    Some(directive) => directive.push(arg.clone()),
+                    None => {
+                        return Err(AppError::Config(format!(
+                            "unexpected argument '{}', options must start with --",
+                            arg
+                        )))
+                    }
+                },
+            }
+        }
+        for directive in directives {
+            let location = format!("argument --{}", directive[0]);
+            let at = |message: String| AppError::Config(format!("{}: {}", location, message));
+            config.apply_line(&directive, &location, &at, &mut state)?;
+        }
+        config.validate()?;
+        Ok((config, state.warnings))
+    }
+
+    /// Checks that need the whole configuration rather than a single directive
+    pub fn validate(&self) -> Result<()> {
+        if self.tiered_storage && self.tiered_max_memory == 0 {
+            return Err(AppError::Config(
+                "tiered-storage requires tiered-max-memory to be set".to_string(),
+            ));
+        }
+        if self.protected_mode {
+            if let Some(address) = self.bind.iter().find(|a| !is_loopback(a)) {
+                return Err(AppError::Config(format!(
+                    "protected-mode yes needs a password, which rudis does not support; \
+                     bind {} is not a loopback address",
+                    address
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply the directives of a redis.conf style file on top of the current
+    /// values. Directives rudis does not implement are skipped with a warning;
+    /// the warnings are also returned so callers can report them.
+    pub fn load_from_file(&mut self, path: &str) -> Result<Vec<String>> {
+        let mut state = LoadState::default();
+        self.load_file(path, &mut state)?;
+        Ok(state.warnings)
+    }
+
+    /// Same as `load_from_file`, for configuration text that is already in
+    /// memory. `origin` names the source in error messages.
+    pub fn load_from_str(&mut self, text: &str, origin: &str) -> Result<Vec<String>> {
+        let mut state = LoadState::default();
+        self.load_text(text, origin, &mut state)?;
+        Ok(state.warnings)
+    }
+
+    fn load_file(&mut self, path: &str, state: &mut LoadState) -> Result<()> {
+        if state.depth >= MAX_INCLUDE_DEPTH {
+            return Err(AppError::Config(format!(
+                "too many nested includes while reading '{}'",
+                path
+            )));
+        }
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| AppError::Config(format!("cannot read config file '{}': {}", path, e)))?;
+        state.depth += 1;
+        let result = self.load_text(&text, path, state);
+        state.depth -= 1;
+        result
+    }
+
+    fn load_text(&mut self, text: &str, origin: &str, state: &mut LoadState) -> Result<()> {
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let location = format!("{}, line {}", origin, index + 1);
+            let at = |message: String| {
+                AppError::Config(format!("{}: {} ('{}')", location, message, line))
+            };
+            let args = parser::split_args(line).map_err(at)?;
+            if !args.is_empty() {
+                self.apply_line(&args, &location, &at, state)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply one directive (name first), wherever it came from
+    fn apply_line(
+        &mut self,
+        args: &[String],
+        location: &str,
+        at: &dyn Fn(String) -> AppError,
+        state: &mut LoadState,
+    ) -> Result<()> {
+        let (name, args) = args.split_first().expect("directive name");
+        let name = name.to_ascii_lowercase();
+        if name == "include" {
+            let [path] = args else {
+                return Err(at("wrong number of arguments".to_string()));
+            };
+            // Errors inside the included file carry its own name and line
+            return self.load_file(path, state);
+        }
+        // An empty requirepass is how redis.conf says "no password"
+        if name == "requirepass" && args == [""] {
+            return Ok(());
+        }
+        if parser::is_security_directive(&name) {
+            return Err(at(format!(
+                "'{}' is not supported and the server would run without it",
+                name
+            )));
+        }
+        if parser::is_unsupported_directive(&name) {
+            let warning = format!("{}: unsupported directive '{}' ignored", location, name);
+            warn!("{}", warning);
+            state.warnings.push(warning);
+            return Ok(());
+        }
+        self.apply_directive(&name, args, state).map_err(at)
     }
 
-    pub fn load_from_file(&mut self, _path: &str) {
-        // TODO: Implement config file loading
+    /// Set a single directive, as CONFIG SET does. Returns the reason on failure.
+    pub fn set_directive(
+        &mut self,
+        name: &str,
+        args: &[String],
+    ) -> std::result::Result<(), String> {
+        self.apply_directive(name, args, &mut LoadState::default())
     }
+
+    fn apply_directive(
+        &mut self,
+        name: &str,
+        args: &[String],
+        state: &mut LoadState,
+    ) -> std::result::Result<(), String> {
+        if name == "save" {
+            // Accept both `save 900 1` and `save "900 1"` (as passed by `--save`)
+            let args: Vec<&str> = if args.iter().all(|a| a.is_empty()) {
+                args.iter().map(String::as_str).collect()
+            } else {
+                args.iter().flat_map(|a| a.split_whitespace()).collect()
+            };
+            let points = Self::parse_save_points(&args).map_err(|e| match e {
+                AppError::Config(message) => message,
+                other => other.to_string(),
+            })?;
+            // The first save line replaces the defaults, later ones add to it
+            if !state.save_seen || points.is_empty() {
+                self.save.clear();
+            }
+            state.save_seen = true;
+            self.save.extend(points);
+            return Ok(());
+        }
+
+        if name == "bind" {
+            // Also accept the list as one argument, as in `--bind "127.0.0.1 ::1"`
+            let addresses: Vec<String> = args
+                .iter()
+                .flat_map(|a| a.split_whitespace())
+                .map(str::to_string)
+                .collect();
+            if addresses.is_empty() {
+                return Err("Bad directive or wrong number of arguments".to_string());
+            }
+            self.bind = addresses;
+            return Ok(());
+        }
+        let [value] = args else {
+            return Err("Bad directive or wrong number of arguments".to_string());
+        };
+        let invalid = |what: &str| format!("Invalid {} '{}'", what, value);
+        match name {
+            "port" => self.port = value.parse().map_err(|_| invalid("port"))?,
+            "maxclients" => {
+                self.max_connections = value
+                    .parse()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| invalid("max clients limit"))?
+            }
+            "databases" => {
+                self.db_num = value
+                    .parse()
+                    .ok()
+                    .filter(|&n| n > 0 && n <= 256)
+                    .ok_or_else(|| invalid("number of databases"))?
+            }
+            "protected-mode" => {
+                self.protected_mode =
+                    parser::parse_bool(value).ok_or("argument must be 'yes' or 'no'")?
+            }
+            "dir" => self.dir = value.clone(),
+            "dbfilename" => {
+                if value.contains('/') || value.is_empty() {
+                    return Err("dbfilename can't be a path, just a filename".to_string());
+                }
+                self.dbfilename = value.clone()
+            }
+            "tiered-storage" => {
+                self.tiered_storage =
+                    parser::parse_bool(value).ok_or("argument must be 'yes' or 'no'")?
+            }
+            "tiered-max-memory" => {
+                self.tiered_max_memory =
+                    parser::parse_memory(value).ok_or_else(|| invalid("memory amount"))?
+            }
+            "tiered-min-value-size" => {
+                self.tiered_min_value_size = parser::parse_memory(value)
+                    .and_then(|n| usize::try_from(n).ok())
+                    .ok_or_else(|| invalid("memory amount"))?
+            }
+            "timeout" => self.timeout = value.parse().map_err(|_| invalid("timeout"))?,
+            "loglevel" => {
+                let level = value.to_ascii_lowercase();
+                if !LOG_LEVELS.contains(&level.as_str()) {
+                    return Err(format!("argument must be one of {}", LOG_LEVELS.join(", ")));
+                }
+                self.loglevel = level
+            }
+            "maxmemory" => {
+                self.maxmemory =
+                    parser::parse_memory(value).ok_or_else(|| invalid("memory amount"))?
+            }
+            _ => return Err("Bad directive or wrong number of arguments".to_string()),
+        }
+        Ok(())
+    }
+
+    /// Parse the arguments of a `save` directive, e.g. `900 1 300 10`.
+    /// An empty argument list (or a single `""`) disables snapshotting.
+    pub fn parse_save_points(args: &[&str]) -> Result<Vec<SavePoint>> {
+        if args.is_empty() || (args.len() == 1 && args[0].is_empty()) {
+            return Ok(Vec::new());
+        }
+        if !args.len().is_multiple_of(2) {
+            return Err(AppError::Config(
+                "save expects <seconds> <changes> pairs".to_string(),
+            ));
+        }
+        args.chunks(2)
+            .map(|pair| {
+                let seconds = pair[0].parse::<u64>();
+                let changes = pair[1].parse::<u64>();
+                match (seconds, changes) {
+                    (Ok(seconds), Ok(changes)) => Ok(SavePoint { seconds, changes }),
+                    _ => Err(AppError::Config(format!(
+                        "invalid save parameters '{} {}'",
+                        pair[0], pair[1]
+                    ))),
+                }
+            })
+            .collect()
+    }
+
+    pub fn rdb_path(&self) -> std::path::PathBuf {
+        std::path::Path::new(&self.dir).join(&self.dbfilename)
+    }
+}
+
+/// Whether a `bind` address only accepts local connections
+fn is_loopback(address: &str) -> bool {
+    let host = address.strip_prefix('-').unwrap_or(address);
+    host == "localhost" || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Bookkeeping for one load, shared across included files
+#[derive(Default)]
+struct LoadState {
+    save_seen: bool,
+    depth: usize,
+    warnings: Vec<String>,
 }
@@ -0,0 +1,223 @@
+// redis.conf syntax helpers
+// Argument splitting (with Redis' quoting rules), memory units and booleans
+
+/// Split a config line into arguments the way Redis does (`sdssplitargs`):
+/// whitespace separated, `"double quotes"` with C-style escapes including
+/// `\xHH`, and `'single quotes'` where only `\'` is special. A closing quote
+/// must be followed by whitespace or the end of the line.
+pub fn split_args(line: &str) -> Result<Vec<String>, String> {
+    let bytes = line.as_bytes();
+    let mut args = Vec::new();
+    let mut i = 0;
+    loop {
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i >= bytes.len() {
+            return Ok(args);
+        }
+
+        let mut current = Vec::new();
+        let mut in_double = false;
+        let mut in_single = false;
+        loop {
+            let Some(&c) = bytes.get(i) else {
+                if in_double || in_single {
+                    return Err("unbalanced quotes in configuration line".to_string());
+                }
+                break;
+            };
+            if in_double {
+                if c == b'\\' && i + 3 < bytes.len() && bytes[i + 1] == b'x' {
+                    let hex = std::str::from_utf8(&bytes[i + 2..i + 4]).ok();
+                    if let Some(byte) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                        current.push(byte);
+                        i += 4;
+                        continue;
+                    }
+                }
+                if c == b'\\' && i + 1 < bytes.len() {
+                    current.push(match bytes[i + 1] {
+                        b'n' => b'\n',
+                        b'r' => b'\r',
+                        b't' => b'\t',
+                        b'b' => 0x08,
+                        b'a' => 0x07,
+                        other => other,
+                    });
+                    i += 2;
+                    continue;
+                }
+                if c == b'"' {
+                    if bytes.get(i + 1).is_some_and(|n| !n.is_ascii_whitespace()) {
+                        return Err("closing quote must be followed by a space".to_string());
+                    }
+                    i += 1;
+                    break;
+                }
+                current.push(c);
+            } else if in_single {
+                if c == b'\\' && bytes.get(i + 1) == Some(&b'\'') {
+                    current.push(b'\'');
+                    i += 2;
+                    continue;
+                }
+                if c == b'\'' {
+                    if bytes.get(i + 1).is_some_and(|n| !n.is_ascii_whitespace()) {
+                        return Err("closing quote must be followed by a space".to_string());
+                    }
+                    i += 1;
+                    break;
+                }
+                current.push(c);
+            } else {
+                match c {
+                    b' ' | b'\t' | b'\n' | b'\r' | b'\0' => break,
+                    b'"' => in_double = true,
+                    b'\'' => in_single = true,
+                    _ => current.push(c),
+                }
+            }
+            i += 1;
+        }
+        args.push(String::from_utf8_lossy(&current).into_owned());
+    }
+}
+
+/// Parse a memory amount like `1gb`, `512mb`, `100k` or a plain byte count.
+/// As in Redis, `k`/`m`/`g` are powers of 1000 and `kb`/`mb`/`gb` powers of 1024.
+pub fn parse_memory(value: &str) -> Option<u64> {
+    let lower = value.to_ascii_lowercase();
+    let split = lower
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(lower.len());
+    let (number, unit) = lower.split_at(split);
+    let multiplier: u64 = match unit {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "yes" => Some(true),
+        "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// Directives from redis.conf that rudis accepts but ignores (with a
+/// warning), so existing config files load unchanged. A trailing `*` matches
+/// any suffix.
+const UNSUPPORTED_DIRECTIVES: &[&str] = &[
+    "acl-pubsub-default",
+    "acllog-max-len",
+    "active-defrag-*",
+    "activedefrag",
+    "activerehashing",
+    "always-show-logo",
+    "aof-*",
+    "appenddirname",
+    "appendfilename",
+    "appendfsync",
+    "appendonly",
+    "auto-aof-rewrite-*",
+    "bind-source-addr",
+    "busy-reply-threshold",
+    "client-output-buffer-limit",
+    "client-query-buffer-limit",
+    "cluster-*",
+    "crash-log-enabled",
+    "crash-memcheck-enabled",
+    "daemonize",
+    "disable-thp",
+    "dynamic-hz",
+    "enable-*",
+    "hash-max-*",
+    "hll-sparse-max-bytes",
+    "hz",
+    "ignore-warnings",
+    "io-threads",
+    "io-threads-do-reads",
+    "jemalloc-bg-thread",
+    "latency-monitor-threshold",
+    "latency-tracking",
+    "latency-tracking-info-percentiles",
+    "lazyfree-*",
+    "lfu-decay-time",
+    "lfu-log-factor",
+    "list-compress-depth",
+    "list-max-*",
+    "loadmodule",
+    "locale-collate",
+    "logfile",
+    "lua-time-limit",
+    "masterauth",
+    "masteruser",
+    "maxmemory-*",
+    "min-replicas-*",
+    "min-slaves-*",
+    "no-appendfsync-on-rewrite",
+    "notify-keyspace-events",
+    "oom-score-adj",
+    "oom-score-adj-values",
+    "pidfile",
+    "proc-title-template",
+    "propagation-error-behavior",
+    "proto-max-bulk-len",
+    "rdb-del-sync-files",
+    "rdb-key-save-delay",
+    "rdb-save-incremental-fsync",
+    "rdbchecksum",
+    "rdbcompression",
+    "rename-command",
+    "repl-*",
+    "replica-*",
+    "replicaof",
+    "sanitize-dump-payload",
+    "set-max-*",
+    "set-proc-title",
+    "shutdown-*",
+    "slave-*",
+    "slaveof",
+    "slowlog-log-slower-than",
+    "slowlog-max-len",
+    "socket-mark-id",
+    "stop-writes-on-bgsave-error",
+    "stream-node-max-*",
+    "supervised",
+    "syslog-enabled",
+    "syslog-facility",
+    "syslog-ident",
+    "tcp-backlog",
+    "tcp-keepalive",
+    "tls-*",
+    "tracking-table-max-keys",
+    "unixsocket",
+    "unixsocketperm",
+    "zset-max-*",
+];
+
+/// Authentication directives rudis cannot enforce. Ignoring them would leave
+/// open a server the file meant to protect, so loading fails instead.
+const SECURITY_DIRECTIVES: &[&str] = &["aclfile", "requirepass", "user"];
+
+pub fn is_security_directive(name: &str) -> bool {
+    SECURITY_DIRECTIVES.contains(&name)
+}
+
+pub fn is_unsupported_directive(name: &str) -> bool {
+    UNSUPPORTED_DIRECTIVES
+        .iter()
+        .any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == *pattern,
+        })
+}
@@ -0,0 +1,354 @@
+// Runtime configuration registry
+// Lists the parameters exposed through CONFIG GET/SET, which of them can
+// change while the server runs and how a change is applied, and implements
+// CONFIG REWRITE.
+
+use super::{parser, Config, SavePoint};
+use crate::database::Database;
+use crate::error::{AppError, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use tracing::{info, warn};
+
+/// Appended above parameters CONFIG REWRITE had to add to the file
+const REWRITE_MARKER: &str = "# Generated by CONFIG REWRITE";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamKind {
+    Plain,
+    /// Several space separated values on one line
+    List,
+    /// A byte count, rewritten with the largest exact unit (`1gb`)
+    Memory,
+    /// One `save` line per save point
+    Save,
+    /// Reported for client compatibility, not a configuration directive
+    ReadOnly,
+}
+
+pub struct ConfigParam {
+    pub name: &'static str,
+    /// Whether CONFIG SET may change it
+    pub mutable: bool,
+    pub kind: ParamKind,
+    pub get: fn(&Config) -> String,
+    /// Applies a new value to the running server. Parameters without a hook
+    /// are read from the shared configuration wherever they are used.
+    pub apply: Option<fn(&Database, &Config)>,
+}
+
+fn yes_no(value: bool) -> String {
+    if value { "yes" } else { "no" }.to_string()
+}
+
+fn save_points(config: &Config) -> String {
+    config
+        .save
+        .iter()
+        .map(|sp| format!("{} {}", sp.seconds, sp.changes))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub static PARAMS: &[ConfigParam] = &[
+    ConfigParam {
+        name: "bind",
+        mutable: false,
+        kind: ParamKind::List,
+        get: |c| c.bind.join(" "),
+        apply: None,
+    },
+    ConfigParam {
+        name: "port",
+        mutable: false,
+        kind: ParamKind::Plain,
+        get: |c| c.port.to_string(),
+        apply: None,
+    },
+    ConfigParam {
+        name: "databases",
+        mutable: false,
+        kind: ParamKind::Plain,
+        get: |c| c.db_num.to_string(),
+        apply: None,
+    },
+    ConfigParam {
+        name: "dir",
+        mutable: false,
+        kind: ParamKind::Plain,
+        get: |c| c.dir.clone(),
+        apply: None,
+    },
+    ConfigParam {
+        name: "dbfilename",
+        mutable: false,
+        kind: ParamKind::Plain,
+        get: |c| c.dbfilename.clone(),
+        apply: None,
+    },
+    ConfigParam {
+        name: "maxclients",
+        mutable: true,
+        kind: ParamKind::Plain,
+        get: |c| c.max_connections.to_string(),
+        apply: None,
+    },
+    ConfigParam {
+        name: "timeout",
+        mutable: true,
+        kind: ParamKind::Plain,
+        get: |c| c.timeout.to_string(),
+        apply: None,
+    },
+    ConfigParam {
+        name: "save",
+        mutable: true,
+        kind: ParamKind::Save,
+        get: save_points,
+        apply: None,
+    },
+    ConfigParam {
+        name: "loglevel",
+        mutable: true,
+        kind: ParamKind::Plain,
+        get: |c| c.loglevel.clone(),
+        apply: Some(|_, c| crate::logging::set_level(&c.loglevel)),
+    },
+    ConfigParam {
+        name: "maxmemory",
+        mutable: true,
+        kind: ParamKind::Memory,
+        get: |c| c.maxmemory.to_string(),
+        // Start refusing (or accepting) writes right away
+        apply: Some(|db, _| {
+            db.refresh_used_memory();
+        }),
+    },
+    ConfigParam {
+        name: "maxmemory-policy",
+        mutable: false,
+        kind: ParamKind::ReadOnly,
+        get: |_| "noeviction".to_string(),
+        apply: None,
+    },
+    ConfigParam {
+        name: "appendonly",
+        mutable: false,
+        kind: ParamKind::ReadOnly,
+        get: |_| "no".to_string(),
+        apply: None,
+    },
+    ConfigParam {
+        name: "tiered-storage",
+        mutable: false,
+        kind: ParamKind::Plain,
+        get: |c| yes_no(c.tiered_storage),
+        apply: None,
+    },
+    ConfigParam {
+        name: "tiered-max-memory",
+        mutable: true,
+        kind: ParamKind::Memory,
+        get: |c| c.tiered_max_memory.to_string(),
+        apply: Some(|db, c| {
+            if let Some(store) = db.tiered() {
+                store.set_max_memory(c.tiered_max_memory);
+            }
+        }),
+    },
+    ConfigParam {
+        name: "tiered-min-value-size",
+        mutable: false,
+        kind: ParamKind::Memory,
+        get: |c| c.tiered_min_value_size.to_string(),
+        apply: None,
+    },
+];
+
+pub fn lookup(name: &str) -> Option<&'static ConfigParam> {
+    PARAMS.iter().find(|p| p.name.eq_ignore_ascii_case(name))
+}
+
+/// Outcome of reloading the config file
+#[derive(Debug, Default)]
+pub struct ReloadReport {
+    /// Parameters changed on the running server: (name, old value, new value)
+    pub applied: Vec<(&'static str, String, String)>,
+    /// Parameters that changed in the file but only take effect on restart
+    pub restart_required: Vec<&'static str>,
+    /// Warnings from loading the file
+    pub warnings: Vec<String>,
+}
+
+/// Re-read the config file (with the original command-line overrides) and
+/// apply every changed runtime-mutable parameter. The new configuration is
+/// validated as a whole first, so on error nothing changes.
+pub fn reload(db: &Database) -> Result<ReloadReport> {
+    let args = db.config().args.clone();
+    if db.config().config_file.is_none() {
+        return Err(AppError::Config(
+            "the server is running without a config file".to_string(),
+        ));
+    }
+    let (loaded, warnings) = Config::from_args(&args)?;
+
+    let mut current = db.config.write();
+    let mut config = current.clone();
+    let mut report = ReloadReport {
+        warnings,
+        ..ReloadReport::default()
+    };
+    let mut changed = Vec::new();
+    for param in PARAMS.iter().filter(|p| p.kind != ParamKind::ReadOnly) {
+        let (old, new) = ((param.get)(&config), (param.get)(&loaded));
+        if old == new {
+            continue;
+        }
+        if !param.mutable {
+            report.restart_required.push(param.name);
+            continue;
+        }
+        config
+            .set_directive(param.name, std::slice::from_ref(&new))
+            .map_err(|e| AppError::Config(format!("{}: {}", param.name, e)))?;
+        report.applied.push((param.name, old, new));
+        changed.push(param);
+    }
+    config.validate()?;
+    *current = config.clone();
+    drop(current);
+
+    // Log before applying, a new loglevel may hide these lines
+    for (name, old, new) in &report.applied {
+        info!(
+            "Config reload: {} changed from '{}' to '{}'",
+            name, old, new
+        );
+    }
+    for name in &report.restart_required {
+        warn!(
+            "Config reload: {} changed in the config file but requires a restart",
+            name
+        );
+    }
+    for param in changed {
+        if let Some(apply) = param.apply {
+            apply(db, &config);
+        }
+    }
+    Ok(report)
+}
+
+/// Quote a value for a config file if the tokenizer would otherwise split it
+fn quote(value: &str) -> String {
+    if !value.is_empty()
+        && !value
+            .chars()
+            .any(|c| c.is_whitespace() || c == '"' || c == '\'' || c == '\\')
+    {
+        return value.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn format_memory(bytes: u64) -> String {
+    const GB: u64 = 1024 * 1024 * 1024;
+    const MB: u64 = 1024 * 1024;
+    const KB: u64 = 1024;
+    match bytes {
+        0 => "0".to_string(),
+        b if b % GB == 0 => format!("{}gb", b / GB),
+        b if b % MB == 0 => format!("{}mb", b / MB),
+        b if b % KB == 0 => format!("{}kb", b / KB),
+        b => b.to_string(),
+    }
+}
+
+/// The config file lines that set `param` to its current value
+fn config_lines(param: &ConfigParam, config: &Config) -> Vec<String> {
+    match param.kind {
+        ParamKind::ReadOnly => Vec::new(),
+        ParamKind::Save if config.save.is_empty() => vec!["save \"\"".to_string()],
+        ParamKind::Save => config
+            .save
+            .iter()
+            .map(|SavePoint { seconds, changes }| format!("save {} {}", seconds, changes))
+            .collect(),
+        ParamKind::Memory => {
+            let value = (param.get)(config).parse().unwrap_or(0);
+            vec![format!("{} {}", param.name, format_memory(value))]
+        }
+        ParamKind::List => {
+            let values = (param.get)(config);
+            let values: Vec<String> = values.split_whitespace().map(quote).collect();
+            vec![format!("{} {}", param.name, values.join(" "))]
+        }
+        ParamKind::Plain => vec![format!("{} {}", param.name, quote(&(param.get)(config)))],
+    }
+}
+
+/// Write `config` back to `path`, keeping comments, unknown directives and
+/// the order of the existing lines. Each known parameter replaces its first
+/// occurrence, later duplicates are dropped, and parameters that differ from
+/// their defaults but are not in the file yet are appended at the end.
+pub fn rewrite(config: &Config, path: &str) -> Result<()> {
+    let original = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut lines = Vec::new();
+    let mut written = HashSet::new();
+    for line in original.lines() {
+        let trimmed = line.trim();
+        let param = match parser::split_args(trimmed) {
+            Ok(args) if !trimmed.starts_with('#') && !args.is_empty() => lookup(&args[0]),
+            _ => None,
+        };
+        match param {
+            Some(param) if param.kind != ParamKind::ReadOnly => {
+                if written.insert(param.name) {
+                    lines.extend(config_lines(param, config));
+                }
+            }
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let defaults = Config::default();
+    let missing: Vec<&ConfigParam> = PARAMS
+        .iter()
+        .filter(|p| p.kind != ParamKind::ReadOnly && !written.contains(p.name))
+        .filter(|p| (p.get)(config) != (p.get)(&defaults))
+        .collect();
+    if !missing.is_empty() {
+        if !lines.iter().any(|line| line.trim() == REWRITE_MARKER) {
+            lines.push(REWRITE_MARKER.to_string());
+        }
+        for param in missing {
+            lines.extend(config_lines(param, config));
+        }
+    }
+
+    let mut text = lines.join("\n");
+    text.push('\n');
+    let target = Path::new(path);
+    let tmp = target.with_extension(format!("rewrite-{}.tmp", std::process::id()));
+    fs::write(&tmp, text)?;
+    fs::rename(&tmp, target)?;
+    Ok(())
+}
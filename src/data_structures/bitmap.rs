@@ -0,0 +1,320 @@
+// Bitmap operations for Rudis
+// Bitmaps are plain strings addressed bit by bit. As in Redis, bit 0 is the
+// most significant bit of the first byte, and bits past the end read as 0.
+
+use bytes::Bytes;
+
+/// Highest bit offset, keeping bitmaps within the 512MB string limit
+pub const MAX_BIT_OFFSET: u64 = 512 * 1024 * 1024 * 8 - 1;
+
+/// What the range of BITCOUNT and BITPOS counts in
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum BitUnit {
+    #[default]
+    Byte,
+    Bit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BitOperation {
+    And,
+    Or,
+    Xor,
+    Not,
+    /// Bits of the first source set in none of the others
+    Diff,
+    /// Bits set in exactly one source
+    One,
+}
+
+/// What BITFIELD does when a SET or INCRBY leaves the type's range
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Overflow {
+    #[default]
+    Wrap,
+    Sat,
+    Fail,
+}
+
+/// Integer type of a BITFIELD operation: `i1` to `i64` or `u1` to `u63`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BitfieldType {
+    pub signed: bool,
+    pub bits: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BitfieldOp {
+    Get {
+        ty: BitfieldType,
+        offset: u64,
+    },
+    Set {
+        ty: BitfieldType,
+        offset: u64,
+        value: i64,
+        overflow: Overflow,
+    },
+    IncrBy {
+        ty: BitfieldType,
+        offset: u64,
+        increment: i64,
+        overflow: Overflow,
+    },
+}
+
+pub fn get_bit(bytes: &[u8], offset: u64) -> u8 {
+    match bytes.get((offset / 8) as usize) {
+        Some(byte) => (byte >> (7 - offset % 8)) & 1,
+        None => 0,
+    }
+}
+
+/// Set the bit at `offset`, which must be inside `bytes`, returning the old one
+pub fn set_bit(bytes: &mut [u8], offset: u64, bit: u8) -> u8 {
+    let byte = &mut bytes[(offset / 8) as usize];
+    let mask = 1 << (7 - offset % 8);
+    let old = (*byte & mask != 0) as u8;
+    if bit == 1 {
+        *byte |= mask;
+    } else {
+        *byte &= !mask;
+    }
+    old
+}
+
+/// Bytes needed to hold every bit below `end`
+pub fn byte_len(end: u64) -> usize {
+    end.div_ceil(8) as usize
+}
+
+/// `start..=end` over `len` units, negative indexes counting from the end
+/// and out of range parts clipped, as GETRANGE does; `None` if empty
+fn clip_range(start: i64, end: i64, len: i64) -> Option<(u64, u64)> {
+    if start < 0 && end < 0 && start > end {
+        return None;
+    }
+    let start = if start < 0 {
+        (len + start).max(0)
+    } else {
+        start
+    };
+    let end = if end < 0 { (len + end).max(0) } else { end }.min(len - 1);
+    (start <= end).then_some((start as u64, end as u64))
+}
+
+/// First and last bit of a clipped range
+fn bit_range(bytes: &[u8], start: i64, end: i64, unit: BitUnit) -> Option<(u64, u64)> {
+    match unit {
+        BitUnit::Byte => {
+            clip_range(start, end, bytes.len() as i64).map(|(start, end)| (start * 8, end * 8 + 7))
+        }
+        BitUnit::Bit => clip_range(start, end, bytes.len() as i64 * 8),
+    }
+}
+
+/// Number of set bits, in the whole string or in `start..=end`
+pub fn bit_count(bytes: &[u8], range: Option<(i64, i64)>, unit: BitUnit) -> u64 {
+    let (start, end) = match range {
+        Some((start, end)) => match bit_range(bytes, start, end, unit) {
+            Some(range) => range,
+            None => return 0,
+        },
+        None if bytes.is_empty() => return 0,
+        None => (0, bytes.len() as u64 * 8 - 1),
+    };
+    let (first, last) = ((start / 8) as usize, (end / 8) as usize);
+    let count: u64 = bytes[first..=last]
+        .iter()
+        .map(|byte| byte.count_ones() as u64)
+        .sum();
+    // Leave out the bits of the first and last byte outside the range
+    let before = (bytes[first] as u32 >> (8 - start % 8)).count_ones();
+    let after = (bytes[last] as u32 & ((1 << (7 - end % 8)) - 1)).count_ones();
+    count - before as u64 - after as u64
+}
+
+/// Offset of the first `bit` in `start..=end`. Without an end, a search for
+/// a 0 that finds none returns the first bit past the string, which reads
+/// as 0; with one, it returns -1 like a search for a 1 that finds none.
+pub fn bit_pos(bytes: &[u8], bit: u8, start: Option<i64>, end: Option<i64>, unit: BitUnit) -> i64 {
+    let Some((from, to)) = bit_range(bytes, start.unwrap_or(0), end.unwrap_or(-1), unit) else {
+        return -1;
+    };
+    // Whole bytes without the bit are skipped at once
+    let skip = if bit == 1 { 0x00 } else { 0xff };
+    let mut offset = from;
+    while offset <= to {
+        if offset % 8 == 0 && offset + 7 <= to && bytes[(offset / 8) as usize] == skip {
+            offset += 8;
+            continue;
+        }
+        if get_bit(bytes, offset) == bit {
+            return offset as i64;
+        }
+        offset += 1;
+    }
+    if bit == 0 && end.is_none() {
+        to as i64 + 1
+    } else {
+        -1
+    }
+}
+
+/// Combine `sources` byte by byte, shorter ones padded with zero bytes
+pub fn bit_op(op: BitOperation, sources: &[Bytes]) -> Vec<u8> {
+    let len = sources.iter().map(Bytes::len).max().unwrap_or(0);
+    (0..len)
+        .map(|i| {
+            let mut bytes = sources
+                .iter()
+                .map(|source| source.get(i).copied().unwrap_or(0));
+            match op {
+                BitOperation::And => bytes.fold(0xff, |acc, byte| acc & byte),
+                BitOperation::Or => bytes.fold(0, |acc, byte| acc | byte),
+                BitOperation::Xor => bytes.fold(0, |acc, byte| acc ^ byte),
+                BitOperation::Not => !bytes.next().unwrap_or(0),
+                BitOperation::Diff => {
+                    let first = bytes.next().unwrap_or(0);
+                    first & !bytes.fold(0, |acc, byte| acc | byte)
+                }
+                BitOperation::One => {
+                    // Bits seen once so far, and bits seen more than once
+                    let (once, _) = bytes.fold((0u8, 0u8), |(once, many), byte| {
+                        let many = many | (once & byte);
+                        ((once | byte) & !many, many)
+                    });
+                    once
+                }
+            }
+        })
+        .collect()
+}
+
+impl BitfieldType {
+    /// `i<bits>` or `u<bits>`, in either case
+    pub fn parse(spec: &[u8]) -> Option<Self> {
+        let (sign, bits) = spec.split_first()?;
+        let signed = match sign {
+            b'i' | b'I' => true,
+            b'u' | b'U' => false,
+            _ => return None,
+        };
+        if bits.is_empty() || !bits.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        let bits: u32 = std::str::from_utf8(bits).ok()?.parse().ok()?;
+        let max = if signed { 64 } else { 63 };
+        (1..=max)
+            .contains(&bits)
+            .then_some(BitfieldType { signed, bits })
+    }
+
+    fn min(self) -> i128 {
+        if self.signed {
+            -(1 << (self.bits - 1))
+        } else {
+            0
+        }
+    }
+
+    fn max(self) -> i128 {
+        if self.signed {
+            (1 << (self.bits - 1)) - 1
+        } else {
+            (1 << self.bits) - 1
+        }
+    }
+
+    /// The field at `offset`
+    pub fn get(self, bytes: &[u8], offset: u64) -> i64 {
+        let raw = (0..self.bits as u64).fold(0u64, |raw, i| {
+            (raw << 1) | get_bit(bytes, offset + i) as u64
+        });
+        let negative = self.signed && self.bits < 64 && raw >> (self.bits - 1) & 1 == 1;
+        if negative {
+            (raw | (u64::MAX << self.bits)) as i64
+        } else {
+            raw as i64
+        }
+    }
+
+    /// Write `value`, which must be in range, at `offset` inside `bytes`
+    fn set(self, bytes: &mut [u8], offset: u64, value: i64) {
+        for i in 0..self.bits as u64 {
+            let bit = (value as u64 >> (self.bits as u64 - 1 - i)) & 1;
+            set_bit(bytes, offset + i, bit as u8);
+        }
+    }
+
+    /// `value` brought into range as `overflow` says, or `None` for FAIL
+    fn fit(self, value: i128, overflow: Overflow) -> Option<i64> {
+        if (self.min()..=self.max()).contains(&value) {
+            return Some(value as i64);
+        }
+        match overflow {
+            Overflow::Wrap => {
+                // Keep the low bits, then sign-extend them for signed types
+                let low = value as u64 & (u64::MAX >> (64 - self.bits));
+                let negative = self.signed && low >> (self.bits - 1) & 1 == 1;
+                Some(if negative && self.bits < 64 {
+                    (low | (u64::MAX << self.bits)) as i64
+                } else {
+                    low as i64
+                })
+            }
+            Overflow::Sat if value > self.max() => Some(self.max() as i64),
+            Overflow::Sat => Some(self.min() as i64),
+            Overflow::Fail => None,
+        }
+    }
+}
+
+impl BitfieldOp {
+    /// End of the bits a SET or INCRBY writes; the string grows to hold them
+    pub fn write_end(&self) -> Option<u64> {
+        match *self {
+            BitfieldOp::Get { .. } => None,
+            BitfieldOp::Set { ty, offset, .. } | BitfieldOp::IncrBy { ty, offset, .. } => {
+                Some(offset + ty.bits as u64)
+            }
+        }
+    }
+
+    /// Run the operation. GET returns the field, SET its old value and
+    /// INCRBY its new one; an overflow under FAIL writes nothing and
+    /// returns `None`.
+    pub fn apply(&self, bytes: &mut [u8]) -> Option<i64> {
+        match *self {
+            BitfieldOp::Get { ty, offset } => Some(ty.get(bytes, offset)),
+            BitfieldOp::Set {
+                ty,
+                offset,
+                value,
+                overflow,
+            } => {
+                // Unsigned types take the value's bits as an unsigned number
+                let value = if ty.signed {
+                    value as i128
+                } else {
+                    value as u64 as i128
+                };
+                let old = ty.get(bytes, offset);
+                let value = ty.fit(value, overflow)?;
+                ty.set(bytes, offset, value);
+                Some(old)
+            }
+            BitfieldOp::IncrBy {
+                ty,
+                offset,
+                increment,
+                overflow,
+            } => {
+                let old = ty.get(bytes, offset);
+                let value = ty.fit(old as i128 + increment as i128, overflow)?;
+                ty.set(bytes, offset, value);
+                Some(value)
+            }
+        }
+    }
+}
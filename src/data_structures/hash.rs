@@ -1,9 +1,14 @@
 // Hash data structure for Rudis
 
+use super::scan::scan;
+use crate::commands::CommandError;
 use bytes::Bytes;
+use rand::seq::IndexedRandom;
+use rand::Rng;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct RedisHash {
     fields: HashMap<Bytes, Bytes>,
 }
@@ -39,6 +44,9 @@ impl RedisHash {
     pub fn values(&self) -> impl Iterator<Item = &Bytes> {
         self.fields.values()
     }
+    pub fn iter(&self) -> impl Iterator<Item = (&Bytes, &Bytes)> {
+        self.fields.iter()
+    }
     pub fn flatten(&self) -> impl Iterator<Item = &Bytes> {
         self.fields.iter().flat_map(|(k, v)| [k, v])
     }
@@ -47,47 +55,88 @@ impl RedisHash {
         self.fields.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
     pub fn hexists(&self, field: &Bytes) -> bool {
         self.fields.contains_key(field)
     }
 
-    pub fn hincrby(
-        &mut self,
-        field: &Bytes,
-        value: i64,
-    ) -> Result<i64, crate::commands::CommandError> {
-        let current_value = if let Some(existing) = self.fields.get(field) {
-            let s = std::str::from_utf8(existing)
-                .map_err(|_| crate::commands::CommandError::InvalidInteger)?;
-            s.parse::<i64>()
-                .map_err(|_| crate::commands::CommandError::InvalidInteger)?
-        } else {
-            0
+    pub fn hincrby(&mut self, field: &Bytes, value: i64) -> Result<i64, CommandError> {
+        let current_value = match self.fields.get(field) {
+            Some(existing) => std::str::from_utf8(existing)
+                .ok()
+                .and_then(|s| s.parse::<i64>().ok())
+                .ok_or(CommandError::HashValueNotInteger)?,
+            None => 0,
         };
 
-        let new_value = current_value + value;
+        let new_value = current_value
+            .checked_add(value)
+            .ok_or(CommandError::Overflow)?;
         self.fields
             .insert(field.clone(), Bytes::from(new_value.to_string()));
         Ok(new_value)
     }
 
-    pub fn hincrbyfloat(
-        &mut self,
-        field: &Bytes,
-        value: f64,
-    ) -> Result<f64, crate::commands::CommandError> {
-        let current_value = if let Some(existing) = self.fields.get(field) {
-            let s = std::str::from_utf8(existing)
-                .map_err(|_| crate::commands::CommandError::InvalidFloat)?;
-            s.parse::<f64>()
-                .map_err(|_| crate::commands::CommandError::InvalidFloat)?
-        } else {
-            0.0
+    pub fn hincrbyfloat(&mut self, field: &Bytes, value: f64) -> Result<f64, CommandError> {
+        let current_value = match self.fields.get(field) {
+            Some(existing) => std::str::from_utf8(existing)
+                .ok()
+                .and_then(|s| s.parse::<f64>().ok())
+                .filter(|n| !n.is_nan())
+                .ok_or(CommandError::HashValueNotFloat)?,
+            None => 0.0,
         };
 
         let new_value = current_value + value;
+        if !new_value.is_finite() {
+            return Err(CommandError::NanOrInfinity);
+        }
         self.fields
             .insert(field.clone(), Bytes::from(new_value.to_string()));
         Ok(new_value)
     }
+
+    /// Set `field` only if it is not there yet; true if it was set
+    pub fn hsetnx(&mut self, field: Bytes, value: Bytes) -> bool {
+        match self.fields.entry(field) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                true
+            }
+        }
+    }
+
+    /// Length of the value of `field`, 0 if there is none
+    pub fn hstrlen(&self, field: &Bytes) -> usize {
+        self.fields.get(field).map_or(0, Bytes::len)
+    }
+
+    /// Fields picked at random, as HRANDFIELD does: `count` different ones,
+    /// or all of them if there are fewer, or for a negative count, `-count`
+    /// fields that may repeat
+    pub fn random_fields(&self, count: i64) -> Vec<(&Bytes, &Bytes)> {
+        let mut rng = rand::rng();
+        let fields: Vec<_> = self.fields.iter().collect();
+        if count >= 0 {
+            fields
+                .choose_multiple(&mut rng, count as usize)
+                .copied()
+                .collect()
+        } else if fields.is_empty() {
+            Vec::new()
+        } else {
+            (0..count.unsigned_abs())
+                .map(|_| fields[rng.random_range(0..fields.len())])
+                .collect()
+        }
+    }
+
+    /// One HSCAN step from `cursor`; see [`scan`]
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<(&Bytes, &Bytes)>) {
+        scan(self.fields.iter().map(|pair| (pair.0, pair)), cursor, count)
+    }
 }
@@ -1,9 +1,30 @@
 // List data structure for Rudis
 
+use crate::commands::CommandError;
 use bytes::Bytes;
 use std::collections::VecDeque;
 
-#[derive(Debug)]
+/// Which end of a list a command works on: `LEFT` is the head
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListEnd {
+    Left,
+    Right,
+}
+
+impl ListEnd {
+    /// `LEFT` or `RIGHT`, in either case
+    pub fn parse(end: &[u8]) -> Option<Self> {
+        if end.eq_ignore_ascii_case(b"LEFT") {
+            Some(ListEnd::Left)
+        } else if end.eq_ignore_ascii_case(b"RIGHT") {
+            Some(ListEnd::Right)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct RedisList {
     items: VecDeque<Bytes>,
 }
@@ -59,28 +80,28 @@ impl RedisList {
         let len = self.items.len() as i64;
         let actual_start = if start < 0 { len + start } else { start };
         let actual_end = if end < 0 { len + end } else { end };
-        if actual_start > actual_end {
-            self.items.clear();
-            return;
-        }
         let start_idx = actual_start.max(0) as usize;
-        let end_idx = (actual_end + 1).min(len) as usize;
+        let end_idx = (actual_end + 1).clamp(0, len) as usize;
         if start_idx >= end_idx {
             self.items.clear();
         } else {
-            // Keep only start_idx..end_idx
-            let new_items = self
-                .items
-                .range(start_idx..end_idx)
-                .cloned()
-                .collect::<VecDeque<_>>();
-            self.items = new_items;
+            // Drop both ends in place rather than copying what is kept
+            self.items.truncate(end_idx);
+            self.items.drain(..start_idx);
         }
     }
     pub fn len(&self) -> usize {
         self.items.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Bytes> {
+        self.items.iter()
+    }
+
     pub(crate) fn set(&mut self, index: i64, value: Bytes) {
         let len = self.items.len() as i64;
         let actual_index = if index < 0 { len + index } else { index };
@@ -89,26 +110,97 @@ impl RedisList {
         }
     }
 
+    /// Insert `value` before or after the first `pivot`, returning the new
+    /// length, or -1 when the pivot is not in the list
     pub(crate) fn insert(
         &mut self,
         ord: &str,
         pivot: &Bytes,
         value: Bytes,
-    ) -> Result<i64, crate::commands::CommandError> {
-        if let Some(idx) = self.items.iter().position(|x| x == pivot) {
-            match ord {
-                "BEFORE" => {
-                    self.items.insert(idx, value);
-                    Ok(idx as i64)
-                }
-                "AFTER" => {
-                    self.items.insert(idx + 1, value);
-                    Ok((idx + 1) as i64)
-                }
-                _ => Err(crate::commands::CommandError::InvalidInsertDirection),
+    ) -> Result<i64, CommandError> {
+        let offset = if ord.eq_ignore_ascii_case("BEFORE") {
+            0
+        } else if ord.eq_ignore_ascii_case("AFTER") {
+            1
+        } else {
+            return Err(CommandError::InvalidInsertDirection);
+        };
+        match self.items.iter().position(|x| x == pivot) {
+            Some(idx) => {
+                self.items.insert(idx + offset, value);
+                Ok(self.items.len() as i64)
             }
+            None => Ok(-1),
+        }
+    }
+
+    pub fn push_to(&mut self, end: ListEnd, item: Bytes) {
+        match end {
+            ListEnd::Left => self.lpush(item),
+            ListEnd::Right => self.rpush(item),
+        }
+    }
+
+    pub fn pop_from(&mut self, end: ListEnd) -> Option<Bytes> {
+        match end {
+            ListEnd::Left => self.lpop(),
+            ListEnd::Right => self.rpop(),
+        }
+    }
+
+    /// Pop up to `count` elements from `end`, in the order they were popped
+    pub fn pop_many(&mut self, end: ListEnd, count: usize) -> Vec<Bytes> {
+        let count = count.min(self.items.len());
+        match end {
+            ListEnd::Left => self.items.drain(..count).collect(),
+            ListEnd::Right => {
+                let len = self.items.len();
+                self.items.drain(len - count..).rev().collect()
+            }
+        }
+    }
+
+    /// Indexes of `element`, as LPOS finds them: skip the first `rank - 1`
+    /// matches (for a negative rank, searching from the tail), return at
+    /// most `count` of them (0 for all) and compare at most `maxlen`
+    /// elements (0 for all)
+    pub fn positions(&self, element: &Bytes, rank: i64, count: usize, maxlen: usize) -> Vec<usize> {
+        let len = self.items.len();
+        let maxlen = if maxlen == 0 { len } else { maxlen.min(len) };
+        let count = if count == 0 { usize::MAX } else { count };
+        let skip = (rank.unsigned_abs() - 1) as usize;
+        let indexes: Box<dyn Iterator<Item = usize>> = if rank > 0 {
+            Box::new(0..maxlen)
         } else {
-            Err(crate::commands::CommandError::PivotNotFound)
+            Box::new((len - maxlen..len).rev())
+        };
+        indexes
+            .filter(|&i| self.items[i] == element)
+            .skip(skip)
+            .take(count)
+            .collect()
+    }
+
+    /// Remove occurrences of `element` as LREM does: the first `count` from
+    /// the head, the last `-count` from the tail, or all of them for 0.
+    /// Returns how many were removed.
+    pub fn remove(&mut self, element: &Bytes, count: i64) -> usize {
+        let rank = if count < 0 { -1 } else { 1 };
+        let mut doomed = self.positions(element, rank, count.unsigned_abs() as usize, 0);
+        if doomed.is_empty() {
+            return 0;
         }
+        doomed.sort_unstable();
+        let mut index = 0;
+        let mut next = doomed.iter().peekable();
+        self.items.retain(|_| {
+            let keep = next.peek() != Some(&&index);
+            if !keep {
+                next.next();
+            }
+            index += 1;
+            keep
+        });
+        doomed.len()
     }
 }
@@ -2,15 +2,17 @@
 // Implements Redis data types: strings, lists, hashes, sets, etc.
 
 // Submodules for different data types
+pub mod bitmap;
 pub mod hash;
 pub mod list;
+pub mod scan;
 pub mod set;
 pub mod sorted_set;
 pub mod string;
 
 // Re-export common types
 pub use hash::RedisHash;
-pub use list::RedisList;
+pub use list::{ListEnd, RedisList};
 pub use set::RedisSet;
 pub use sorted_set::RedisSortedSet;
 pub use string::RedisString;
@@ -0,0 +1,36 @@
+// Cursor-based iteration for the SCAN family of commands
+
+use std::hash::{BuildHasher, BuildHasherDefault, DefaultHasher, Hash};
+
+/// Where an element comes in scan order. The hash has fixed keys, so an
+/// element keeps its place however the collection around it changes.
+fn position(element: impl Hash) -> u64 {
+    BuildHasherDefault::<DefaultHasher>::default().hash_one(element)
+}
+
+/// One step of a scan: about `count` of `elements` from `cursor` on, and the
+/// cursor to continue from, 0 once every element was visited. Elements are
+/// visited in scan order rather than storage order, so one present for the
+/// whole scan is returned exactly once, even if others are added or removed
+/// between the steps.
+pub fn scan<K: Hash, T>(
+    elements: impl IntoIterator<Item = (K, T)>,
+    cursor: u64,
+    count: usize,
+) -> (u64, Vec<T>) {
+    let mut found: Vec<(u64, T)> = elements
+        .into_iter()
+        .map(|(element, item)| (position(element), item))
+        .filter(|(position, _)| *position >= cursor)
+        .collect();
+    found.sort_unstable_by_key(|(position, _)| *position);
+    // Elements sharing a position are returned together, as the cursor
+    // cannot point between them
+    let end = match found.get(count.max(1) - 1) {
+        Some(&(last, _)) => found.partition_point(|(position, _)| *position <= last),
+        None => found.len(),
+    };
+    let next = found.get(end).map_or(0, |(position, _)| *position);
+    found.truncate(end);
+    (next, found.into_iter().map(|(_, item)| item).collect())
+}
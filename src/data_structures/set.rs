@@ -1,8 +1,11 @@
 // Set data structure for Rudis
 
+use super::scan::scan;
 use bytes::Bytes;
+use rand::seq::IteratorRandom;
+use rand::Rng;
 use std::collections::HashSet;
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct RedisSet {
     members: HashSet<Bytes>,
 }
@@ -29,7 +32,95 @@ impl RedisSet {
         self.members.iter().collect()
     }
 
-    pub(crate) fn scard(&self) -> usize {
+    pub fn iter(&self) -> impl Iterator<Item = &Bytes> {
+        self.members.iter()
+    }
+
+    pub fn scard(&self) -> usize {
         self.members.len()
     }
+
+    /// Remove and return `count` members picked at random, or all of them
+    /// if there are fewer
+    pub fn pop_random(&mut self, count: usize) -> Vec<Bytes> {
+        if count >= self.members.len() {
+            return self.members.drain().collect();
+        }
+        let picked = self
+            .members
+            .iter()
+            .cloned()
+            .choose_multiple(&mut rand::rng(), count);
+        for member in &picked {
+            self.members.remove(member);
+        }
+        picked
+    }
+
+    /// Members picked at random, as SRANDMEMBER does: `count` different
+    /// ones, or all of them if there are fewer, or for a negative count,
+    /// `-count` members that may repeat
+    pub fn random_members(&self, count: i64) -> Vec<&Bytes> {
+        let mut rng = rand::rng();
+        if count >= 0 {
+            return self
+                .members
+                .iter()
+                .choose_multiple(&mut rng, count as usize);
+        }
+        let members: Vec<_> = self.members.iter().collect();
+        if members.is_empty() {
+            return Vec::new();
+        }
+        (0..count.unsigned_abs())
+            .map(|_| members[rng.random_range(0..members.len())])
+            .collect()
+    }
+
+    /// One SSCAN step from `cursor`; see [`scan`]
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<&Bytes>) {
+        scan(
+            self.members.iter().map(|member| (member, member)),
+            cursor,
+            count,
+        )
+    }
+
+    /// Members of every one of `sets`, at most `limit` of them (0 for no
+    /// limit). Only the smallest set is walked; the others are probed.
+    pub fn intersection<'a>(sets: &[&'a RedisSet], limit: usize) -> Vec<&'a Bytes> {
+        let Some(smallest) = sets.iter().min_by_key(|set| set.scard()) else {
+            return Vec::new();
+        };
+        let matches = smallest
+            .iter()
+            .filter(|member| sets.iter().all(|set| set.sismember(member)));
+        if limit == 0 {
+            matches.collect()
+        } else {
+            matches.take(limit).collect()
+        }
+    }
+
+    /// Members of any of `sets`
+    pub fn union<'a>(sets: &[&'a RedisSet]) -> Vec<&'a Bytes> {
+        let union: HashSet<&Bytes> = sets.iter().flat_map(|set| set.iter()).collect();
+        union.into_iter().collect()
+    }
+
+    /// Members of `first` in none of `others`
+    pub fn difference<'a>(first: &'a RedisSet, others: &[&RedisSet]) -> Vec<&'a Bytes> {
+        first
+            .iter()
+            .filter(|member| !others.iter().any(|set| set.sismember(member)))
+            .collect()
+    }
+}
+
+impl FromIterator<Bytes> for RedisSet {
+    fn from_iter<I: IntoIterator<Item = Bytes>>(members: I) -> Self {
+        RedisSet {
+            members: members.into_iter().collect(),
+        }
+    }
 }
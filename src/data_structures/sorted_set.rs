@@ -21,7 +21,7 @@ impl PartialOrd for Score {
 
 impl Eq for Score {}
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct RedisSortedSet {
     members: HashMap<Bytes, Score>,
     ordered_members: BTreeSet<(Score, Bytes)>,
@@ -35,15 +35,21 @@ impl RedisSortedSet {
         }
     }
 
-    pub fn zadd(&mut self, member: Bytes, score: f64) {
+    /// Add `member` or update its score; true if it is a new member
+    pub fn zadd(&mut self, member: Bytes, score: f64) -> bool {
         let score = Score(score);
         // Remove old entry if exists
-        if let Some(old_score) = self.members.get(&member) {
-            self.ordered_members
-                .remove(&(old_score.clone(), member.clone()));
-        }
+        let existed = match self.members.get(&member) {
+            Some(old_score) => {
+                self.ordered_members
+                    .remove(&(old_score.clone(), member.clone()));
+                true
+            }
+            None => false,
+        };
         self.members.insert(member.clone(), score.clone());
         self.ordered_members.insert((score, member));
+        !existed
     }
 
     pub fn zscore(&self, member: &Bytes) -> Option<f64> {
@@ -63,6 +69,11 @@ impl RedisSortedSet {
         self.members.len()
     }
 
+    /// Members with their scores, in ascending score order
+    pub fn iter(&self) -> impl Iterator<Item = (&Bytes, f64)> {
+        self.ordered_members.iter().map(|(s, m)| (m, s.0))
+    }
+
     pub fn zrank(&self, member: &Bytes) -> Option<usize> {
         self.ordered_members.iter().position(|(_, m)| m == member)
     }
@@ -3,33 +3,115 @@
 use bytes::{Bytes, BytesMut};
 use std::str::FromStr;
 
+/// Longest string Redis stores in a single allocation with its header
+const EMBSTR_SIZE_LIMIT: usize = 44;
+
+/// How a string is held. Values that are the canonical form of an `i64`,
+/// like the ones INCR writes, are kept as the number, as Redis' int encoding
+/// does, and only turned into bytes when read. Like Redis, short values are
+/// stored as `Embstr` and anything edited in place (APPEND, SETRANGE, SETBIT)
+/// becomes `Raw`.
+#[derive(Debug, Clone)]
+enum Encoding {
+    Int(i64),
+    Embstr(Bytes),
+    Raw(Bytes),
+}
+
 #[derive(Debug, Clone)]
 pub struct RedisString {
-    value: Bytes,
+    value: Encoding,
+}
+
+/// `value` as an integer if it is exactly how that integer is written: no
+/// sign but a leading `-`, no leading zeros and no spaces
+fn canonical_integer(value: &[u8]) -> Option<i64> {
+    // i64::MIN has 20 characters
+    if value.is_empty() || value.len() > 20 {
+        return None;
+    }
+    let integer: i64 = std::str::from_utf8(value).ok()?.parse().ok()?;
+    let canonical = matches!(value, [b'0'] | [b'-', b'1'..=b'9', ..] | [b'1'..=b'9', ..]);
+    canonical.then_some(integer)
 }
 
 impl RedisString {
     pub fn new(value: Bytes) -> Self {
+        let value = match canonical_integer(&value) {
+            Some(integer) => Encoding::Int(integer),
+            None if value.len() <= EMBSTR_SIZE_LIMIT => Encoding::Embstr(value),
+            None => Encoding::Raw(value),
+        };
         RedisString { value }
     }
 
+    pub fn from_integer(value: i64) -> Self {
+        RedisString {
+            value: Encoding::Int(value),
+        }
+    }
+
     pub fn get(&self) -> Bytes {
-        self.value.clone()
+        match &self.value {
+            Encoding::Int(integer) => Bytes::from(integer.to_string()),
+            Encoding::Embstr(bytes) | Encoding::Raw(bytes) => bytes.clone(),
+        }
     }
 
     pub fn set(&mut self, value: Bytes) {
-        self.value = value;
+        *self = RedisString::new(value);
+    }
+
+    /// The value as a number, without parsing when it is int encoded.
+    /// Other values only count if written canonically, as for the encoding.
+    pub fn integer(&self) -> Option<i64> {
+        match &self.value {
+            Encoding::Int(integer) => Some(*integer),
+            Encoding::Embstr(bytes) | Encoding::Raw(bytes) => canonical_integer(bytes),
+        }
+    }
+
+    /// What OBJECT ENCODING reports
+    pub fn encoding(&self) -> &'static str {
+        match &self.value {
+            Encoding::Int(_) => "int",
+            Encoding::Embstr(_) => "embstr",
+            Encoding::Raw(_) => "raw",
+        }
     }
 
     pub(crate) fn append(&mut self, value: Bytes) {
-        let mut new_value = BytesMut::with_capacity(self.value.len() + value.len());
-        new_value.extend_from_slice(&self.value);
+        let current = self.get();
+        let mut new_value = BytesMut::with_capacity(current.len() + value.len());
+        new_value.extend_from_slice(&current);
         new_value.extend_from_slice(&value);
-        self.value = new_value.freeze();
+        self.value = Encoding::Raw(new_value.freeze());
+    }
+
+    /// Edit the bytes in place, zero-padding them to at least `min_len`
+    /// first. The buffer is only copied if it is shared.
+    pub(crate) fn update<R>(&mut self, min_len: usize, f: impl FnOnce(&mut [u8]) -> R) -> R {
+        let current = match std::mem::replace(&mut self.value, Encoding::Int(0)) {
+            Encoding::Int(integer) => Bytes::from(integer.to_string()),
+            Encoding::Embstr(bytes) | Encoding::Raw(bytes) => bytes,
+        };
+        let mut bytes = BytesMut::from(current);
+        if bytes.len() < min_len {
+            bytes.resize(min_len, 0);
+        }
+        let result = f(&mut bytes);
+        self.value = Encoding::Raw(bytes.freeze());
+        result
     }
 
     pub(crate) fn len(&self) -> usize {
-        self.value.len()
+        match &self.value {
+            Encoding::Int(integer) => {
+                let digits = integer.unsigned_abs().checked_ilog10().unwrap_or(0) as usize + 1;
+                digits + (*integer < 0) as usize
+            }
+            Encoding::Embstr(bytes) | Encoding::Raw(bytes) => bytes.len(),
+        }
     }
 
     /// Try to parse the string as a number (integer or float)
@@ -38,7 +120,12 @@ impl RedisString {
         // We return Result<F, ()> to simplify error handling for now,
         // as Utf8Error and ParseIntError/ParseFloatError are different types.
         // In a real app we'd want a unified error type here.
-        let s = std::str::from_utf8(&self.value).map_err(|_| ())?;
-        s.parse::<F>().map_err(|_| ())
+        match &self.value {
+            Encoding::Int(integer) => integer.to_string().parse::<F>().map_err(|_| ()),
+            Encoding::Embstr(bytes) | Encoding::Raw(bytes) => {
+                let s = std::str::from_utf8(bytes).map_err(|_| ())?;
+                s.parse::<F>().map_err(|_| ())
+            }
+        }
     }
 }
@@ -0,0 +1,129 @@
+use super::{Database, RedisValue};
+use crate::commands::{CommandError, Result};
+use crate::data_structures::bitmap::{self, BitOperation, BitUnit, BitfieldOp};
+use crate::data_structures::RedisString;
+use crate::database::traits::{BitmapOp, StringOp};
+use bytes::Bytes;
+use dashmap::mapref::entry::Entry;
+
+impl Database {
+    /// Apply `f` to the bytes of the string at `key`, zero-padded to at
+    /// least `min_len`. A missing key is created.
+    fn update_bits<R>(
+        &self,
+        key: &Bytes,
+        min_len: usize,
+        f: impl FnOnce(&mut [u8]) -> R,
+    ) -> Result<R> {
+        match self.data_for(key).entry(key.clone()) {
+            Entry::Occupied(mut entry) => match entry.get_mut() {
+                RedisValue::String(value) => Ok(value.update(min_len, f)),
+                _ => Err(CommandError::WrongType),
+            },
+            Entry::Vacant(entry) => {
+                let mut value = RedisString::new(Bytes::new());
+                let result = value.update(min_len, f);
+                entry.insert(RedisValue::String(value));
+                Ok(result)
+            }
+        }
+    }
+}
+
+impl BitmapOp for Database {
+    /// Set or clear one bit, growing the string as needed; returns the old bit
+    fn setbit(&self, key: &Bytes, offset: u64, bit: u8) -> Result<u8> {
+        let old = self.update_bits(key, bitmap::byte_len(offset + 1), |bytes| {
+            bitmap::set_bit(bytes, offset, bit)
+        })?;
+        self.changed(1);
+        Ok(old)
+    }
+
+    fn getbit(&self, key: &Bytes, offset: u64) -> Result<u8> {
+        Ok(self
+            .get(key)?
+            .map_or(0, |value| bitmap::get_bit(&value, offset)))
+    }
+
+    fn bitcount(&self, key: &Bytes, range: Option<(i64, i64)>, unit: BitUnit) -> Result<u64> {
+        Ok(self
+            .get(key)?
+            .map_or(0, |value| bitmap::bit_count(&value, range, unit)))
+    }
+
+    /// A missing key is all zeros: the first 0 is at 0, and there is no 1
+    fn bitpos(
+        &self,
+        key: &Bytes,
+        bit: u8,
+        start: Option<i64>,
+        end: Option<i64>,
+        unit: BitUnit,
+    ) -> Result<i64> {
+        Ok(match self.get(key)? {
+            Some(value) => bitmap::bit_pos(&value, bit, start, end, unit),
+            None if bit == 1 => -1,
+            None => 0,
+        })
+    }
+
+    /// Store the result of `op` over `keys` at `dest`, deleting it if the
+    /// result is empty; returns its length
+    fn bitop(&self, op: BitOperation, dest: &Bytes, keys: &[Bytes]) -> Result<usize> {
+        let mut locked_keys: Vec<&Bytes> = keys.iter().collect();
+        locked_keys.push(dest);
+        let len = self.with_keys_locked(&locked_keys, |locked| {
+            let mut sources = Vec::with_capacity(keys.len());
+            for key in keys {
+                let source = match locked.get(key) {
+                    Some(value) => self
+                        .with_loaded(value, |value| match value {
+                            RedisValue::String(value) => Some(value.get()),
+                            _ => None,
+                        })
+                        .map_err(|_| CommandError::InternalError)?
+                        .ok_or(CommandError::WrongType)?,
+                    None => Bytes::new(),
+                };
+                sources.push(source);
+            }
+            let result = bitmap::bit_op(op, &sources);
+            let len = result.len();
+            if result.is_empty() {
+                locked.remove(dest);
+            } else {
+                let value = RedisString::new(Bytes::from(result));
+                locked.insert(dest.clone(), RedisValue::String(value));
+            }
+            Ok(len)
+        })?;
+        self.changed(1);
+        Ok(len)
+    }
+
+    /// Run BITFIELD operations in order. The string grows to hold every
+    /// field written, even by operations that fail on overflow; reads alone
+    /// never create the key.
+    fn bitfield(&self, key: &Bytes, ops: &[BitfieldOp]) -> Result<Vec<Option<i64>>> {
+        match ops.iter().filter_map(BitfieldOp::write_end).max() {
+            Some(end) => {
+                let results = self.update_bits(key, bitmap::byte_len(end), |bytes| {
+                    ops.iter().map(|op| op.apply(bytes)).collect()
+                })?;
+                self.changed(ops.iter().filter(|op| op.write_end().is_some()).count());
+                Ok(results)
+            }
+            None => {
+                let value = self.get(key)?.unwrap_or_default();
+                Ok(ops
+                    .iter()
+                    .map(|op| match *op {
+                        BitfieldOp::Get { ty, offset } => Some(ty.get(&value, offset)),
+                        _ => None,
+                    })
+                    .collect())
+            }
+        }
+    }
+}
@@ -0,0 +1,213 @@
+// Clients blocked on lists
+// BLPOP, BRPOP, BLMOVE and BLMPOP wait here while every list they name is
+// empty. Commands that push onto a list hand the new elements to the clients
+// waiting on it, oldest first, so each element reaches exactly one client.
+
+use super::Database;
+use crate::commands::Result;
+use crate::data_structures::ListEnd;
+use crate::database::traits::ListOp;
+use bytes::Bytes;
+use parking_lot::Mutex;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// What a blocked client does once one of its lists has elements
+#[derive(Debug, Clone)]
+pub enum BlockedOp {
+    /// Pop up to `count` elements (BLPOP, BRPOP, BLMPOP)
+    Pop { end: ListEnd, count: usize },
+    /// Move one element onto `destination` (BLMOVE, BRPOPLPUSH)
+    Move {
+        destination: Bytes,
+        from: ListEnd,
+        to: ListEnd,
+    },
+}
+
+/// The list a blocked client was served from and what it got
+pub type Popped = Option<(Bytes, Vec<Bytes>)>;
+
+#[derive(Debug)]
+struct Waiter {
+    db: u8,
+    keys: Vec<Bytes>,
+    op: BlockedOp,
+    reply: oneshot::Sender<Result<Popped>>,
+}
+
+#[derive(Debug, Default)]
+struct Waiters {
+    /// Ids of the clients blocked on each key of each database, oldest first
+    queues: HashMap<(u8, Bytes), VecDeque<u64>>,
+    waiters: HashMap<u64, Waiter>,
+}
+
+impl Waiters {
+    fn remove(&mut self, id: u64) -> Option<Waiter> {
+        let waiter = self.waiters.remove(&id)?;
+        for key in &waiter.keys {
+            let queue_key = (waiter.db, key.clone());
+            if let Some(queue) = self.queues.get_mut(&queue_key) {
+                queue.retain(|&queued| queued != id);
+                if queue.is_empty() {
+                    self.queues.remove(&queue_key);
+                }
+            }
+        }
+        Some(waiter)
+    }
+}
+
+/// The clients blocked on lists, across all databases
+#[derive(Debug, Default)]
+pub struct BlockedClients {
+    waiters: Mutex<Waiters>,
+    /// Number of blocked clients, checked without the lock by every push
+    count: AtomicUsize,
+    next_id: AtomicU64,
+}
+
+impl BlockedClients {
+    pub fn len(&self) -> usize {
+        self.count.load(Ordering::SeqCst)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A client's place in the queues, given up when it stops waiting
+struct Registration<'a> {
+    db: &'a Database,
+    id: u64,
+}
+
+impl Registration<'_> {
+    /// Leave the queues; false if the client was served first
+    fn cancel(&self) -> bool {
+        let removed = self.db.blocked.waiters.lock().remove(self.id).is_some();
+        if removed {
+            self.db.blocked.count.fetch_sub(1, Ordering::SeqCst);
+        }
+        removed
+    }
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+impl Database {
+    /// Number of clients waiting in a blocking list command
+    pub fn blocked_clients(&self) -> usize {
+        self.blocked.len()
+    }
+
+    /// Run `op` on the first of `keys` holding elements, waiting up to
+    /// `timeout` (forever for `None`) for one to get some. `Ok(None)` means
+    /// the timeout passed first.
+    pub async fn block_on_lists(
+        &self,
+        keys: &[Bytes],
+        op: BlockedOp,
+        timeout: Option<Duration>,
+    ) -> Result<Popped> {
+        let db = self.current_index();
+        let (id, mut reply) = {
+            let mut waiters = self.blocked.waiters.lock();
+            // Counted before looking at the lists: a push the attempt below
+            // misses then sees the count and serves this client
+            self.blocked.count.fetch_add(1, Ordering::SeqCst);
+            let result = self.run_blocked_op(keys, &op);
+            if !matches!(result, Ok(None)) {
+                self.blocked.count.fetch_sub(1, Ordering::SeqCst);
+                drop(waiters);
+                self.signal_moved(&op, &result);
+                return result;
+            }
+            let id = self.blocked.next_id.fetch_add(1, Ordering::Relaxed);
+            for key in keys {
+                waiters
+                    .queues
+                    .entry((db, key.clone()))
+                    .or_default()
+                    .push_back(id);
+            }
+            let (sender, reply) = oneshot::channel();
+            waiters.waiters.insert(
+                id,
+                Waiter {
+                    db,
+                    keys: keys.to_vec(),
+                    op,
+                    reply: sender,
+                },
+            );
+            (id, reply)
+        };
+        let registration = Registration { db: self, id };
+        let served = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, &mut reply).await.ok(),
+            None => Some((&mut reply).await),
+        };
+        match served {
+            Some(result) => result.unwrap_or(Ok(None)),
+            // Served while the timeout was firing: the reply is already sent
+            None if !registration.cancel() => reply.try_recv().unwrap_or(Ok(None)),
+            None => Ok(None),
+        }
+    }
+
+    /// Hand the elements just pushed onto `key` to the clients blocked on
+    /// it. Elements moved onto another list by BLMOVE are handed on in turn.
+    pub(super) fn signal_list_ready(&self, key: &Bytes) {
+        if self.blocked.is_empty() {
+            return;
+        }
+        let db = self.current_index();
+        let mut ready = vec![key.clone()];
+        while let Some(key) = ready.pop() {
+            let mut waiters = self.blocked.waiters.lock();
+            let queue_key = (db, key.clone());
+            while let Some(&id) = waiters.queues.get(&queue_key).and_then(VecDeque::front) {
+                let op = waiters.waiters[&id].op.clone();
+                let result = self.run_blocked_op(std::slice::from_ref(&key), &op);
+                if matches!(result, Ok(None)) {
+                    break;
+                }
+                if let (BlockedOp::Move { destination, .. }, Ok(Some(_))) = (&op, &result) {
+                    ready.push(destination.clone());
+                }
+                let waiter = waiters.remove(id).expect("queued clients are registered");
+                self.blocked.count.fetch_sub(1, Ordering::SeqCst);
+                let _ = waiter.reply.send(result);
+            }
+        }
+    }
+
+    fn run_blocked_op(&self, keys: &[Bytes], op: &BlockedOp) -> Result<Popped> {
+        match op {
+            BlockedOp::Pop { end, count } => self.lmpop(keys, *end, *count),
+            BlockedOp::Move {
+                destination,
+                from,
+                to,
+            } => Ok(self
+                .move_element(&keys[0], destination, *from, *to)?
+                .map(|element| (keys[0].clone(), vec![element]))),
+        }
+    }
+
+    /// Serve the clients blocked on the list an unblocked BLMOVE pushed to
+    fn signal_moved(&self, op: &BlockedOp, result: &Result<Popped>) {
+        if let (BlockedOp::Move { destination, .. }, Ok(Some(_))) = (op, result) {
+            self.signal_list_ready(destination);
+        }
+    }
+}
@@ -3,77 +3,45 @@ use crate::commands::{CommandError, Result};
 use crate::data_structures::RedisHash;
 use crate::database::traits::HashOp;
 use bytes::Bytes;
+use dashmap::mapref::entry::Entry;
 
 impl HashOp for Database {
     fn hset(&self, hash: &Bytes, field: Bytes, value: Bytes) -> Result<i64> {
-        let data = self.current_data();
-        match data.get_mut(hash) {
-            Some(mut entry) => {
-                match entry.value_mut() {
-                    RedisValue::Hash(existing_hash) => {
-                        // Hash exists, update/add the field
-                        Ok(existing_hash.hset(field, value))
-                    }
-                    _ => {
-                        // Key exists but is not a hash
-                        Err(CommandError::WrongType)
-                    }
-                }
-            }
-            None => {
-                // Key doesn't exist, create new hash
-                let mut new_hash = RedisHash::new();
-                new_hash.hset(field, value);
-                data.insert(hash.clone(), RedisValue::Hash(new_hash));
-                Ok(1) // New field was added
-            }
-        }
+        Ok(self.hset_multiple(hash, &[(field, value)])? as i64)
     }
 
     fn hget(&self, hash: &Bytes, field: &Bytes) -> Result<Option<Bytes>> {
-        match self.current_data().get(hash) {
+        match self.data_for(hash).get(hash) {
             Some(entry) => match entry.value() {
-                RedisValue::Hash(existing_hash) => Ok(existing_hash.hget(field).map(|s| s.clone())),
+                RedisValue::Hash(existing_hash) => Ok(existing_hash.hget(field).cloned()),
                 _ => Err(CommandError::WrongType),
             },
             None => Ok(None),
         }
     }
 
-    fn hdel(&self, hash: &Bytes, field: &Bytes) -> bool {
-        if let Some(mut entry) = self.current_data().get_mut(hash) {
-            if let RedisValue::Hash(existing_hash) = entry.value_mut() {
-                existing_hash.hdel(field)
-            } else {
-                false
-            }
-        } else {
-            false
-        }
+    fn hdel(&self, hash: &Bytes, field: &Bytes) -> Result<bool> {
+        Ok(self.hdel_multiple(hash, std::slice::from_ref(field))? == 1)
     }
 
-    fn hdel_multiple(&self, hash: &Bytes, fields: &[Bytes]) -> usize {
-        if let Some(mut entry) = self.current_data().get_mut(hash) {
-            if let RedisValue::Hash(existing_hash) = entry.value_mut() {
-                fields
-                    .iter()
-                    .filter(|field| existing_hash.hdel(field))
-                    .count()
-            } else {
-                0
-            }
-        } else {
-            0
-        }
+    fn hdel_multiple(&self, hash: &Bytes, fields: &[Bytes]) -> Result<usize> {
+        let deleted = self.remove_elements(hash, 0, |value| match value {
+            RedisValue::Hash(existing_hash) => Ok(fields
+                .iter()
+                .filter(|field| existing_hash.hdel(field))
+                .count()),
+            _ => Err(CommandError::WrongType),
+        })?;
+        self.changed(deleted);
+        Ok(deleted)
     }
 
     fn hget_all(&self, hash: &Bytes) -> Result<Vec<Bytes>> {
-        match self.current_data().get(hash) {
+        match self.data_for(hash).get(hash) {
             Some(entry) => match entry.value() {
-                RedisValue::Hash(existing_hash) => Ok(existing_hash
-                    .flatten()
-                    .map(|s| s.clone())
-                    .collect::<Vec<Bytes>>()),
+                RedisValue::Hash(existing_hash) => {
+                    Ok(existing_hash.flatten().cloned().collect::<Vec<Bytes>>())
+                }
                 _ => Err(CommandError::WrongType),
             },
             None => Ok(Vec::new()), // Empty array for non-existent keys
@@ -81,12 +49,11 @@ impl HashOp for Database {
     }
 
     fn hkeys(&self, hash: &Bytes) -> Result<Vec<Bytes>> {
-        match self.current_data().get(hash) {
+        match self.data_for(hash).get(hash) {
             Some(entry) => match entry.value() {
-                RedisValue::Hash(existing_hash) => Ok(existing_hash
-                    .keys()
-                    .map(|s| s.clone())
-                    .collect::<Vec<Bytes>>()),
+                RedisValue::Hash(existing_hash) => {
+                    Ok(existing_hash.keys().cloned().collect::<Vec<Bytes>>())
+                }
                 _ => Err(CommandError::WrongType),
             },
             None => Ok(Vec::new()), // Empty array for non-existent keys
@@ -94,12 +61,11 @@ impl HashOp for Database {
     }
 
     fn hvals(&self, hash: &Bytes) -> Result<Vec<Bytes>> {
-        match self.current_data().get(hash) {
+        match self.data_for(hash).get(hash) {
             Some(entry) => match entry.value() {
-                RedisValue::Hash(existing_hash) => Ok(existing_hash
-                    .values()
-                    .map(|s| s.clone())
-                    .collect::<Vec<Bytes>>()),
+                RedisValue::Hash(existing_hash) => {
+                    Ok(existing_hash.values().cloned().collect::<Vec<Bytes>>())
+                }
                 _ => Err(CommandError::WrongType),
             },
             None => Ok(Vec::new()), // Empty array for non-existent keys
@@ -107,7 +73,7 @@ impl HashOp for Database {
     }
 
     fn hlen(&self, hash: &Bytes) -> Result<usize> {
-        match self.current_data().get(hash) {
+        match self.data_for(hash).get(hash) {
             Some(entry) => match entry.value() {
                 RedisValue::Hash(existing_hash) => Ok(existing_hash.len()),
                 _ => Err(CommandError::WrongType),
@@ -117,7 +83,7 @@ impl HashOp for Database {
     }
 
     fn hexists(&self, hash: &Bytes, field: &Bytes) -> Result<bool> {
-        match self.current_data().get(hash) {
+        match self.data_for(hash).get(hash) {
             Some(entry) => match entry.value() {
                 RedisValue::Hash(existing_hash) => Ok(existing_hash.hexists(field)),
                 _ => Err(CommandError::WrongType),
@@ -127,50 +93,110 @@ impl HashOp for Database {
     }
 
     fn hincrby(&self, hash: &Bytes, field: &Bytes, value: i64) -> Result<i64> {
-        let data = self.current_data();
-        match data.get_mut(hash) {
-            Some(mut entry) => match entry.value_mut() {
-                RedisValue::Hash(existing_hash) => match existing_hash.hincrby(field, value) {
-                    Ok(result) => Ok(result),
-                    Err(_) => Err(CommandError::InvalidInteger),
-                },
+        let result = self.update_hash(hash, |existing_hash| existing_hash.hincrby(field, value))?;
+        self.changed(1);
+        Ok(result)
+    }
+
+    fn hincrbyfloat(&self, hash: &Bytes, field: &Bytes, value: f64) -> Result<f64> {
+        let result = self.update_hash(hash, |existing_hash| {
+            existing_hash.hincrbyfloat(field, value)
+        })?;
+        self.changed(1);
+        Ok(result)
+    }
+
+    /// Every field written counts as a change, new or not, as in Redis
+    fn hset_multiple(&self, hash: &Bytes, pairs: &[(Bytes, Bytes)]) -> Result<usize> {
+        let added = self.update_hash(hash, |existing_hash| {
+            Ok(pairs
+                .iter()
+                .map(|(field, value)| existing_hash.hset(field.clone(), value.clone()))
+                .sum::<i64>() as usize)
+        })?;
+        self.changed(pairs.len());
+        Ok(added)
+    }
+
+    fn hsetnx(&self, hash: &Bytes, field: Bytes, value: Bytes) -> Result<bool> {
+        let set = self.update_hash(hash, |existing_hash| Ok(existing_hash.hsetnx(field, value)))?;
+        self.changed(set as usize);
+        Ok(set)
+    }
+
+    fn hmget(&self, hash: &Bytes, fields: &[Bytes]) -> Result<Vec<Option<Bytes>>> {
+        self.read_hash(hash, |existing_hash| {
+            fields
+                .iter()
+                .map(|field| existing_hash.and_then(|h| h.hget(field).cloned()))
+                .collect()
+        })
+    }
+
+    fn hstrlen(&self, hash: &Bytes, field: &Bytes) -> Result<usize> {
+        self.read_hash(hash, |existing_hash| {
+            existing_hash.map_or(0, |h| h.hstrlen(field))
+        })
+    }
+
+    fn hrandfield(&self, hash: &Bytes, count: i64) -> Result<Vec<(Bytes, Bytes)>> {
+        self.read_hash(hash, |existing_hash| {
+            existing_hash.map_or_else(Vec::new, |h| {
+                h.random_fields(count)
+                    .into_iter()
+                    .map(|(field, value)| (field.clone(), value.clone()))
+                    .collect()
+            })
+        })
+    }
+
+    fn hscan(&self, hash: &Bytes, cursor: u64, count: usize) -> Result<(u64, Vec<(Bytes, Bytes)>)> {
+        self.read_hash(hash, |existing_hash| match existing_hash {
+            Some(h) => {
+                let (next, pairs) = h.scan(cursor, count);
+                let pairs = pairs
+                    .into_iter()
+                    .map(|(field, value)| (field.clone(), value.clone()))
+                    .collect();
+                (next, pairs)
+            }
+            None => (0, Vec::new()),
+        })
+    }
+}
+
+impl Database {
+    /// Run `f` on the hash at `key`, which is created if missing. A new hash
+    /// is only stored if `f` succeeds, so a failed HINCRBY creates no key.
+    fn update_hash<R>(
+        &self,
+        key: &Bytes,
+        f: impl FnOnce(&mut RedisHash) -> Result<R>,
+    ) -> Result<R> {
+        match self.data_for(key).entry(key.clone()) {
+            Entry::Occupied(mut entry) => match entry.get_mut() {
+                RedisValue::Hash(existing_hash) => f(existing_hash),
                 _ => Err(CommandError::WrongType),
             },
-            None => {
-                // Key doesn't exist, create new hash with field set to value
+            Entry::Vacant(entry) => {
                 let mut new_hash = RedisHash::new();
-                match new_hash.hincrby(field, value) {
-                    Ok(result) => {
-                        data.insert(hash.clone(), RedisValue::Hash(new_hash));
-                        Ok(result)
-                    }
-                    Err(_) => Err(CommandError::InvalidInteger),
+                let result = f(&mut new_hash)?;
+                if !new_hash.is_empty() {
+                    entry.insert(RedisValue::Hash(new_hash));
                 }
+                Ok(result)
             }
         }
     }
 
-    fn hincrbyfloat(&self, hash: &Bytes, field: &Bytes, value: f64) -> Result<f64> {
-        let data = self.current_data();
-        match data.get_mut(hash) {
-            Some(mut entry) => match entry.value_mut() {
-                RedisValue::Hash(existing_hash) => match existing_hash.hincrbyfloat(field, value) {
-                    Ok(result) => Ok(result),
-                    Err(_) => Err(CommandError::InvalidFloat),
-                },
+    /// Run `f` on the hash at `key`, or on `None` if there is no such key
+    fn read_hash<R>(&self, key: &Bytes, f: impl FnOnce(Option<&RedisHash>) -> R) -> Result<R> {
+        match self.data_for(key).get(key) {
+            Some(entry) => match entry.value() {
+                RedisValue::Hash(existing_hash) => Ok(f(Some(existing_hash))),
                 _ => Err(CommandError::WrongType),
             },
-            None => {
-                // Key doesn't exist, create new hash with field set to value
-                let mut new_hash = RedisHash::new();
-                match new_hash.hincrbyfloat(field, value) {
-                    Ok(result) => {
-                        data.insert(hash.clone(), RedisValue::Hash(new_hash));
-                        Ok(result)
-                    }
-                    Err(_) => Err(CommandError::InvalidFloat),
-                }
-            }
+            None => Ok(f(None)),
         }
     }
 }
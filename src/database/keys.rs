@@ -1,6 +1,7 @@
-use super::{Database};
+use super::{Database, RedisValue, Session};
 use crate::commands::{CommandError, Result};
 use crate::database::traits::KeyOp;
+use crate::persistence::rdb;
 use bytes::Bytes;
 use regex::Regex;
 use std::time::{Duration, SystemTime};
@@ -8,36 +9,123 @@ use std::time::{Duration, SystemTime};
 impl KeyOp for Database {
     fn exist(&self, keys: &[Bytes]) -> usize {
         keys.iter()
-            .filter(|key| self.current_data().contains_key(*key))
+            .filter(|key| !self.expire_if_needed(key) && self.current_data().contains_key(*key))
             .count()
     }
 
-    fn expire(&self, key: &Bytes, seconds: u64) -> Result<()> {
-        if let Some(new_time) = SystemTime::now().checked_add(Duration::from_secs(seconds)) {
-            let exp_map = self.current_expiration();
-            exp_map.insert(key.clone(), new_time);
-            Ok(())
+    fn expire(&self, key: &Bytes, seconds: u64) -> Result<bool> {
+        match seconds.checked_mul(1000) {
+            Some(millis) => self.pexpire(key, millis),
+            None => Err(CommandError::InvalidRange),
+        }
+    }
+
+    fn pexpire(&self, key: &Bytes, millis: u64) -> Result<bool> {
+        match SystemTime::now().checked_add(Duration::from_millis(millis)) {
+            Some(at) => Ok(self.pexpire_at(key, at)),
+            None => Err(CommandError::InvalidRange),
+        }
+    }
+
+    /// A time already past deletes the key, as in Redis
+    fn pexpire_at(&self, key: &Bytes, at: SystemTime) -> bool {
+        if self.exist(std::slice::from_ref(key)) == 0 {
+            return false;
+        }
+        if at <= SystemTime::now() {
+            self.current_expiration().remove(key);
+            self.current_data().remove(key);
         } else {
-            Err(CommandError::InvalidRange)
+            self.current_expiration().insert(key.clone(), at);
         }
+        self.changed(1);
+        true
+    }
+
+    fn persist(&self, key: &Bytes) -> bool {
+        let persisted = self.exist(std::slice::from_ref(key)) > 0
+            && self.current_expiration().remove(key).is_some();
+        self.changed(persisted as usize);
+        persisted
     }
 
     fn ttl(&self, key: &Bytes) -> i64 {
+        match self.pttl(key) {
+            ms if ms < 0 => ms,
+            // Round to the nearest second like Redis
+            ms => (ms + 500) / 1000,
+        }
+    }
+
+    fn pttl(&self, key: &Bytes) -> i64 {
+        if self.expire_if_needed(key) || !self.current_data().contains_key(key) {
+            return -2;
+        }
+        match self.current_expiration().get(key) {
+            Some(entry) => match entry.value().duration_since(SystemTime::now()) {
+                Ok(duration) => duration.as_millis() as i64,
+                Err(_) => -2, // expired
+            },
+            None => -1,
+        }
+    }
+
+    fn dump(&self, key: &Bytes) -> Option<Vec<u8>> {
+        if self.pttl(key) == -2 {
+            return None;
+        }
+        self.data_for(key)
+            .get(key)
+            .map(|entry| rdb::dump_value(entry.value()))
+    }
+
+    fn restore(
+        &self,
+        key: &Bytes,
+        value: RedisValue,
+        expire_at: Option<SystemTime>,
+        replace: bool,
+    ) -> Result<()> {
+        if !replace && self.pttl(key) != -2 {
+            return Err(CommandError::BusyKey);
+        }
+        self.touch(key);
+        let data = self.current_data();
         let exp_map = self.current_expiration();
-        if let Some(entry) = exp_map.get(key) {
-            if let Ok(duration) = entry.value().duration_since(SystemTime::now()) {
-                duration.as_secs() as i64
-            } else {
-                -2 // expired
+        // An absolute TTL in the past or an empty collection: the key is gone
+        // as soon as it lands
+        let gone =
+            value.is_empty_collection() || matches!(expire_at, Some(at) if at <= SystemTime::now());
+        let is_list = matches!(value, RedisValue::List(_));
+        match expire_at {
+            _ if gone => {
+                data.remove(key);
+                exp_map.remove(key);
             }
-        } else {
-            -1 // no expiration
+            Some(at) => {
+                data.insert(key.clone(), value);
+                exp_map.insert(key.clone(), at);
+            }
+            None => {
+                data.insert(key.clone(), value);
+                exp_map.remove(key);
+            }
+        }
+        self.changed(1);
+        if is_list && !gone {
+            self.signal_list_ready(key);
         }
+        Ok(())
     }
 
     fn keys(&self, pattern: &Bytes) -> Result<Vec<Bytes>> {
         let data = self.current_data();
         let keys: Vec<Bytes> = data.iter().map(|entry| entry.key().clone()).collect();
+        // Expire lazily once the shards are unlocked
+        let keys: Vec<Bytes> = keys
+            .into_iter()
+            .filter(|key| !self.expire_if_needed(key))
+            .collect();
         // Basic glob matching for *
         // Ideally use a glob library or regex on String if we assume keys are strings.
         // Redis keys are binary, so regex is tricky if not UTF-8.
@@ -63,6 +151,7 @@ impl KeyOp for Database {
     }
 
     fn flush_all(&self) -> bool {
+        self.changed(self.current_data().len());
         self.current_data().clear();
         self.current_expiration().clear();
         true
@@ -71,6 +160,7 @@ impl KeyOp for Database {
     fn flush_db(&self) -> bool {
         for i in 0..self.data.len() {
             if let Some(db_data) = self.data.get(&(i as u8)) {
+                self.changed(db_data.len());
                 db_data.clear();
             }
             if let Some(db_exp) = self.data_expiration_time.get(&(i as u8)) {
@@ -84,6 +174,36 @@ impl KeyOp for Database {
         if db as usize >= self.data.len() {
             return;
         }
-        *self.current_db.lock() = db;
+        if !Session::select_current(db) {
+            *self.current_db.lock() = db;
+        }
+    }
+}
+
+impl Database {
+    /// Delete `key` only if it still dumps to `payload`. MIGRATE calls this
+    /// once the target accepted `payload`, so a write that landed during the
+    /// exchange keeps the newer value here instead of being lost.
+    pub fn del_if_unchanged(&self, key: &Bytes, payload: &[u8]) -> Result<bool> {
+        let deleted = self.with_keys_locked(&[key], |locked| {
+            let unchanged = match (locked.get(key), &self.tiered) {
+                // Reloading could reorder the members; the spilled bytes are
+                // exactly what a DUMP of the value wrote
+                (Some(RedisValue::Spilled(spilled)), Some(store)) => {
+                    store
+                        .read_payload(spilled)
+                        .map_err(|_| CommandError::InternalError)?
+                        == payload
+                }
+                (Some(value), _) => rdb::dump_value(value) == payload,
+                (None, _) => false,
+            };
+            if unchanged {
+                locked.remove(key);
+            }
+            Ok(unchanged)
+        })?;
+        self.changed(deleted as usize);
+        Ok(deleted)
     }
 }
@@ -1,107 +1,105 @@
 use super::{Database, RedisValue};
 use crate::commands::{CommandError, Result};
-use crate::data_structures::RedisList;
+use crate::data_structures::{ListEnd, RedisList};
 use crate::database::traits::ListOp;
 use bytes::Bytes;
 
 impl ListOp for Database {
-    fn lpush(&self, key: &Bytes, values: &[Bytes]) -> usize {
-        let data = self.current_data();
-        if let Some(mut entry) = data.get_mut(key) {
-            if let RedisValue::List(list) = entry.value_mut() {
-                values.iter().for_each(|value| list.lpush(value.clone()));
-                list.len()
-            } else {
-                0
+    fn lpush(&self, key: &Bytes, values: &[Bytes]) -> Result<usize> {
+        let data = self.data_for(key);
+        let len = match data.get_mut(key) {
+            Some(mut entry) => match entry.value_mut() {
+                RedisValue::List(list) => {
+                    values.iter().for_each(|value| list.lpush(value.clone()));
+                    Ok(list.len())
+                }
+                _ => Err(CommandError::WrongType),
+            },
+            None => {
+                // Create new list
+                let mut new_list = RedisList::new();
+                values
+                    .iter()
+                    .for_each(|value| new_list.lpush(value.clone()));
+                let len = new_list.len();
+                data.insert(key.clone(), RedisValue::List(new_list));
+                Ok(len)
             }
-        } else {
-            // Create new list
-            let mut new_list = RedisList::new();
-            values
-                .iter()
-                .for_each(|value| new_list.lpush(value.clone()));
-            let len = new_list.len();
-            data.insert(key.clone(), RedisValue::List(new_list));
-            len
-        }
+        }?;
+        self.changed(values.len());
+        self.signal_list_ready(key);
+        Ok(len)
     }
 
-    fn rpush(&self, key: &Bytes, values: &[Bytes]) -> usize {
-        let data = self.current_data();
-        if let Some(mut entry) = data.get_mut(key) {
-            if let RedisValue::List(list) = entry.value_mut() {
-                values.iter().for_each(|value| list.rpush(value.clone()));
-                list.len()
-            } else {
-                0
+    fn rpush(&self, key: &Bytes, values: &[Bytes]) -> Result<usize> {
+        let data = self.data_for(key);
+        let len = match data.get_mut(key) {
+            Some(mut entry) => match entry.value_mut() {
+                RedisValue::List(list) => {
+                    values.iter().for_each(|value| list.rpush(value.clone()));
+                    Ok(list.len())
+                }
+                _ => Err(CommandError::WrongType),
+            },
+            None => {
+                let mut new_list = RedisList::new();
+                values
+                    .iter()
+                    .for_each(|value| new_list.rpush(value.clone()));
+                let len = new_list.len();
+                data.insert(key.clone(), RedisValue::List(new_list));
+                Ok(len)
             }
-        } else {
-            let mut new_list = RedisList::new();
-            values
-                .iter()
-                .for_each(|value| new_list.rpush(value.clone()));
-            let len = new_list.len();
-            data.insert(key.clone(), RedisValue::List(new_list));
-            len
-        }
+        }?;
+        self.changed(values.len());
+        self.signal_list_ready(key);
+        Ok(len)
     }
 
-    fn lpop(&self, key: &Bytes) -> Option<Bytes> {
-        let data = self.current_data();
-        if let Some(mut entry) = data.get_mut(key) {
-            if let RedisValue::List(list) = entry.value_mut() {
-                list.lpop()
-            } else {
-                None
-            }
-        } else {
-            None
-        }
+    fn lpop(&self, key: &Bytes) -> Result<Option<Bytes>> {
+        let popped = self.remove_elements(key, None, |value| match value {
+            RedisValue::List(list) => Ok(list.lpop()),
+            _ => Err(CommandError::WrongType),
+        })?;
+        self.changed(popped.is_some() as usize);
+        Ok(popped)
     }
 
-    fn rpop(&self, key: &Bytes) -> Option<Bytes> {
-        let data = self.current_data();
-        if let Some(mut entry) = data.get_mut(key) {
-            if let RedisValue::List(list) = entry.value_mut() {
-                list.rpop()
-            } else {
-                None
-            }
-        } else {
-            None
-        }
+    fn rpop(&self, key: &Bytes) -> Result<Option<Bytes>> {
+        let popped = self.remove_elements(key, None, |value| match value {
+            RedisValue::List(list) => Ok(list.rpop()),
+            _ => Err(CommandError::WrongType),
+        })?;
+        self.changed(popped.is_some() as usize);
+        Ok(popped)
     }
 
-    fn llen(&self, key: &Bytes) -> usize {
-        if let Some(entry) = self.current_data().get(key) {
-            if let RedisValue::List(list) = entry.value() {
-                list.len()
-            } else {
-                0
-            }
-        } else {
-            0
+    fn llen(&self, key: &Bytes) -> Result<usize> {
+        match self.data_for(key).get(key) {
+            Some(entry) => match entry.value() {
+                RedisValue::List(list) => Ok(list.len()),
+                _ => Err(CommandError::WrongType),
+            },
+            None => Ok(0),
         }
     }
 
-    fn lindex(&self, key: &Bytes, index: i64) -> Option<Bytes> {
-        if let Some(entry) = self.current_data().get(key) {
-            if let RedisValue::List(list) = entry.value() {
-                list.index(index).map(|s| s.clone())
-            } else {
-                None
-            }
-        } else {
-            None
+    fn lindex(&self, key: &Bytes, index: i64) -> Result<Option<Bytes>> {
+        match self.data_for(key).get(key) {
+            Some(entry) => match entry.value() {
+                RedisValue::List(list) => Ok(list.index(index).cloned()),
+                _ => Err(CommandError::WrongType),
+            },
+            None => Ok(None),
         }
     }
 
     fn lrange(&self, key: &Bytes, start: i64, end: i64) -> Result<Vec<Bytes>> {
-        if let Some(entry) = self.current_data().get(key) {
+        if let Some(entry) = self.data_for(key).get(key) {
             if let RedisValue::List(list) = entry.value() {
                 Ok(list.range(start, end))
             } else {
-                Ok(Vec::new())
+                Err(CommandError::WrongType)
             }
         } else {
             Ok(Vec::new())
@@ -109,22 +107,20 @@ impl ListOp for Database {
     }
 
     fn ltrim(&self, key: &Bytes, start: i64, end: i64) -> Result<()> {
-        let data = self.current_data();
-        if let Some(mut entry) = data.get_mut(key) {
-            if let RedisValue::List(list) = entry.value_mut() {
+        let removed = self.remove_elements(key, 0, |value| match value {
+            RedisValue::List(list) => {
+                let len = list.len();
                 list.trim(start, end);
-                Ok(())
-            } else {
-                Err(CommandError::WrongType)
+                Ok(len - list.len())
             }
-        } else {
-            // Key mismatch or doesn't exist - usually ok or error depending on command
-            Ok(())
-        }
+            _ => Err(CommandError::WrongType),
+        })?;
+        self.changed(removed);
+        Ok(())
     }
 
     fn lset(&self, key: &Bytes, index: i64, value: Bytes) -> Result<()> {
-        let data = self.current_data();
+        let data = self.data_for(key);
         if let Some(mut entry) = data.get_mut(key) {
             if let RedisValue::List(list) = entry.value_mut() {
                 let len = list.len() as i64;
@@ -132,6 +128,7 @@ impl ListOp for Database {
                     return Err(CommandError::IndexOutOfRange);
                 }
                 list.set(index, value);
+                self.changed(1);
                 Ok(())
             } else {
                 Err(CommandError::WrongType)
@@ -142,17 +139,168 @@ impl ListOp for Database {
     }
 
     fn linsert(&self, key: &Bytes, ord: &str, pivot: &Bytes, value: Bytes) -> Result<i64> {
-        let data = self.current_data();
+        // A bad direction is an error even when there is no list
+        if !ord.eq_ignore_ascii_case("BEFORE") && !ord.eq_ignore_ascii_case("AFTER") {
+            return Err(CommandError::InvalidInsertDirection);
+        }
+        let data = self.data_for(key);
         if let Some(mut entry) = data.get_mut(key) {
             if let RedisValue::List(list) = entry.value_mut() {
-                list.insert(ord, pivot, value)
+                let len = list.insert(ord, pivot, value)?;
+                self.changed((len > 0) as usize);
+                Ok(len)
             } else {
                 Err(CommandError::WrongType)
             }
         } else {
-            // Key not found = 0? Or error?
-            // LINSERT on missing key does nothing and returns 0.
+            // LINSERT on a missing key does nothing and returns 0
             Ok(0)
         }
     }
+
+    fn lpushx(&self, key: &Bytes, values: &[Bytes]) -> Result<usize> {
+        self.push_existing(key, ListEnd::Left, values)
+    }
+
+    fn rpushx(&self, key: &Bytes, values: &[Bytes]) -> Result<usize> {
+        self.push_existing(key, ListEnd::Right, values)
+    }
+
+    fn pop_many(&self, key: &Bytes, end: ListEnd, count: usize) -> Result<Option<Vec<Bytes>>> {
+        let popped = self.remove_elements(key, None, |value| match value {
+            RedisValue::List(list) => Ok(Some(list.pop_many(end, count))),
+            _ => Err(CommandError::WrongType),
+        })?;
+        self.changed(popped.as_ref().map_or(0, Vec::len));
+        Ok(popped)
+    }
+
+    fn lpos(
+        &self,
+        key: &Bytes,
+        element: &Bytes,
+        rank: i64,
+        count: usize,
+        maxlen: usize,
+    ) -> Result<Vec<usize>> {
+        match self.data_for(key).get(key) {
+            Some(entry) => match entry.value() {
+                RedisValue::List(list) => Ok(list.positions(element, rank, count, maxlen)),
+                _ => Err(CommandError::WrongType),
+            },
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn lrem(&self, key: &Bytes, count: i64, element: &Bytes) -> Result<usize> {
+        let removed = self.remove_elements(key, 0, |value| match value {
+            RedisValue::List(list) => Ok(list.remove(element, count)),
+            _ => Err(CommandError::WrongType),
+        })?;
+        self.changed(removed);
+        Ok(removed)
+    }
+
+    fn lmove(
+        &self,
+        source: &Bytes,
+        destination: &Bytes,
+        from: ListEnd,
+        to: ListEnd,
+    ) -> Result<Option<Bytes>> {
+        let moved = self.move_element(source, destination, from, to)?;
+        if moved.is_some() {
+            self.signal_list_ready(destination);
+        }
+        Ok(moved)
+    }
+
+    fn lmpop(
+        &self,
+        keys: &[Bytes],
+        end: ListEnd,
+        count: usize,
+    ) -> Result<Option<(Bytes, Vec<Bytes>)>> {
+        let locked_keys: Vec<&Bytes> = keys.iter().collect();
+        let popped = self.with_keys_locked(&locked_keys, |locked| {
+            self.load_locked(locked, &locked_keys)?;
+            for key in keys {
+                match locked.get_mut(key) {
+                    Some(RedisValue::List(list)) if !list.is_empty() => {
+                        let elements = list.pop_many(end, count);
+                        if list.is_empty() {
+                            locked.remove(key);
+                        }
+                        return Ok(Some((key.clone(), elements)));
+                    }
+                    Some(RedisValue::List(_)) | None => {}
+                    Some(_) => return Err(CommandError::WrongType),
+                }
+            }
+            Ok(None)
+        })?;
+        self.changed(popped.as_ref().map_or(0, |(_, elements)| elements.len()));
+        Ok(popped)
+    }
+}
+
+impl Database {
+    /// LPUSHX and RPUSHX: push onto a list that already exists
+    fn push_existing(&self, key: &Bytes, end: ListEnd, values: &[Bytes]) -> Result<usize> {
+        match self.data_for(key).get_mut(key) {
+            Some(mut entry) => match entry.value_mut() {
+                RedisValue::List(list) => {
+                    values
+                        .iter()
+                        .for_each(|value| list.push_to(end, value.clone()));
+                    self.changed(values.len());
+                    Ok(list.len())
+                }
+                _ => Err(CommandError::WrongType),
+            },
+            None => Ok(0),
+        }
+    }
+
+    /// LMOVE without waking the clients blocked on the destination
+    pub(super) fn move_element(
+        &self,
+        source: &Bytes,
+        destination: &Bytes,
+        from: ListEnd,
+        to: ListEnd,
+    ) -> Result<Option<Bytes>> {
+        let moved = self.with_keys_locked(&[source, destination], |locked| {
+            self.load_locked(locked, &[source, destination])?;
+            match locked.get(source) {
+                Some(RedisValue::List(_)) => {}
+                Some(_) => return Err(CommandError::WrongType),
+                None => return Ok(None),
+            }
+            if !matches!(locked.get(destination), None | Some(RedisValue::List(_))) {
+                return Err(CommandError::WrongType);
+            }
+            let Some(RedisValue::List(list)) = locked.get_mut(source) else {
+                unreachable!("source checked above");
+            };
+            let Some(element) = list.pop_from(from) else {
+                return Ok(None);
+            };
+            match locked.get_mut(destination) {
+                Some(RedisValue::List(list)) => list.push_to(to, element.clone()),
+                _ => {
+                    let mut new_list = RedisList::new();
+                    new_list.push_to(to, element.clone());
+                    locked.insert(destination.clone(), RedisValue::List(new_list));
+                }
+            }
+            // Moving within one list leaves it as long as before
+            if matches!(locked.get(source), Some(RedisValue::List(list)) if list.is_empty()) {
+                locked.remove(source);
+            }
+            Ok(Some(element))
+        })?;
+        self.changed(moved.is_some() as usize);
+        Ok(moved)
+    }
 }
@@ -2,25 +2,109 @@
 // In-memory data store implementation
 
 use crate::commands::{CommandError, Result};
+use crate::config::Config;
 use crate::data_structures::{RedisHash, RedisList, RedisSet, RedisSortedSet, RedisString};
+use crate::modules::ModuleValue;
+use crate::persistence::SaveState;
+use blocking::BlockedClients;
 use bytes::Bytes;
-use dashmap::DashMap;
-use parking_lot::Mutex;
-use regex::Regex;
-use std::collections::HashSet;
-use std::time::{Duration, SystemTime};
+use dashmap::{mapref::entry::Entry, DashMap, RwLockWriteGuard, SharedValue};
+use hashbrown::raw::RawTable;
+use parking_lot::{Mutex, RwLock, RwLockReadGuard};
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::SystemTime;
 use std::{collections::HashMap, sync::Arc};
+use tiered::{SpilledValue, TieredStore};
+use tracing::error;
+
+pub use session::Session;
 
 // Type definitions
 pub type SharedDatabase = Arc<Database>;
 
 #[derive(Debug)]
-pub(crate) enum RedisValue {
+pub enum RedisValue {
     String(RedisString),
     Hash(RedisHash),
     List(RedisList),
     Set(RedisSet),
     SortedSet(RedisSortedSet),
+    /// A value moved to the tiered store; only seen by code that walks a
+    /// whole database, key lookups load it back first
+    Spilled(SpilledValue),
+    /// A value of a type defined by a module
+    Module(Box<dyn ModuleValue>),
+}
+
+impl RedisValue {
+    /// Name reported by the TYPE command
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            RedisValue::String(_) => "string",
+            RedisValue::Hash(_) => "hash",
+            RedisValue::List(_) => "list",
+            RedisValue::Set(_) => "set",
+            RedisValue::SortedSet(_) => "zset",
+            RedisValue::Spilled(spilled) => spilled.type_name,
+            RedisValue::Module(value) => value.type_name(),
+        }
+    }
+
+    /// Name reported by OBJECT ENCODING. Collections report the Redis
+    /// encoding closest to how they are held here.
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            RedisValue::String(s) => s.encoding(),
+            RedisValue::Hash(_) | RedisValue::Set(_) => "hashtable",
+            RedisValue::List(_) => "quicklist",
+            RedisValue::SortedSet(_) => "skiplist",
+            RedisValue::Spilled(_) | RedisValue::Module(_) => "raw",
+        }
+    }
+
+    /// Rough number of bytes the value occupies in memory
+    pub fn estimated_size(&self) -> usize {
+        // Per element bookkeeping (pointers, lengths, hash table slots)
+        const ENTRY_OVERHEAD: usize = 16;
+        match self {
+            RedisValue::String(s) => s.len() + ENTRY_OVERHEAD,
+            RedisValue::List(list) => list.iter().map(|i| i.len() + ENTRY_OVERHEAD).sum(),
+            RedisValue::Set(set) => set.iter().map(|m| m.len() + ENTRY_OVERHEAD).sum(),
+            RedisValue::SortedSet(zset) => {
+                zset.iter().map(|(m, _)| m.len() + 8 + ENTRY_OVERHEAD).sum()
+            }
+            RedisValue::Hash(hash) => hash
+                .iter()
+                .map(|(f, v)| f.len() + v.len() + ENTRY_OVERHEAD)
+                .sum(),
+            RedisValue::Spilled(_) => 0,
+            RedisValue::Module(value) => value.memory_usage(),
+        }
+    }
+
+    /// Whether this is a list, hash, set, sorted set or module value with no
+    /// elements left. Such values are never stored; the key is deleted instead.
+    pub fn is_empty_collection(&self) -> bool {
+        match self {
+            RedisValue::List(list) => list.is_empty(),
+            RedisValue::Hash(hash) => hash.is_empty(),
+            RedisValue::Set(set) => set.scard() == 0,
+            RedisValue::SortedSet(zset) => zset.zcard() == 0,
+            RedisValue::Module(value) => value.is_empty(),
+            RedisValue::String(_) | RedisValue::Spilled(_) => false,
+        }
+    }
+}
+
+/// Counters reported by `INFO stats` and reset by CONFIG RESETSTAT
+#[derive(Debug, Default)]
+pub struct ServerStats {
+    pub connections_received: AtomicU64,
+    pub rejected_connections: AtomicU64,
+    pub commands_processed: AtomicU64,
+    /// Clients currently connected (not reset)
+    pub connected_clients: AtomicUsize,
 }
 
 #[derive(Debug)]
@@ -28,8 +112,20 @@ pub struct Database {
     pub(crate) data: HashMap<u8, DashMap<Bytes, RedisValue>>,
     pub(crate) data_expiration_time: HashMap<u8, DashMap<Bytes, SystemTime>>,
     pub(crate) current_db: Mutex<u8>,
+    pub(crate) save_state: SaveState,
+    pub(crate) tiered: Option<TieredStore>,
+    /// The running configuration, changed by CONFIG SET
+    pub(crate) config: RwLock<Config>,
+    pub(crate) stats: ServerStats,
+    /// Estimated dataset size as of the last `refresh_used_memory`
+    pub(crate) used_memory: AtomicU64,
+    /// Clients waiting in BLPOP and the other blocking list commands
+    pub(crate) blocked: BlockedClients,
 }
 
+pub mod blocking;
+pub mod session;
+pub mod tiered;
 pub mod traits;
 
 impl Database {
@@ -45,65 +141,350 @@ impl Database {
             data,
             data_expiration_time,
             current_db: Mutex::new(0),
+            save_state: SaveState::default(),
+            tiered: None,
+            config: RwLock::new(Config {
+                db_num,
+                ..Config::default()
+            }),
+            stats: ServerStats::default(),
+            used_memory: AtomicU64::new(0),
+            blocked: BlockedClients::default(),
         }
     }
     pub fn new_shared(db_num: usize) -> SharedDatabase {
         Arc::new(Self::new(db_num))
     }
-    pub fn data_type(&self, key: &Bytes) -> &str {
+    pub fn save_state(&self) -> &SaveState {
+        &self.save_state
+    }
+    /// Spill cold values to `store` from now on
+    pub fn enable_tiered(&mut self, store: TieredStore) {
+        self.tiered = Some(store);
+    }
+    pub fn tiered(&self) -> Option<&TieredStore> {
+        self.tiered.as_ref()
+    }
+    pub fn config(&self) -> RwLockReadGuard<'_, Config> {
+        self.config.read()
+    }
+    /// Replace the running configuration; see `config::registry` for how
+    /// individual changes take effect
+    pub fn set_config(&self, config: Config) {
+        *self.config.write() = config;
+    }
+    pub fn stats(&self) -> &ServerStats {
+        &self.stats
+    }
+    /// Zero the counters reported by INFO (CONFIG RESETSTAT)
+    pub fn reset_stats(&self) {
+        self.stats.connections_received.store(0, Ordering::Relaxed);
+        self.stats.rejected_connections.store(0, Ordering::Relaxed);
+        self.stats.commands_processed.store(0, Ordering::Relaxed);
+        if let Some(store) = &self.tiered {
+            store.stats().disk_hits.store(0, Ordering::Relaxed);
+            store.stats().spills.store(0, Ordering::Relaxed);
+        }
+    }
+    pub fn used_memory(&self) -> u64 {
+        self.used_memory.load(Ordering::Relaxed)
+    }
+    /// Recompute the estimated dataset size by walking every database
+    pub fn refresh_used_memory(&self) -> u64 {
+        let used: u64 = self
+            .data
+            .values()
+            .map(|data| {
+                data.iter()
+                    .map(|entry| (entry.key().len() + entry.value().estimated_size()) as u64)
+                    .sum::<u64>()
+            })
+            .sum();
+        self.used_memory.store(used, Ordering::Relaxed);
+        used
+    }
+    /// Whether writes that may grow the dataset must be refused
+    pub fn over_maxmemory(&self) -> bool {
+        let maxmemory = self.config().maxmemory;
+        maxmemory > 0 && self.used_memory() > maxmemory
+    }
+    /// Call `f` with the full value, reading it from the tiered store if it
+    /// was spilled. Used by code that walks a whole database.
+    pub fn with_loaded<R>(
+        &self,
+        value: &RedisValue,
+        f: impl FnOnce(&RedisValue) -> R,
+    ) -> crate::error::Result<R> {
+        match (value, &self.tiered) {
+            (RedisValue::Spilled(spilled), Some(store)) => Ok(f(&store.load(spilled)?)),
+            (RedisValue::Spilled(_), None) => Err(crate::error::AppError::Database(
+                "spilled value without a tiered store".to_string(),
+            )),
+            _ => Ok(f(value)),
+        }
+    }
+    pub fn data_type(&self, key: &Bytes) -> &'static str {
+        self.expire_if_needed(key);
         match self.current_data().get(key) {
-            Some(data) => match data.value() {
-                RedisValue::String(_) => "string",
-                RedisValue::Hash(_) => "hash",
-                RedisValue::List(_) => "list",
-                RedisValue::Set(_) => "set",
-                RedisValue::SortedSet(_) => "zset",
-            },
+            Some(data) => data.value().type_name(),
             None => "none",
         }
     }
+    /// The encoding OBJECT ENCODING reports, or `None` for a missing key
+    pub fn object_encoding(&self, key: &Bytes) -> Option<&'static str> {
+        self.data_for(key)
+            .get(key)
+            .map(|data| data.value().encoding())
+    }
+    /// Index of the database commands operate on: the running session's
+    /// selection, or the shared default outside a session
+    fn current_index(&self) -> u8 {
+        Session::current_db().unwrap_or_else(|| *self.current_db.lock())
+    }
+
     fn current_data(&self) -> &DashMap<Bytes, RedisValue> {
-        let db = *self.current_db.lock();
+        let db = self.current_index();
         self.data.get(&db).unwrap()
     }
 
+    /// The current database, with `key` loaded back into memory if it was
+    /// spilled. Every lookup of a single key's value goes through here.
+    fn data_for(&self, key: &Bytes) -> &DashMap<Bytes, RedisValue> {
+        self.expire_if_needed(key);
+        let data = self.current_data();
+        if let Some(store) = &self.tiered {
+            self.touch(key);
+            if let Some(mut entry) = data.get_mut(key) {
+                if let RedisValue::Spilled(spilled) = entry.value() {
+                    match store.load(spilled) {
+                        Ok(value) => {
+                            *entry.value_mut() = value;
+                            store.stats().disk_hits.fetch_add(1, Ordering::Relaxed);
+                        }
+                        Err(e) => error!(
+                            "Cannot load spilled key '{}': {}",
+                            String::from_utf8_lossy(key),
+                            e
+                        ),
+                    }
+                }
+            }
+        }
+        data
+    }
+
+    /// Delete `key` if its expiry time has passed, returning whether it did.
+    /// Keys expire lazily, the first time they are looked up afterwards.
+    fn expire_if_needed(&self, key: &Bytes) -> bool {
+        let expiration = self.current_expiration();
+        let now = SystemTime::now();
+        if !matches!(expiration.get(key), Some(at) if *at <= now) {
+            return false;
+        }
+        // Lock the value before the expiry, in the order `remove_elements` uses
+        let removed = self.current_data().remove_if(key, |_, _| {
+            expiration.remove_if(key, |_, at| *at <= now).is_some()
+        });
+        removed.is_some()
+    }
+
+    /// Record an access to `key` for tiered storage; writes that replace the
+    /// whole value call this instead of loading the old one
+    fn touch(&self, key: &Bytes) {
+        if let Some(store) = &self.tiered {
+            store.touch(self.current_index(), key);
+        }
+    }
+
+    /// Count `changes` modified keys or elements towards the `save` points.
+    /// Every write records what it actually changed, so a DEL of a missing
+    /// key counts nothing and an MSET of N keys counts N.
+    fn changed(&self, changes: usize) {
+        self.save_state.add_dirty(changes as u64);
+    }
+
+    /// Apply `f` to the value at `key`, or return `missing` if there is none.
+    /// When `f` removes the last element of a collection, the key and its
+    /// expiry are deleted while the key's shard is still locked, so no other
+    /// client ever sees the empty value.
+    fn remove_elements<R>(
+        &self,
+        key: &Bytes,
+        missing: R,
+        f: impl FnOnce(&mut RedisValue) -> Result<R>,
+    ) -> Result<R> {
+        match self.data_for(key).entry(key.clone()) {
+            Entry::Occupied(mut entry) => {
+                let result = f(entry.get_mut())?;
+                if entry.get().is_empty_collection() {
+                    entry.remove();
+                    self.current_expiration().remove(key);
+                }
+                Ok(result)
+            }
+            Entry::Vacant(_) => Ok(missing),
+        }
+    }
+
+    /// Run `f` with every shard holding one of `keys` write-locked, so other
+    /// clients see a multi-key update either entirely or not at all. Shards
+    /// are locked in index order, so two such calls cannot deadlock, and
+    /// single-key operations lock one shard at a time.
+    fn with_keys_locked<R>(&self, keys: &[&Bytes], f: impl FnOnce(&mut LockedKeys<'_>) -> R) -> R {
+        for key in keys {
+            self.touch(key);
+        }
+        let data = self.current_data();
+        let mut indexes: Vec<usize> = keys.iter().map(|key| data.determine_map(*key)).collect();
+        indexes.sort_unstable();
+        indexes.dedup();
+        let mut locked = LockedKeys {
+            data,
+            expiration: self.current_expiration(),
+            shards: indexes
+                .into_iter()
+                .map(|index| (index, data.shards()[index].write()))
+                .collect(),
+            now: SystemTime::now(),
+        };
+        f(&mut locked)
+    }
+
+    /// Bring spilled values among `keys` back into memory, as `data_for`
+    /// does for one key, before `with_keys_locked` code reads or changes them
+    fn load_locked(&self, locked: &mut LockedKeys<'_>, keys: &[&Bytes]) -> Result<()> {
+        let Some(store) = &self.tiered else {
+            return Ok(());
+        };
+        for key in keys {
+            if let Some(value) = locked.get_mut(key) {
+                if let RedisValue::Spilled(spilled) = value {
+                    *value = store.load(spilled).map_err(|e| {
+                        error!(
+                            "Cannot load spilled key '{}': {}",
+                            String::from_utf8_lossy(key),
+                            e
+                        );
+                        CommandError::InternalError
+                    })?;
+                    store.stats().disk_hits.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn current_expiration(&self) -> &DashMap<Bytes, SystemTime> {
-        let db = *self.current_db.lock();
+        let db = self.current_index();
         self.data_expiration_time.get(&db).unwrap()
     }
 
     fn add_value(&self, key: &Bytes, val: i64) -> Result<i64> {
-        let data = self.current_data();
+        let data = self.data_for(key);
         if let Some(mut entry) = data.get_mut(key) {
             if let RedisValue::String(current_value) = entry.value_mut() {
-                // Parse existing Bytes to i64
-                let val_bytes = current_value.get();
-                let s =
-                    std::str::from_utf8(&val_bytes).map_err(|_| CommandError::InvalidInteger)?;
-                match s.parse::<i64>() {
-                    Ok(integer) => {
-                        let new_integer = integer + val;
-                        *current_value = RedisString::new(Bytes::from(new_integer.to_string()));
-                        Ok(new_integer)
-                    }
-                    Err(_) => Err(CommandError::InvalidInteger),
-                }
+                // Int encoded counters are added to without any parsing
+                let integer = current_value
+                    .integer()
+                    .ok_or(CommandError::InvalidInteger)?;
+                let new_integer = integer.checked_add(val).ok_or(CommandError::Overflow)?;
+                *current_value = RedisString::from_integer(new_integer);
+                self.changed(1);
+                Ok(new_integer)
             } else {
                 Err(CommandError::WrongType)
             }
         } else {
             data.insert(
                 key.clone(),
-                RedisValue::String(RedisString::new(Bytes::from(val.to_string()))),
+                RedisValue::String(RedisString::from_integer(val)),
             );
+            self.changed(1);
             Ok(val)
         }
     }
 }
 
-pub mod keys;
-pub mod strings;
+/// One shard of a keyspace map, as the raw-api exposes it
+type Shard = RawTable<(Bytes, SharedValue<RedisValue>)>;
+
+/// Keys whose shards `Database::with_keys_locked` holds. Only the keys it
+/// was given can be read or written.
+struct LockedKeys<'a> {
+    data: &'a DashMap<Bytes, RedisValue>,
+    expiration: &'a DashMap<Bytes, SystemTime>,
+    shards: Vec<(usize, RwLockWriteGuard<'a, Shard>)>,
+    now: SystemTime,
+}
+
+impl LockedKeys<'_> {
+    fn shard(&mut self, key: &Bytes) -> (u64, &mut Shard) {
+        let index = self.data.determine_map(key);
+        let hash = self.data.hasher().hash_one(key);
+        let (_, shard) = self
+            .shards
+            .iter_mut()
+            .find(|(locked, _)| *locked == index)
+            .expect("key outside the locked set");
+        (hash, shard)
+    }
+
+    fn is_expired(&self, key: &Bytes) -> bool {
+        matches!(self.expiration.get(key), Some(at) if *at <= self.now)
+    }
+
+    /// The value at `key`; expired keys read as missing. Values of several
+    /// keys can be read at once.
+    fn get(&self, key: &Bytes) -> Option<&RedisValue> {
+        if self.is_expired(key) {
+            return None;
+        }
+        let index = self.data.determine_map(key);
+        let hash = self.data.hasher().hash_one(key);
+        let (_, shard) = self
+            .shards
+            .iter()
+            .find(|(locked, _)| *locked == index)
+            .expect("key outside the locked set");
+        shard
+            .get(hash, |(k, _)| k == key)
+            .map(|(_, value)| value.get())
+    }
+
+    fn get_mut(&mut self, key: &Bytes) -> Option<&mut RedisValue> {
+        if self.is_expired(key) {
+            return None;
+        }
+        let (hash, shard) = self.shard(key);
+        shard
+            .get_mut(hash, |(k, _)| k == key)
+            .map(|(_, value)| value.get_mut())
+    }
+
+    /// Store `value` at `key`, replacing any value and TTL
+    fn insert(&mut self, key: Bytes, value: RedisValue) {
+        self.expiration.remove(&key);
+        let data = self.data;
+        let (hash, shard) = self.shard(&key);
+        shard.remove_entry(hash, |(k, _)| *k == key);
+        shard.insert(hash, (key, SharedValue::new(value)), |(k, _)| {
+            data.hasher().hash_one(k)
+        });
+    }
+
+    /// Delete `key` and its TTL
+    fn remove(&mut self, key: &Bytes) {
+        self.expiration.remove(key);
+        let (hash, shard) = self.shard(key);
+        shard.remove_entry(hash, |(k, _)| k == key);
+    }
+}
+
+pub mod bitmaps;
 pub mod hashes;
+pub mod keys;
 pub mod lists;
+pub mod modules;
 pub mod sets;
+pub mod strings;
 pub mod zsets;
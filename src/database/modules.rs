@@ -0,0 +1,82 @@
+use super::{Database, RedisValue};
+use crate::commands::{CommandError, Result};
+use crate::modules::ModuleValue;
+use bytes::Bytes;
+use dashmap::mapref::entry::Entry;
+
+/// The module value of type `T` inside `value`, or WRONGTYPE
+fn downcast_mut<T: ModuleValue>(value: &mut RedisValue) -> Result<&mut T> {
+    match value {
+        RedisValue::Module(value) => value.downcast_mut().ok_or(CommandError::WrongType),
+        _ => Err(CommandError::WrongType),
+    }
+}
+
+// Access for module commands to the values of their own types. A key holding
+// a builtin value or another module's type is a WRONGTYPE error.
+impl Database {
+    /// Call `f` with the value at `key`, or return `None` if there is none
+    pub fn read_module_value<T: ModuleValue, R>(
+        &self,
+        key: &Bytes,
+        f: impl FnOnce(&T) -> R,
+    ) -> Result<Option<R>> {
+        match self.data_for(key).get(key) {
+            Some(entry) => match entry.value() {
+                RedisValue::Module(value) => value
+                    .downcast_ref()
+                    .map(|value| Some(f(value)))
+                    .ok_or(CommandError::WrongType),
+                _ => Err(CommandError::WrongType),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Apply `f` to the value at `key`, or return `missing` if there is none.
+    /// The key is deleted if `f` leaves the value empty.
+    pub fn update_module_value<T: ModuleValue, R>(
+        &self,
+        key: &Bytes,
+        missing: R,
+        f: impl FnOnce(&mut T) -> Result<R>,
+    ) -> Result<R> {
+        let mut found = false;
+        let result = self.remove_elements(key, missing, |value| {
+            found = true;
+            f(downcast_mut(value)?)
+        })?;
+        self.changed(found as usize);
+        Ok(result)
+    }
+
+    /// Apply `f` to the value at `key`, storing the one `create` returns first
+    /// if there is none. Empty values are not kept.
+    pub fn upsert_module_value<T: ModuleValue, R>(
+        &self,
+        key: &Bytes,
+        create: impl FnOnce() -> T,
+        f: impl FnOnce(&mut T) -> Result<R>,
+    ) -> Result<R> {
+        let result = match self.data_for(key).entry(key.clone()) {
+            Entry::Occupied(mut entry) => {
+                let result = f(downcast_mut(entry.get_mut())?)?;
+                if entry.get().is_empty_collection() {
+                    entry.remove();
+                    self.current_expiration().remove(key);
+                }
+                result
+            }
+            Entry::Vacant(entry) => {
+                let mut value = create();
+                let result = f(&mut value)?;
+                if !value.is_empty() {
+                    entry.insert(RedisValue::Module(Box::new(value)));
+                }
+                result
+            }
+        };
+        self.changed(1);
+        Ok(result)
+    }
+}
@@ -0,0 +1,75 @@
+// Per-connection state
+// Each network connection and embedded client runs its commands inside its
+// own `Session`, so SELECT or HELLO on one connection does not move the others.
+
+use crate::networking::resp::Protocol;
+use parking_lot::Mutex;
+use std::future::Future;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+tokio::task_local! {
+    static SESSION: Arc<Session>;
+}
+
+#[derive(Debug, Default)]
+pub struct Session {
+    db: AtomicU8,
+    protocol: Mutex<Protocol>,
+}
+
+impl Session {
+    pub fn new() -> Arc<Session> {
+        Arc::new(Session::default())
+    }
+
+    /// The database this session has selected
+    pub fn db(&self) -> u8 {
+        self.db.load(Ordering::Relaxed)
+    }
+
+    /// The protocol replies to this session are encoded in
+    pub fn protocol(&self) -> Protocol {
+        *self.protocol.lock()
+    }
+
+    pub fn set_protocol(&self, protocol: Protocol) {
+        *self.protocol.lock() = protocol;
+    }
+
+    /// Run `f` with this session's state. Database code called outside any
+    /// session (persistence, tests using `Database` directly) uses the
+    /// shared default database instead.
+    pub async fn scope<F: Future>(self: &Arc<Self>, f: F) -> F::Output {
+        SESSION.scope(Arc::clone(self), f).await
+    }
+
+    /// Run `f` in a throwaway session that has `db` selected, for code that
+    /// writes to several databases without moving anyone's selection
+    pub(crate) fn with_db<R>(db: u8, f: impl FnOnce() -> R) -> R {
+        let session = Session::new();
+        session.db.store(db, Ordering::Relaxed);
+        SESSION.sync_scope(session, f)
+    }
+
+    /// The session running the current task, if any
+    pub fn current() -> Option<Arc<Session>> {
+        SESSION.try_with(Arc::clone).ok()
+    }
+
+    /// Selected database of the session running the current task, if any
+    pub(super) fn current_db() -> Option<u8> {
+        SESSION.try_with(|session| session.db()).ok()
+    }
+
+    /// Point the running session at `db`; false outside a session
+    pub(super) fn select_current(db: u8) -> bool {
+        match Session::current() {
+            Some(session) => {
+                session.db.store(db, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -1,25 +1,107 @@
-use super::{Database, RedisValue};
+use super::{Database, LockedKeys, RedisValue};
 use crate::commands::{CommandError, Result};
 use crate::data_structures::RedisSet;
 use crate::database::traits::SetOp;
 use bytes::Bytes;
-use std::collections::HashSet;
+
+/// SINTER, SUNION or SDIFF, plain or with STORE
+#[derive(Debug, Clone, Copy)]
+enum SetAlgebra {
+    Inter,
+    Union,
+    Diff,
+}
+
+/// The members `op` yields, missing keys counting as empty sets
+fn combine<'a>(op: SetAlgebra, sets: &[Option<&'a RedisSet>]) -> Vec<&'a Bytes> {
+    let present = |sets: &[Option<&'a RedisSet>]| -> Vec<&'a RedisSet> {
+        sets.iter().flatten().copied().collect()
+    };
+    match op {
+        SetAlgebra::Inter if sets.iter().any(Option::is_none) => Vec::new(),
+        SetAlgebra::Inter => RedisSet::intersection(&present(sets), 0),
+        SetAlgebra::Union => RedisSet::union(&present(sets)),
+        SetAlgebra::Diff => match sets.split_first() {
+            Some((Some(first), others)) => RedisSet::difference(first, &present(others)),
+            _ => Vec::new(),
+        },
+    }
+}
+
+impl Database {
+    /// The sets at `keys`, `None` for missing keys; spilled ones are loaded
+    fn locked_sets<'a>(
+        &self,
+        locked: &'a mut LockedKeys<'_>,
+        keys: &[Bytes],
+    ) -> Result<Vec<Option<&'a RedisSet>>> {
+        self.load_locked(locked, &keys.iter().collect::<Vec<_>>())?;
+        let locked = &*locked;
+        keys.iter()
+            .map(|key| match locked.get(key) {
+                Some(RedisValue::Set(set)) => Ok(Some(set)),
+                Some(_) => Err(CommandError::WrongType),
+                None => Ok(None),
+            })
+            .collect()
+    }
+
+    /// SINTER, SUNION and SDIFF, over a consistent view of the sets
+    fn set_algebra(&self, op: SetAlgebra, keys: &[Bytes]) -> Result<Vec<Bytes>> {
+        let locked_keys: Vec<&Bytes> = keys.iter().collect();
+        self.with_keys_locked(&locked_keys, |locked| {
+            let sets = self.locked_sets(locked, keys)?;
+            Ok(combine(op, &sets).into_iter().cloned().collect())
+        })
+    }
+
+    /// The STORE variants: replace `destination` with the result, or delete
+    /// it if the result is empty, with every key locked throughout
+    fn set_algebra_store(
+        &self,
+        op: SetAlgebra,
+        destination: &Bytes,
+        keys: &[Bytes],
+    ) -> Result<usize> {
+        let mut locked_keys: Vec<&Bytes> = keys.iter().collect();
+        locked_keys.push(destination);
+        let len = self.with_keys_locked(&locked_keys, |locked| {
+            let sets = self.locked_sets(locked, keys)?;
+            let result: RedisSet = combine(op, &sets).into_iter().cloned().collect();
+            let len = result.scard();
+            if len == 0 {
+                locked.remove(destination);
+            } else {
+                locked.insert(destination.clone(), RedisValue::Set(result));
+            }
+            Ok(len)
+        })?;
+        self.changed(1);
+        Ok(len)
+    }
+
+    /// Call `f` with the set at `key`, or with `None` if there is none
+    fn read_set<R>(&self, key: &Bytes, f: impl FnOnce(Option<&RedisSet>) -> R) -> Result<R> {
+        match self.data_for(key).get(key) {
+            Some(entry) => match entry.value() {
+                RedisValue::Set(set) => Ok(f(Some(set))),
+                _ => Err(CommandError::WrongType),
+            },
+            None => Ok(f(None)),
+        }
+    }
+}
 
 impl SetOp for Database {
-    fn sadd(&self, key: &Bytes, values: &[Bytes]) -> usize {
-        let data = self.current_data();
-        match data.get_mut(key) {
-            Some(mut entry) => {
-                match entry.value_mut() {
-                    RedisValue::Set(set) => {
-                        values.iter().filter(|val| set.sadd((*val).clone())).count()
-                    }
-                    _ => {
-                        // Key exists but is wrong type
-                        0
-                    }
+    fn sadd(&self, key: &Bytes, values: &[Bytes]) -> Result<usize> {
+        let data = self.data_for(key);
+        let added = match data.get_mut(key) {
+            Some(mut entry) => match entry.value_mut() {
+                RedisValue::Set(set) => {
+                    Ok(values.iter().filter(|val| set.sadd((*val).clone())).count())
                 }
-            }
+                _ => Err(CommandError::WrongType),
+            },
             None => {
                 // Key doesn't exist, create new set
                 let mut new_set = RedisSet::new();
@@ -28,104 +110,154 @@ impl SetOp for Database {
                     .filter(|val| new_set.sadd((*val).clone()))
                     .count();
                 data.insert(key.clone(), RedisValue::Set(new_set));
-                added
+                Ok(added)
             }
-        }
+        }?;
+        self.changed(added);
+        Ok(added)
     }
 
-    fn srem(&self, key: &Bytes, values: &[Bytes]) -> usize {
-        let data = self.current_data();
-        if let Some(mut entry) = data.get_mut(key) {
-            if let RedisValue::Set(set) = entry.value_mut() {
-                values.iter().filter(|val| set.srem(val)).count()
-            } else {
-                0
-            }
-        } else {
-            0
-        }
+    fn srem(&self, key: &Bytes, values: &[Bytes]) -> Result<usize> {
+        let removed = self.remove_elements(key, 0, |value| match value {
+            RedisValue::Set(set) => Ok(values.iter().filter(|val| set.srem(val)).count()),
+            _ => Err(CommandError::WrongType),
+        })?;
+        self.changed(removed);
+        Ok(removed)
     }
 
     fn smembers(&self, key: &Bytes) -> Result<Vec<Bytes>> {
-        if let Some(entry) = self.current_data().get(key) {
-            if let RedisValue::Set(set) = entry.value() {
-                Ok(set.smembers().into_iter().map(|s| s.clone()).collect())
-            } else {
-                Err(CommandError::WrongType)
-            }
-        } else {
-            Err(CommandError::WrongType)
+        match self.data_for(key).get(key) {
+            Some(entry) => match entry.value() {
+                RedisValue::Set(set) => Ok(set.smembers().into_iter().cloned().collect()),
+                _ => Err(CommandError::WrongType),
+            },
+            None => Ok(Vec::new()),
         }
     }
 
-    fn scard(&self, key: &Bytes) -> usize {
-        if let Some(entry) = self.current_data().get(key) {
-            if let RedisValue::Set(set) = entry.value() {
-                set.scard()
-            } else {
-                0
-            }
-        } else {
-            0
+    fn scard(&self, key: &Bytes) -> Result<usize> {
+        match self.data_for(key).get(key) {
+            Some(entry) => match entry.value() {
+                RedisValue::Set(set) => Ok(set.scard()),
+                _ => Err(CommandError::WrongType),
+            },
+            None => Ok(0),
         }
     }
 
-    fn sismember(&self, key: &Bytes, member: &Bytes) -> bool {
-        if let Some(entry) = self.current_data().get(key) {
-            if let RedisValue::Set(set) = entry.value() {
-                set.sismember(member)
-            } else {
-                false
-            }
-        } else {
-            false
+    fn sismember(&self, key: &Bytes, member: &Bytes) -> Result<bool> {
+        match self.data_for(key).get(key) {
+            Some(entry) => match entry.value() {
+                RedisValue::Set(set) => Ok(set.sismember(member)),
+                _ => Err(CommandError::WrongType),
+            },
+            None => Ok(false),
         }
     }
 
     fn sinter(&self, keys: &[Bytes]) -> Result<Vec<Bytes>> {
-        let mut res = Vec::new();
-        for key in keys {
-            if let Some(entry) = self.current_data().get(key) {
-                if let RedisValue::Set(set) = entry.value() {
-                    for ele in set.smembers() {
-                        res.push(ele.clone());
-                    }
-                } else {
-                    return Err(CommandError::WrongType);
-                }
-            } else {
-                return Err(CommandError::WrongType);
-            }
-        }
-        Ok(res)
+        self.set_algebra(SetAlgebra::Inter, keys)
     }
 
     fn sunion(&self, keys: &[Bytes]) -> Result<Vec<Bytes>> {
-        match self.sinter(keys) {
-            Ok(res) => Ok(res
-                .into_iter()
-                .collect::<HashSet<_>>()
-                .into_iter()
-                .collect()),
-            Err(e) => Err(e),
-        }
+        self.set_algebra(SetAlgebra::Union, keys)
     }
 
     fn sdiff(&self, keys: &[Bytes]) -> Result<Vec<Bytes>> {
-        match self.smembers(&keys[0]) {
-            Ok(res) => {
-                let mut res: HashSet<_> = res.into_iter().collect();
-                match self.sunion(&keys[1..]) {
-                    Ok(members) => {
-                        for member in members {
-                            res.remove(&member);
-                        }
-                        Ok(res.into_iter().collect())
-                    }
-                    Err(e) => Err(e),
+        self.set_algebra(SetAlgebra::Diff, keys)
+    }
+
+    fn sinterstore(&self, destination: &Bytes, keys: &[Bytes]) -> Result<usize> {
+        self.set_algebra_store(SetAlgebra::Inter, destination, keys)
+    }
+
+    fn sunionstore(&self, destination: &Bytes, keys: &[Bytes]) -> Result<usize> {
+        self.set_algebra_store(SetAlgebra::Union, destination, keys)
+    }
+
+    fn sdiffstore(&self, destination: &Bytes, keys: &[Bytes]) -> Result<usize> {
+        self.set_algebra_store(SetAlgebra::Diff, destination, keys)
+    }
+
+    fn sintercard(&self, keys: &[Bytes], limit: usize) -> Result<usize> {
+        let locked_keys: Vec<&Bytes> = keys.iter().collect();
+        self.with_keys_locked(&locked_keys, |locked| {
+            let sets = self.locked_sets(locked, keys)?;
+            let sets: Option<Vec<&RedisSet>> = sets.into_iter().collect();
+            Ok(sets.map_or(0, |sets| RedisSet::intersection(&sets, limit).len()))
+        })
+    }
+
+    fn spop(&self, key: &Bytes, count: usize) -> Result<Vec<Bytes>> {
+        let popped = self.remove_elements(key, Vec::new(), |value| match value {
+            RedisValue::Set(set) => Ok(set.pop_random(count)),
+            _ => Err(CommandError::WrongType),
+        })?;
+        self.changed(popped.len());
+        Ok(popped)
+    }
+
+    fn srandmember(&self, key: &Bytes, count: i64) -> Result<Vec<Bytes>> {
+        self.read_set(key, |set| {
+            set.map(|set| set.random_members(count).into_iter().cloned().collect())
+                .unwrap_or_default()
+        })
+    }
+
+    fn smove(&self, source: &Bytes, destination: &Bytes, member: &Bytes) -> Result<bool> {
+        let mut moved = false;
+        let result = self.with_keys_locked(&[source, destination], |locked| {
+            self.load_locked(locked, &[source, destination])?;
+            for key in [source, destination] {
+                if !matches!(locked.get(key), None | Some(RedisValue::Set(_))) {
+                    return Err(CommandError::WrongType);
                 }
             }
-            Err(e) => Err(e),
-        }
+            let Some(RedisValue::Set(set)) = locked.get_mut(source) else {
+                return Ok(false);
+            };
+            if source == destination {
+                return Ok(set.sismember(member));
+            }
+            if !set.srem(member) {
+                return Ok(false);
+            }
+            if set.scard() == 0 {
+                locked.remove(source);
+            }
+            match locked.get_mut(destination) {
+                Some(RedisValue::Set(set)) => {
+                    set.sadd(member.clone());
+                }
+                _ => {
+                    let new_set = std::iter::once(member.clone()).collect();
+                    locked.insert(destination.clone(), RedisValue::Set(new_set));
+                }
+            }
+            moved = true;
+            Ok(true)
+        });
+        self.changed(moved as usize);
+        result
+    }
+
+    fn smismember(&self, key: &Bytes, members: &[Bytes]) -> Result<Vec<bool>> {
+        self.read_set(key, |set| {
+            members
+                .iter()
+                .map(|member| set.is_some_and(|set| set.sismember(member)))
+                .collect()
+        })
+    }
+
+    fn sscan(&self, key: &Bytes, cursor: u64, count: usize) -> Result<(u64, Vec<Bytes>)> {
+        self.read_set(key, |set| match set {
+            Some(set) => {
+                let (next, members) = set.scan(cursor, count);
+                (next, members.into_iter().cloned().collect())
+            }
+            None => (0, Vec::new()),
+        })
     }
 }
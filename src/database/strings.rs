@@ -2,22 +2,28 @@ use super::{Database, RedisValue};
 use crate::commands::{CommandError, Result};
 use crate::data_structures::RedisString;
 use crate::database::traits::StringOp;
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
+use dashmap::mapref::entry::Entry;
+use std::time::SystemTime;
+
+/// Largest string SETRANGE may create, Redis' default `proto-max-bulk-len`
+const MAX_STRING_LEN: usize = 512 * 1024 * 1024;
 
 impl StringOp for Database {
-    fn get(&self, key: &Bytes) -> Option<Bytes> {
-        if let Some(value_ref) = self.current_data().get(key) {
-            if let RedisValue::String(value) = value_ref.value() {
-                Some(value.get())
-            } else {
-                None
-            }
-        } else {
-            None
+    fn get(&self, key: &Bytes) -> Result<Option<Bytes>> {
+        match self.data_for(key).get(key) {
+            Some(value_ref) => match value_ref.value() {
+                RedisValue::String(value) => Ok(Some(value.get())),
+                _ => Err(CommandError::WrongType),
+            },
+            None => Ok(None),
         }
     }
 
     fn set(&self, key: &Bytes, value: Bytes) {
+        self.touch(key);
+        // A plain SET discards any TTL
+        self.current_expiration().remove(key);
         let data = self.current_data();
         match data.get_mut(key) {
             Some(mut value_ref) => {
@@ -34,13 +40,26 @@ impl StringOp for Database {
                 data.insert(key.clone(), RedisValue::String(RedisString::new(value)));
             }
         }
+        self.changed(1);
+    }
+
+    /// SET and a TTL, counted as one change
+    fn set_with_expiry(&self, key: &Bytes, value: Bytes, at: SystemTime) {
+        self.set(key, value);
+        self.current_expiration().insert(key.clone(), at);
     }
 
     fn del(&self, keys: &[Bytes]) -> usize {
         let data = self.current_data();
-        keys.iter()
-            .filter(|key| data.remove(*key).is_some())
-            .count()
+        let deleted = keys
+            .iter()
+            .filter(|key| {
+                self.current_expiration().remove(*key);
+                data.remove(*key).is_some()
+            })
+            .count();
+        self.changed(deleted);
+        deleted
     }
 
     fn incr(&self, key: &Bytes) -> Result<i64> {
@@ -61,37 +80,205 @@ impl StringOp for Database {
     fn decr_by(&self, key: &Bytes, value: Bytes) -> Result<i64> {
         let s = std::str::from_utf8(&value).map_err(|_| CommandError::InvalidInteger)?;
         let val = s.parse::<i64>().map_err(|_| CommandError::InvalidInteger)?;
-        self.add_value(key, -val)
+        self.add_value(
+            key,
+            val.checked_neg().ok_or(CommandError::DecrementOverflow)?,
+        )
     }
 
-    fn append(&self, key: &Bytes, value: Bytes) -> usize {
-        let data = self.current_data();
-        if let Some(mut value_ref) = data.get_mut(key) {
-            if let RedisValue::String(current_value) = value_ref.value_mut() {
-                current_value.append(value);
-                current_value.len()
-            } else {
-                drop(value_ref);
-                let cloned_val = value.clone();
+    fn append(&self, key: &Bytes, value: Bytes) -> Result<usize> {
+        let data = self.data_for(key);
+        match data.get_mut(key) {
+            Some(mut value_ref) => match value_ref.value_mut() {
+                RedisValue::String(current_value) => {
+                    current_value.append(value);
+                    self.changed(1);
+                    Ok(current_value.len())
+                }
+                _ => Err(CommandError::WrongType),
+            },
+            None => {
+                let len = value.len();
                 data.insert(key.clone(), RedisValue::String(RedisString::new(value)));
-                cloned_val.len()
+                self.changed(1);
+                Ok(len)
             }
+        }
+    }
+
+    fn str_len(&self, key: &Bytes) -> Result<usize> {
+        match self.data_for(key).get(key) {
+            Some(value_ref) => match value_ref.value() {
+                RedisValue::String(value) => Ok(value.len()),
+                _ => Err(CommandError::WrongType),
+            },
+            None => Ok(0),
+        }
+    }
+
+    /// Negative offsets count from the end; out of range parts are clipped
+    fn get_range(&self, key: &Bytes, start: i64, end: i64) -> Result<Bytes> {
+        let Some(value) = self.get(key)? else {
+            return Ok(Bytes::new());
+        };
+        let len = value.len() as i64;
+        if start < 0 && end < 0 && start > end {
+            return Ok(Bytes::new());
+        }
+        let start = if start < 0 {
+            (len + start).max(0)
+        } else {
+            start
+        };
+        let end = if end < 0 {
+            (len + end).max(0)
         } else {
-            let cloned_val = value.clone();
-            data.insert(key.clone(), RedisValue::String(RedisString::new(value)));
-            cloned_val.len()
+            end.min(len - 1)
+        };
+        if start > end || len == 0 {
+            return Ok(Bytes::new());
         }
+        Ok(value.slice(start as usize..=end as usize))
     }
 
-    fn str_len(&self, key: &Bytes) -> usize {
-        if let Some(value_ref) = self.current_data().get(key) {
-            if let RedisValue::String(value) = value_ref.value() {
-                value.len()
+    /// Overwrite from `offset`, padding with zero bytes; returns the new length
+    fn set_range(&self, key: &Bytes, offset: i64, value: Bytes) -> Result<usize> {
+        let offset = usize::try_from(offset).map_err(|_| CommandError::OffsetOutOfRange)?;
+        let data = self.data_for(key);
+        let check_length = || match offset.checked_add(value.len()) {
+            Some(len) if len <= MAX_STRING_LEN => Ok(()),
+            _ => Err(CommandError::StringTooLong),
+        };
+        match data.entry(key.clone()) {
+            Entry::Occupied(mut entry) => {
+                let RedisValue::String(current) = entry.get_mut() else {
+                    return Err(CommandError::WrongType);
+                };
+                if value.is_empty() {
+                    return Ok(current.len());
+                }
+                check_length()?;
+                let mut bytes = BytesMut::from(&current.get()[..]);
+                if bytes.len() < offset + value.len() {
+                    bytes.resize(offset + value.len(), 0);
+                }
+                bytes[offset..offset + value.len()].copy_from_slice(&value);
+                current.set(bytes.freeze());
+                self.changed(1);
+                Ok(current.len())
+            }
+            // Nothing to write: the key is not created
+            Entry::Vacant(_) if value.is_empty() => Ok(0),
+            Entry::Vacant(entry) => {
+                check_length()?;
+                let mut bytes = BytesMut::zeroed(offset);
+                bytes.extend_from_slice(&value);
+                let len = bytes.len();
+                entry.insert(RedisValue::String(RedisString::new(bytes.freeze())));
+                self.changed(1);
+                Ok(len)
+            }
+        }
+    }
+
+    fn incr_by_float(&self, key: &Bytes, value: f64) -> Result<f64> {
+        let add = |number: f64| {
+            let result = number + value;
+            if result.is_finite() {
+                Ok((result, Bytes::from(result.to_string())))
             } else {
-                0
+                Err(CommandError::NanOrInfinity)
             }
-        } else {
-            0
+        };
+        match self.data_for(key).entry(key.clone()) {
+            Entry::Occupied(mut entry) => {
+                let RedisValue::String(current) = entry.get_mut() else {
+                    return Err(CommandError::WrongType);
+                };
+                let number = current
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|n| !n.is_nan())
+                    .ok_or(CommandError::InvalidFloat)?;
+                let (result, formatted) = add(number)?;
+                current.set(formatted);
+                self.changed(1);
+                Ok(result)
+            }
+            Entry::Vacant(entry) => {
+                let (result, formatted) = add(0.0)?;
+                entry.insert(RedisValue::String(RedisString::new(formatted)));
+                self.changed(1);
+                Ok(result)
+            }
+        }
+    }
+
+    fn get_del(&self, key: &Bytes) -> Result<Option<Bytes>> {
+        match self.data_for(key).entry(key.clone()) {
+            Entry::Occupied(entry) => match entry.get() {
+                RedisValue::String(value) => {
+                    let value = value.get();
+                    entry.remove();
+                    self.current_expiration().remove(key);
+                    self.changed(1);
+                    Ok(Some(value))
+                }
+                _ => Err(CommandError::WrongType),
+            },
+            Entry::Vacant(_) => Ok(None),
+        }
+    }
+
+    /// Values of every key as of one instant; other types read as nil
+    fn mget(&self, keys: &[Bytes]) -> Vec<Option<Bytes>> {
+        let locked: Vec<&Bytes> = keys.iter().collect();
+        self.with_keys_locked(&locked, |locked| {
+            keys.iter()
+                .map(|key| {
+                    let value = locked.get(key)?;
+                    self.with_loaded(value, |value| match value {
+                        RedisValue::String(s) => Some(s.get()),
+                        _ => None,
+                    })
+                    .ok()
+                    .flatten()
+                })
+                .collect()
+        })
+    }
+
+    fn mset(&self, pairs: &[(Bytes, Bytes)]) {
+        let keys: Vec<&Bytes> = pairs.iter().map(|(key, _)| key).collect();
+        self.with_keys_locked(&keys, |locked| {
+            for (key, value) in pairs {
+                locked.insert(
+                    key.clone(),
+                    RedisValue::String(RedisString::new(value.clone())),
+                );
+            }
+        });
+        self.changed(pairs.len());
+    }
+
+    /// Set every key, or none of them if any exists (whatever its type)
+    fn msetnx(&self, pairs: &[(Bytes, Bytes)]) -> bool {
+        let keys: Vec<&Bytes> = pairs.iter().map(|(key, _)| key).collect();
+        let set = self.with_keys_locked(&keys, |locked| {
+            if keys.iter().any(|key| locked.get(key).is_some()) {
+                return false;
+            }
+            for (key, value) in pairs {
+                locked.insert(
+                    key.clone(),
+                    RedisValue::String(RedisString::new(value.clone())),
+                );
+            }
+            true
+        });
+        if set {
+            self.changed(pairs.len());
         }
+        set
     }
 }
@@ -0,0 +1,317 @@
+// Tiered storage for Rudis
+// Keeps keys and metadata in memory and moves the values of cold, large keys
+// to append-only segment files. A spilled value is replaced in place by a
+// `RedisValue::Spilled` placeholder and loaded back on its next access.
+//
+// Segments only live as long as the process: snapshots always contain the
+// full values, so the segment directory is wiped on startup.
+
+use super::{Database, RedisValue, SharedDatabase};
+use crate::config::Config;
+use crate::error::{AppError, Result};
+use crate::persistence::rdb;
+use bytes::Bytes;
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tracing::{debug, error};
+
+/// A new segment is started once the active one grows past this size
+const SEGMENT_MAX_BYTES: u64 = 64 * 1024 * 1024;
+/// Keys accessed more recently than this are never spilled
+const MIN_IDLE_MS: u64 = 1000;
+
+/// Where a spilled value lives on disk, plus the metadata that must stay
+/// available without reading it back
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpilledValue {
+    pub type_name: &'static str,
+    pub segment: u32,
+    pub offset: u64,
+    pub len: u64,
+}
+
+/// Counters reported by `INFO tiered`
+#[derive(Debug, Default)]
+pub struct TieredStats {
+    /// Estimated bytes of values still in memory, as of the last cycle
+    pub resident_bytes: AtomicU64,
+    /// Spilled keys and their payload bytes, as of the last cycle
+    pub spilled_keys: AtomicU64,
+    pub spilled_bytes: AtomicU64,
+    /// Accesses that had to read a value back from disk
+    pub disk_hits: AtomicU64,
+    /// Values moved to disk since startup
+    pub spills: AtomicU64,
+    pub segments: AtomicU64,
+}
+
+#[derive(Debug)]
+struct Segments {
+    active: u32,
+    writer: File,
+    written: u64,
+    readers: HashMap<u32, File>,
+}
+
+#[derive(Debug)]
+pub struct TieredStore {
+    dir: PathBuf,
+    /// Changed at runtime by CONFIG SET tiered-max-memory
+    max_memory: AtomicU64,
+    min_value_size: usize,
+    epoch: Instant,
+    /// Last access per (db, key), in milliseconds since `epoch`
+    access: DashMap<(u8, Bytes), u64>,
+    segments: Mutex<Segments>,
+    stats: TieredStats,
+}
+
+fn segment_path(dir: &Path, id: u32) -> PathBuf {
+    dir.join(format!("tiered-{}.seg", id))
+}
+
+fn open_segment(dir: &Path, id: u32) -> Result<File> {
+    Ok(OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(segment_path(dir, id))?)
+}
+
+impl TieredStore {
+    /// Open the store in `<dir>/tiered`, discarding segments of a previous run
+    pub fn open(config: &Config) -> Result<Self> {
+        if config.tiered_max_memory == 0 {
+            return Err(AppError::Config(
+                "tiered-storage requires tiered-max-memory to be set".to_string(),
+            ));
+        }
+        let dir = Path::new(&config.dir).join("tiered");
+        fs::create_dir_all(&dir)?;
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "seg") {
+                fs::remove_file(path)?;
+            }
+        }
+        let writer = open_segment(&dir, 0)?;
+        let store = TieredStore {
+            dir,
+            max_memory: AtomicU64::new(config.tiered_max_memory),
+            min_value_size: config.tiered_min_value_size,
+            epoch: Instant::now(),
+            access: DashMap::new(),
+            segments: Mutex::new(Segments {
+                active: 0,
+                writer,
+                written: 0,
+                readers: HashMap::new(),
+            }),
+            stats: TieredStats::default(),
+        };
+        store.stats.segments.store(1, Ordering::Relaxed);
+        Ok(store)
+    }
+
+    pub fn max_memory(&self) -> u64 {
+        self.max_memory.load(Ordering::Relaxed)
+    }
+
+    pub fn set_max_memory(&self, max_memory: u64) {
+        self.max_memory.store(max_memory, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> &TieredStats {
+        &self.stats
+    }
+
+    fn now(&self) -> u64 {
+        self.epoch.elapsed().as_millis() as u64
+    }
+
+    pub(crate) fn touch(&self, db: u8, key: &Bytes) {
+        self.access.insert((db, key.clone()), self.now());
+    }
+
+    fn last_access(&self, db: u8, key: &Bytes) -> u64 {
+        self.access
+            .get(&(db, key.clone()))
+            .map(|e| *e.value())
+            .unwrap_or(0)
+    }
+
+    /// Append a DUMP payload to the active segment
+    fn append(&self, payload: &[u8]) -> Result<(u32, u64)> {
+        let mut segments = self.segments.lock();
+        if segments.written >= SEGMENT_MAX_BYTES {
+            let next = segments.active + 1;
+            segments.writer = open_segment(&self.dir, next)?;
+            segments.active = next;
+            segments.written = 0;
+            self.stats.segments.fetch_add(1, Ordering::Relaxed);
+        }
+        let offset = segments.written;
+        segments.writer.write_all(payload)?;
+        segments.written += payload.len() as u64;
+        Ok((segments.active, offset))
+    }
+
+    /// Read a spilled value back from its segment
+    pub fn load(&self, spilled: &SpilledValue) -> Result<RedisValue> {
+        rdb::restore_value(&self.read_payload(spilled)?)
+    }
+
+    /// The DUMP payload a spilled value was written as
+    pub fn read_payload(&self, spilled: &SpilledValue) -> Result<Vec<u8>> {
+        let mut payload = vec![0u8; spilled.len as usize];
+        {
+            let mut segments = self.segments.lock();
+            let reader = match segments.readers.entry(spilled.segment) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    entry.insert(File::open(segment_path(&self.dir, spilled.segment))?)
+                }
+            };
+            reader.seek(SeekFrom::Start(spilled.offset))?;
+            reader.read_exact(&mut payload)?;
+        }
+        Ok(payload)
+    }
+
+    /// Delete every segment except the active one and those in `live`
+    fn collect_garbage(&self, live: &HashSet<u32>) {
+        let mut segments = self.segments.lock();
+        let active = segments.active;
+        let dead: Vec<u32> = (0..active).filter(|id| !live.contains(id)).collect();
+        for id in dead {
+            segments.readers.remove(&id);
+            let path = segment_path(&self.dir, id);
+            if path.exists() {
+                match fs::remove_file(&path) {
+                    Ok(()) => {
+                        self.stats.segments.fetch_sub(1, Ordering::Relaxed);
+                        debug!("Removed tiered segment {}", path.display());
+                    }
+                    Err(e) => error!("Cannot remove tiered segment {}: {}", path.display(), e),
+                }
+            }
+        }
+    }
+}
+
+struct Candidate {
+    db: u8,
+    key: Bytes,
+    size: u64,
+    last_access: u64,
+}
+
+impl Database {
+    /// Spill the least recently used large values until the resident estimate
+    /// fits the budget, then drop segments nothing points to any more
+    pub fn tiered_cycle(&self) -> Result<()> {
+        let Some(store) = &self.tiered else {
+            return Ok(());
+        };
+        let mut resident = 0u64;
+        let mut spilled_keys = 0u64;
+        let mut spilled_bytes = 0u64;
+        let mut live = HashSet::new();
+        let mut candidates = Vec::new();
+
+        for (&db, data) in &self.data {
+            for entry in data.iter() {
+                match entry.value() {
+                    RedisValue::Spilled(spilled) => {
+                        live.insert(spilled.segment);
+                        spilled_keys += 1;
+                        spilled_bytes += spilled.len;
+                    }
+                    value => {
+                        let size = (entry.key().len() + value.estimated_size()) as u64;
+                        resident += size;
+                        if size as usize >= store.min_value_size {
+                            candidates.push(Candidate {
+                                db,
+                                key: entry.key().clone(),
+                                size,
+                                last_access: store.last_access(db, entry.key()),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        // Forget access times of deleted keys
+        store
+            .access
+            .retain(|(db, key), _| self.data[db].contains_key(key));
+
+        let max_memory = store.max_memory();
+        if resident > max_memory {
+            candidates.sort_by_key(|c| c.last_access);
+            for candidate in candidates {
+                if resident <= max_memory {
+                    break;
+                }
+                let Some(mut entry) = self.data[&candidate.db].get_mut(&candidate.key) else {
+                    continue;
+                };
+                // Touched while we were scanning, or already spilled
+                if store.last_access(candidate.db, &candidate.key) + MIN_IDLE_MS > store.now()
+                    || matches!(entry.value(), RedisValue::Spilled(_))
+                {
+                    continue;
+                }
+                let payload = rdb::dump_value(entry.value());
+                let (segment, offset) = store.append(&payload)?;
+                live.insert(segment);
+                let type_name = entry.value().type_name();
+                *entry.value_mut() = RedisValue::Spilled(SpilledValue {
+                    type_name,
+                    segment,
+                    offset,
+                    len: payload.len() as u64,
+                });
+                resident -= candidate.size;
+                spilled_keys += 1;
+                spilled_bytes += payload.len() as u64;
+                store.stats.spills.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        store
+            .stats
+            .resident_bytes
+            .store(resident, Ordering::Relaxed);
+        store
+            .stats
+            .spilled_keys
+            .store(spilled_keys, Ordering::Relaxed);
+        store
+            .stats
+            .spilled_bytes
+            .store(spilled_bytes, Ordering::Relaxed);
+        store.collect_garbage(&live);
+        Ok(())
+    }
+}
+
+/// Run the tiering cycle once per second
+pub async fn run_tiering(db: SharedDatabase) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        let db = db.clone();
+        match tokio::task::spawn_blocking(move || db.tiered_cycle()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => error!("Tiered storage cycle failed: {}", e),
+            Err(e) => error!("Tiered storage cycle panicked: {}", e),
+        }
+    }
+}
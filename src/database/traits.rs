@@ -1,26 +1,59 @@
 // Database operations traits for Rudis
 
 use crate::commands::Result;
+use crate::data_structures::bitmap::{BitOperation, BitUnit, BitfieldOp};
+use crate::data_structures::ListEnd;
+use crate::database::RedisValue;
 use bytes::Bytes;
+use std::time::SystemTime;
 
 // Traits
+//
+// Operations on a typed value return `CommandError::WrongType` when the key
+// holds another type, and treat a missing key as an empty value of their own
+// type. Only SET, DEL and the key commands ignore the type.
 pub trait StringOp {
-    fn get(&self, key: &Bytes) -> Option<Bytes>;
+    fn get(&self, key: &Bytes) -> Result<Option<Bytes>>;
     fn set(&self, key: &Bytes, value: Bytes);
+    fn set_with_expiry(&self, key: &Bytes, value: Bytes, at: SystemTime);
     fn del(&self, keys: &[Bytes]) -> usize;
     fn incr(&self, key: &Bytes) -> Result<i64>;
     fn decr(&self, key: &Bytes) -> Result<i64>;
     fn incr_by(&self, key: &Bytes, value: Bytes) -> Result<i64>;
     fn decr_by(&self, key: &Bytes, value: Bytes) -> Result<i64>;
-    fn append(&self, key: &Bytes, value: Bytes) -> usize;
-    fn str_len(&self, key: &Bytes) -> usize;
+    fn append(&self, key: &Bytes, value: Bytes) -> Result<usize>;
+    fn str_len(&self, key: &Bytes) -> Result<usize>;
+    fn get_range(&self, key: &Bytes, start: i64, end: i64) -> Result<Bytes>;
+    fn set_range(&self, key: &Bytes, offset: i64, value: Bytes) -> Result<usize>;
+    fn incr_by_float(&self, key: &Bytes, value: f64) -> Result<f64>;
+    fn get_del(&self, key: &Bytes) -> Result<Option<Bytes>>;
+    fn mget(&self, keys: &[Bytes]) -> Vec<Option<Bytes>>;
+    fn mset(&self, pairs: &[(Bytes, Bytes)]);
+    fn msetnx(&self, pairs: &[(Bytes, Bytes)]) -> bool;
+}
+
+// Bitmaps are strings; a missing key reads as an empty one
+pub trait BitmapOp {
+    fn setbit(&self, key: &Bytes, offset: u64, bit: u8) -> Result<u8>;
+    fn getbit(&self, key: &Bytes, offset: u64) -> Result<u8>;
+    fn bitcount(&self, key: &Bytes, range: Option<(i64, i64)>, unit: BitUnit) -> Result<u64>;
+    fn bitpos(
+        &self,
+        key: &Bytes,
+        bit: u8,
+        start: Option<i64>,
+        end: Option<i64>,
+        unit: BitUnit,
+    ) -> Result<i64>;
+    fn bitop(&self, op: BitOperation, dest: &Bytes, keys: &[Bytes]) -> Result<usize>;
+    fn bitfield(&self, key: &Bytes, ops: &[BitfieldOp]) -> Result<Vec<Option<i64>>>;
 }
 
 pub trait HashOp {
     fn hset(&self, hash: &Bytes, field: Bytes, value: Bytes) -> Result<i64>;
     fn hget(&self, hash: &Bytes, field: &Bytes) -> Result<Option<Bytes>>;
-    fn hdel(&self, hash: &Bytes, field: &Bytes) -> bool;
-    fn hdel_multiple(&self, hash: &Bytes, fields: &[Bytes]) -> usize;
+    fn hdel(&self, hash: &Bytes, field: &Bytes) -> Result<bool>;
+    fn hdel_multiple(&self, hash: &Bytes, fields: &[Bytes]) -> Result<usize>;
     fn hget_all(&self, hash: &Bytes) -> Result<Vec<Bytes>>;
     fn hkeys(&self, hash: &Bytes) -> Result<Vec<Bytes>>;
     fn hvals(&self, hash: &Bytes) -> Result<Vec<Bytes>>;
@@ -28,48 +61,100 @@ pub trait HashOp {
     fn hexists(&self, hash: &Bytes, field: &Bytes) -> Result<bool>;
     fn hincrby(&self, hash: &Bytes, field: &Bytes, value: i64) -> Result<i64>;
     fn hincrbyfloat(&self, hash: &Bytes, field: &Bytes, value: f64) -> Result<f64>;
+    fn hset_multiple(&self, hash: &Bytes, pairs: &[(Bytes, Bytes)]) -> Result<usize>;
+    fn hsetnx(&self, hash: &Bytes, field: Bytes, value: Bytes) -> Result<bool>;
+    fn hmget(&self, hash: &Bytes, fields: &[Bytes]) -> Result<Vec<Option<Bytes>>>;
+    fn hstrlen(&self, hash: &Bytes, field: &Bytes) -> Result<usize>;
+    fn hrandfield(&self, hash: &Bytes, count: i64) -> Result<Vec<(Bytes, Bytes)>>;
+    fn hscan(&self, hash: &Bytes, cursor: u64, count: usize) -> Result<(u64, Vec<(Bytes, Bytes)>)>;
 }
 
 pub trait ListOp {
-    fn lpush(&self, key: &Bytes, values: &[Bytes]) -> usize;
-    fn rpush(&self, key: &Bytes, values: &[Bytes]) -> usize;
-    fn lpop(&self, key: &Bytes) -> Option<Bytes>;
-    fn rpop(&self, key: &Bytes) -> Option<Bytes>;
-    fn llen(&self, key: &Bytes) -> usize;
-    fn lindex(&self, key: &Bytes, index: i64) -> Option<Bytes>;
+    fn lpush(&self, key: &Bytes, values: &[Bytes]) -> Result<usize>;
+    fn rpush(&self, key: &Bytes, values: &[Bytes]) -> Result<usize>;
+    fn lpop(&self, key: &Bytes) -> Result<Option<Bytes>>;
+    fn rpop(&self, key: &Bytes) -> Result<Option<Bytes>>;
+    fn llen(&self, key: &Bytes) -> Result<usize>;
+    fn lindex(&self, key: &Bytes, index: i64) -> Result<Option<Bytes>>;
     fn lrange(&self, key: &Bytes, start: i64, end: i64) -> Result<Vec<Bytes>>;
     fn ltrim(&self, key: &Bytes, start: i64, end: i64) -> Result<()>;
     fn lset(&self, key: &Bytes, index: i64, value: Bytes) -> Result<()>;
     fn linsert(&self, key: &Bytes, ord: &str, pivot: &Bytes, value: Bytes) -> Result<i64>;
+    fn lpushx(&self, key: &Bytes, values: &[Bytes]) -> Result<usize>;
+    fn rpushx(&self, key: &Bytes, values: &[Bytes]) -> Result<usize>;
+    fn pop_many(&self, key: &Bytes, end: ListEnd, count: usize) -> Result<Option<Vec<Bytes>>>;
+    fn lpos(
+        &self,
+        key: &Bytes,
+        element: &Bytes,
+        rank: i64,
+        count: usize,
+        maxlen: usize,
+    ) -> Result<Vec<usize>>;
+    fn lrem(&self, key: &Bytes, count: i64, element: &Bytes) -> Result<usize>;
+    fn lmove(
+        &self,
+        source: &Bytes,
+        destination: &Bytes,
+        from: ListEnd,
+        to: ListEnd,
+    ) -> Result<Option<Bytes>>;
+    fn lmpop(
+        &self,
+        keys: &[Bytes],
+        end: ListEnd,
+        count: usize,
+    ) -> Result<Option<(Bytes, Vec<Bytes>)>>;
 }
 
 pub trait SetOp {
-    fn sadd(&self, key: &Bytes, values: &[Bytes]) -> usize;
-    fn srem(&self, key: &Bytes, values: &[Bytes]) -> usize;
+    fn sadd(&self, key: &Bytes, values: &[Bytes]) -> Result<usize>;
+    fn srem(&self, key: &Bytes, values: &[Bytes]) -> Result<usize>;
     fn smembers(&self, key: &Bytes) -> Result<Vec<Bytes>>;
-    fn scard(&self, key: &Bytes) -> usize;
-    fn sismember(&self, key: &Bytes, member: &Bytes) -> bool;
+    fn scard(&self, key: &Bytes) -> Result<usize>;
+    fn sismember(&self, key: &Bytes, member: &Bytes) -> Result<bool>;
     fn sinter(&self, keys: &[Bytes]) -> Result<Vec<Bytes>>;
     fn sunion(&self, keys: &[Bytes]) -> Result<Vec<Bytes>>;
     fn sdiff(&self, keys: &[Bytes]) -> Result<Vec<Bytes>>;
+    fn sinterstore(&self, destination: &Bytes, keys: &[Bytes]) -> Result<usize>;
+    fn sunionstore(&self, destination: &Bytes, keys: &[Bytes]) -> Result<usize>;
+    fn sdiffstore(&self, destination: &Bytes, keys: &[Bytes]) -> Result<usize>;
+    fn sintercard(&self, keys: &[Bytes], limit: usize) -> Result<usize>;
+    fn spop(&self, key: &Bytes, count: usize) -> Result<Vec<Bytes>>;
+    fn srandmember(&self, key: &Bytes, count: i64) -> Result<Vec<Bytes>>;
+    fn smove(&self, source: &Bytes, destination: &Bytes, member: &Bytes) -> Result<bool>;
+    fn smismember(&self, key: &Bytes, members: &[Bytes]) -> Result<Vec<bool>>;
+    fn sscan(&self, key: &Bytes, cursor: u64, count: usize) -> Result<(u64, Vec<Bytes>)>;
 }
 
 pub trait SortedSetOp {
-    fn zadd(&self, key: &Bytes, pair: &[(f64, Bytes)]) -> usize;
-    fn zrem(&self, key: &Bytes, values: &[Bytes]) -> usize;
+    fn zadd(&self, key: &Bytes, pair: &[(f64, Bytes)]) -> Result<usize>;
+    fn zrem(&self, key: &Bytes, values: &[Bytes]) -> Result<usize>;
     fn zrange(&self, key: &Bytes, start: i64, stop: i64) -> Result<Vec<Bytes>>;
     fn zrange_by_score(&self, key: &Bytes, min: f64, max: f64) -> Result<Vec<Bytes>>;
-    fn zcard(&self, key: &Bytes) -> usize;
-    fn zscore(&self, key: &Bytes, member: &Bytes) -> Option<f64>;
-    fn zrank(&self, key: &Bytes, member: &Bytes) -> Option<usize>;
+    fn zcard(&self, key: &Bytes) -> Result<usize>;
+    fn zscore(&self, key: &Bytes, member: &Bytes) -> Result<Option<f64>>;
+    fn zrank(&self, key: &Bytes, member: &Bytes) -> Result<Option<usize>>;
 }
 
 pub trait KeyOp {
     fn exist(&self, keys: &[Bytes]) -> usize;
-    fn expire(&self, key: &Bytes, seconds: u64) -> Result<()>;
+    fn expire(&self, key: &Bytes, seconds: u64) -> Result<bool>;
+    fn pexpire(&self, key: &Bytes, millis: u64) -> Result<bool>;
+    fn pexpire_at(&self, key: &Bytes, at: SystemTime) -> bool;
+    fn persist(&self, key: &Bytes) -> bool;
     fn ttl(&self, key: &Bytes) -> i64;
+    fn pttl(&self, key: &Bytes) -> i64;
+    fn dump(&self, key: &Bytes) -> Option<Vec<u8>>;
+    fn restore(
+        &self,
+        key: &Bytes,
+        value: RedisValue,
+        expire_at: Option<SystemTime>,
+        replace: bool,
+    ) -> Result<()>;
     fn keys(&self, pattern: &Bytes) -> Result<Vec<Bytes>>;
     fn flush_all(&self) -> bool;
     fn flush_db(&self) -> bool;
     fn select(&self, db: u8);
-}
\ No newline at end of file
+}
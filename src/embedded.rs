@@ -0,0 +1,219 @@
+// In-process client for Rudis
+// Runs commands against a `SharedDatabase` without a network hop. Each client
+// has its own session, and commands go through the same parser and handlers
+// as the TCP path, so replies, errors and expiry behave identically.
+
+use crate::commands::reply::FromReply;
+use crate::commands::{Command, CommandError, Reply, Result};
+use crate::database::{Session, SharedDatabase};
+use crate::networking::resp::RespValue;
+use bytes::Bytes;
+use std::sync::Arc;
+
+pub struct Client {
+    db: SharedDatabase,
+    session: Arc<Session>,
+}
+
+impl Client {
+    /// A new client on database 0 of `db`
+    pub fn new(db: &SharedDatabase) -> Self {
+        Client {
+            db: db.clone(),
+            session: Session::new(),
+        }
+    }
+
+    /// The database this client has selected
+    pub fn db_index(&self) -> u8 {
+        self.session.db()
+    }
+
+    /// Run any command, given as its name followed by its arguments. Errors,
+    /// including unknown commands and wrong arity, come back as
+    /// `Reply::Error` exactly as a network client would see them.
+    pub async fn call(&self, args: &[&[u8]]) -> Reply {
+        self.execute(args.iter().map(|arg| Bytes::copy_from_slice(arg)))
+            .await
+    }
+
+    async fn execute(&self, args: impl IntoIterator<Item = Bytes>) -> Reply {
+        let frame = RespValue::Array(args.into_iter().map(RespValue::BulkString).collect());
+        match Command::try_parse(&frame) {
+            Ok(command) => self.session.scope(command.execute(&self.db)).await,
+            Err(e) => e.into(),
+        }
+    }
+
+    async fn query<T: FromReply>(&self, args: Vec<Bytes>) -> Result<T> {
+        match self.execute(args).await {
+            Reply::Error(message) => Err(CommandError::from_reply(message)),
+            reply => T::from_reply(reply),
+        }
+    }
+
+    pub async fn select(&self, index: u8) -> Result<()> {
+        self.query(vec![arg("SELECT"), arg(index.to_string())])
+            .await
+    }
+
+    pub async fn get(&self, key: impl AsRef<[u8]>) -> Result<Option<Bytes>> {
+        self.query(vec![arg("GET"), arg(key)]).await
+    }
+
+    pub async fn set(&self, key: impl AsRef<[u8]>, value: impl AsRef<[u8]>) -> Result<()> {
+        self.query(vec![arg("SET"), arg(key), arg(value)]).await
+    }
+
+    /// SET with an expiry in seconds
+    pub async fn set_ex(
+        &self,
+        key: impl AsRef<[u8]>,
+        value: impl AsRef<[u8]>,
+        seconds: u64,
+    ) -> Result<()> {
+        let args = vec![arg("SETEX"), arg(key), arg(seconds.to_string()), arg(value)];
+        self.query(args).await
+    }
+
+    pub async fn del<K: AsRef<[u8]>>(&self, keys: &[K]) -> Result<i64> {
+        self.query(with_args(vec![arg("DEL")], keys)).await
+    }
+
+    pub async fn exists(&self, key: impl AsRef<[u8]>) -> Result<bool> {
+        self.query(vec![arg("EXISTS"), arg(key)]).await
+    }
+
+    /// Set a TTL in seconds; false if the key does not exist
+    pub async fn expire(&self, key: impl AsRef<[u8]>, seconds: u64) -> Result<bool> {
+        self.query(vec![arg("EXPIRE"), arg(key), arg(seconds.to_string())])
+            .await
+    }
+
+    /// Remaining TTL in seconds: -1 without an expiry, -2 for a missing key
+    pub async fn ttl(&self, key: impl AsRef<[u8]>) -> Result<i64> {
+        self.query(vec![arg("TTL"), arg(key)]).await
+    }
+
+    pub async fn incr(&self, key: impl AsRef<[u8]>) -> Result<i64> {
+        self.query(vec![arg("INCR"), arg(key)]).await
+    }
+
+    pub async fn incr_by(&self, key: impl AsRef<[u8]>, delta: i64) -> Result<i64> {
+        self.query(vec![arg("INCRBY"), arg(key), arg(delta.to_string())])
+            .await
+    }
+
+    /// Set one hash field; true if the field is new
+    pub async fn hset(
+        &self,
+        key: impl AsRef<[u8]>,
+        field: impl AsRef<[u8]>,
+        value: impl AsRef<[u8]>,
+    ) -> Result<bool> {
+        self.query(vec![arg("HSET"), arg(key), arg(field), arg(value)])
+            .await
+    }
+
+    pub async fn hget(
+        &self,
+        key: impl AsRef<[u8]>,
+        field: impl AsRef<[u8]>,
+    ) -> Result<Option<Bytes>> {
+        self.query(vec![arg("HGET"), arg(key), arg(field)]).await
+    }
+
+    pub async fn hgetall(&self, key: impl AsRef<[u8]>) -> Result<Vec<(Bytes, Bytes)>> {
+        self.query(vec![arg("HGETALL"), arg(key)]).await
+    }
+
+    pub async fn hdel<F: AsRef<[u8]>>(&self, key: impl AsRef<[u8]>, fields: &[F]) -> Result<i64> {
+        self.query(with_args(vec![arg("HDEL"), arg(key)], fields))
+            .await
+    }
+
+    /// Push to the head of a list, returning its new length
+    pub async fn lpush<V: AsRef<[u8]>>(&self, key: impl AsRef<[u8]>, values: &[V]) -> Result<i64> {
+        self.query(with_args(vec![arg("LPUSH"), arg(key)], values))
+            .await
+    }
+
+    /// Push to the tail of a list, returning its new length
+    pub async fn rpush<V: AsRef<[u8]>>(&self, key: impl AsRef<[u8]>, values: &[V]) -> Result<i64> {
+        self.query(with_args(vec![arg("RPUSH"), arg(key)], values))
+            .await
+    }
+
+    pub async fn lpop(&self, key: impl AsRef<[u8]>) -> Result<Option<Bytes>> {
+        self.query(vec![arg("LPOP"), arg(key)]).await
+    }
+
+    pub async fn rpop(&self, key: impl AsRef<[u8]>) -> Result<Option<Bytes>> {
+        self.query(vec![arg("RPOP"), arg(key)]).await
+    }
+
+    pub async fn lrange(&self, key: impl AsRef<[u8]>, start: i64, stop: i64) -> Result<Vec<Bytes>> {
+        let args = vec![
+            arg("LRANGE"),
+            arg(key),
+            arg(start.to_string()),
+            arg(stop.to_string()),
+        ];
+        self.query(args).await
+    }
+
+    pub async fn sadd<M: AsRef<[u8]>>(&self, key: impl AsRef<[u8]>, members: &[M]) -> Result<i64> {
+        self.query(with_args(vec![arg("SADD"), arg(key)], members))
+            .await
+    }
+
+    pub async fn srem<M: AsRef<[u8]>>(&self, key: impl AsRef<[u8]>, members: &[M]) -> Result<i64> {
+        self.query(with_args(vec![arg("SREM"), arg(key)], members))
+            .await
+    }
+
+    pub async fn smembers(&self, key: impl AsRef<[u8]>) -> Result<Vec<Bytes>> {
+        self.query(vec![arg("SMEMBERS"), arg(key)]).await
+    }
+
+    /// Add scored members, returning how many were new
+    pub async fn zadd<M: AsRef<[u8]>>(
+        &self,
+        key: impl AsRef<[u8]>,
+        members: &[(f64, M)],
+    ) -> Result<i64> {
+        let mut args = vec![arg("ZADD"), arg(key)];
+        for (score, member) in members {
+            args.push(arg(score.to_string()));
+            args.push(arg(member));
+        }
+        self.query(args).await
+    }
+
+    pub async fn zrange(&self, key: impl AsRef<[u8]>, start: i64, stop: i64) -> Result<Vec<Bytes>> {
+        let args = vec![
+            arg("ZRANGE"),
+            arg(key),
+            arg(start.to_string()),
+            arg(stop.to_string()),
+        ];
+        self.query(args).await
+    }
+
+    pub async fn zscore(
+        &self,
+        key: impl AsRef<[u8]>,
+        member: impl AsRef<[u8]>,
+    ) -> Result<Option<f64>> {
+        self.query(vec![arg("ZSCORE"), arg(key), arg(member)]).await
+    }
+}
+
+fn arg(value: impl AsRef<[u8]>) -> Bytes {
+    Bytes::copy_from_slice(value.as_ref())
+}
+
+fn with_args<A: AsRef<[u8]>>(mut args: Vec<Bytes>, rest: &[A]) -> Vec<Bytes> {
+    args.extend(rest.iter().map(arg));
+    args
+}
@@ -30,6 +30,9 @@ pub enum AppError {
     #[error("utf8 error: {0}")]
     Utf8(#[from] std::string::FromUtf8Error),
 
+    #[error("Module error: {0}")]
+    Module(String),
+
     #[error("Invalid command")]
     InvalidCommand,
 
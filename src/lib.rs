@@ -1,10 +1,16 @@
 // Rudis - A Redis clone in Rust
 
+pub mod client;
 pub mod commands;
 pub mod config;
 pub mod data_structures;
 pub mod database;
+pub mod embedded;
 pub mod error;
+pub mod logging;
+pub mod modules;
 pub mod networking;
 pub mod persistence;
 pub mod server;
+
+pub use embedded::Client;
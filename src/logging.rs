@@ -0,0 +1,39 @@
+// Logging setup for Rudis
+// Maps Redis log levels onto tracing and lets CONFIG SET loglevel change the
+// level of the running subscriber.
+
+use std::sync::OnceLock;
+use tracing_subscriber::{
+    filter::LevelFilter, fmt, layer::SubscriberExt, reload, util::SubscriberInitExt, Registry,
+};
+
+static RELOAD: OnceLock<reload::Handle<LevelFilter, Registry>> = OnceLock::new();
+
+/// The tracing filter for a Redis `loglevel` value
+pub fn level_filter(loglevel: &str) -> LevelFilter {
+    match loglevel {
+        "debug" => LevelFilter::TRACE,
+        "verbose" => LevelFilter::DEBUG,
+        "warning" => LevelFilter::WARN,
+        "nothing" => LevelFilter::OFF,
+        _ => LevelFilter::INFO,
+    }
+}
+
+/// Install the global subscriber at `loglevel`. Later calls are ignored.
+pub fn init(loglevel: &str) {
+    let (filter, handle) = reload::Layer::new(level_filter(loglevel));
+    if RELOAD.set(handle).is_ok() {
+        tracing_subscriber::registry()
+            .with(filter)
+            .with(fmt::layer())
+            .init();
+    }
+}
+
+/// Change the level of the subscriber installed by `init`, if any
+pub fn set_level(loglevel: &str) {
+    if let Some(handle) = RELOAD.get() {
+        let _ = handle.modify(|filter| *filter = level_filter(loglevel));
+    }
+}
@@ -1,34 +1,83 @@
 use std::process;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
-mod commands;
-mod config;
-mod data_structures;
-mod database;
-mod error;
-mod networking;
-mod persistence;
-mod server;
+use rudis::{config::Config, error::Result, logging, server::Server};
 
-use crate::{config::Config, error::Result, server::Server};
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+fn usage() {
+    println!("Usage: rudis [/path/to/rudis.conf] [options]");
+    println!("       rudis --test-config [/path/to/rudis.conf] [options]");
+    println!("       rudis -v or --version");
+    println!("       rudis -h or --help");
+    println!();
+    println!("Any configuration directive can be passed as --name value,");
+    println!("overriding the value from the configuration file.");
+    println!();
+    println!("Examples:");
+    println!("       rudis /etc/rudis/rudis.conf");
+    println!("       rudis --port 7000 --bind 0.0.0.0 --dir /data");
+    println!("       rudis /etc/rudis/rudis.conf --save \"900 1\" --databases 32");
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt::init();
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("-v" | "--version") => {
+            println!("rudis v{}", VERSION);
+            return Ok(());
+        }
+        Some("-h" | "--help") => {
+            usage();
+            return Ok(());
+        }
+        _ => {}
+    }
+    let test_config = args.iter().any(|a| a == "--test-config");
+    args.retain(|a| a != "--test-config");
+
+    if test_config {
+        return match Config::from_args(&args) {
+            Ok((_, warnings)) => {
+                for warning in warnings {
+                    eprintln!("warning: {}", warning);
+                }
+                println!("Configuration OK");
+                Ok(())
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        };
+    }
 
-    info!("Starting Rudis - A Redis-like server in Rust");
+    // Load configuration from an optional config file and --name overrides
+    let (config, warnings) = match Config::from_args(&args) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
 
-    // Load configuration
-    let config = Config::default();
+    // Initialize tracing at the configured level
+    logging::init(&config.loglevel);
+    info!("Starting Rudis v{} - A Redis-like server in Rust", VERSION);
+    for warning in warnings {
+        warn!("{}", warning);
+    }
 
     // Initialize and start the server
     let server = Server::new(config).await?;
-    info!(
-        "Server listening on {}:{}",
-        server.config().host,
-        server.config().port
-    );
+    let addrs = server.local_addrs();
+    if addrs.is_empty() {
+        info!("TCP listening disabled (port 0)");
+    }
+    for addr in addrs {
+        info!("Server listening on {}", addr);
+    }
 
     if let Err(e) = server.run().await {
         error!("Server error: {}", e);
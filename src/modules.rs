@@ -0,0 +1,355 @@
+// Server-side extension modules for Rudis
+// A `Module` is registered once at startup, before the server accepts
+// connections. It can add commands, which are dispatched through the command
+// table like the builtin ones, and value types, which live in the keyspace as
+// `RedisValue::Module` and take part in RDB snapshots and memory accounting
+// through the `ModuleValue` hooks.
+
+use crate::commands::table::{CommandSpec, Flag};
+use crate::commands::{command_helper, table, Command, Reply, Result};
+use crate::database::Database;
+use crate::error::AppError;
+use crate::networking::resp::RespValue;
+use bytes::Bytes;
+use parking_lot::RwLock;
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+/// Characters allowed in a type name; the RDB module id packs each into 6 bits
+const TYPE_NAME_CHARSET: &[u8] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+/// Type names are exactly this long, as in Redis
+pub const TYPE_NAME_LEN: usize = 9;
+
+pub trait Module: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Reported by MODULE LIST and HELLO
+    fn version(&self) -> i64 {
+        1
+    }
+
+    fn commands(&self) -> Vec<ModuleCommand> {
+        Vec::new()
+    }
+
+    fn types(&self) -> Vec<Arc<dyn ModuleType>> {
+        Vec::new()
+    }
+}
+
+/// A value kind defined by a module; knows how to read its values back
+pub trait ModuleType: Send + Sync {
+    /// Nine characters from `A-Z a-z 0-9 - _`, unique across modules. This
+    /// is what TYPE reports and what RDB files record.
+    fn name(&self) -> &'static str;
+
+    /// Stored next to every value so `load` can read older layouts; below 1024
+    fn encoding_version(&self) -> u16 {
+        0
+    }
+
+    /// Rebuild a value from what `ModuleValue::save` wrote
+    fn load(
+        &self,
+        payload: &[u8],
+        encoding_version: u16,
+    ) -> crate::error::Result<Box<dyn ModuleValue>>;
+}
+
+/// A value stored under a key by a module
+pub trait ModuleValue: Any + Send + Sync + fmt::Debug {
+    /// Name of the `ModuleType` that loads this value
+    fn type_name(&self) -> &'static str;
+
+    /// Serialize the value for RDB snapshots, DUMP and the tiered store
+    fn save(&self) -> Vec<u8>;
+
+    /// Rough number of bytes the value occupies in memory
+    fn memory_usage(&self) -> usize;
+
+    /// Empty values are deleted like empty collections
+    fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+impl dyn ModuleValue {
+    pub fn downcast_ref<T: ModuleValue>(&self) -> Option<&T> {
+        (self as &dyn Any).downcast_ref()
+    }
+
+    pub fn downcast_mut<T: ModuleValue>(&mut self) -> Option<&mut T> {
+        (self as &mut dyn Any).downcast_mut()
+    }
+}
+
+/// Runs a module command with its arguments, the name excluded
+pub type Handler = Arc<dyn Fn(&Database, &[Bytes]) -> Result<Reply> + Send + Sync>;
+
+/// A command added by a module
+pub struct ModuleCommand {
+    name: String,
+    arity: i32,
+    flags: Vec<Flag>,
+    keys: (i32, i32, i32),
+    summary: String,
+    handler: Handler,
+}
+
+impl ModuleCommand {
+    /// `arity` counts the name, negative meaning "at least", as in the
+    /// command table
+    pub fn new<F>(name: &str, arity: i32, handler: F) -> Self
+    where
+        F: Fn(&Database, &[Bytes]) -> Result<Reply> + Send + Sync + 'static,
+    {
+        ModuleCommand {
+            name: name.to_ascii_lowercase(),
+            arity,
+            flags: Vec::new(),
+            keys: (0, 0, 0),
+            summary: String::new(),
+            handler: Arc::new(handler),
+        }
+    }
+
+    pub fn flags(mut self, flags: &[Flag]) -> Self {
+        self.flags = flags.to_vec();
+        self
+    }
+
+    /// Position of the first and last key (negative counts from the end) and
+    /// the step between keys
+    pub fn keys(mut self, first: i32, last: i32, step: i32) -> Self {
+        self.keys = (first, last, step);
+        self
+    }
+
+    pub fn summary(mut self, summary: &str) -> Self {
+        self.summary = summary.to_string();
+        self
+    }
+
+    /// A table entry for the command. Specs live as long as the process, like
+    /// the builtin table, so they are leaked.
+    fn into_spec(self) -> (&'static CommandSpec, Handler) {
+        let mut categories = vec![if self.flags.contains(&Flag::Write) {
+            "@write"
+        } else {
+            "@read"
+        }];
+        categories.push(if self.flags.contains(&Flag::Fast) {
+            "@fast"
+        } else {
+            "@slow"
+        });
+        let spec = CommandSpec {
+            name: Box::leak(self.name.into_boxed_str()),
+            arity: self.arity,
+            flags: Box::leak(self.flags.into_boxed_slice()),
+            first_key: self.keys.0,
+            last_key: self.keys.1,
+            step: self.keys.2,
+            categories: Box::leak(categories.into_boxed_slice()),
+            group: "module",
+            summary: Box::leak(self.summary.into_boxed_str()),
+            parse: parse_module_command,
+            key_fn: None,
+            subcommands: &[],
+        };
+        (Box::leak(Box::new(spec)), self.handler)
+    }
+}
+
+struct RegisteredCommand {
+    spec: &'static CommandSpec,
+    handler: Handler,
+    module: Arc<str>,
+}
+
+#[derive(Default)]
+struct Registry {
+    /// Name and version of each module, in registration order
+    modules: Vec<(Arc<str>, i64)>,
+    commands: HashMap<&'static str, RegisteredCommand>,
+    types: HashMap<&'static str, Arc<dyn ModuleType>>,
+}
+
+fn registry() -> &'static RwLock<Registry> {
+    static REGISTRY: OnceLock<RwLock<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(RwLock::default)
+}
+
+fn check_type_name(name: &str) -> std::result::Result<(), AppError> {
+    if name.len() != TYPE_NAME_LEN || !name.bytes().all(|b| TYPE_NAME_CHARSET.contains(&b)) {
+        return Err(AppError::Module(format!(
+            "type name '{}' must be {} characters from A-Z, a-z, 0-9, '-' and '_'",
+            name, TYPE_NAME_LEN
+        )));
+    }
+    Ok(())
+}
+
+/// Add a module's commands and types. Nothing is registered if any command
+/// or type name is already taken, by the server or another module.
+pub fn register(module: impl Module + 'static) -> crate::error::Result<()> {
+    let name: Arc<str> = module.name().into();
+    let commands = module.commands();
+    let types = module.types();
+
+    let mut registry = registry().write();
+    if registry.modules.iter().any(|(loaded, _)| *loaded == name) {
+        return Err(AppError::Module(format!(
+            "module '{}' is already loaded",
+            name
+        )));
+    }
+    let mut command_names = Vec::with_capacity(commands.len());
+    for command in &commands {
+        if command.name.is_empty() || command.name.contains('|') {
+            return Err(AppError::Module(format!(
+                "invalid command name '{}'",
+                command.name
+            )));
+        }
+        let builtin = table::COMMANDS.iter().any(|spec| spec.name == command.name);
+        if builtin
+            || registry.commands.contains_key(command.name.as_str())
+            || command_names.contains(&&command.name)
+        {
+            return Err(AppError::Module(format!(
+                "command '{}' already exists",
+                command.name
+            )));
+        }
+        command_names.push(&command.name);
+    }
+    let mut type_names = Vec::with_capacity(types.len());
+    for module_type in &types {
+        let type_name = module_type.name();
+        check_type_name(type_name)?;
+        if registry.types.contains_key(type_name) || type_names.contains(&type_name) {
+            return Err(AppError::Module(format!(
+                "type '{}' already exists",
+                type_name
+            )));
+        }
+        type_names.push(type_name);
+    }
+
+    for command in commands {
+        let (spec, handler) = command.into_spec();
+        registry.commands.insert(
+            spec.name,
+            RegisteredCommand {
+                spec,
+                handler,
+                module: Arc::clone(&name),
+            },
+        );
+    }
+    for module_type in types {
+        registry.types.insert(module_type.name(), module_type);
+    }
+    registry.modules.push((name, module.version()));
+    Ok(())
+}
+
+/// The table entry of a module command, by its lower case name
+pub fn lookup(name: &str) -> Option<&'static CommandSpec> {
+    registry()
+        .read()
+        .commands
+        .get(name)
+        .map(|command| command.spec)
+}
+
+/// Every module command, sorted by name
+pub fn specs() -> Vec<&'static CommandSpec> {
+    let mut specs: Vec<_> = registry()
+        .read()
+        .commands
+        .values()
+        .map(|command| command.spec)
+        .collect();
+    specs.sort_by_key(|spec| spec.name);
+    specs
+}
+
+/// Name of the module that added a command
+pub fn module_of(command: &str) -> Option<Arc<str>> {
+    registry()
+        .read()
+        .commands
+        .get(command)
+        .map(|command| Arc::clone(&command.module))
+}
+
+/// A registered value type, by the name its values report
+pub fn find_type(name: &str) -> Option<Arc<dyn ModuleType>> {
+    registry().read().types.get(name).cloned()
+}
+
+/// Loaded modules as MODULE LIST and HELLO report them
+pub fn list() -> Reply {
+    Reply::Array(
+        registry()
+            .read()
+            .modules
+            .iter()
+            .map(|(name, version)| {
+                Reply::Map(vec![
+                    (Reply::bulk("name"), Reply::bulk(name.to_string())),
+                    (Reply::bulk("ver"), Reply::Integer(*version)),
+                    (Reply::bulk("path"), Reply::bulk("")),
+                    (Reply::bulk("args"), Reply::Array(Vec::new())),
+                ])
+            })
+            .collect(),
+    )
+}
+
+/// Run a module command. The handler is cloned out first so commands can
+/// look up the registry themselves.
+pub fn call(name: &str, db: &Database, args: &[Bytes]) -> Reply {
+    let handler = match registry().read().commands.get(name) {
+        Some(command) => Arc::clone(&command.handler),
+        None => return Reply::error(format!("ERR unknown command '{}'", name)),
+    };
+    handler(db, args).unwrap_or_else(Reply::from)
+}
+
+/// Parser of every module command: the table has already checked the arity
+fn parse_module_command(elements: &[RespValue]) -> Option<Command> {
+    let args = command_helper::extract_bulk_strings(elements)?;
+    let (name, args) = args.split_first()?;
+    let spec = lookup(&String::from_utf8_lossy(name).to_ascii_lowercase())?;
+    Some(Command::Module(spec.name, args.to_vec()))
+}
+
+/// Pack a type name and encoding version into the 64-bit RDB module id
+pub(crate) fn type_id(name: &str, encoding_version: u16) -> u64 {
+    let id = name.bytes().fold(0u64, |id, b| {
+        let index = TYPE_NAME_CHARSET.iter().position(|&c| c == b).unwrap_or(0);
+        (id << 6) | index as u64
+    });
+    (id << 10) | (encoding_version as u64 & 0x3ff)
+}
+
+/// The type name and encoding version packed by `type_id`
+pub(crate) fn parse_type_id(id: u64) -> (String, u16) {
+    let encoding_version = (id & 0x3ff) as u16;
+    let mut packed = id >> 10;
+    let mut name = vec![0u8; TYPE_NAME_LEN];
+    for c in name.iter_mut().rev() {
+        *c = TYPE_NAME_CHARSET[(packed & 63) as usize];
+        packed >>= 6;
+    }
+    (
+        String::from_utf8(name).unwrap_or_default(),
+        encoding_version,
+    )
+}
@@ -1,33 +1,173 @@
 // Networking module for Rudis
 // Handles TCP connections and protocol parsing
 pub mod resp;
-use crate::commands::{command_helper::format_error, Command};
-use crate::database::SharedDatabase;
-use std::{io, net::SocketAddr};
+use crate::commands::{Command, CommandError, Reply};
+use crate::database::{Session, SharedDatabase};
+use bytes::BytesMut;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs},
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
 use tokio::{
     io::AsyncWriteExt,
-    net::{TcpListener, TcpStream},
+    net::{tcp::ReadHalf, TcpListener, TcpStream},
+    task::JoinSet,
 };
-use tracing::info;
+use tracing::{debug, info, warn};
+
+/// Pending connection queue length, as Redis' default `tcp-backlog`
+const TCP_BACKLOG: i32 = 511;
 
 pub struct Networking {
-    listener: TcpListener,
+    listeners: Vec<Arc<TcpListener>>,
+}
+
+/// Resolve one `bind` entry: `*` is every IPv4 address, `::*` every IPv6 one
+fn resolve(host: &str, port: u16) -> io::Result<SocketAddr> {
+    let ip = match host {
+        "*" => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        "::*" => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        host => match host.parse() {
+            Ok(ip) => ip,
+            Err(_) => {
+                return (host, port).to_socket_addrs()?.next().ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotFound, "no address found for host")
+                })
+            }
+        },
+    };
+    Ok(SocketAddr::new(ip, port))
+}
+
+/// Errors that let a `-` prefixed bind address be skipped, the same errnos
+/// as Redis: the address is not on this host or its family is unsupported
+pub fn is_unavailable_address(e: &io::Error) -> bool {
+    matches!(
+        e.raw_os_error(),
+        Some(libc::EADDRNOTAVAIL | libc::EAFNOSUPPORT | libc::EPROTONOSUPPORT)
+    )
+}
+
+/// Bind a listening socket. IPv6 sockets are IPv6 only so that `0.0.0.0`
+/// and `::` can be bound side by side on dual-stack hosts.
+fn bind_listener(addr: SocketAddr) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(TCP_BACKLOG)?;
+    TcpListener::from_std(socket.into())
 }
 
 impl Networking {
     pub async fn new(addr: &str) -> std::io::Result<Self> {
         let listener = TcpListener::bind(addr).await?;
-        Ok(Networking { listener })
+        Ok(Networking {
+            listeners: vec![Arc::new(listener)],
+        })
+    }
+
+    /// Listen on every address of a `bind` list. Addresses prefixed with `-`
+    /// are skipped when they are not available on this host; port 0 means
+    /// no TCP listener at all.
+    pub fn bind(addresses: &[String], port: u16) -> io::Result<Self> {
+        let mut listeners = Vec::new();
+        if port == 0 {
+            return Ok(Networking { listeners });
+        }
+        for address in addresses {
+            let (optional, host) = match address.strip_prefix('-') {
+                Some(host) => (true, host),
+                None => (false, address.as_str()),
+            };
+            match resolve(host, port).and_then(bind_listener) {
+                Ok(listener) => listeners.push(Arc::new(listener)),
+                Err(e) if optional && is_unavailable_address(&e) => {
+                    warn!("Skipping optional bind address {}: {}", host, e);
+                }
+                Err(e) => {
+                    return Err(io::Error::new(
+                        e.kind(),
+                        format!("could not listen on {}:{}: {}", host, port, e),
+                    ))
+                }
+            }
+        }
+        if listeners.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrNotAvailable,
+                "none of the bind addresses is available",
+            ));
+        }
+        Ok(Networking { listeners })
+    }
+
+    /// Address of the first listener
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        match self.listeners.first() {
+            Some(listener) => listener.local_addr(),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "TCP listening is disabled",
+            )),
+        }
+    }
+
+    pub fn local_addrs(&self) -> Vec<SocketAddr> {
+        self.listeners
+            .iter()
+            .filter_map(|l| l.local_addr().ok())
+            .collect()
     }
 
     pub async fn listen(&self, db: &SharedDatabase) -> tokio::io::Result<()> {
-        // TODO: Implement connection handling
         info!("Listening for connections...");
+        if self.listeners.is_empty() {
+            // TCP disabled: keep running for the background tasks
+            std::future::pending::<()>().await;
+        }
+        let mut accept_loops = JoinSet::new();
+        for listener in &self.listeners {
+            accept_loops.spawn(Self::accept_loop(Arc::clone(listener), db.clone()));
+        }
+        // Accept loops only end on error
+        match accept_loops.join_next().await {
+            Some(Ok(result)) => result,
+            Some(Err(e)) => Err(io::Error::other(e)),
+            None => Ok(()),
+        }
+    }
 
+    async fn accept_loop(listener: Arc<TcpListener>, db: SharedDatabase) -> io::Result<()> {
         loop {
-            let (stream, _addr) = self.listener.accept().await?;
+            let (mut stream, _addr) = listener.accept().await?;
+            let stats = db.stats();
+            stats.connections_received.fetch_add(1, Ordering::Relaxed);
+            let max_clients = db.config().max_connections;
+            if stats.connected_clients.load(Ordering::Relaxed) >= max_clients {
+                stats.rejected_connections.fetch_add(1, Ordering::Relaxed);
+                let reply = Reply::from(CommandError::MaxClientsReached);
+                let _ = stream
+                    .write_all(&resp::encode(&reply, resp::Protocol::default()))
+                    .await;
+                continue;
+            }
+            stats.connected_clients.fetch_add(1, Ordering::Relaxed);
             let db_ref = db.clone();
-            tokio::spawn(async move { Self::handle(stream, _addr, &db_ref).await });
+            tokio::spawn(async move {
+                let result = Self::handle(stream, _addr, &db_ref).await;
+                db_ref
+                    .stats()
+                    .connected_clients
+                    .fetch_sub(1, Ordering::Relaxed);
+                result
+            });
         }
     }
     pub async fn handle(
@@ -35,11 +175,13 @@ impl Networking {
         _addr: SocketAddr,
         db: &SharedDatabase,
     ) -> tokio::io::Result<()> {
-        use bytes::BytesMut;
         use tokio::io::AsyncReadExt;
 
         let (mut reader, mut writer) = stream.split();
         let mut buffer = BytesMut::with_capacity(4096);
+        // SELECT, the protocol HELLO chose and other per-client state live
+        // in the connection's session
+        let session = Session::new();
 
         loop {
             // Try to decode frames from the buffer
@@ -55,16 +197,28 @@ impl Networking {
                         // Advance the buffer by the number of bytes consumed
                         let _ = buffer.split_to(consumed);
 
-                        let response = match Command::parse(&frame) {
-                            Some(cmd) => {
-                                if cmd == Command::Quit {
-                                    return Ok(());
+                        let (reply, quit) = match Command::try_parse(&frame) {
+                            Ok(cmd) if cmd.is_blocking() => {
+                                // A blocked client that goes away stops
+                                // waiting, so no element is handed to it
+                                tokio::select! {
+                                    biased;
+                                    reply = session.scope(cmd.execute(db)) => (reply, false),
+                                    _ = Self::closed(&mut reader, &mut buffer) => return Ok(()),
                                 }
-                                cmd.execute(&db).await
                             }
-                            None => format_error(crate::commands::CommandError::UnknownCommand),
+                            Ok(cmd) => {
+                                let quit = cmd == Command::Quit;
+                                (session.scope(cmd.execute(db)).await, quit)
+                            }
+                            Err(e) => (e.into(), false),
                         };
-                        writer.write_all(&response).await?;
+                        writer
+                            .write_all(&resp::encode(&reply, session.protocol()))
+                            .await?;
+                        if quit {
+                            return Ok(());
+                        }
                     }
                     Ok(None) => {
                         // Incomplete frame, break inner loop to read more data
@@ -78,8 +232,25 @@ impl Networking {
                 }
             }
 
-            // Read more data into buffer
-            let n = reader.read_buf(&mut buffer).await?;
+            // Read more data into buffer, dropping clients idle for longer
+            // than the configured timeout
+            let timeout = db.config().timeout;
+            let n = if timeout > 0 {
+                match tokio::time::timeout(
+                    Duration::from_secs(timeout),
+                    reader.read_buf(&mut buffer),
+                )
+                .await
+                {
+                    Ok(read) => read?,
+                    Err(_) => {
+                        debug!("Closing idle client {}", _addr);
+                        break;
+                    }
+                }
+            } else {
+                reader.read_buf(&mut buffer).await?
+            };
             if n == 0 {
                 // Connection closed
                 break;
@@ -87,4 +258,16 @@ impl Networking {
         }
         Ok(())
     }
+
+    /// Wait for the client to close the connection. Commands it pipelines
+    /// meanwhile are kept in `buffer` for when the current one is done.
+    async fn closed(reader: &mut ReadHalf<'_>, buffer: &mut BytesMut) {
+        use tokio::io::AsyncReadExt;
+
+        while let Ok(n) = reader.read_buf(buffer).await {
+            if n == 0 {
+                return;
+            }
+        }
+    }
 }
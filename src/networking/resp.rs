@@ -1,3 +1,235 @@
+use crate::commands::reply::Reply;
+use bytes::{BufMut, Bytes, BytesMut};
 use redis_protocol::resp2::types::Frame;
+use std::io;
 
 pub type RespValue = Frame;
+
+/// Protocol version a connection speaks; replies are encoded for it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Protocol {
+    #[default]
+    Resp2,
+    Resp3,
+}
+
+/// Encode a reply for the wire. RESP2 has no maps, sets, doubles, nulls or
+/// pushes, so those are sent as the arrays and bulk strings Redis uses.
+pub fn encode(reply: &Reply, protocol: Protocol) -> Bytes {
+    let mut buf = BytesMut::new();
+    write(&mut buf, reply, protocol);
+    buf.freeze()
+}
+
+fn write_header(buf: &mut BytesMut, kind: u8, len: usize) {
+    buf.put_u8(kind);
+    buf.put_slice(len.to_string().as_bytes());
+    buf.put_slice(b"\r\n");
+}
+
+fn write_line(buf: &mut BytesMut, kind: u8, line: &str) {
+    buf.put_u8(kind);
+    buf.put_slice(line.as_bytes());
+    buf.put_slice(b"\r\n");
+}
+
+fn write_bulk(buf: &mut BytesMut, value: &[u8]) {
+    write_header(buf, b'$', value.len());
+    buf.put_slice(value);
+    buf.put_slice(b"\r\n");
+}
+
+/// Doubles as Redis prints them: shortest round-trip form, `inf`, `-inf`
+/// and `nan`
+fn format_double(value: f64) -> String {
+    if value.is_nan() {
+        "nan".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn write_aggregate<'a>(
+    buf: &mut BytesMut,
+    kind: u8,
+    items: impl ExactSizeIterator<Item = &'a Reply>,
+    protocol: Protocol,
+) {
+    write_header(buf, kind, items.len());
+    for item in items {
+        write(buf, item, protocol);
+    }
+}
+
+fn write(buf: &mut BytesMut, reply: &Reply, protocol: Protocol) {
+    let resp3 = protocol == Protocol::Resp3;
+    match reply {
+        Reply::Status(status) => write_line(buf, b'+', status),
+        Reply::Error(message) => write_line(buf, b'-', message),
+        Reply::Integer(value) => write_line(buf, b':', &value.to_string()),
+        Reply::Bulk(value) => write_bulk(buf, value),
+        Reply::Null | Reply::NullArray if resp3 => buf.put_slice(b"_\r\n"),
+        Reply::Null => buf.put_slice(b"$-1\r\n"),
+        Reply::NullArray => buf.put_slice(b"*-1\r\n"),
+        Reply::Array(items) => write_aggregate(buf, b'*', items.iter(), protocol),
+        Reply::Set(items) => {
+            write_aggregate(buf, if resp3 { b'~' } else { b'*' }, items.iter(), protocol)
+        }
+        Reply::Push(items) => {
+            write_aggregate(buf, if resp3 { b'>' } else { b'*' }, items.iter(), protocol)
+        }
+        Reply::Map(pairs) => {
+            if resp3 {
+                write_header(buf, b'%', pairs.len());
+            } else {
+                write_header(buf, b'*', pairs.len() * 2);
+            }
+            for (key, value) in pairs {
+                write(buf, key, protocol);
+                write(buf, value, protocol);
+            }
+        }
+        Reply::Pairs(pairs) if resp3 => {
+            write_header(buf, b'*', pairs.len());
+            for (first, second) in pairs {
+                write_header(buf, b'*', 2);
+                write(buf, first, protocol);
+                write(buf, second, protocol);
+            }
+        }
+        Reply::Pairs(pairs) => {
+            write_header(buf, b'*', pairs.len() * 2);
+            for (first, second) in pairs {
+                write(buf, first, protocol);
+                write(buf, second, protocol);
+            }
+        }
+        Reply::Double(value) if resp3 => write_line(buf, b',', &format_double(*value)),
+        Reply::Double(value) => write_bulk(buf, format_double(*value).as_bytes()),
+    }
+}
+
+/// Decode one reply from the start of `buf`, as sent by a RESP2 or RESP3
+/// server. Returns the reply and the bytes it used, or `None` while the reply
+/// is incomplete. RESP3 booleans become integers, attributes are skipped and
+/// verbatim strings lose their format prefix.
+pub fn decode(buf: &[u8]) -> io::Result<Option<(Reply, usize)>> {
+    let mut pos = 0;
+    Ok(read(buf, &mut pos)?.map(|reply| (reply, pos)))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Protocol error: {}", message),
+    )
+}
+
+fn read_line<'a>(buf: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+    let rest = &buf[*pos..];
+    let end = rest.windows(2).position(|w| w == b"\r\n")?;
+    *pos += end + 2;
+    Some(&rest[..end])
+}
+
+fn parse<T: std::str::FromStr>(line: &[u8]) -> io::Result<T> {
+    std::str::from_utf8(line)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| invalid("invalid number"))
+}
+
+/// Read `count` replies, or `count` key/value pairs for a map
+fn read_items(buf: &[u8], pos: &mut usize, count: usize) -> io::Result<Option<Vec<Reply>>> {
+    let mut items = Vec::with_capacity(count.min(1024));
+    for _ in 0..count {
+        match read(buf, pos)? {
+            Some(item) => items.push(item),
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(items))
+}
+
+fn read(buf: &[u8], pos: &mut usize) -> io::Result<Option<Reply>> {
+    let Some(&kind) = buf.get(*pos) else {
+        return Ok(None);
+    };
+    let start = *pos;
+    *pos += 1;
+    let Some(line) = read_line(buf, pos) else {
+        *pos = start;
+        return Ok(None);
+    };
+    let text = || String::from_utf8_lossy(line).into_owned();
+    let reply = match kind {
+        b'+' => Reply::Status(text().into()),
+        b'-' => Reply::Error(text()),
+        b':' => Reply::Integer(parse(line)?),
+        b'(' => Reply::Bulk(Bytes::copy_from_slice(line)),
+        b'#' => Reply::Integer((line == b"t") as i64),
+        b',' => Reply::Double(match line {
+            b"inf" => f64::INFINITY,
+            b"-inf" => f64::NEG_INFINITY,
+            line => parse(line)?,
+        }),
+        b'_' => Reply::Null,
+        b'$' | b'!' | b'=' => {
+            let len: i64 = parse(line)?;
+            if len < 0 {
+                return Ok(Some(Reply::Null));
+            }
+            let len = len as usize;
+            if buf.len() < *pos + len + 2 {
+                *pos = start;
+                return Ok(None);
+            }
+            let data = &buf[*pos..*pos + len];
+            *pos += len + 2;
+            match kind {
+                b'!' => Reply::Error(String::from_utf8_lossy(data).into_owned()),
+                b'=' => Reply::Bulk(Bytes::copy_from_slice(data.get(4..).unwrap_or_default())),
+                _ => Reply::Bulk(Bytes::copy_from_slice(data)),
+            }
+        }
+        b'*' | b'~' | b'>' | b'%' | b'|' => {
+            let count: i64 = parse(line)?;
+            if count < 0 {
+                return Ok(Some(Reply::NullArray));
+            }
+            let pairs = matches!(kind, b'%' | b'|');
+            let len = if pairs {
+                count as usize * 2
+            } else {
+                count as usize
+            };
+            let Some(mut items) = read_items(buf, pos, len)? else {
+                *pos = start;
+                return Ok(None);
+            };
+            match kind {
+                b'~' => Reply::Set(items),
+                b'>' => Reply::Push(items),
+                b'%' => {
+                    let mut map = Vec::with_capacity(items.len() / 2);
+                    while let (Some(value), Some(key)) = (items.pop(), items.pop()) {
+                        map.push((key, value));
+                    }
+                    map.reverse();
+                    Reply::Map(map)
+                }
+                // Attributes describe the reply that follows
+                b'|' => match read(buf, pos)? {
+                    Some(reply) => reply,
+                    None => {
+                        *pos = start;
+                        return Ok(None);
+                    }
+                },
+                _ => Reply::Array(items),
+            }
+        }
+        _ => return Err(invalid("unknown reply type")),
+    };
+    Ok(Some(reply))
+}
@@ -0,0 +1,308 @@
+// AOF file format for Rudis
+// Validates append-only files and multi-part AOF manifests
+
+use super::rdb;
+use crate::error::{AppError, Result};
+
+/// Outcome of scanning an AOF file
+#[derive(Debug, Default, PartialEq)]
+pub struct AofReport {
+    pub size: usize,
+    /// Byte offset up to which the file holds complete, valid commands
+    pub ok_up_to: usize,
+    /// Line number (1-based) matching `ok_up_to`
+    pub ok_up_to_line: usize,
+    pub commands: usize,
+    /// Length of the RDB preamble, if the file starts with one
+    pub rdb_preamble: Option<usize>,
+    pub error: Option<String>,
+}
+
+impl AofReport {
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Bytes that would be dropped by truncating to `ok_up_to`
+    pub fn diff(&self) -> usize {
+        self.size - self.ok_up_to
+    }
+}
+
+struct Scanner<'a> {
+    data: &'a [u8],
+    pos: usize,
+    line: usize,
+}
+
+impl<'a> Scanner<'a> {
+    /// Read up to the next CRLF, returning the line without the terminator
+    fn read_line(&mut self) -> std::result::Result<&'a [u8], String> {
+        let rest = &self.data[self.pos..];
+        match rest.windows(2).position(|w| w == b"\r\n") {
+            Some(end) => {
+                self.pos += end + 2;
+                self.line += 1;
+                Ok(&rest[..end])
+            }
+            None => Err("Unexpected EOF reading line".to_string()),
+        }
+    }
+
+    fn read_prefixed_number(&mut self, prefix: u8) -> std::result::Result<i64, String> {
+        let line = self.read_line()?;
+        if line.first() != Some(&prefix) {
+            return Err(format!(
+                "Expected prefix '{}', got: '{}'",
+                prefix as char,
+                String::from_utf8_lossy(line)
+            ));
+        }
+        std::str::from_utf8(&line[1..])
+            .ok()
+            .and_then(|n| n.parse::<i64>().ok())
+            .ok_or_else(|| format!("Invalid number: '{}'", String::from_utf8_lossy(line)))
+    }
+
+    fn read_command(&mut self) -> std::result::Result<Vec<&'a [u8]>, String> {
+        let argc = self.read_prefixed_number(b'*')?;
+        if argc < 1 {
+            return Err(format!("Invalid argument count {}", argc));
+        }
+        let mut argv = Vec::with_capacity(argc as usize);
+        for _ in 0..argc {
+            let len = self.read_prefixed_number(b'$')?;
+            if len < 0 {
+                return Err(format!("Invalid bulk length {}", len));
+            }
+            let len = len as usize;
+            if self.data.len() - self.pos < len + 2 {
+                return Err("Unexpected EOF reading bulk string".to_string());
+            }
+            let arg = &self.data[self.pos..self.pos + len];
+            if &self.data[self.pos + len..self.pos + len + 2] != b"\r\n" {
+                return Err("Bulk string is not terminated by CRLF".to_string());
+            }
+            self.pos += len + 2;
+            self.line += 1;
+            argv.push(arg);
+        }
+        Ok(argv)
+    }
+}
+
+/// Scan an AOF (optionally starting with an RDB preamble) and find the
+/// longest prefix made of complete commands. A `MULTI` without its `EXEC`
+/// is treated as corrupt from the `MULTI` onwards.
+pub fn check(data: &[u8]) -> AofReport {
+    let mut report = AofReport {
+        size: data.len(),
+        ok_up_to_line: 1,
+        ..Default::default()
+    };
+    let mut scanner = Scanner {
+        data,
+        pos: 0,
+        line: 1,
+    };
+
+    if data.starts_with(b"REDIS") {
+        match rdb::parse(data, |_| Ok(())) {
+            Ok(summary) => {
+                report.rdb_preamble = Some(summary.length);
+                scanner.pos = summary.length;
+                report.ok_up_to = summary.length;
+            }
+            Err(e) => {
+                report.error = Some(format!("Invalid RDB preamble: {}", e));
+                return report;
+            }
+        }
+    }
+
+    // Start of a MULTI block that has not seen its EXEC yet
+    let mut multi: Option<usize> = None;
+    while scanner.pos < data.len() {
+        let start = scanner.pos;
+        // Annotations such as "#TS:<unix time>" are allowed between commands
+        if data[scanner.pos] == b'#' {
+            if let Err(e) = scanner.read_line() {
+                report.error = Some(e);
+                break;
+            }
+        } else {
+            match scanner.read_command() {
+                Ok(argv) => {
+                    report.commands += 1;
+                    if argv[0].eq_ignore_ascii_case(b"MULTI") {
+                        if multi.is_some() {
+                            report.error = Some("Unexpected MULTI".to_string());
+                            break;
+                        }
+                        multi = Some(start);
+                    } else if argv[0].eq_ignore_ascii_case(b"EXEC") && multi.take().is_none() {
+                        report.error = Some("Unexpected EXEC".to_string());
+                        break;
+                    }
+                }
+                Err(e) => {
+                    report.error = Some(e);
+                    break;
+                }
+            }
+        }
+        if multi.is_none() {
+            report.ok_up_to = scanner.pos;
+            report.ok_up_to_line = scanner.line;
+        }
+    }
+
+    if report.error.is_none() && multi.is_some() {
+        report.error = Some("Reached EOF before reading EXEC for MULTI".to_string());
+    }
+    report
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AofFileType {
+    Base,
+    History,
+    Incr,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AofManifestEntry {
+    pub name: String,
+    pub seq: u64,
+    pub file_type: AofFileType,
+}
+
+/// A Redis 7 style multi-part AOF manifest
+#[derive(Debug, Default, PartialEq)]
+pub struct AofManifest {
+    pub entries: Vec<AofManifestEntry>,
+}
+
+/// Split a manifest line into tokens, honouring double quotes
+fn split_manifest_line(line: &str) -> Option<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let mut token = String::new();
+        if c == '"' {
+            chars.next();
+            loop {
+                match chars.next()? {
+                    '"' => break,
+                    '\\' => token.push(chars.next()?),
+                    other => token.push(other),
+                }
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+        }
+        tokens.push(token);
+    }
+    Some(tokens)
+}
+
+impl AofManifest {
+    /// Whether `data` looks like a manifest rather than an AOF
+    pub fn is_manifest(data: &[u8]) -> bool {
+        String::from_utf8_lossy(data)
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty() && !l.starts_with('#'))
+            .is_some_and(|l| l.starts_with("file "))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut manifest = AofManifest::default();
+        for (lineno, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |msg: &str| {
+                AppError::Serialization(format!(
+                    "Invalid AOF manifest at line {}: {}",
+                    lineno + 1,
+                    msg
+                ))
+            };
+            let tokens = split_manifest_line(line).ok_or_else(|| invalid("unbalanced quotes"))?;
+            if !tokens.len().is_multiple_of(2) {
+                return Err(invalid("expected key value pairs"));
+            }
+            let (mut name, mut seq, mut file_type) = (None, None, None);
+            for pair in tokens.chunks(2) {
+                match pair[0].as_str() {
+                    "file" => name = Some(pair[1].clone()),
+                    "seq" => {
+                        seq = Some(
+                            pair[1]
+                                .parse::<u64>()
+                                .map_err(|_| invalid("seq is not a number"))?,
+                        )
+                    }
+                    "type" => {
+                        file_type = Some(match pair[1].as_str() {
+                            "b" => AofFileType::Base,
+                            "h" => AofFileType::History,
+                            "i" => AofFileType::Incr,
+                            _ => return Err(invalid("unknown file type")),
+                        })
+                    }
+                    // Unknown keys are ignored, as newer versions may add some
+                    _ => {}
+                }
+            }
+            match (name, seq, file_type) {
+                (Some(name), Some(seq), Some(file_type)) => {
+                    if file_type == AofFileType::Base && manifest.base().is_some() {
+                        return Err(invalid("found duplicate base file information"));
+                    }
+                    manifest.entries.push(AofManifestEntry {
+                        name,
+                        seq,
+                        file_type,
+                    });
+                }
+                _ => return Err(invalid("missing file, seq or type")),
+            }
+        }
+        if manifest.base().is_none() && manifest.incrs().is_empty() {
+            return Err(AppError::Serialization(
+                "Invalid AOF manifest: no base or incr files".to_string(),
+            ));
+        }
+        Ok(manifest)
+    }
+
+    pub fn base(&self) -> Option<&AofManifestEntry> {
+        self.entries
+            .iter()
+            .find(|e| e.file_type == AofFileType::Base)
+    }
+
+    /// Incremental files in replay order
+    pub fn incrs(&self) -> Vec<&AofManifestEntry> {
+        let mut incrs: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|e| e.file_type == AofFileType::Incr)
+            .collect();
+        incrs.sort_by_key(|e| e.seq);
+        incrs
+    }
+}
@@ -0,0 +1,34 @@
+// CRC-64/Jones checksum, as used by Redis for RDB files and DUMP payloads
+// (reflected polynomial 0xad93d23594c935a9, zero init, no final xor)
+
+const POLY: u64 = 0x95ac_9329_ac4b_c9b5;
+
+const fn build_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u64;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ POLY
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+static TABLE: [u64; 256] = build_table();
+
+/// Continue a running checksum over `data`. Start with `crc = 0`.
+pub fn crc64(mut crc: u64, data: &[u8]) -> u64 {
+    for &byte in data {
+        crc = TABLE[((crc ^ byte as u64) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc
+}
@@ -0,0 +1,308 @@
+// JSON lines keyspace format for Rudis
+// One key per line: {"db":0,"key":"k","pttl":null,"type":"string","value":"v"}
+//
+// Byte strings are written as JSON strings when they are valid UTF-8 and as
+// {"base64":"..."} otherwise, so any keyspace survives a round trip.
+
+use crate::data_structures::{RedisHash, RedisList, RedisSet, RedisSortedSet, RedisString};
+use crate::database::traits::KeyOp;
+use crate::database::{Database, RedisValue, Session};
+use crate::error::{AppError, Result};
+use crate::modules;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum JsonBytes {
+    Text(String),
+    Binary { base64: String },
+}
+
+impl From<&Bytes> for JsonBytes {
+    fn from(bytes: &Bytes) -> Self {
+        match std::str::from_utf8(bytes) {
+            Ok(s) => JsonBytes::Text(s.to_string()),
+            Err(_) => JsonBytes::Binary {
+                base64: STANDARD.encode(bytes),
+            },
+        }
+    }
+}
+
+impl TryFrom<JsonBytes> for Bytes {
+    type Error = AppError;
+
+    fn try_from(value: JsonBytes) -> Result<Self> {
+        match value {
+            JsonBytes::Text(s) => Ok(Bytes::from(s)),
+            JsonBytes::Binary { base64 } => STANDARD
+                .decode(base64)
+                .map(Bytes::from)
+                .map_err(|e| AppError::Serialization(format!("invalid base64: {}", e))),
+        }
+    }
+}
+
+/// Sorted set scores; infinities have no JSON number form so they are
+/// written as the strings "inf" and "-inf"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum JsonScore {
+    Number(f64),
+    Text(String),
+}
+
+impl From<f64> for JsonScore {
+    fn from(score: f64) -> Self {
+        if score.is_finite() {
+            JsonScore::Number(score)
+        } else if score > 0.0 {
+            JsonScore::Text("inf".to_string())
+        } else {
+            JsonScore::Text("-inf".to_string())
+        }
+    }
+}
+
+impl TryFrom<JsonScore> for f64 {
+    type Error = AppError;
+
+    fn try_from(score: JsonScore) -> Result<Self> {
+        let score = match score {
+            JsonScore::Number(n) => n,
+            JsonScore::Text(s) => match s.to_lowercase().as_str() {
+                "inf" | "+inf" => f64::INFINITY,
+                "-inf" => f64::NEG_INFINITY,
+                _ => s
+                    .parse()
+                    .map_err(|_| AppError::Serialization(format!("invalid score '{}'", s)))?,
+            },
+        };
+        if score.is_nan() {
+            return Err(AppError::Serialization("score is NaN".to_string()));
+        }
+        Ok(score)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum JsonValue {
+    String(JsonBytes),
+    List(Vec<JsonBytes>),
+    Set(Vec<JsonBytes>),
+    Zset(Vec<(JsonBytes, JsonScore)>),
+    Hash(Vec<(JsonBytes, JsonBytes)>),
+    Module(JsonModuleValue),
+}
+
+/// A module value as its type's `save` hook wrote it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonModuleValue {
+    pub module_type: String,
+    pub encoding_version: u16,
+    pub payload: JsonBytes,
+}
+
+/// A single exported key
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonRecord {
+    pub db: u8,
+    pub key: JsonBytes,
+    /// Remaining time to live in milliseconds, `null` for persistent keys
+    pub pttl: Option<u64>,
+    #[serde(flatten)]
+    pub value: JsonValue,
+}
+
+impl JsonRecord {
+    /// Build a record; unordered collections are sorted so exports diff cleanly
+    pub fn new(db: u8, key: &Bytes, value: &RedisValue, pttl: Option<u64>) -> Self {
+        let value = match value {
+            RedisValue::String(s) => JsonValue::String((&s.get()).into()),
+            RedisValue::List(list) => JsonValue::List(list.iter().map(Into::into).collect()),
+            RedisValue::Set(set) => {
+                let mut members: Vec<&Bytes> = set.iter().collect();
+                members.sort();
+                JsonValue::Set(members.into_iter().map(Into::into).collect())
+            }
+            RedisValue::SortedSet(zset) => JsonValue::Zset(
+                zset.iter()
+                    .map(|(member, score)| (member.into(), score.into()))
+                    .collect(),
+            ),
+            RedisValue::Hash(hash) => {
+                let mut pairs: Vec<(&Bytes, &Bytes)> = hash.iter().collect();
+                pairs.sort();
+                JsonValue::Hash(
+                    pairs
+                        .into_iter()
+                        .map(|(field, value)| (field.into(), value.into()))
+                        .collect(),
+                )
+            }
+            RedisValue::Module(value) => JsonValue::Module(JsonModuleValue {
+                module_type: value.type_name().to_string(),
+                encoding_version: modules::find_type(value.type_name())
+                    .map_or(0, |module_type| module_type.encoding_version()),
+                payload: (&Bytes::from(value.save())).into(),
+            }),
+            RedisValue::Spilled(_) => {
+                unreachable!("spilled values are loaded before exporting")
+            }
+        };
+        JsonRecord {
+            db,
+            key: key.into(),
+            pttl,
+            value,
+        }
+    }
+
+    pub fn key_bytes(&self) -> Result<Bytes> {
+        self.key.clone().try_into()
+    }
+
+    pub fn to_redis_value(&self) -> Result<RedisValue> {
+        Ok(match self.value.clone() {
+            JsonValue::String(s) => RedisValue::String(RedisString::new(s.try_into()?)),
+            JsonValue::List(items) => {
+                let mut list = RedisList::new();
+                for item in items {
+                    list.rpush(item.try_into()?);
+                }
+                RedisValue::List(list)
+            }
+            JsonValue::Set(members) => {
+                let mut set = RedisSet::new();
+                for member in members {
+                    set.sadd(member.try_into()?);
+                }
+                RedisValue::Set(set)
+            }
+            JsonValue::Zset(members) => {
+                let mut zset = RedisSortedSet::new();
+                for (member, score) in members {
+                    zset.zadd(member.try_into()?, score.try_into()?);
+                }
+                RedisValue::SortedSet(zset)
+            }
+            JsonValue::Hash(pairs) => {
+                let mut hash = RedisHash::new();
+                for (field, value) in pairs {
+                    hash.hset(field.try_into()?, value.try_into()?);
+                }
+                RedisValue::Hash(hash)
+            }
+            JsonValue::Module(value) => {
+                let module_type = modules::find_type(&value.module_type).ok_or_else(|| {
+                    AppError::Serialization(format!("unknown module type '{}'", value.module_type))
+                })?;
+                let payload: Bytes = value.payload.try_into()?;
+                RedisValue::Module(module_type.load(&payload, value.encoding_version)?)
+            }
+        })
+    }
+}
+
+/// Write one database (or all of them when `index` is `None`) as JSON lines,
+/// keys sorted within each database. Returns the number of keys written.
+pub fn export(db: &Database, index: Option<u8>, out: &mut impl Write) -> Result<usize> {
+    let now = SystemTime::now();
+    let mut indexes: Vec<u8> = match index {
+        Some(i) if db.data.contains_key(&i) => vec![i],
+        Some(i) => {
+            return Err(AppError::Database(format!(
+                "DB index {} is out of range",
+                i
+            )))
+        }
+        None => db.data.keys().copied().collect(),
+    };
+    indexes.sort_unstable();
+
+    let mut written = 0;
+    for i in indexes {
+        let data = &db.data[&i];
+        let expires = &db.data_expiration_time[&i];
+        let mut keys: Vec<Bytes> = data.iter().map(|e| e.key().clone()).collect();
+        keys.sort();
+        for key in keys {
+            let pttl = match expires.get(&key).map(|e| *e.value()) {
+                Some(at) => match at.duration_since(now) {
+                    Ok(left) => Some(left.as_millis() as u64),
+                    // Already expired
+                    Err(_) => continue,
+                },
+                None => None,
+            };
+            let Some(entry) = data.get(&key) else {
+                continue;
+            };
+            let record =
+                db.with_loaded(entry.value(), |value| JsonRecord::new(i, &key, value, pttl))?;
+            drop(entry);
+            serde_json::to_writer(&mut *out, &record)?;
+            out.write_all(b"\n")?;
+            written += 1;
+        }
+    }
+    Ok(written)
+}
+
+/// Parse JSON lines, skipping blank lines. Errors carry the line number.
+pub fn parse(input: &str) -> Result<Vec<JsonRecord>> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(n, line)| {
+            serde_json::from_str::<JsonRecord>(line)
+                .map_err(|e| AppError::Serialization(format!("line {}: {}", n + 1, e)))
+        })
+        .collect()
+}
+
+/// Load records into `db`. Everything is validated before the first key is
+/// written, so a bad line or (without `replace`) an existing key leaves the
+/// database untouched. Returns the number of keys imported.
+pub fn import(db: &Database, records: Vec<JsonRecord>, replace: bool) -> Result<usize> {
+    let mut prepared = Vec::with_capacity(records.len());
+    for record in records {
+        if !db.data.contains_key(&record.db) {
+            return Err(AppError::Database(format!(
+                "DB index {} is out of range",
+                record.db
+            )));
+        }
+        let key = record.key_bytes()?;
+        if !replace && db.data[&record.db].contains_key(&key) {
+            return Err(AppError::Database(format!(
+                "key '{}' already exists in db {}",
+                String::from_utf8_lossy(&key),
+                record.db
+            )));
+        }
+        let expire_at = record
+            .pttl
+            .map(|ms| SystemTime::now() + Duration::from_millis(ms));
+        let value = record.to_redis_value()?;
+        // Empty collections cannot exist in the keyspace
+        if value.is_empty_collection() {
+            continue;
+        }
+        prepared.push((record.db, key, value, expire_at));
+    }
+
+    let count = prepared.len();
+    for (index, key, value, expire_at) in prepared {
+        // RESTORE's path wakes blocked clients and records the access for
+        // tiered storage; the key was checked against `replace` above
+        Session::with_db(index, || db.restore(&key, value, expire_at, true))?;
+    }
+    Ok(count)
+}
@@ -1,22 +1,196 @@
 // Persistence module for Rudis
-// Handles AOF and RDB persistence
+// Handles RDB snapshots, save-point scheduling and keyspace export
+
+pub mod aof;
+pub mod crc64;
+pub mod jsonl;
+pub mod rdb;
+
+use crate::{
+    config::{Config, SavePoint},
+    database::{Database, SharedDatabase},
+    error::Result,
+};
+use std::{
+    fs,
+    io::Write,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::{info, warn};
+
+/// After a failed background save, wait this long before trying again
+const BGSAVE_RETRY_DELAY: u64 = 5;
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Snapshot bookkeeping shared between command execution and the scheduler
+#[derive(Debug)]
+pub struct SaveState {
+    dirty: AtomicU64,
+    last_save: AtomicU64,
+    last_bgsave_try: AtomicU64,
+    bgsave_in_progress: AtomicBool,
+    last_bgsave_ok: AtomicBool,
+}
+
+impl Default for SaveState {
+    fn default() -> Self {
+        SaveState {
+            dirty: AtomicU64::new(0),
+            last_save: AtomicU64::new(unix_time()),
+            last_bgsave_try: AtomicU64::new(0),
+            bgsave_in_progress: AtomicBool::new(false),
+            last_bgsave_ok: AtomicBool::new(true),
+        }
+    }
+}
+
+impl SaveState {
+    /// Record `changes` writes since the last snapshot
+    pub fn add_dirty(&self, changes: u64) {
+        self.dirty.fetch_add(changes, Ordering::Relaxed);
+    }
+
+    /// Writes since the last successful snapshot (`rdb_changes_since_last_save`)
+    pub fn dirty(&self) -> u64 {
+        self.dirty.load(Ordering::Relaxed)
+    }
+
+    /// Unix time of the last successful snapshot (or of startup)
+    pub fn last_save(&self) -> u64 {
+        self.last_save.load(Ordering::Relaxed)
+    }
+
+    pub fn bgsave_in_progress(&self) -> bool {
+        self.bgsave_in_progress.load(Ordering::Relaxed)
+    }
+
+    pub fn last_bgsave_ok(&self) -> bool {
+        self.last_bgsave_ok.load(Ordering::Relaxed)
+    }
+
+    /// Mark a save as finished; `dirty_before` is the counter value when it
+    /// started, so writes that raced with the snapshot stay counted.
+    fn finish_save(&self, dirty_before: u64, ok: bool) {
+        if ok {
+            self.dirty.fetch_sub(dirty_before, Ordering::Relaxed);
+            self.last_save.store(unix_time(), Ordering::Relaxed);
+        }
+        self.last_bgsave_ok.store(ok, Ordering::Relaxed);
+    }
+
+    /// Whether any save point is satisfied right now
+    pub fn should_save(&self, save_points: &[SavePoint], now: u64) -> bool {
+        if self.bgsave_in_progress() {
+            return false;
+        }
+        // Don't hammer the disk after a failure
+        if !self.last_bgsave_ok()
+            && now.saturating_sub(self.last_bgsave_try.load(Ordering::Relaxed)) < BGSAVE_RETRY_DELAY
+        {
+            return false;
+        }
+        let dirty = self.dirty();
+        let elapsed = now.saturating_sub(self.last_save());
+        save_points
+            .iter()
+            .any(|sp| dirty >= sp.changes && elapsed >= sp.seconds)
+    }
+}
 
 pub struct Persistence {
-    // TODO: Add persistence fields
+    rdb_path: PathBuf,
 }
 
 impl Persistence {
-    pub fn new() -> Self {
-        Persistence {}
+    pub fn new(config: &Config) -> Self {
+        Persistence {
+            rdb_path: config.rdb_path(),
+        }
+    }
+
+    /// Write a snapshot of `db` to the RDB file (synchronously).
+    /// The file is written under a temporary name and renamed into place.
+    pub fn save(&self, db: &Database) -> Result<()> {
+        let dirty_before = db.save_state.dirty();
+        let result = self.write_snapshot(db);
+        db.save_state.finish_save(dirty_before, result.is_ok());
+        result
+    }
+
+    fn write_snapshot(&self, db: &Database) -> Result<()> {
+        let payload = rdb::dump(db)?;
+        let dir = self
+            .rdb_path
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("."));
+        let tmp_path = dir.join(format!("temp-{}.rdb", std::process::id()));
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(&payload)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &self.rdb_path)?;
+        Ok(())
+    }
+
+    /// Load the RDB file into `db` if it exists. Returns the number of keys loaded.
+    pub fn load(&self, db: &Database) -> Result<usize> {
+        match fs::read(&self.rdb_path) {
+            Ok(data) => rdb::load(db, &data),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e.into()),
+        }
     }
 
-    pub fn save(&self) {
-        // TODO: Implement save to disk
-        println!("Saving data...");
+    /// Snapshot in a blocking task so the connection tasks keep running
+    pub async fn bgsave(self: &Arc<Self>, db: &SharedDatabase) -> Result<()> {
+        let state = &db.save_state;
+        if state.bgsave_in_progress.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+        state.last_bgsave_try.store(unix_time(), Ordering::Relaxed);
+
+        let persistence = Arc::clone(self);
+        let db_ref = Arc::clone(db);
+        let result = tokio::task::spawn_blocking(move || persistence.save(&db_ref))
+            .await
+            .unwrap_or_else(|e| Err(crate::error::AppError::Other(e.to_string())));
+        state.bgsave_in_progress.store(false, Ordering::Release);
+        result
     }
 
-    pub fn load(&self) {
-        // TODO: Implement load from disk
-        println!("Loading data...");
+    /// Check the save points once a second and snapshot when one is met.
+    /// The points are read from the running configuration on every tick, so
+    /// CONFIG SET save takes effect immediately.
+    pub async fn run_scheduler(self: Arc<Self>, db: SharedDatabase) {
+        let mut ticker = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            ticker.tick().await;
+            let save_points = db.config().save.clone();
+            if !db.save_state.should_save(&save_points, unix_time()) {
+                continue;
+            }
+            let changes = db.save_state.dirty();
+            info!(
+                "{} changes since last save, saving to {}...",
+                changes,
+                self.rdb_path.display()
+            );
+            match self.bgsave(&db).await {
+                Ok(()) => info!("Background saving terminated with success"),
+                Err(e) => warn!("Background saving error: {}", e),
+            }
+        }
     }
 }
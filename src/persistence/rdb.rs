@@ -0,0 +1,670 @@
+// RDB snapshot format for Rudis
+// Encodes and decodes the Redis RDB file layout (version 9 object encodings)
+
+use super::crc64::crc64;
+use crate::data_structures::{RedisHash, RedisList, RedisSet, RedisSortedSet, RedisString};
+use crate::database::{Database, RedisValue};
+use crate::error::{AppError, Result};
+use crate::modules;
+use bytes::Bytes;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Version written into new snapshots
+pub const RDB_VERSION: u16 = 9;
+/// Highest version this reader accepts
+pub const RDB_MAX_VERSION: u16 = 12;
+
+// Special opcodes
+const RDB_OPCODE_AUX: u8 = 250;
+const RDB_OPCODE_RESIZEDB: u8 = 251;
+const RDB_OPCODE_EXPIRETIME_MS: u8 = 252;
+const RDB_OPCODE_EXPIRETIME: u8 = 253;
+const RDB_OPCODE_SELECTDB: u8 = 254;
+const RDB_OPCODE_EOF: u8 = 255;
+
+// Object types
+pub const RDB_TYPE_STRING: u8 = 0;
+pub const RDB_TYPE_LIST: u8 = 1;
+pub const RDB_TYPE_SET: u8 = 2;
+pub const RDB_TYPE_ZSET: u8 = 3;
+pub const RDB_TYPE_HASH: u8 = 4;
+pub const RDB_TYPE_ZSET_2: u8 = 5;
+pub const RDB_TYPE_MODULE_2: u8 = 7;
+
+// Module values are a series of opcodes; ours are a single string
+const RDB_MODULE_OPCODE_EOF: u64 = 0;
+const RDB_MODULE_OPCODE_STRING: u64 = 5;
+
+// Length prefixes
+const RDB_6BITLEN: u8 = 0;
+const RDB_14BITLEN: u8 = 1;
+const RDB_32BITLEN: u8 = 0x80;
+const RDB_64BITLEN: u8 = 0x81;
+const RDB_ENCVAL: u8 = 3;
+
+// Special string encodings (when the length prefix is RDB_ENCVAL)
+const RDB_ENC_INT8: u8 = 0;
+const RDB_ENC_INT16: u8 = 1;
+const RDB_ENC_INT32: u8 = 2;
+const RDB_ENC_LZF: u8 = 3;
+
+/// Longest string a snapshot may hold, Redis' default `proto-max-bulk-len`
+const MAX_STRING_LEN: usize = 512 * 1024 * 1024;
+/// Most bytes LZF can produce per input byte: a three byte back reference
+/// copies 264
+const LZF_MAX_EXPANSION: usize = 88;
+
+fn corrupt(msg: impl Into<String>) -> AppError {
+    AppError::Serialization(format!("RDB: {}", msg.into()))
+}
+
+/// The RDB type byte that `write_object` emits for a value
+pub fn object_type(value: &RedisValue) -> u8 {
+    match value {
+        RedisValue::String(_) => RDB_TYPE_STRING,
+        RedisValue::List(_) => RDB_TYPE_LIST,
+        RedisValue::Set(_) => RDB_TYPE_SET,
+        RedisValue::SortedSet(_) => RDB_TYPE_ZSET_2,
+        RedisValue::Hash(_) => RDB_TYPE_HASH,
+        RedisValue::Module(_) => RDB_TYPE_MODULE_2,
+        RedisValue::Spilled(_) => unreachable!("spilled values are loaded before serializing"),
+    }
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Default)]
+pub struct RdbWriter {
+    buf: Vec<u8>,
+}
+
+impl RdbWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write_header(&mut self) {
+        self.buf
+            .extend_from_slice(format!("REDIS{:04}", RDB_VERSION).as_bytes());
+    }
+
+    pub fn write_aux(&mut self, key: &[u8], value: &[u8]) {
+        self.buf.push(RDB_OPCODE_AUX);
+        self.write_string(key);
+        self.write_string(value);
+    }
+
+    pub fn write_select_db(&mut self, db: u64, db_size: u64, expires_size: u64) {
+        self.buf.push(RDB_OPCODE_SELECTDB);
+        self.write_length(db);
+        self.buf.push(RDB_OPCODE_RESIZEDB);
+        self.write_length(db_size);
+        self.write_length(expires_size);
+    }
+
+    pub fn write_entry(&mut self, key: &[u8], value: &RedisValue, expire_at_ms: Option<u64>) {
+        if let Some(ms) = expire_at_ms {
+            self.buf.push(RDB_OPCODE_EXPIRETIME_MS);
+            self.buf.extend_from_slice(&ms.to_le_bytes());
+        }
+        self.buf.push(object_type(value));
+        self.write_string(key);
+        self.write_object(value);
+    }
+
+    /// Terminate the file with the EOF opcode and its CRC64 and return the bytes
+    pub fn finish(mut self) -> Vec<u8> {
+        self.buf.push(RDB_OPCODE_EOF);
+        let checksum = crc64(0, &self.buf);
+        self.buf.extend_from_slice(&checksum.to_le_bytes());
+        self.buf
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.buf
+    }
+
+    fn write_length(&mut self, len: u64) {
+        if len < (1 << 6) {
+            self.buf.push((len as u8) | (RDB_6BITLEN << 6));
+        } else if len < (1 << 14) {
+            self.buf.push(((len >> 8) as u8) | (RDB_14BITLEN << 6));
+            self.buf.push(len as u8);
+        } else if len <= u32::MAX as u64 {
+            self.buf.push(RDB_32BITLEN);
+            self.buf.extend_from_slice(&(len as u32).to_be_bytes());
+        } else {
+            self.buf.push(RDB_64BITLEN);
+            self.buf.extend_from_slice(&len.to_be_bytes());
+        }
+    }
+
+    /// Write a string, using the compact integer encodings when the bytes are
+    /// the canonical representation of a 32 bit integer (like Redis does).
+    pub fn write_string(&mut self, s: &[u8]) {
+        if s.len() <= 11 {
+            if let Some(n) = std::str::from_utf8(s)
+                .ok()
+                .and_then(|v| v.parse::<i64>().ok())
+                .filter(|n| n.to_string().as_bytes() == s)
+            {
+                if let Ok(n) = i8::try_from(n) {
+                    self.buf.push((RDB_ENCVAL << 6) | RDB_ENC_INT8);
+                    self.buf.push(n as u8);
+                    return;
+                } else if let Ok(n) = i16::try_from(n) {
+                    self.buf.push((RDB_ENCVAL << 6) | RDB_ENC_INT16);
+                    self.buf.extend_from_slice(&n.to_le_bytes());
+                    return;
+                } else if let Ok(n) = i32::try_from(n) {
+                    self.buf.push((RDB_ENCVAL << 6) | RDB_ENC_INT32);
+                    self.buf.extend_from_slice(&n.to_le_bytes());
+                    return;
+                }
+            }
+        }
+        self.write_length(s.len() as u64);
+        self.buf.extend_from_slice(s);
+    }
+
+    pub fn write_object(&mut self, value: &RedisValue) {
+        match value {
+            RedisValue::String(s) => self.write_string(&s.get()),
+            RedisValue::List(list) => {
+                self.write_length(list.len() as u64);
+                for item in list.iter() {
+                    self.write_string(item);
+                }
+            }
+            RedisValue::Set(set) => {
+                self.write_length(set.scard() as u64);
+                for member in set.iter() {
+                    self.write_string(member);
+                }
+            }
+            RedisValue::SortedSet(zset) => {
+                self.write_length(zset.zcard() as u64);
+                // Redis stores ZSET_2 members from the highest score down
+                let members: Vec<_> = zset.iter().collect();
+                for (member, score) in members.into_iter().rev() {
+                    self.write_string(member);
+                    self.buf.extend_from_slice(&score.to_le_bytes());
+                }
+            }
+            RedisValue::Hash(hash) => {
+                self.write_length(hash.len() as u64);
+                for (field, value) in hash.iter() {
+                    self.write_string(field);
+                    self.write_string(value);
+                }
+            }
+            RedisValue::Module(value) => {
+                // The module id packs the type name and encoding version
+                let encoding_version = modules::find_type(value.type_name())
+                    .map_or(0, |module_type| module_type.encoding_version());
+                self.write_length(modules::type_id(value.type_name(), encoding_version));
+                self.write_length(RDB_MODULE_OPCODE_STRING);
+                self.write_string(&value.save());
+                self.write_length(RDB_MODULE_OPCODE_EOF);
+            }
+            RedisValue::Spilled(_) => {
+                unreachable!("spilled values are loaded before serializing")
+            }
+        }
+    }
+}
+
+/// Serialize a single value in the DUMP payload format: the RDB object, the
+/// RDB version (2 bytes LE) and a CRC64 of everything before it (8 bytes LE)
+pub fn dump_value(value: &RedisValue) -> Vec<u8> {
+    let mut writer = RdbWriter::new();
+    writer.buf.push(object_type(value));
+    writer.write_object(value);
+    let mut buf = writer.into_inner();
+    buf.extend_from_slice(&RDB_VERSION.to_le_bytes());
+    let checksum = crc64(0, &buf);
+    buf.extend_from_slice(&checksum.to_le_bytes());
+    buf
+}
+
+/// Whether a DUMP payload carries a version we understand and a valid CRC64
+pub fn check_dump_payload(payload: &[u8]) -> bool {
+    if payload.len() < 10 {
+        return false;
+    }
+    let footer = &payload[payload.len() - 10..];
+    let version = u16::from_le_bytes([footer[0], footer[1]]);
+    let stored = u64::from_le_bytes(footer[2..].try_into().unwrap());
+    version <= RDB_MAX_VERSION && crc64(0, &payload[..payload.len() - 8]) == stored
+}
+
+/// Decode a DUMP payload produced by `dump_value` (or by Redis, as long as
+/// the object uses one of the encodings above)
+pub fn restore_value(payload: &[u8]) -> Result<RedisValue> {
+    if !check_dump_payload(payload) {
+        return Err(corrupt("DUMP payload version or checksum are wrong"));
+    }
+    let body = &payload[..payload.len() - 10];
+    let mut reader = RdbReader::new(body);
+    let rdb_type = reader.read_u8()?;
+    let value = reader.read_object(rdb_type)?;
+    if reader.position() != body.len() {
+        return Err(corrupt("trailing bytes after DUMP object"));
+    }
+    Ok(value)
+}
+
+/// A key read back from an RDB file
+#[derive(Debug)]
+pub struct RdbEntry {
+    pub db: u64,
+    pub key: Bytes,
+    pub value: RedisValue,
+    pub expire_at_ms: Option<u64>,
+}
+
+/// File level information gathered while parsing
+#[derive(Debug, Default)]
+pub struct RdbSummary {
+    pub version: u16,
+    pub aux: Vec<(Bytes, Bytes)>,
+    /// `None` when the file was written with checksums disabled
+    pub checksum: Option<u64>,
+    /// Number of bytes the RDB occupies, including the trailing checksum
+    pub length: usize,
+}
+
+pub struct RdbReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> RdbReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        RdbReader { data, pos: 0 }
+    }
+
+    /// Offset of the next unread byte
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8> {
+        let byte = *self
+            .data
+            .get(self.pos)
+            .ok_or_else(|| corrupt(format!("unexpected end of file at offset {}", self.pos)))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn read_exact(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.data.len() - self.pos < n {
+            return Err(corrupt(format!(
+                "unexpected end of file at offset {} (needed {} bytes)",
+                self.pos, n
+            )));
+        }
+        let slice = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(slice)
+    }
+
+    fn read_u64_le(&mut self) -> Result<u64> {
+        let bytes = self.read_exact(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// Returns the decoded length and whether it is a special encoding marker
+    fn read_length_with_encoding(&mut self) -> Result<(u64, bool)> {
+        let first = self.read_u8()?;
+        match first >> 6 {
+            RDB_6BITLEN => Ok(((first & 0x3f) as u64, false)),
+            RDB_14BITLEN => {
+                let next = self.read_u8()?;
+                Ok(((((first & 0x3f) as u64) << 8) | next as u64, false))
+            }
+            RDB_ENCVAL => Ok(((first & 0x3f) as u64, true)),
+            _ => match first {
+                RDB_32BITLEN => {
+                    let bytes = self.read_exact(4)?;
+                    Ok((u32::from_be_bytes(bytes.try_into().unwrap()) as u64, false))
+                }
+                RDB_64BITLEN => {
+                    let bytes = self.read_exact(8)?;
+                    Ok((u64::from_be_bytes(bytes.try_into().unwrap()), false))
+                }
+                _ => Err(corrupt(format!("unknown length encoding {:#04x}", first))),
+            },
+        }
+    }
+
+    pub fn read_length(&mut self) -> Result<u64> {
+        match self.read_length_with_encoding()? {
+            (len, false) => Ok(len),
+            (_, true) => Err(corrupt(
+                "unexpected encoded value where a length was expected",
+            )),
+        }
+    }
+
+    fn read_count(&mut self) -> Result<usize> {
+        let len = self.read_length()?;
+        // Every element takes at least one byte, anything larger is corrupt
+        if len > (self.data.len() - self.pos) as u64 {
+            return Err(corrupt(format!("length {} exceeds remaining input", len)));
+        }
+        Ok(len as usize)
+    }
+
+    pub fn read_string(&mut self) -> Result<Bytes> {
+        let (len, encoded) = self.read_length_with_encoding()?;
+        if !encoded {
+            let len = usize::try_from(len).map_err(|_| corrupt("string too long"))?;
+            return Ok(Bytes::copy_from_slice(self.read_exact(len)?));
+        }
+        match len as u8 {
+            RDB_ENC_INT8 => Ok(Bytes::from((self.read_u8()? as i8).to_string())),
+            RDB_ENC_INT16 => {
+                let bytes = self.read_exact(2)?;
+                Ok(Bytes::from(
+                    i16::from_le_bytes(bytes.try_into().unwrap()).to_string(),
+                ))
+            }
+            RDB_ENC_INT32 => {
+                let bytes = self.read_exact(4)?;
+                Ok(Bytes::from(
+                    i32::from_le_bytes(bytes.try_into().unwrap()).to_string(),
+                ))
+            }
+            RDB_ENC_LZF => {
+                let compressed_len = self.read_count()?;
+                let len = self.read_length()?;
+                // Refuse lengths the input cannot produce before decoding
+                if len > compressed_len.saturating_mul(LZF_MAX_EXPANSION) as u64
+                    || len > MAX_STRING_LEN as u64
+                {
+                    return Err(corrupt(format!("invalid LZF length {}", len)));
+                }
+                let compressed = self.read_exact(compressed_len)?;
+                Ok(Bytes::from(lzf_decompress(compressed, len as usize)?))
+            }
+            other => Err(corrupt(format!("unknown string encoding {}", other))),
+        }
+    }
+
+    /// Old style ZSET scores: a length prefixed ASCII double
+    fn read_string_double(&mut self) -> Result<f64> {
+        match self.read_u8()? {
+            253 => Ok(f64::NAN),
+            254 => Ok(f64::INFINITY),
+            255 => Ok(f64::NEG_INFINITY),
+            len => {
+                let bytes = self.read_exact(len as usize)?;
+                std::str::from_utf8(bytes)
+                    .ok()
+                    .and_then(|s| s.parse::<f64>().ok())
+                    .ok_or_else(|| corrupt("invalid double value"))
+            }
+        }
+    }
+
+    fn read_binary_double(&mut self) -> Result<f64> {
+        Ok(f64::from_bits(self.read_u64_le()?))
+    }
+
+    pub fn read_object(&mut self, rdb_type: u8) -> Result<RedisValue> {
+        match rdb_type {
+            RDB_TYPE_STRING => Ok(RedisValue::String(RedisString::new(self.read_string()?))),
+            RDB_TYPE_LIST => {
+                let mut list = RedisList::new();
+                for _ in 0..self.read_count()? {
+                    list.rpush(self.read_string()?);
+                }
+                Ok(RedisValue::List(list))
+            }
+            RDB_TYPE_SET => {
+                let mut set = RedisSet::new();
+                for _ in 0..self.read_count()? {
+                    set.sadd(self.read_string()?);
+                }
+                Ok(RedisValue::Set(set))
+            }
+            RDB_TYPE_ZSET | RDB_TYPE_ZSET_2 => {
+                let mut zset = RedisSortedSet::new();
+                for _ in 0..self.read_count()? {
+                    let member = self.read_string()?;
+                    let score = if rdb_type == RDB_TYPE_ZSET {
+                        self.read_string_double()?
+                    } else {
+                        self.read_binary_double()?
+                    };
+                    if score.is_nan() {
+                        return Err(corrupt("zset score is NaN"));
+                    }
+                    zset.zadd(member, score);
+                }
+                Ok(RedisValue::SortedSet(zset))
+            }
+            RDB_TYPE_HASH => {
+                let mut hash = RedisHash::new();
+                for _ in 0..self.read_count()? {
+                    let field = self.read_string()?;
+                    let value = self.read_string()?;
+                    hash.hset(field, value);
+                }
+                Ok(RedisValue::Hash(hash))
+            }
+            RDB_TYPE_MODULE_2 => {
+                let (name, encoding_version) = modules::parse_type_id(self.read_length()?);
+                let module_type = modules::find_type(&name)
+                    .ok_or_else(|| corrupt(format!("unknown module type '{}'", name)))?;
+                if self.read_length()? != RDB_MODULE_OPCODE_STRING {
+                    return Err(corrupt(format!("unsupported '{}' value layout", name)));
+                }
+                let payload = self.read_string()?;
+                if self.read_length()? != RDB_MODULE_OPCODE_EOF {
+                    return Err(corrupt(format!("unsupported '{}' value layout", name)));
+                }
+                let value = module_type
+                    .load(&payload, encoding_version)
+                    .map_err(|e| corrupt(format!("cannot load '{}' value: {}", name, e)))?;
+                Ok(RedisValue::Module(value))
+            }
+            other => Err(corrupt(format!("unsupported object type {}", other))),
+        }
+    }
+}
+
+/// The buffer grows with the output rather than trusting `expected_len`, and
+/// decoding stops as soon as it would be exceeded
+fn lzf_decompress(input: &[u8], expected_len: usize) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < input.len() {
+        if out.len() > expected_len {
+            return Err(corrupt("LZF payload length mismatch"));
+        }
+        let ctrl = input[i] as usize;
+        i += 1;
+        if ctrl < 32 {
+            // Literal run of ctrl + 1 bytes
+            let len = ctrl + 1;
+            let literal = input
+                .get(i..i + len)
+                .ok_or_else(|| corrupt("truncated LZF literal"))?;
+            out.extend_from_slice(literal);
+            i += len;
+        } else {
+            // Back reference
+            let mut len = ctrl >> 5;
+            if len == 7 {
+                len += *input
+                    .get(i)
+                    .ok_or_else(|| corrupt("truncated LZF reference"))?
+                    as usize;
+                i += 1;
+            }
+            let low = *input
+                .get(i)
+                .ok_or_else(|| corrupt("truncated LZF reference"))? as usize;
+            i += 1;
+            let offset = ((ctrl & 0x1f) << 8) + low + 1;
+            if offset > out.len() {
+                return Err(corrupt("invalid LZF back reference"));
+            }
+            let start = out.len() - offset;
+            for k in 0..len + 2 {
+                let byte = out[start + k];
+                out.push(byte);
+            }
+        }
+    }
+    if out.len() != expected_len {
+        return Err(corrupt("LZF payload length mismatch"));
+    }
+    Ok(out)
+}
+
+/// Parse a complete RDB file, handing every key to `on_entry`.
+/// The trailing CRC64 is verified unless the file has checksums disabled.
+pub fn parse(data: &[u8], mut on_entry: impl FnMut(RdbEntry) -> Result<()>) -> Result<RdbSummary> {
+    let mut reader = RdbReader::new(data);
+    let header = reader.read_exact(9)?;
+    if &header[..5] != b"REDIS" {
+        return Err(corrupt("wrong signature, not an RDB file"));
+    }
+    let version = std::str::from_utf8(&header[5..])
+        .ok()
+        .and_then(|v| v.parse::<u16>().ok())
+        .ok_or_else(|| corrupt("invalid version number"))?;
+    if version == 0 || version > RDB_MAX_VERSION {
+        return Err(corrupt(format!(
+            "can't handle RDB format version {}",
+            version
+        )));
+    }
+
+    let mut summary = RdbSummary {
+        version,
+        ..Default::default()
+    };
+    let mut db = 0;
+    let mut expire_at_ms = None;
+
+    loop {
+        let opcode = reader.read_u8()?;
+        match opcode {
+            RDB_OPCODE_EXPIRETIME_MS => {
+                expire_at_ms = Some(reader.read_u64_le()?);
+            }
+            RDB_OPCODE_EXPIRETIME => {
+                let bytes = reader.read_exact(4)?;
+                let secs = u32::from_le_bytes(bytes.try_into().unwrap());
+                expire_at_ms = Some(secs as u64 * 1000);
+            }
+            RDB_OPCODE_SELECTDB => {
+                db = reader.read_length()?;
+            }
+            RDB_OPCODE_RESIZEDB => {
+                reader.read_length()?;
+                reader.read_length()?;
+            }
+            RDB_OPCODE_AUX => {
+                let key = reader.read_string()?;
+                let value = reader.read_string()?;
+                summary.aux.push((key, value));
+            }
+            RDB_OPCODE_EOF => {
+                if version >= 5 {
+                    let body_len = reader.position();
+                    let stored = reader.read_u64_le()?;
+                    if stored != 0 {
+                        let computed = crc64(0, &data[..body_len]);
+                        if computed != stored {
+                            return Err(corrupt(format!(
+                                "checksum mismatch (stored {:016x}, computed {:016x})",
+                                stored, computed
+                            )));
+                        }
+                        summary.checksum = Some(stored);
+                    }
+                }
+                summary.length = reader.position();
+                return Ok(summary);
+            }
+            rdb_type => {
+                let key = reader.read_string()?;
+                let value = reader.read_object(rdb_type)?;
+                on_entry(RdbEntry {
+                    db,
+                    key,
+                    value,
+                    expire_at_ms: expire_at_ms.take(),
+                })?;
+            }
+        }
+    }
+}
+
+/// Serialize every logical database into a complete RDB file.
+/// Fails only when a value spilled to tiered storage can't be read back.
+pub fn dump(db: &Database) -> Result<Vec<u8>> {
+    let now = SystemTime::now();
+    let mut writer = RdbWriter::new();
+    writer.write_header();
+    writer.write_aux(b"redis-ver", b"6.0.0");
+    writer.write_aux(b"redis-bits", b"64");
+    writer.write_aux(b"ctime", (unix_millis(now) / 1000).to_string().as_bytes());
+
+    let mut indexes: Vec<_> = db.data.keys().copied().collect();
+    indexes.sort_unstable();
+    for index in indexes {
+        let data = &db.data[&index];
+        if data.is_empty() {
+            continue;
+        }
+        let expires = &db.data_expiration_time[&index];
+        writer.write_select_db(index as u64, data.len() as u64, expires.len() as u64);
+        for entry in data.iter() {
+            let expire_at = expires.get(entry.key()).map(|e| *e.value());
+            if matches!(expire_at, Some(at) if at <= now) {
+                continue;
+            }
+            db.with_loaded(entry.value(), |value| {
+                writer.write_entry(entry.key(), value, expire_at.map(unix_millis))
+            })?;
+        }
+    }
+    Ok(writer.finish())
+}
+
+/// Load an RDB file into `db`, skipping keys that already expired and
+/// empty collections. Returns the number of keys loaded.
+pub fn load(db: &Database, data: &[u8]) -> Result<usize> {
+    let now_ms = unix_millis(SystemTime::now());
+    let mut loaded = 0;
+    parse(data, |entry| {
+        let index = u8::try_from(entry.db)
+            .ok()
+            .filter(|i| db.data.contains_key(i))
+            .ok_or_else(|| corrupt(format!("DB index {} is out of range", entry.db)))?;
+        if matches!(entry.expire_at_ms, Some(at) if at <= now_ms)
+            || entry.value.is_empty_collection()
+        {
+            return Ok(());
+        }
+        if let Some(at) = entry.expire_at_ms {
+            db.data_expiration_time[&index]
+                .insert(entry.key.clone(), UNIX_EPOCH + Duration::from_millis(at));
+        }
+        db.data[&index].insert(entry.key, entry.value);
+        loaded += 1;
+        Ok(())
+    })?;
+    Ok(loaded)
+}
@@ -2,30 +2,51 @@
 // Handles the main server loop and client connections
 
 use crate::{
-    config::Config,
-    database::{Database, SharedDatabase},
+    config::{registry, Config},
+    database::{
+        tiered::{self, TieredStore},
+        Database, SharedDatabase,
+    },
     error::{AppError, Result},
     networking::Networking,
+    persistence::Persistence,
 };
+use std::{future::Future, net::SocketAddr, sync::Arc, time::Duration};
+use tracing::{error, info};
 
 pub struct Server {
     networking: Networking,
     config: Config,
     database: SharedDatabase,
+    persistence: Arc<Persistence>,
 }
 
 impl Server {
     pub async fn new(config: Config) -> Result<Self> {
-        let networking = Networking::new(&format!("{}:{}", &config.host, &config.port))
-            .await
-            .map_err(AppError::Io)?;
+        let networking = Networking::bind(&config.bind, config.port).map_err(AppError::Io)?;
 
-        let database = Database::new_shared(config.db_num);
+        let mut database = Database::new(config.db_num);
+        if config.tiered_storage {
+            database.enable_tiered(TieredStore::open(&config)?);
+            info!(
+                "Tiered storage enabled, {} bytes resident budget",
+                config.tiered_max_memory
+            );
+        }
+        database.set_config(config.clone());
+        let database = Arc::new(database);
+
+        let persistence = Arc::new(Persistence::new(&config));
+        let loaded = persistence.load(&database)?;
+        if loaded > 0 {
+            info!("DB loaded from disk: {} keys", loaded);
+        }
 
         Ok(Server {
             networking,
             config,
             database,
+            persistence,
         })
     }
 
@@ -33,10 +54,117 @@ impl Server {
         &self.config
     }
 
+    /// Addresses the server accepts TCP connections on
+    pub fn local_addrs(&self) -> Vec<SocketAddr> {
+        self.networking.local_addrs()
+    }
+
+    /// Serve until SIGTERM or SIGINT, then save a final snapshot
     pub async fn run(&self) -> Result<()> {
-        // loop {
-        self.networking.listen(&self.database).await?;
-        // }
+        self.run_until(shutdown_signal()).await
+    }
+
+    /// Serve until `shutdown` completes, then save a final snapshot
+    pub async fn run_until(&self, shutdown: impl Future<Output = ()>) -> Result<()> {
+        tokio::spawn(Arc::clone(&self.persistence).run_scheduler(Arc::clone(&self.database)));
+        if self.database.tiered().is_some() {
+            tokio::spawn(tiered::run_tiering(Arc::clone(&self.database)));
+        }
+        tokio::spawn(run_memory_accounting(Arc::clone(&self.database)));
+        #[cfg(unix)]
+        tokio::spawn(reload_on_sighup(Arc::clone(&self.database)));
+        tokio::select! {
+            result = self.networking.listen(&self.database) => result?,
+            () = shutdown => {
+                info!("Received shutdown request, preparing to shut down...");
+                self.save_before_shutdown().await?;
+            }
+        }
         Ok(())
     }
+
+    /// Like Redis, snapshot on a clean stop when save points are configured
+    /// so the writes since the last save point are not lost
+    async fn save_before_shutdown(&self) -> Result<()> {
+        if self.database.config().save.is_empty() {
+            return Ok(());
+        }
+        // The final save writes the same temporary file as a background one
+        while self.database.save_state().bgsave_in_progress() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        info!("Saving the final RDB snapshot before exiting.");
+        let persistence = Arc::clone(&self.persistence);
+        let db = Arc::clone(&self.database);
+        match tokio::task::spawn_blocking(move || persistence.save(&db)).await {
+            Ok(Ok(())) => {
+                info!("DB saved on disk");
+                Ok(())
+            }
+            Ok(Err(e)) => {
+                error!("Error trying to save the DB: {}", e);
+                Err(e)
+            }
+            Err(e) => Err(AppError::Other(e.to_string())),
+        }
+    }
+}
+
+/// Resolve on the first SIGTERM or SIGINT
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = terminate.recv() => {}
+                    _ = tokio::signal::ctrl_c() => {}
+                }
+                return;
+            }
+            Err(e) => error!("Cannot install SIGTERM handler: {}", e),
+        }
+    }
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        error!("Cannot install SIGINT handler: {}", e);
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Refresh the dataset size estimate once a second while `maxmemory` is set
+async fn run_memory_accounting(db: SharedDatabase) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        if db.config().maxmemory > 0 {
+            let db = db.clone();
+            let _ = tokio::task::spawn_blocking(move || db.refresh_used_memory()).await;
+        }
+    }
+}
+
+/// Re-read the config file whenever the process receives SIGHUP
+#[cfg(unix)]
+async fn reload_on_sighup(db: SharedDatabase) {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            error!("Cannot install SIGHUP handler: {}", e);
+            return;
+        }
+    };
+    while hangup.recv().await.is_some() {
+        info!("Received SIGHUP, reloading configuration");
+        let db = db.clone();
+        match tokio::task::spawn_blocking(move || registry::reload(&db)).await {
+            Ok(Ok(report)) if report.applied.is_empty() => {
+                info!("Config reload: no runtime settings changed")
+            }
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => error!("Config reload failed, keeping the old configuration: {}", e),
+            Err(e) => error!("Config reload panicked: {}", e),
+        }
+    }
 }
@@ -0,0 +1,361 @@
+// Tests for the bitmap commands on strings
+
+use rudis::commands::{CommandError, Reply};
+use rudis::database::Database;
+use rudis::Client;
+
+fn client() -> Client {
+    Client::new(&Database::new_shared(16))
+}
+
+fn ints(values: &[Option<i64>]) -> Reply {
+    Reply::Array(values.iter().map(|&v| v.into()).collect())
+}
+
+#[tokio::test]
+async fn test_setbit_and_getbit() {
+    let client = client();
+    assert_eq!(
+        client.call(&[b"SETBIT", b"b", b"7", b"1"]).await,
+        Reply::Integer(0)
+    );
+    assert_eq!(
+        client.call(&[b"SETBIT", b"b", b"7", b"0"]).await,
+        Reply::Integer(1)
+    );
+    // The string grows with zero bytes to hold the offset
+    assert_eq!(
+        client.call(&[b"SETBIT", b"b", b"17", b"1"]).await,
+        Reply::Integer(0)
+    );
+    assert_eq!(
+        client.call(&[b"GET", b"b"]).await,
+        Reply::bulk(&b"\x00\x00\x40"[..])
+    );
+    assert_eq!(
+        client.call(&[b"GETBIT", b"b", b"17"]).await,
+        Reply::Integer(1)
+    );
+    assert_eq!(
+        client.call(&[b"GETBIT", b"b", b"1000"]).await,
+        Reply::Integer(0)
+    );
+    assert_eq!(
+        client.call(&[b"GETBIT", b"missing", b"0"]).await,
+        Reply::Integer(0)
+    );
+
+    assert_eq!(
+        client.call(&[b"SETBIT", b"b", b"-1", b"1"]).await,
+        CommandError::InvalidBitOffset.into()
+    );
+    assert_eq!(
+        client.call(&[b"SETBIT", b"b", b"4294967296", b"1"]).await,
+        CommandError::InvalidBitOffset.into()
+    );
+    assert_eq!(
+        client.call(&[b"SETBIT", b"b", b"0", b"2"]).await,
+        CommandError::InvalidBit.into()
+    );
+    client.call(&[b"RPUSH", b"list", b"x"]).await;
+    assert_eq!(
+        client.call(&[b"SETBIT", b"list", b"0", b"1"]).await,
+        CommandError::WrongType.into()
+    );
+}
+
+#[tokio::test]
+async fn test_bitcount() {
+    let client = client();
+    client.set("k", "foobar").await.unwrap();
+    for (args, expected) in [
+        (&[][..], 26),
+        (&[&b"0"[..], b"0"], 4),
+        (&[b"1", b"1"], 6),
+        (&[b"1", b"1", b"BYTE"], 6),
+        (&[b"5", b"30", b"BIT"], 17),
+        (&[b"-2", b"-1"], 7),
+        (&[b"-1", b"-2"], 0),
+        (&[b"10", b"20"], 0),
+    ] {
+        let mut command: Vec<&[u8]> = vec![b"BITCOUNT", b"k"];
+        command.extend_from_slice(args);
+        assert_eq!(
+            client.call(&command).await,
+            Reply::Integer(expected),
+            "{:?}",
+            args
+        );
+    }
+    assert_eq!(
+        client.call(&[b"BITCOUNT", b"missing"]).await,
+        Reply::Integer(0)
+    );
+    assert_eq!(
+        client.call(&[b"BITCOUNT", b"k", b"0"]).await,
+        CommandError::SyntaxError.into()
+    );
+    assert_eq!(
+        client.call(&[b"BITCOUNT", b"k", b"0", b"1", b"WORD"]).await,
+        CommandError::SyntaxError.into()
+    );
+}
+
+#[tokio::test]
+async fn test_bitpos() {
+    let client = client();
+    client.set("k", &b"\xff\xf0\x00"[..]).await.unwrap();
+    for (args, expected) in [
+        (&[&b"0"[..]][..], 12),
+        (&[b"1", b"2"], -1),
+        (&[b"1", b"1"], 8),
+        (&[b"0", b"2", b"-1"], 16),
+        (&[b"1", b"7", b"15", b"BIT"], 7),
+        (&[b"0", b"7", b"15", b"BIT"], 12),
+    ] {
+        let mut command: Vec<&[u8]> = vec![b"BITPOS", b"k"];
+        command.extend_from_slice(args);
+        assert_eq!(
+            client.call(&command).await,
+            Reply::Integer(expected),
+            "{:?}",
+            args
+        );
+    }
+
+    // Clear bits past the end count only when no end was given
+    client.set("ones", &b"\xff\xff"[..]).await.unwrap();
+    assert_eq!(
+        client.call(&[b"BITPOS", b"ones", b"0"]).await,
+        Reply::Integer(16)
+    );
+    assert_eq!(
+        client.call(&[b"BITPOS", b"ones", b"0", b"0", b"-1"]).await,
+        Reply::Integer(-1)
+    );
+    assert_eq!(
+        client.call(&[b"BITPOS", b"missing", b"0"]).await,
+        Reply::Integer(0)
+    );
+    assert_eq!(
+        client.call(&[b"BITPOS", b"missing", b"1"]).await,
+        Reply::Integer(-1)
+    );
+    assert_eq!(
+        client.call(&[b"BITPOS", b"k", b"2"]).await,
+        CommandError::InvalidBitArgument.into()
+    );
+}
+
+#[tokio::test]
+async fn test_bitop() {
+    let client = client();
+    client.set("a", &b"\xf0\x0f"[..]).await.unwrap();
+    client.set("b", &b"\xff"[..]).await.unwrap();
+    client.set("c", &b"\x3c\x01"[..]).await.unwrap();
+    for (op, sources, expected) in [
+        ("AND", &["a", "b"][..], &b"\xf0\x00"[..]),
+        ("OR", &["a", "b"], b"\xff\x0f"),
+        ("XOR", &["a", "b"], b"\x0f\x0f"),
+        ("NOT", &["a"], b"\x0f\xf0"),
+        ("DIFF", &["a", "c"], b"\xc0\x0e"),
+        ("ONE", &["a", "b", "c"], b"\x03\x0e"),
+        ("AND", &["a", "missing"], b"\x00\x00"),
+    ] {
+        let mut command: Vec<&[u8]> = vec![b"BITOP", op.as_bytes(), b"dest"];
+        command.extend(sources.iter().map(|s| s.as_bytes()));
+        assert_eq!(
+            client.call(&command).await,
+            Reply::Integer(expected.len() as i64),
+            "{}",
+            op
+        );
+        assert_eq!(
+            client.call(&[b"GET", b"dest"]).await,
+            Reply::bulk(expected),
+            "{}",
+            op
+        );
+    }
+
+    // An empty result deletes the destination
+    assert_eq!(
+        client
+            .call(&[b"BITOP", b"OR", b"dest", b"missing", b"other"])
+            .await,
+        Reply::Integer(0)
+    );
+    assert_eq!(client.call(&[b"EXISTS", b"dest"]).await, Reply::Integer(0));
+
+    assert_eq!(
+        client.call(&[b"BITOP", b"NOT", b"dest", b"a", b"b"]).await,
+        CommandError::Custom("BITOP NOT must be called with a single source key.".into()).into()
+    );
+    assert_eq!(
+        client.call(&[b"BITOP", b"DIFF", b"dest", b"a"]).await,
+        CommandError::Custom("BITOP DIFF must be called with at least two source keys.".into())
+            .into()
+    );
+    assert_eq!(
+        client.call(&[b"BITOP", b"NAND", b"dest", b"a"]).await,
+        CommandError::SyntaxError.into()
+    );
+    client.call(&[b"RPUSH", b"list", b"x"]).await;
+    assert_eq!(
+        client
+            .call(&[b"BITOP", b"OR", b"dest", b"a", b"list"])
+            .await,
+        CommandError::WrongType.into()
+    );
+}
+
+#[tokio::test]
+async fn test_bitfield() {
+    let client = client();
+    assert_eq!(
+        client
+            .call(&[
+                b"BITFIELD",
+                b"f",
+                b"SET",
+                b"i8",
+                b"0",
+                b"-100",
+                b"GET",
+                b"u4",
+                b"0",
+                b"INCRBY",
+                b"u8",
+                b"#1",
+                b"10",
+                b"GET",
+                b"i8",
+                b"8"
+            ])
+            .await,
+        ints(&[Some(0), Some(9), Some(10), Some(10)])
+    );
+    assert_eq!(
+        client.call(&[b"GET", b"f"]).await,
+        Reply::bulk(&b"\x9c\x0a"[..])
+    );
+
+    // Overflow handling applies to the operations after it
+    client.set("n", &b"\xfe"[..]).await.unwrap();
+    let incr = |overflow: &'static [u8]| -> Vec<&'static [u8]> {
+        vec![
+            b"BITFIELD",
+            b"n",
+            b"OVERFLOW",
+            overflow,
+            b"INCRBY",
+            b"u8",
+            b"0",
+            b"5",
+        ]
+    };
+    assert_eq!(client.call(&incr(b"FAIL")).await, ints(&[None]));
+    assert_eq!(client.call(&incr(b"SAT")).await, ints(&[Some(255)]));
+    assert_eq!(client.call(&incr(b"WRAP")).await, ints(&[Some(4)]));
+    assert_eq!(
+        client
+            .call(&[
+                b"BITFIELD",
+                b"s",
+                b"OVERFLOW",
+                b"SAT",
+                b"INCRBY",
+                b"i4",
+                b"0",
+                b"-100",
+                b"OVERFLOW",
+                b"WRAP",
+                b"SET",
+                b"i4",
+                b"4",
+                b"9",
+                b"GET",
+                b"i4",
+                b"4"
+            ])
+            .await,
+        ints(&[Some(-8), Some(0), Some(-7)])
+    );
+    assert_eq!(
+        client
+            .call(&[
+                b"BITFIELD",
+                b"s",
+                b"INCRBY",
+                b"i64",
+                b"64",
+                b"-1",
+                b"INCRBY",
+                b"i64",
+                b"64",
+                b"-9223372036854775808"
+            ])
+            .await,
+        ints(&[Some(-1), Some(i64::MAX)])
+    );
+
+    // Reads alone do not create the key
+    assert_eq!(
+        client
+            .call(&[b"BITFIELD", b"none", b"GET", b"u8", b"0"])
+            .await,
+        ints(&[Some(0)])
+    );
+    assert_eq!(client.call(&[b"EXISTS", b"none"]).await, Reply::Integer(0));
+    assert_eq!(client.call(&[b"BITFIELD", b"none"]).await, ints(&[]));
+
+    assert_eq!(
+        client
+            .call(&[b"BITFIELD_RO", b"f", b"GET", b"u8", b"8"])
+            .await,
+        ints(&[Some(10)])
+    );
+    assert_eq!(
+        client
+            .call(&[b"BITFIELD_RO", b"f", b"SET", b"u8", b"8", b"1"])
+            .await,
+        CommandError::Custom("BITFIELD_RO only supports the GET subcommand".into()).into()
+    );
+    for bad_type in [&b"u64"[..], b"i65", b"x8", b"i0", b"i"] {
+        assert_eq!(
+            client
+                .call(&[b"BITFIELD", b"f", b"GET", bad_type, b"0"])
+                .await,
+            CommandError::InvalidBitfieldType.into()
+        );
+    }
+    assert_eq!(
+        client
+            .call(&[b"BITFIELD", b"f", b"GET", b"u8", b"-1"])
+            .await,
+        CommandError::InvalidBitOffset.into()
+    );
+    // A field must end within the 512MB limit, not only start there
+    assert_eq!(
+        client
+            .call(&[b"BITFIELD", b"f", b"SET", b"u63", b"4294967295", b"1"])
+            .await,
+        CommandError::InvalidBitOffset.into()
+    );
+    assert_eq!(
+        client
+            .call(&[b"BITFIELD", b"f", b"GET", b"u8", b"4294967288"])
+            .await,
+        Reply::Array(vec![Reply::Integer(0)])
+    );
+    assert_eq!(
+        client.call(&[b"BITFIELD", b"f", b"GET", b"u8"]).await,
+        CommandError::SyntaxError.into()
+    );
+    assert_eq!(
+        client
+            .call(&[b"BITFIELD", b"f", b"OVERFLOW", b"NONE"])
+            .await,
+        CommandError::Custom("Invalid OVERFLOW type specified".into()).into()
+    );
+}
@@ -0,0 +1,249 @@
+// Tests for the blocking list commands: BLPOP, BRPOP, BLMOVE and BLMPOP
+
+use rudis::commands::{CommandError, Reply};
+use rudis::database::{Database, SharedDatabase};
+use rudis::networking::Networking;
+use rudis::Client;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+
+fn bulks(values: &[&'static str]) -> Reply {
+    Reply::Array(values.iter().map(|&v| Reply::bulk(v)).collect())
+}
+
+/// Run a command on a client of its own in the background
+fn spawn_call(db: &SharedDatabase, args: &[&'static [u8]]) -> JoinHandle<Reply> {
+    let client = Client::new(db);
+    let args = args.to_vec();
+    tokio::spawn(async move { client.call(&args).await })
+}
+
+/// Wait until `count` clients are blocked
+async fn blocked(db: &SharedDatabase, count: usize) {
+    let start = Instant::now();
+    while db.blocked_clients() != count {
+        assert!(
+            start.elapsed() < Duration::from_secs(5),
+            "clients never blocked"
+        );
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_blocking_pop_without_waiting() {
+    let db = Database::new_shared(16);
+    let client = Client::new(&db);
+    client.call(&[b"RPUSH", b"b", b"1", b"2"]).await;
+    assert_eq!(
+        client.call(&[b"BLPOP", b"a", b"b", b"0"]).await,
+        bulks(&["b", "1"])
+    );
+    assert_eq!(
+        client.call(&[b"BRPOP", b"a", b"b", b"0"]).await,
+        bulks(&["b", "2"])
+    );
+    assert_eq!(client.call(&[b"EXISTS", b"b"]).await, Reply::Integer(0));
+
+    // The timeout is in seconds and may have a fraction
+    let start = Instant::now();
+    assert_eq!(
+        client.call(&[b"BLPOP", b"a", b"0.05"]).await,
+        Reply::NullArray
+    );
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    assert_eq!(
+        client
+            .call(&[b"BLMOVE", b"a", b"b", b"LEFT", b"LEFT", b"0.01"])
+            .await,
+        Reply::Null
+    );
+    assert_eq!(db.blocked_clients(), 0);
+
+    assert_eq!(
+        client.call(&[b"BLPOP", b"a", b"soon"]).await,
+        CommandError::InvalidTimeout.into()
+    );
+    assert_eq!(
+        client.call(&[b"BLPOP", b"a", b"inf"]).await,
+        CommandError::InvalidTimeout.into()
+    );
+    assert_eq!(
+        client.call(&[b"BRPOP", b"a", b"-1"]).await,
+        CommandError::NegativeTimeout.into()
+    );
+    client.call(&[b"SET", b"s", b"x"]).await;
+    assert_eq!(
+        client.call(&[b"BLPOP", b"a", b"s", b"0"]).await,
+        CommandError::WrongType.into()
+    );
+    assert_eq!(
+        client.call(&[b"BLMPOP", b"0", b"1", b"a", b"UP"]).await,
+        CommandError::SyntaxError.into()
+    );
+    assert_eq!(
+        client
+            .call(&[b"COMMAND", b"GETKEYS", b"BLMPOP", b"0", b"2", b"a", b"b", b"LEFT"])
+            .await,
+        bulks(&["a", "b"])
+    );
+    assert_eq!(
+        client
+            .call(&[b"COMMAND", b"GETKEYS", b"BLPOP", b"a", b"b", b"0"])
+            .await,
+        bulks(&["a", "b"])
+    );
+}
+
+#[tokio::test]
+async fn test_waiters_are_served_in_order() {
+    let db = Database::new_shared(16);
+    let client = Client::new(&db);
+    let first = spawn_call(&db, &[b"BRPOP", b"q", b"0"]);
+    blocked(&db, 1).await;
+    let second = spawn_call(&db, &[b"BLPOP", b"other", b"q", b"0"]);
+    blocked(&db, 2).await;
+    let third = spawn_call(&db, &[b"BRPOP", b"q", b"0"]);
+    blocked(&db, 3).await;
+
+    // Two elements for three clients: the oldest two get one each
+    assert_eq!(
+        client.call(&[b"RPUSH", b"q", b"a", b"b"]).await,
+        Reply::Integer(2)
+    );
+    assert_eq!(first.await.unwrap(), bulks(&["q", "b"]));
+    assert_eq!(second.await.unwrap(), bulks(&["q", "a"]));
+    assert_eq!(db.blocked_clients(), 1);
+    assert_eq!(client.call(&[b"EXISTS", b"q"]).await, Reply::Integer(0));
+
+    client.call(&[b"LPUSH", b"q", b"c", b"d"]).await;
+    assert_eq!(third.await.unwrap(), bulks(&["q", "c"]));
+    assert_eq!(
+        client.call(&[b"LRANGE", b"q", b"0", b"-1"]).await,
+        bulks(&["d"])
+    );
+    assert_eq!(db.blocked_clients(), 0);
+}
+
+#[tokio::test]
+async fn test_blmove_hands_elements_on() {
+    let db = Database::new_shared(16);
+    let client = Client::new(&db);
+    let mover = spawn_call(&db, &[b"BLMOVE", b"src", b"dst", b"RIGHT", b"LEFT", b"0"]);
+    blocked(&db, 1).await;
+    let popper = spawn_call(&db, &[b"BLPOP", b"dst", b"0"]);
+    blocked(&db, 2).await;
+
+    client.call(&[b"RPUSH", b"src", b"x"]).await;
+    assert_eq!(mover.await.unwrap(), Reply::bulk("x"));
+    // The moved element went on to the client blocked on the destination
+    assert_eq!(popper.await.unwrap(), bulks(&["dst", "x"]));
+    assert_eq!(
+        client.call(&[b"EXISTS", b"src", b"dst"]).await,
+        Reply::Integer(0)
+    );
+
+    let waiter = spawn_call(&db, &[b"BRPOPLPUSH", b"src", b"dst", b"0"]);
+    blocked(&db, 1).await;
+    client.call(&[b"RPUSH", b"src", b"y", b"z"]).await;
+    assert_eq!(waiter.await.unwrap(), Reply::bulk("z"));
+    assert_eq!(
+        client.call(&[b"LRANGE", b"dst", b"0", b"-1"]).await,
+        bulks(&["z"])
+    );
+}
+
+#[tokio::test]
+async fn test_blmpop() {
+    let db = Database::new_shared(16);
+    let client = Client::new(&db);
+    let waiter = spawn_call(
+        &db,
+        &[b"BLMPOP", b"0", b"2", b"a", b"b", b"LEFT", b"COUNT", b"2"],
+    );
+    blocked(&db, 1).await;
+    client.call(&[b"RPUSH", b"b", b"1", b"2", b"3"]).await;
+    assert_eq!(
+        waiter.await.unwrap(),
+        Reply::Array(vec![Reply::bulk("b"), bulks(&["1", "2"])])
+    );
+    assert_eq!(
+        client
+            .call(&[b"BLMPOP", b"0.01", b"1", b"a", b"RIGHT"])
+            .await,
+        Reply::NullArray
+    );
+}
+
+#[tokio::test]
+async fn test_timed_out_clients_stop_waiting() {
+    let db = Database::new_shared(16);
+    let client = Client::new(&db);
+    let waiter = spawn_call(&db, &[b"BLPOP", b"q", b"0.05"]);
+    blocked(&db, 1).await;
+    assert_eq!(waiter.await.unwrap(), Reply::NullArray);
+    assert_eq!(db.blocked_clients(), 0);
+    client.call(&[b"RPUSH", b"q", b"kept"]).await;
+    assert_eq!(client.call(&[b"LLEN", b"q"]).await, Reply::Integer(1));
+
+    // Blocking only applies to the database the client selected
+    let other = Client::new(&db);
+    other.select(1).await.unwrap();
+    let other = tokio::spawn(async move { other.call(&[b"BLPOP", b"q", b"0"]).await });
+    blocked(&db, 1).await;
+    client.call(&[b"RPUSH", b"q", b"db0"]).await;
+    assert_eq!(db.blocked_clients(), 1);
+    let db1 = Client::new(&db);
+    db1.select(1).await.unwrap();
+    db1.call(&[b"RPUSH", b"q", b"db1"]).await;
+    assert_eq!(other.await.unwrap(), bulks(&["q", "db1"]));
+}
+
+#[tokio::test]
+async fn test_disconnected_clients_stop_waiting() {
+    // Bind to a port that was free a moment ago
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let networking = Networking::bind(&["127.0.0.1".to_string()], port).unwrap();
+    let addr = networking.local_addr().unwrap();
+    let db = Database::new_shared(16);
+    let server_db = db.clone();
+    tokio::spawn(async move { networking.listen(&server_db).await });
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"*3\r\n$5\r\nBLPOP\r\n$1\r\nq\r\n$1\r\n0\r\n")
+        .await
+        .unwrap();
+    blocked(&db, 1).await;
+    drop(stream);
+    blocked(&db, 0).await;
+
+    // The element stays in the list rather than going to the closed client
+    let client = Client::new(&db);
+    client.call(&[b"RPUSH", b"q", b"job"]).await;
+    assert_eq!(client.call(&[b"LLEN", b"q"]).await, Reply::Integer(1));
+}
+
+#[tokio::test]
+async fn test_keyspace_import_wakes_blocked_clients() {
+    let db = Database::new_shared(16);
+    let waiter = Client::new(&db);
+    waiter.call(&[b"SELECT", b"1"]).await;
+    let pop = tokio::spawn(async move { waiter.call(&[b"BLPOP", b"q", b"0"]).await });
+    blocked(&db, 1).await;
+
+    let line = br#"{"db":1,"key":"q","pttl":null,"type":"list","value":["job"]}"#;
+    let client = Client::new(&db);
+    assert_eq!(
+        client.call(&[b"KEYSPACE", b"IMPORT", line]).await,
+        Reply::Integer(1)
+    );
+    let popped = tokio::time::timeout(Duration::from_secs(5), pop).await;
+    assert_eq!(popped.unwrap().unwrap(), bulks(&["q", "job"]));
+}
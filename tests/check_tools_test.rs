@@ -0,0 +1,181 @@
+// Tests for the AOF validator and the rudis-check-aof / rudis-check-rdb binaries
+
+use bytes::Bytes;
+use rudis::database::traits::{KeyOp, ListOp, StringOp};
+use rudis::database::Database;
+use rudis::persistence::aof::{self, AofFileType, AofManifest};
+use rudis::persistence::rdb;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const SET_CMD: &[u8] = b"*3\r\n$3\r\nSET\r\n$1\r\na\r\n$1\r\n1\r\n";
+
+#[test]
+fn test_aof_check_valid_file() {
+    let mut data = Vec::new();
+    data.extend_from_slice(SET_CMD);
+    data.extend_from_slice(b"#TS:1700000000\r\n");
+    data.extend_from_slice(b"*1\r\n$5\r\nMULTI\r\n");
+    data.extend_from_slice(SET_CMD);
+    data.extend_from_slice(b"*1\r\n$4\r\nEXEC\r\n");
+
+    let report = aof::check(&data);
+    assert!(report.is_ok(), "{:?}", report.error);
+    assert_eq!(report.commands, 4);
+    assert_eq!(report.ok_up_to, data.len());
+    assert_eq!(report.diff(), 0);
+}
+
+#[test]
+fn test_aof_check_truncated_tail() {
+    let mut data = SET_CMD.to_vec();
+    data.extend_from_slice(&SET_CMD[..SET_CMD.len() - 4]);
+
+    let report = aof::check(&data);
+    assert!(!report.is_ok());
+    assert_eq!(report.ok_up_to, SET_CMD.len());
+    assert_eq!(report.diff(), SET_CMD.len() - 4);
+}
+
+#[test]
+fn test_aof_check_unterminated_multi() {
+    let mut data = SET_CMD.to_vec();
+    data.extend_from_slice(b"*1\r\n$5\r\nMULTI\r\n");
+    data.extend_from_slice(SET_CMD);
+
+    let report = aof::check(&data);
+    assert_eq!(
+        report.error.as_deref(),
+        Some("Reached EOF before reading EXEC for MULTI")
+    );
+    // Truncating must drop the whole transaction
+    assert_eq!(report.ok_up_to, SET_CMD.len());
+}
+
+#[test]
+fn test_aof_check_rdb_preamble() {
+    let db = Database::new(16);
+    db.set(&Bytes::from("k"), Bytes::from("v"));
+    let mut data = rdb::dump(&db).unwrap();
+    let preamble = data.len();
+    data.extend_from_slice(SET_CMD);
+
+    let report = aof::check(&data);
+    assert!(report.is_ok());
+    assert_eq!(report.rdb_preamble, Some(preamble));
+    assert_eq!(report.commands, 1);
+}
+
+#[test]
+fn test_parse_manifest() {
+    let text = "file appendonly.aof.1.base.rdb seq 1 type b\n\
+                file appendonly.aof.2.incr.aof seq 2 type i\n\
+                file \"appendonly.aof.1.incr.aof\" seq 1 type i\n";
+    assert!(AofManifest::is_manifest(text.as_bytes()));
+    assert!(!AofManifest::is_manifest(SET_CMD));
+
+    let manifest = AofManifest::parse(text).unwrap();
+    assert_eq!(manifest.base().unwrap().name, "appendonly.aof.1.base.rdb");
+    let incrs: Vec<_> = manifest.incrs().iter().map(|e| e.seq).collect();
+    assert_eq!(incrs, vec![1, 2]);
+    assert_eq!(manifest.entries[1].file_type, AofFileType::Incr);
+
+    assert!(AofManifest::parse("file a seq x type i\n").is_err());
+    assert!(AofManifest::parse("file a seq 1 type z\n").is_err());
+    assert!(AofManifest::parse("file a seq 1 type b\nfile b seq 2 type b\n").is_err());
+}
+
+#[test]
+fn test_check_aof_binary_fix() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("appendonly.aof");
+    let mut data = SET_CMD.to_vec();
+    data.extend_from_slice(b"*3\r\n$3\r\nSET");
+    std::fs::write(&path, &data).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_rudis-check-aof"))
+        .arg(&path)
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(!status.success());
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rudis-check-aof"))
+        .arg("--fix")
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"y\n").unwrap();
+    assert!(child.wait().unwrap().success());
+    assert_eq!(std::fs::read(&path).unwrap(), SET_CMD);
+}
+
+#[test]
+fn test_check_aof_binary_manifest() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = Database::new(16);
+    db.set(&Bytes::from("k"), Bytes::from("v"));
+    std::fs::write(
+        dir.path().join("appendonly.aof.1.base.rdb"),
+        rdb::dump(&db).unwrap(),
+    )
+    .unwrap();
+    std::fs::write(dir.path().join("appendonly.aof.1.incr.aof"), SET_CMD).unwrap();
+    let manifest = dir.path().join("appendonly.aof.manifest");
+    std::fs::write(
+        &manifest,
+        "file appendonly.aof.1.base.rdb seq 1 type b\nfile appendonly.aof.1.incr.aof seq 1 type i\n",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rudis-check-aof"))
+        .arg(&manifest)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("All AOF files and manifest are valid")
+    );
+}
+
+#[test]
+fn test_check_rdb_binary() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("dump.rdb");
+    let db = Database::new(16);
+    db.set(&Bytes::from("small"), Bytes::from("v"));
+    db.set(&Bytes::from("large"), Bytes::from("x".repeat(1000)));
+    db.rpush(&Bytes::from("list"), &[Bytes::from("a"), Bytes::from("b")])
+        .unwrap();
+    db.expire(&Bytes::from("small"), 100).unwrap();
+    std::fs::write(&path, rdb::dump(&db).unwrap()).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rudis-check-rdb"))
+        .arg(&path)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("Checksum OK"));
+    assert!(stdout.contains("db0: keys=3 (list=1, string=2), expires=1, already_expired=0"));
+    let largest = stdout
+        .lines()
+        .skip_while(|l| !l.starts_with("Largest"))
+        .nth(1)
+        .unwrap();
+    assert!(largest.contains("\"large\""));
+
+    // Corrupt the checksum
+    let mut data = std::fs::read(&path).unwrap();
+    let last = data.len() - 1;
+    data[last] ^= 0xff;
+    std::fs::write(&path, &data).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rudis-check-rdb"))
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("checksum mismatch"));
+}
@@ -0,0 +1,136 @@
+// Tests for the embedded in-process client
+
+use bytes::Bytes;
+use rudis::commands::{CommandError, Reply};
+use rudis::data_structures::RedisString;
+use rudis::database::traits::KeyOp;
+use rudis::database::{Database, RedisValue};
+use rudis::Client;
+use std::time::{Duration, SystemTime};
+
+#[tokio::test]
+async fn test_typed_methods() {
+    let db = Database::new_shared(16);
+    let client = Client::new(&db);
+
+    client.set("k", "v").await.unwrap();
+    assert_eq!(client.get("k").await.unwrap(), Some(Bytes::from("v")));
+    assert_eq!(client.get("missing").await.unwrap(), None);
+    assert_eq!(client.incr_by("n", 5).await.unwrap(), 5);
+    assert_eq!(client.incr("n").await.unwrap(), 6);
+
+    assert!(client.hset("h", "f", "1").await.unwrap());
+    assert_eq!(
+        client.hgetall("h").await.unwrap(),
+        vec![(Bytes::from("f"), Bytes::from("1"))]
+    );
+    assert_eq!(client.hget("h", "nope").await.unwrap(), None);
+
+    assert_eq!(client.rpush("l", &["a", "b", "c"]).await.unwrap(), 3);
+    assert_eq!(client.lpop("l").await.unwrap(), Some(Bytes::from("a")));
+    assert_eq!(
+        client.lrange("l", 0, -1).await.unwrap(),
+        vec![Bytes::from("b"), Bytes::from("c")]
+    );
+
+    assert_eq!(client.sadd("s", &["x", "x", "y"]).await.unwrap(), 2);
+    assert_eq!(client.smembers("s").await.unwrap().len(), 2);
+
+    assert_eq!(
+        client.zadd("z", &[(2.0, "b"), (1.5, "a")]).await.unwrap(),
+        2
+    );
+    assert_eq!(
+        client.zrange("z", 0, -1).await.unwrap(),
+        vec![Bytes::from("a"), Bytes::from("b")]
+    );
+    assert_eq!(client.zscore("z", "a").await.unwrap(), Some(1.5));
+
+    assert_eq!(client.del(&["k", "h", "missing"]).await.unwrap(), 2);
+    assert!(!client.exists("k").await.unwrap());
+}
+
+#[tokio::test]
+async fn test_errors_match_the_network_path() {
+    let db = Database::new_shared(16);
+    let client = Client::new(&db);
+    client.set("str", "v").await.unwrap();
+
+    assert_eq!(
+        client.lpush("str", &["x"]).await,
+        Err(CommandError::WrongType)
+    );
+    assert_eq!(client.incr("str").await, Err(CommandError::InvalidInteger));
+
+    assert_eq!(
+        client.call(&[b"NOPE", b"a"]).await,
+        Reply::Error("ERR unknown command 'NOPE', with args beginning with: 'a' ".to_string())
+    );
+    assert_eq!(
+        client.call(&[b"GET"]).await,
+        Reply::Error("ERR wrong number of arguments for 'get' command".to_string())
+    );
+    assert_eq!(client.call(&[b"GET", b"str"]).await, Reply::bulk("v"));
+}
+
+#[tokio::test]
+async fn test_expiry() {
+    let db = Database::new_shared(16);
+    let client = Client::new(&db);
+
+    client.set_ex("session", "token", 100).await.unwrap();
+    let ttl = client.ttl("session").await.unwrap();
+    assert!(ttl > 95 && ttl <= 100, "ttl was {}", ttl);
+
+    // A plain SET drops the TTL
+    client.set("session", "token").await.unwrap();
+    assert_eq!(client.ttl("session").await.unwrap(), -1);
+
+    assert!(!client.expire("missing", 10).await.unwrap());
+    assert_eq!(client.ttl("missing").await.unwrap(), -2);
+
+    // Past deadlines are enforced on the next lookup
+    client.rpush("l", &["a"]).await.unwrap();
+    assert!(client.expire("l", 0).await.unwrap());
+    assert_eq!(
+        client.lrange("l", 0, -1).await.unwrap(),
+        Vec::<Bytes>::new()
+    );
+    assert!(!client.exists("l").await.unwrap());
+    assert_eq!(client.call(&[b"TYPE", b"l"]).await, Reply::status("none"));
+
+    let value = RedisValue::String(RedisString::new(Bytes::from("v")));
+    db.restore(
+        &Bytes::from("old"),
+        value,
+        Some(SystemTime::now() + Duration::from_millis(20)),
+        false,
+    )
+    .unwrap();
+    assert_eq!(client.get("old").await.unwrap(), Some(Bytes::from("v")));
+    tokio::time::sleep(Duration::from_millis(40)).await;
+    assert_eq!(client.get("old").await.unwrap(), None);
+    assert_eq!(
+        client.call(&[b"KEYS", b"old*"]).await,
+        Reply::Array(Vec::new())
+    );
+}
+
+#[tokio::test]
+async fn test_each_client_has_its_own_session() {
+    let db = Database::new_shared(16);
+    let first = Client::new(&db);
+    let second = Client::new(&db);
+
+    first.select(3).await.unwrap();
+    assert_eq!(first.db_index(), 3);
+    assert_eq!(second.db_index(), 0);
+
+    first.set("k", "db3").await.unwrap();
+    second.set("k", "db0").await.unwrap();
+    assert_eq!(first.get("k").await.unwrap(), Some(Bytes::from("db3")));
+    assert_eq!(second.get("k").await.unwrap(), Some(Bytes::from("db0")));
+
+    // Code outside any session still sees the default database
+    assert_eq!(db.exist(&[Bytes::from("k")]), 1);
+}
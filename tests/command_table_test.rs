@@ -0,0 +1,174 @@
+// Tests for the command table and the COMMAND command
+
+use bytes::Bytes;
+use rudis::commands::table::{self, Flag};
+use rudis::commands::Command;
+use rudis::database::{Database, SharedDatabase};
+use rudis::networking::resp::{encode, Protocol, RespValue};
+
+fn request(args: &[&str]) -> RespValue {
+    RespValue::Array(
+        args.iter()
+            .map(|a| RespValue::BulkString(Bytes::from(a.to_string())))
+            .collect(),
+    )
+}
+
+async fn run(db: &SharedDatabase, args: &[&str]) -> String {
+    let reply = Command::parse(&request(args))
+        .expect("command should parse")
+        .execute(db)
+        .await;
+    String::from_utf8_lossy(&encode(&reply, Protocol::Resp2)).to_string()
+}
+
+#[test]
+fn test_names_are_case_insensitive() {
+    // These used to be matched against mixed-case names and never parsed
+    assert_eq!(
+        Command::parse(&request(&["llen", "list"])),
+        Some(Command::LLen(Bytes::from("list")))
+    );
+    assert_eq!(
+        Command::parse(&request(&["SCARD", "set"])),
+        Some(Command::SCard(Bytes::from("set")))
+    );
+    assert_eq!(
+        Command::parse(&request(&["SDiff", "a", "b"])),
+        Some(Command::SDiff(vec![Bytes::from("a"), Bytes::from("b")]))
+    );
+    assert_eq!(
+        Command::parse(&request(&["config", "resetSTAT"])),
+        Some(Command::ConfigResetStat)
+    );
+}
+
+#[test]
+fn test_arity_is_enforced() {
+    assert!(Command::parse(&request(&["GET"])).is_none());
+    assert!(Command::parse(&request(&["GET", "a", "b"])).is_none());
+    assert!(Command::parse(&request(&["DEL"])).is_none());
+    assert!(Command::parse(&request(&["DEL", "a", "b", "c"])).is_some());
+    assert!(Command::parse(&request(&["CONFIG"])).is_none());
+    assert!(Command::parse(&request(&["CONFIG", "NOPE"])).is_none());
+    assert!(Command::parse(&request(&["NOPE"])).is_none());
+}
+
+#[test]
+fn test_table_is_consistent() {
+    for spec in table::COMMANDS {
+        for sub in spec.subcommands {
+            let (container, _) = sub.name.split_once('|').unwrap();
+            assert_eq!(container, spec.name);
+            assert!(table::lookup(&sub.name.to_uppercase()).is_some());
+        }
+        assert!(
+            !(spec.has_flag(Flag::Write) && spec.has_flag(Flag::ReadOnly)),
+            "{} is both write and readonly",
+            spec.name
+        );
+        if spec.has_flag(Flag::DenyOom) {
+            assert!(spec.has_flag(Flag::Write), "{}", spec.name);
+        }
+    }
+    let set = Command::parse(&request(&["SET", "k", "v"])).unwrap();
+    assert_eq!(set.name(), "set");
+    assert!(set.is_write() && set.is_denied_oom());
+    let del = Command::parse(&request(&["DEL", "k"])).unwrap();
+    assert!(del.is_write() && !del.is_denied_oom());
+    let get = Command::parse(&request(&["GET", "k"])).unwrap();
+    assert!(!get.is_write());
+    let export = Command::parse(&request(&["KEYSPACE", "EXPORT"])).unwrap();
+    assert_eq!(export.name(), "keyspace|export");
+}
+
+#[tokio::test]
+async fn test_command_count_and_info() {
+    let db = Database::new_shared(16);
+    assert_eq!(
+        run(&db, &["COMMAND", "COUNT"]).await,
+        format!(":{}\r\n", table::COMMANDS.len())
+    );
+
+    let info = run(&db, &["COMMAND", "INFO", "get", "nope"]).await;
+    assert_eq!(
+        info,
+        "*2\r\n*10\r\n$3\r\nget\r\n:2\r\n*2\r\n+readonly\r\n+fast\r\n:1\r\n:1\r\n:1\r\n\
+         *3\r\n+@read\r\n+@string\r\n+@fast\r\n*0\r\n*0\r\n*0\r\n*-1\r\n"
+    );
+
+    let mset = run(&db, &["COMMAND", "INFO", "MSET"]).await;
+    assert!(mset.starts_with("*1\r\n*10\r\n$4\r\nmset\r\n:-3\r\n"));
+    assert!(mset.contains(":1\r\n:-1\r\n:2\r\n"));
+
+    // Containers list their subcommands
+    let config = run(&db, &["COMMAND", "INFO", "config"]).await;
+    assert!(config.contains("$10\r\nconfig|get\r\n"));
+
+    let all = run(&db, &["COMMAND"]).await;
+    assert!(all.starts_with(&format!("*{}\r\n", table::COMMANDS.len())));
+}
+
+#[tokio::test]
+async fn test_command_docs_and_list() {
+    let db = Database::new_shared(16);
+    let docs = run(&db, &["COMMAND", "DOCS", "get"]).await;
+    assert!(docs.starts_with("*2\r\n$3\r\nget\r\n*4\r\n$7\r\nsummary\r\n"));
+    assert!(docs.contains("$5\r\ngroup\r\n$6\r\nstring\r\n"));
+
+    let list = run(&db, &["COMMAND", "LIST"]).await;
+    assert!(list.contains("$3\r\nget\r\n"));
+    assert!(list.contains("$13\r\ncommand|count\r\n"));
+
+    let hashes = run(&db, &["COMMAND", "LIST", "FILTERBY", "ACLCAT", "hash"]).await;
+    assert!(hashes.contains("$4\r\nhset\r\n"));
+    assert!(!hashes.contains("$3\r\nget\r\n"));
+
+    assert_eq!(
+        run(&db, &["COMMAND", "LIST", "FILTERBY", "PATTERN", "z*by*"]).await,
+        "*1\r\n$13\r\nzrangebyscore\r\n"
+    );
+    assert_eq!(
+        run(&db, &["COMMAND", "LIST", "FILTERBY", "MODULE", "json"]).await,
+        "*0\r\n"
+    );
+    assert!(Command::parse(&request(&["COMMAND", "LIST", "FILTERBY", "NOPE", "x"])).is_none());
+}
+
+#[tokio::test]
+async fn test_command_getkeys() {
+    let db = Database::new_shared(16);
+    assert_eq!(
+        run(&db, &["COMMAND", "GETKEYS", "SET", "k", "v"]).await,
+        "*1\r\n$1\r\nk\r\n"
+    );
+    assert_eq!(
+        run(&db, &["COMMAND", "GETKEYS", "MSET", "a", "1", "b", "2"]).await,
+        "*2\r\n$1\r\na\r\n$1\r\nb\r\n"
+    );
+    assert_eq!(
+        run(
+            &db,
+            &["COMMAND", "GETKEYS", "MIGRATE", "h", "6379", "", "0", "5000", "KEYS", "x", "y"]
+        )
+        .await,
+        "*2\r\n$1\r\nx\r\n$1\r\ny\r\n"
+    );
+    assert_eq!(
+        run(
+            &db,
+            &["COMMAND", "GETKEYS", "MIGRATE", "h", "6379", "k", "0", "5000"]
+        )
+        .await,
+        "*1\r\n$1\r\nk\r\n"
+    );
+    assert!(run(&db, &["COMMAND", "GETKEYS", "NOPE", "x"])
+        .await
+        .contains("Invalid command specified"));
+    assert!(run(&db, &["COMMAND", "GETKEYS", "GET"])
+        .await
+        .contains("Invalid number of arguments"));
+    assert!(run(&db, &["COMMAND", "GETKEYS", "PING"])
+        .await
+        .contains("no key arguments"));
+}
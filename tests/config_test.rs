@@ -0,0 +1,438 @@
+// Tests for redis.conf parsing
+
+use bytes::Bytes;
+use rudis::commands::command_helper::glob_match;
+use rudis::commands::Command;
+use rudis::config::parser::{parse_bool, parse_memory, split_args};
+use rudis::config::{registry, Config, SavePoint};
+use rudis::database::{Database, SharedDatabase};
+use rudis::networking::resp::{encode, Protocol, RespValue};
+use std::sync::atomic::Ordering;
+
+#[test]
+fn test_split_args_quoting() {
+    assert_eq!(split_args("  port   6380 ").unwrap(), vec!["port", "6380"]);
+    assert_eq!(
+        split_args(r#"dir "/var/lib/my rudis" 'it\'s' "a\tb\x41""#).unwrap(),
+        vec!["dir", "/var/lib/my rudis", "it's", "a\tbA"]
+    );
+    assert_eq!(split_args(r#"save """#).unwrap(), vec!["save", ""]);
+    assert!(split_args(r#"dir "unterminated"#).is_err());
+    assert!(split_args(r#"dir "a"b"#).is_err());
+}
+
+#[test]
+fn test_memory_units_and_booleans() {
+    assert_eq!(parse_memory("1024"), Some(1024));
+    assert_eq!(parse_memory("1k"), Some(1000));
+    assert_eq!(parse_memory("1kb"), Some(1024));
+    assert_eq!(parse_memory("512mb"), Some(512 * 1024 * 1024));
+    assert_eq!(parse_memory("1GB"), Some(1024 * 1024 * 1024));
+    assert_eq!(parse_memory("2g"), Some(2_000_000_000));
+    assert_eq!(parse_memory("1tb"), None);
+    assert_eq!(parse_memory("mb"), None);
+    assert_eq!(parse_bool("YES"), Some(true));
+    assert_eq!(parse_bool("no"), Some(false));
+    assert_eq!(parse_bool("1"), None);
+}
+
+#[test]
+fn test_load_redis_conf_with_include() {
+    let dir = tempfile::tempdir().unwrap();
+    let extra = dir.path().join("extra.conf");
+    std::fs::write(&extra, "databases 4\nsave 60 1000\n").unwrap();
+    let main = dir.path().join("redis.conf");
+    std::fs::write(
+        &main,
+        format!(
+            "# Redis configuration\n\
+             bind 0.0.0.0\n\
+             port 6380\n\
+             \n\
+             appendonly yes\n\
+             maxmemory-policy allkeys-lru\n\
+             save 900 1\n\
+             include {}\n\
+             dbfilename \"my dump.rdb\"\n\
+             TIERED-STORAGE yes\n\
+             tiered-max-memory 1gb\n\
+             tiered-min-value-size 4kb\n",
+            extra.display()
+        ),
+    )
+    .unwrap();
+
+    let mut config = Config::default();
+    let warnings = config.load_from_file(main.to_str().unwrap()).unwrap();
+    assert_eq!(config.bind, vec!["0.0.0.0"]);
+    assert_eq!(config.port, 6380);
+    assert_eq!(config.db_num, 4);
+    assert_eq!(config.dbfilename, "my dump.rdb");
+    // The first save line replaces the defaults, later ones accumulate
+    assert_eq!(
+        config.save,
+        vec![
+            SavePoint {
+                seconds: 900,
+                changes: 1
+            },
+            SavePoint {
+                seconds: 60,
+                changes: 1000
+            }
+        ]
+    );
+    assert!(config.tiered_storage);
+    assert_eq!(config.tiered_max_memory, 1024 * 1024 * 1024);
+    assert_eq!(config.tiered_min_value_size, 4096);
+    assert_eq!(warnings.len(), 2);
+    assert!(warnings[0].contains("line 5") && warnings[0].contains("appendonly"));
+
+    config.load_from_str("save \"\"\n", "inline").unwrap();
+    assert!(config.save.is_empty());
+}
+
+#[test]
+fn test_config_errors_report_line() {
+    let cases = [
+        ("port 6379\nno-such-directive 1\n", "line 2"),
+        ("port 70000\n", "Invalid port"),
+        ("\n\ntiered-storage maybe\n", "line 3"),
+        ("tiered-max-memory 1tb\n", "Invalid memory amount"),
+        ("save 900\n", "pairs"),
+        ("port\n", "wrong number of arguments"),
+        ("dir \"unterminated\n", "unbalanced quotes"),
+        ("include /nonexistent/rudis.conf\n", "cannot read"),
+    ];
+    for (text, expected) in cases {
+        let mut config = Config::default();
+        let err = config.load_from_str(text, "test.conf").unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains(expected), "{:?}: {}", text, message);
+        assert!(message.starts_with("Configuration error"), "{}", message);
+    }
+
+    // An include cycle is reported instead of recursing forever
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("loop.conf");
+    std::fs::write(&path, format!("include {}\n", path.display())).unwrap();
+    let err = Config::default()
+        .load_from_file(path.to_str().unwrap())
+        .unwrap_err();
+    assert!(err.to_string().contains("nested includes"));
+}
+
+#[test]
+fn test_security_directives_fail_closed() {
+    for text in [
+        "requirepass secret\n",
+        "user alice on >pw ~* +@all\n",
+        "aclfile /etc/redis/users.acl\n",
+    ] {
+        let err = Config::default()
+            .load_from_str(text, "test.conf")
+            .unwrap_err();
+        assert!(err.to_string().contains("line 1"), "{}", err);
+    }
+    let mut config = Config::default();
+    let warnings = config
+        .load_from_str("requirepass \"\"\nprotected-mode yes\n", "test.conf")
+        .unwrap();
+    assert!(warnings.is_empty());
+
+    // Protected mode without a password only allows local connections
+    assert!(config.protected_mode);
+    config.validate().unwrap();
+    let (config, _) = Config::from_args(&args(&[
+        "--protected-mode",
+        "yes",
+        "--bind",
+        "127.0.0.1 -::1",
+    ]))
+    .unwrap();
+    assert!(config.protected_mode);
+    let err =
+        Config::from_args(&args(&["--protected-mode", "yes", "--bind", "0.0.0.0"])).unwrap_err();
+    assert!(err.to_string().contains("protected-mode"), "{}", err);
+    assert!(Config::from_args(&args(&["--protected-mode", "no", "--bind", "*"])).is_ok());
+}
+
+fn args(list: &[&str]) -> Vec<String> {
+    list.iter().map(|a| a.to_string()).collect()
+}
+
+#[test]
+fn test_command_line_overrides_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("rudis.conf");
+    std::fs::write(&path, "port 6380\ndir /var/lib/rudis\nsave 900 1\n").unwrap();
+    let path = path.to_str().unwrap();
+
+    let (config, warnings) = Config::from_args(&args(&[
+        path,
+        "--port",
+        "7000",
+        "--BIND",
+        "0.0.0.0",
+        "--save",
+        "60 100",
+        "--appendonly",
+        "yes",
+    ]))
+    .unwrap();
+    assert_eq!(config.port, 7000);
+    assert_eq!(config.bind, vec!["0.0.0.0"]);
+    assert_eq!(config.dir, "/var/lib/rudis");
+    // Overrides behave as if appended to the file
+    assert_eq!(config.save.len(), 2);
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("--appendonly"));
+
+    // Overrides alone, without a file
+    let (config, _) = Config::from_args(&args(&["--dir", "/data", "--save", ""])).unwrap();
+    assert_eq!(config.dir, "/data");
+    assert!(config.save.is_empty());
+    assert_eq!(config.port, 6379);
+
+    let err = Config::from_args(&args(&["--port", "nope"])).unwrap_err();
+    assert!(err.to_string().contains("argument --port"), "{}", err);
+    assert!(Config::from_args(&args(&["--no-such-option", "1"])).is_err());
+    assert!(Config::from_args(&args(&[path, "stray"])).is_err());
+    // Whole-configuration checks run too
+    assert!(Config::from_args(&args(&["--tiered-storage", "yes"])).is_err());
+}
+
+async fn run(db: &SharedDatabase, args: &[&str]) -> String {
+    let resp = RespValue::Array(
+        args.iter()
+            .map(|a| RespValue::BulkString(Bytes::from(a.to_string())))
+            .collect(),
+    );
+    let reply = Command::parse(&resp)
+        .expect("command should parse")
+        .execute(db)
+        .await;
+    String::from_utf8_lossy(&encode(&reply, Protocol::Resp2)).to_string()
+}
+
+#[test]
+fn test_glob_match() {
+    assert!(glob_match(b"max*", b"maxmemory", false));
+    assert!(glob_match(b"*memory*", b"tiered-max-memory", false));
+    assert!(glob_match(b"p?rt", b"port", false));
+    assert!(glob_match(b"[a-d]atabases", b"databases", false));
+    assert!(!glob_match(b"[^d]atabases", b"databases", false));
+    assert!(glob_match(b"PORT", b"port", true));
+    assert!(!glob_match(b"PORT", b"port", false));
+    assert!(glob_match(b"a\\*", b"a*", false));
+    assert!(!glob_match(b"a\\*", b"ab", false));
+}
+
+#[tokio::test]
+async fn test_config_get_and_set() {
+    let db = Database::new_shared(16);
+    assert_eq!(
+        run(&db, &["CONFIG", "GET", "port"]).await,
+        "*2\r\n$4\r\nport\r\n$4\r\n6379\r\n"
+    );
+    let reply = run(&db, &["config", "get", "MAXMEMORY*", "save"]).await;
+    assert!(reply.starts_with("*6\r\n"), "{}", reply);
+    assert!(reply.contains("maxmemory-policy\r\n$10\r\nnoeviction"));
+    assert!(reply.contains("3600 1 300 100 60 10000"));
+    assert_eq!(run(&db, &["CONFIG", "GET", "nothing*"]).await, "*0\r\n");
+
+    let reply = run(
+        &db,
+        &[
+            "CONFIG",
+            "SET",
+            "maxmemory",
+            "1mb",
+            "timeout",
+            "30",
+            "save",
+            "900 1",
+        ],
+    )
+    .await;
+    assert_eq!(reply, "+OK\r\n");
+    assert_eq!(db.config().maxmemory, 1024 * 1024);
+    assert_eq!(db.config().timeout, 30);
+    assert_eq!(
+        db.config().save,
+        vec![SavePoint {
+            seconds: 900,
+            changes: 1
+        }]
+    );
+    run(&db, &["CONFIG", "SET", "save", ""]).await;
+    assert!(db.config().save.is_empty());
+
+    // A failure anywhere leaves every parameter unchanged
+    let reply = run(&db, &["CONFIG", "SET", "timeout", "5", "maxmemory", "lots"]).await;
+    assert!(reply.contains("CONFIG SET failed"), "{}", reply);
+    assert!(reply.contains("'maxmemory'"));
+    assert_eq!(db.config().timeout, 30);
+    let reply = run(&db, &["CONFIG", "SET", "port", "7000"]).await;
+    assert!(reply.contains("can't set immutable config"), "{}", reply);
+    let reply = run(&db, &["CONFIG", "SET", "no-such-param", "1"]).await;
+    assert!(reply.contains("Unknown option"), "{}", reply);
+    let reply = run(&db, &["CONFIG", "SET", "timeout", "1", "timeout", "2"]).await;
+    assert!(reply.contains("duplicate parameter"), "{}", reply);
+    let reply = run(&db, &["CONFIG", "SET", "loglevel", "chatty"]).await;
+    assert!(reply.contains("argument must be one of"), "{}", reply);
+    assert_eq!(
+        run(&db, &["CONFIG", "SET", "loglevel", "warning"]).await,
+        "+OK\r\n"
+    );
+}
+
+#[tokio::test]
+async fn test_maxmemory_refuses_growing_writes() {
+    let db = Database::new_shared(16);
+    run(&db, &["SET", "big", &"x".repeat(4096)]).await;
+    assert_eq!(
+        run(&db, &["CONFIG", "SET", "maxmemory", "1kb"]).await,
+        "+OK\r\n"
+    );
+    assert!(db.used_memory() > 1024);
+    let reply = run(&db, &["SET", "more", "data"]).await;
+    assert!(reply.contains("OOM command not allowed"), "{}", reply);
+    // Reads and deletions still work
+    assert!(run(&db, &["GET", "big"]).await.starts_with("$4096"));
+    assert_eq!(run(&db, &["DEL", "big"]).await, ":1\r\n");
+
+    db.refresh_used_memory();
+    assert_eq!(run(&db, &["SET", "more", "data"]).await, "+OK\r\n");
+    run(&db, &["CONFIG", "SET", "maxmemory", "0"]).await;
+    assert!(!db.over_maxmemory());
+}
+
+#[tokio::test]
+async fn test_config_resetstat() {
+    let db = Database::new_shared(16);
+    run(&db, &["PING"]).await;
+    assert!(db.stats().commands_processed.load(Ordering::Relaxed) > 0);
+    assert_eq!(run(&db, &["CONFIG", "RESETSTAT"]).await, "+OK\r\n");
+    // RESETSTAT itself is counted before the reset
+    assert_eq!(db.stats().commands_processed.load(Ordering::Relaxed), 0);
+    assert!(run(&db, &["INFO", "stats"])
+        .await
+        .contains("total_commands_processed:1\r\n"));
+}
+
+#[tokio::test]
+async fn test_config_rewrite_keeps_comments_and_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("rudis.conf");
+    std::fs::write(
+        &path,
+        "# Network\n\
+         port 6380\n\
+         \n\
+         # Snapshots\n\
+         save 900 1\n\
+         save 300 10\n\
+         appendonly no\n\
+         # Memory\n\
+         maxmemory 100mb\n",
+    )
+    .unwrap();
+    let (config, _) = Config::from_args(&args(&[path.to_str().unwrap()])).unwrap();
+    let db = Database::new_shared(16);
+    db.set_config(config);
+
+    let reply = run(
+        &db,
+        &[
+            "CONFIG",
+            "SET",
+            "save",
+            "60 5",
+            "maxmemory",
+            "2gb",
+            "timeout",
+            "10",
+        ],
+    )
+    .await;
+    assert_eq!(reply, "+OK\r\n");
+    assert_eq!(run(&db, &["CONFIG", "REWRITE"]).await, "+OK\r\n");
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(
+        text,
+        "# Network\n\
+         port 6380\n\
+         \n\
+         # Snapshots\n\
+         save 60 5\n\
+         appendonly no\n\
+         # Memory\n\
+         maxmemory 2gb\n\
+         # Generated by CONFIG REWRITE\n\
+         timeout 10\n"
+    );
+
+    // Rewriting again is stable and the file loads back to the same values
+    assert_eq!(run(&db, &["CONFIG", "REWRITE"]).await, "+OK\r\n");
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), text);
+    let (reloaded, _) = Config::from_args(&args(&[path.to_str().unwrap()])).unwrap();
+    assert_eq!(reloaded.maxmemory, 2 * 1024 * 1024 * 1024);
+    assert_eq!(reloaded.timeout, 10);
+
+    let without_file = Database::new_shared(16);
+    let reply = run(&without_file, &["CONFIG", "REWRITE"]).await;
+    assert!(reply.contains("without a config file"), "{}", reply);
+}
+
+#[test]
+fn test_reload_applies_mutable_settings() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("rudis.conf");
+    std::fs::write(&path, "port 6380\ntimeout 0\nmaxmemory 100mb\nsave 900 1\n").unwrap();
+    let (config, _) =
+        Config::from_args(&args(&[path.to_str().unwrap(), "--maxclients", "50"])).unwrap();
+    let db = Database::new_shared(16);
+    db.set_config(config);
+
+    std::fs::write(
+        &path,
+        "port 7000\ntimeout 60\nmaxmemory 100mb\nsave 60 5\nmaxclients 10\n",
+    )
+    .unwrap();
+    let report = registry::reload(&db).unwrap();
+    let applied: Vec<&str> = report.applied.iter().map(|(name, ..)| *name).collect();
+    assert_eq!(applied, vec!["timeout", "save"]);
+    assert_eq!(report.restart_required, vec!["port"]);
+    assert_eq!(db.config().timeout, 60);
+    assert_eq!(db.config().port, 6380);
+    // Command-line overrides still win over the file
+    assert_eq!(db.config().max_connections, 50);
+
+    // A broken file leaves the running configuration alone
+    std::fs::write(&path, "timeout 5\nmaxmemory 1tb\n").unwrap();
+    let err = registry::reload(&db).unwrap_err();
+    assert!(err.to_string().contains("line 2"), "{}", err);
+    assert_eq!(db.config().timeout, 60);
+    std::fs::write(&path, "timeout 5\ntiered-storage yes\n").unwrap();
+    assert!(registry::reload(&db).is_err());
+    assert_eq!(db.config().timeout, 60);
+
+    assert!(registry::reload(&Database::new_shared(16)).is_err());
+}
+
+#[test]
+fn test_bind_lists() {
+    let mut config = Config::default();
+    config
+        .load_from_str("bind 127.0.0.1 -::1 10.0.0.5\nport 0\n", "test.conf")
+        .unwrap();
+    assert_eq!(config.bind, vec!["127.0.0.1", "-::1", "10.0.0.5"]);
+    assert_eq!(config.port, 0);
+
+    let (config, _) = Config::from_args(&args(&["--bind", "* -::*"])).unwrap();
+    assert_eq!(config.bind, vec!["*", "-::*"]);
+    assert!(Config::default()
+        .load_from_str("bind\n", "test.conf")
+        .is_err());
+}
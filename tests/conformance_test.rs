@@ -0,0 +1,297 @@
+// Conformance tests for type checks and missing keys
+// Runs every typed command against a key of each type, a missing key and a
+// key spilled to tiered storage, and checks the exact replies Redis gives.
+
+use bytes::Bytes;
+use rudis::commands::Command;
+use rudis::config::Config;
+use rudis::database::tiered::TieredStore;
+use rudis::database::{Database, SharedDatabase};
+use rudis::networking::resp::{encode, Protocol, RespValue};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+const WRONGTYPE: &str = "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n";
+
+const TYPES: &[&str] = &["string", "hash", "list", "set", "zset"];
+
+async fn run(db: &SharedDatabase, args: &[&str]) -> String {
+    let resp = RespValue::Array(
+        args.iter()
+            .map(|a| RespValue::BulkString(Bytes::from(a.to_string())))
+            .collect(),
+    );
+    let reply = Command::parse(&resp)
+        .unwrap_or_else(|| panic!("{:?} should parse", args))
+        .execute(db)
+        .await;
+    String::from_utf8_lossy(&encode(&reply, Protocol::Resp2)).to_string()
+}
+
+/// Store a value of type `kind` at `key`
+async fn populate(db: &SharedDatabase, kind: &str, key: &str) {
+    let args: &[&str] = match kind {
+        "string" => &["SET", key, "1"],
+        "hash" => &["HSET", key, "f", "1"],
+        "list" => &["RPUSH", key, "p", "q"],
+        "set" => &["SADD", key, "m"],
+        "zset" => &["ZADD", key, "1", "m"],
+        _ => unreachable!(),
+    };
+    assert!(!run(db, args).await.starts_with('-'));
+}
+
+/// (type the command works on, arguments with `k` as the key, reply for a missing key)
+const COMMANDS: &[(&str, &[&str], &str)] = &[
+    ("string", &["GET", "k"], "$-1\r\n"),
+    ("string", &["GETSET", "k", "v"], "$-1\r\n"),
+    ("string", &["APPEND", "k", "abc"], ":3\r\n"),
+    ("string", &["STRLEN", "k"], ":0\r\n"),
+    ("string", &["INCR", "k"], ":1\r\n"),
+    ("string", &["DECR", "k"], ":-1\r\n"),
+    ("string", &["INCRBY", "k", "5"], ":5\r\n"),
+    ("string", &["DECRBY", "k", "5"], ":-5\r\n"),
+    ("string", &["INCRBYFLOAT", "k", "1.5"], "$3\r\n1.5\r\n"),
+    ("string", &["GETRANGE", "k", "0", "-1"], "$0\r\n\r\n"),
+    ("string", &["SUBSTR", "k", "0", "-1"], "$0\r\n\r\n"),
+    ("string", &["SETRANGE", "k", "2", "abc"], ":5\r\n"),
+    ("string", &["GETDEL", "k"], "$-1\r\n"),
+    ("string", &["GETEX", "k", "EX", "100"], "$-1\r\n"),
+    ("hash", &["HSET", "k", "f", "v"], ":1\r\n"),
+    ("hash", &["HGET", "k", "f"], "$-1\r\n"),
+    ("hash", &["HDEL", "k", "f"], ":0\r\n"),
+    ("hash", &["HGETALL", "k"], "*0\r\n"),
+    ("hash", &["HKEYS", "k"], "*0\r\n"),
+    ("hash", &["HVALS", "k"], "*0\r\n"),
+    ("hash", &["HLEN", "k"], ":0\r\n"),
+    ("hash", &["HEXISTS", "k", "f"], ":0\r\n"),
+    ("hash", &["HINCRBY", "k", "f", "2"], ":2\r\n"),
+    ("hash", &["HINCRBYFLOAT", "k", "f", "1.5"], "$3\r\n1.5\r\n"),
+    ("hash", &["HMSET", "k", "f", "v", "g", "w"], "+OK\r\n"),
+    ("hash", &["HMGET", "k", "f", "g"], "*2\r\n$-1\r\n$-1\r\n"),
+    ("hash", &["HSETNX", "k", "f", "v"], ":1\r\n"),
+    ("hash", &["HSTRLEN", "k", "f"], ":0\r\n"),
+    ("hash", &["HRANDFIELD", "k"], "$-1\r\n"),
+    ("hash", &["HRANDFIELD", "k", "-2", "WITHVALUES"], "*0\r\n"),
+    ("hash", &["HSCAN", "k", "0"], "*2\r\n$1\r\n0\r\n*0\r\n"),
+    ("list", &["LPUSH", "k", "a"], ":1\r\n"),
+    ("list", &["RPUSH", "k", "a", "b"], ":2\r\n"),
+    ("list", &["LPOP", "k"], "$-1\r\n"),
+    ("list", &["RPOP", "k"], "$-1\r\n"),
+    ("list", &["LLEN", "k"], ":0\r\n"),
+    ("list", &["LINDEX", "k", "0"], "$-1\r\n"),
+    ("list", &["LRANGE", "k", "0", "-1"], "*0\r\n"),
+    ("list", &["LTRIM", "k", "0", "1"], "+OK\r\n"),
+    ("list", &["LSET", "k", "0", "v"], "-ERR no such key\r\n"),
+    ("list", &["LINSERT", "k", "BEFORE", "p", "v"], ":0\r\n"),
+    ("list", &["LPOP", "k", "2"], "*-1\r\n"),
+    ("list", &["LPUSHX", "k", "a"], ":0\r\n"),
+    ("list", &["RPUSHX", "k", "a"], ":0\r\n"),
+    ("list", &["LPOS", "k", "p"], "$-1\r\n"),
+    ("list", &["LPOS", "k", "p", "COUNT", "0"], "*0\r\n"),
+    ("list", &["LREM", "k", "0", "p"], ":0\r\n"),
+    ("list", &["LMOVE", "k", "dst", "LEFT", "RIGHT"], "$-1\r\n"),
+    ("list", &["RPOPLPUSH", "k", "dst"], "$-1\r\n"),
+    ("list", &["LMPOP", "1", "k", "LEFT"], "*-1\r\n"),
+    ("set", &["SADD", "k", "a", "b", "a"], ":2\r\n"),
+    ("set", &["SREM", "k", "a"], ":0\r\n"),
+    ("set", &["SMEMBERS", "k"], "*0\r\n"),
+    ("set", &["SCARD", "k"], ":0\r\n"),
+    ("set", &["SISMEMBER", "k", "a"], ":0\r\n"),
+    ("set", &["SINTER", "k"], "*0\r\n"),
+    ("set", &["SUNION", "k"], "*0\r\n"),
+    ("set", &["SDIFF", "k"], "*0\r\n"),
+    ("set", &["SPOP", "k"], "$-1\r\n"),
+    ("set", &["SPOP", "k", "2"], "*0\r\n"),
+    ("set", &["SRANDMEMBER", "k"], "$-1\r\n"),
+    ("set", &["SRANDMEMBER", "k", "-2"], "*0\r\n"),
+    ("set", &["SMISMEMBER", "k", "a", "m"], "*2\r\n:0\r\n:0\r\n"),
+    ("set", &["SMOVE", "k", "sdst", "m"], ":0\r\n"),
+    ("set", &["SINTERCARD", "1", "k"], ":0\r\n"),
+    ("set", &["SINTERSTORE", "sdst", "k"], ":0\r\n"),
+    ("set", &["SUNIONSTORE", "sdst", "k"], ":0\r\n"),
+    ("set", &["SDIFFSTORE", "sdst", "k"], ":0\r\n"),
+    ("set", &["SSCAN", "k", "0"], "*2\r\n$1\r\n0\r\n*0\r\n"),
+    ("zset", &["ZADD", "k", "1", "a", "2", "b"], ":2\r\n"),
+    ("zset", &["ZREM", "k", "a"], ":0\r\n"),
+    ("zset", &["ZRANGE", "k", "0", "-1"], "*0\r\n"),
+    ("zset", &["ZRANGEBYSCORE", "k", "0", "10"], "*0\r\n"),
+    ("zset", &["ZCARD", "k"], ":0\r\n"),
+    ("zset", &["ZSCORE", "k", "a"], "$-1\r\n"),
+    ("zset", &["ZRANK", "k", "a"], "$-1\r\n"),
+];
+
+#[tokio::test]
+async fn test_every_command_on_a_missing_key() {
+    for (_, args, expected) in COMMANDS {
+        let db = Database::new_shared(16);
+        assert_eq!(run(&db, args).await, *expected, "{:?}", args);
+    }
+}
+
+#[tokio::test]
+async fn test_every_command_on_every_wrong_type() {
+    for (own, args, _) in COMMANDS {
+        for kind in TYPES {
+            let db = Database::new_shared(16);
+            populate(&db, kind, "k").await;
+            let reply = run(&db, args).await;
+            if kind == own {
+                assert!(!reply.starts_with('-'), "{:?} on {}: {}", args, kind, reply);
+            } else {
+                assert_eq!(reply, WRONGTYPE, "{:?} on {}", args, kind);
+                // The value is left untouched
+                assert_eq!(
+                    run(&db, &["TYPE", "k"]).await,
+                    format!("+{}\r\n", kind),
+                    "{:?} on {}",
+                    args,
+                    kind
+                );
+            }
+        }
+    }
+}
+
+/// A database that spills every value once it has been idle for a second
+fn tiered_db(dir: &std::path::Path) -> SharedDatabase {
+    let config = Config {
+        dir: dir.to_string_lossy().to_string(),
+        tiered_storage: true,
+        tiered_max_memory: 1,
+        tiered_min_value_size: 1,
+        ..Config::default()
+    };
+    let mut db = Database::new(16);
+    db.enable_tiered(TieredStore::open(&config).unwrap());
+    Arc::new(db)
+}
+
+#[tokio::test]
+async fn test_every_command_on_a_spilled_key() {
+    let dir = tempfile::tempdir().unwrap();
+    let spilled = tiered_db(dir.path());
+    let resident = Database::new_shared(16);
+    // A key per command and type, so that one wait spills them all
+    let key = |index: usize, kind: &str| format!("{}{}", kind, index);
+    for index in 0..COMMANDS.len() {
+        for kind in TYPES {
+            populate(&spilled, kind, &key(index, kind)).await;
+            populate(&resident, kind, &key(index, kind)).await;
+        }
+    }
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    spilled.tiered_cycle().unwrap();
+    let stats = spilled.tiered().unwrap().stats();
+    assert_eq!(
+        stats.spilled_keys.load(Ordering::Relaxed) as usize,
+        COMMANDS.len() * TYPES.len()
+    );
+
+    // A spilled value answers exactly as the same value in memory
+    for (index, (_, args, _)) in COMMANDS.iter().enumerate() {
+        for kind in TYPES {
+            let key = key(index, kind);
+            let args: Vec<&str> = args
+                .iter()
+                .map(|arg| if *arg == "k" { key.as_str() } else { arg })
+                .collect();
+            assert_eq!(
+                run(&spilled, &args).await,
+                run(&resident, &args).await,
+                "{:?} on a spilled {}",
+                args,
+                kind
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_multi_key_commands_check_every_key() {
+    for kind in TYPES.iter().filter(|k| **k != "set") {
+        let db = Database::new_shared(16);
+        populate(&db, "set", "s").await;
+        populate(&db, kind, "other").await;
+        for command in ["SINTER", "SUNION", "SDIFF"] {
+            assert_eq!(run(&db, &[command, "s", "other"]).await, WRONGTYPE);
+            // A missing key counts as an empty set
+            assert!(run(&db, &[command, "s", "missing"]).await.starts_with('*'));
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_type_agnostic_commands() {
+    for kind in TYPES {
+        let db = Database::new_shared(16);
+        populate(&db, kind, "k").await;
+        assert_eq!(run(&db, &["EXISTS", "k"]).await, ":1\r\n");
+        assert_eq!(run(&db, &["TYPE", "k"]).await, format!("+{}\r\n", kind));
+        assert_eq!(run(&db, &["SETNX", "k", "v"]).await, ":0\r\n");
+        assert_eq!(run(&db, &["SET", "k", "v", "NX"]).await, "$-1\r\n");
+        // MGET reports other types as nil instead of failing
+        if *kind != "string" {
+            assert_eq!(run(&db, &["MGET", "k"]).await, "*1\r\n$-1\r\n");
+        }
+        // SET and MSET replace a value of any type
+        assert_eq!(run(&db, &["MSET", "k", "w"]).await, "+OK\r\n");
+        assert_eq!(run(&db, &["SET", "k", "v"]).await, "+OK\r\n");
+        assert_eq!(run(&db, &["GET", "k"]).await, "$1\r\nv\r\n");
+        assert_eq!(run(&db, &["DEL", "k"]).await, ":1\r\n");
+        assert_eq!(run(&db, &["TYPE", "k"]).await, "+none\r\n");
+        assert_eq!(run(&db, &["TTL", "k"]).await, ":-2\r\n");
+    }
+}
+
+#[tokio::test]
+async fn test_set_algebra() {
+    let db = Database::new_shared(16);
+    run(&db, &["SADD", "a", "1", "2", "3"]).await;
+    run(&db, &["SADD", "b", "2", "3", "4"]).await;
+    let sorted = |reply: String| {
+        let mut members: Vec<String> = reply
+            .split("\r\n")
+            .skip(1)
+            .filter(|l| !l.starts_with('$') && !l.is_empty())
+            .map(str::to_string)
+            .collect();
+        members.sort();
+        members
+    };
+    assert_eq!(sorted(run(&db, &["SINTER", "a", "b"]).await), ["2", "3"]);
+    assert_eq!(
+        sorted(run(&db, &["SUNION", "a", "b"]).await),
+        ["1", "2", "3", "4"]
+    );
+    assert_eq!(sorted(run(&db, &["SDIFF", "a", "b"]).await), ["1"]);
+    assert_eq!(run(&db, &["SINTER", "a", "missing"]).await, "*0\r\n");
+}
+
+#[tokio::test]
+async fn test_linsert_and_zadd_replies() {
+    let db = Database::new_shared(16);
+    run(&db, &["RPUSH", "l", "a", "c"]).await;
+    // The new length, or -1 when the pivot is missing
+    assert_eq!(
+        run(&db, &["LINSERT", "l", "before", "c", "b"]).await,
+        ":3\r\n"
+    );
+    assert_eq!(
+        run(&db, &["LINSERT", "l", "AFTER", "x", "y"]).await,
+        ":-1\r\n"
+    );
+    assert_eq!(
+        run(&db, &["LRANGE", "l", "0", "-1"]).await,
+        "*3\r\n$1\r\na\r\n$1\r\nb\r\n$1\r\nc\r\n"
+    );
+
+    // ZADD counts new members only
+    assert_eq!(run(&db, &["ZADD", "z", "1", "a", "2", "b"]).await, ":2\r\n");
+    assert_eq!(run(&db, &["ZADD", "z", "5", "a", "3", "c"]).await, ":1\r\n");
+    assert_eq!(
+        run(&db, &["ZRANGE", "z", "0", "-1"]).await,
+        "*3\r\n$1\r\nb\r\n$1\r\nc\r\n$1\r\na\r\n"
+    );
+}
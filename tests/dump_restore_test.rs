@@ -0,0 +1,362 @@
+// Tests for DUMP, RESTORE and MIGRATE
+
+use bytes::Bytes;
+use rudis::commands::Command;
+use rudis::database::traits::{KeyOp, ListOp, SetOp, StringOp};
+use rudis::database::{Database, SharedDatabase};
+use rudis::networking::resp::{encode, Protocol, RespValue};
+use rudis::networking::Networking;
+use rudis::persistence::{crc64::crc64, rdb};
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+async fn run(db: &SharedDatabase, args: &[&[u8]]) -> Bytes {
+    let resp = RespValue::Array(
+        args.iter()
+            .map(|p| RespValue::BulkString(Bytes::copy_from_slice(p)))
+            .collect(),
+    );
+    let reply = Command::parse(&resp)
+        .expect("command should parse")
+        .execute(db)
+        .await;
+    encode(&reply, Protocol::Resp2)
+}
+
+/// Payload of a bulk string reply
+fn bulk_payload(reply: &[u8]) -> Vec<u8> {
+    let start = reply.iter().position(|&b| b == b'\n').unwrap() + 1;
+    reply[start..reply.len() - 2].to_vec()
+}
+
+/// Start a second instance on an ephemeral port
+async fn start_target() -> (SharedDatabase, SocketAddr) {
+    let networking = Networking::new("127.0.0.1:0").await.unwrap();
+    let addr = networking.local_addr().unwrap();
+    let db = Database::new_shared(16);
+    let listen_db = db.clone();
+    tokio::spawn(async move { networking.listen(&listen_db).await });
+    (db, addr)
+}
+
+#[test]
+fn test_dump_payload_format() {
+    let db = Database::new(16);
+    db.set(&Bytes::from("key"), Bytes::from("value"));
+    let payload = db.dump(&Bytes::from("key")).unwrap();
+
+    // type, length-prefixed string, RDB version, CRC64
+    assert_eq!(&payload[..7], b"\x00\x05value");
+    assert_eq!(&payload[7..9], &rdb::RDB_VERSION.to_le_bytes());
+    assert_eq!(&payload[9..], &crc64(0, &payload[..9]).to_le_bytes());
+    assert!(rdb::check_dump_payload(&payload));
+
+    assert_eq!(db.dump(&Bytes::from("missing")), None);
+}
+
+#[tokio::test]
+async fn test_dump_restore_round_trip() {
+    let db = Database::new_shared(16);
+    db.rpush(&Bytes::from("list"), &[Bytes::from("a"), Bytes::from("b")])
+        .unwrap();
+    db.sadd(&Bytes::from("set"), &[Bytes::from("m")]).unwrap();
+    run(&db, &[b"HSET", b"hash", b"f", b"v"]).await;
+    run(&db, &[b"SET", b"str", b"12345"]).await;
+
+    for key in ["list", "set", "hash", "str"] {
+        let payload = bulk_payload(&run(&db, &[b"DUMP", key.as_bytes()]).await);
+        let copy = format!("{}-copy", key);
+        assert_eq!(
+            &run(&db, &[b"RESTORE", copy.as_bytes(), b"0", &payload]).await[..],
+            b"+OK\r\n"
+        );
+        assert_eq!(
+            db.dump(&Bytes::from(copy)).unwrap(),
+            payload,
+            "{} did not round trip",
+            key
+        );
+    }
+    assert_eq!(
+        db.lrange(&Bytes::from("list-copy"), 0, -1).unwrap(),
+        vec![Bytes::from("a"), Bytes::from("b")]
+    );
+    assert_eq!(&run(&db, &[b"DUMP", b"missing"]).await[..], b"$-1\r\n");
+}
+
+#[tokio::test]
+async fn test_restore_options_and_errors() {
+    let db = Database::new_shared(16);
+    run(&db, &[b"SET", b"src", b"v"]).await;
+    let payload = bulk_payload(&run(&db, &[b"DUMP", b"src"]).await);
+
+    let busy = run(&db, &[b"RESTORE", b"src", b"0", &payload]).await;
+    assert!(String::from_utf8_lossy(&busy).contains("BUSYKEY"));
+    assert_eq!(
+        &run(&db, &[b"RESTORE", b"src", b"5000", &payload, b"REPLACE"]).await[..],
+        b"+OK\r\n"
+    );
+    let pttl = db.pttl(&Bytes::from("src"));
+    assert!(pttl > 4000 && pttl <= 5000);
+
+    // ABSTTL in the past: accepted, but the key does not survive
+    assert_eq!(
+        &run(&db, &[b"RESTORE", b"old", b"1000", &payload, b"ABSTTL"]).await[..],
+        b"+OK\r\n"
+    );
+    assert_eq!(db.exist(&[Bytes::from("old")]), 0);
+    let future = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis()
+        + 60_000;
+    let future = future.to_string();
+    run(
+        &db,
+        &[b"RESTORE", b"abs", future.as_bytes(), &payload, b"ABSTTL"],
+    )
+    .await;
+    assert!(db.ttl(&Bytes::from("abs")) > 50);
+
+    let ok = run(
+        &db,
+        &[b"RESTORE", b"lfu", b"0", &payload, b"IDLETIME", b"10"],
+    )
+    .await;
+    assert_eq!(&ok[..], b"+OK\r\n");
+    let bad_freq = run(&db, &[b"RESTORE", b"k", b"0", &payload, b"FREQ", b"256"]).await;
+    assert!(String::from_utf8_lossy(&bad_freq).contains("Invalid FREQ value"));
+    let bad_ttl = run(&db, &[b"RESTORE", b"k", b"-1", &payload]).await;
+    assert!(String::from_utf8_lossy(&bad_ttl).contains("Invalid TTL value"));
+
+    let mut corrupt = payload.clone();
+    corrupt[1] ^= 0xff;
+    let reply = run(&db, &[b"RESTORE", b"k", b"0", &corrupt]).await;
+    assert!(String::from_utf8_lossy(&reply).contains("DUMP payload version or checksum are wrong"));
+
+    let both = RespValue::Array(
+        ["RESTORE", "k", "0", "x", "IDLETIME", "1", "FREQ", "1"]
+            .iter()
+            .map(|p| RespValue::BulkString(Bytes::from(p.to_string())))
+            .collect(),
+    );
+    assert!(Command::parse(&both).is_none());
+}
+
+#[tokio::test]
+async fn test_restore_rejects_oversized_lzf_length() {
+    let db = Database::new_shared(16);
+    // A valid checksum over an LZF string claiming 2^40 bytes from a one
+    // byte literal
+    let mut payload = vec![0x00, 0xc3, 0x02, 0x81];
+    payload.extend_from_slice(&(1u64 << 40).to_be_bytes());
+    payload.extend_from_slice(&[0x00, b'x']);
+    payload.extend_from_slice(&rdb::RDB_VERSION.to_le_bytes());
+    payload.extend_from_slice(&crc64(0, &payload).to_le_bytes());
+    assert!(rdb::check_dump_payload(&payload));
+
+    let reply = run(&db, &[b"RESTORE", b"k", b"0", &payload]).await;
+    assert_eq!(&reply[..], b"-ERR Bad data format\r\n");
+    assert_eq!(db.exist(&[Bytes::from("k")]), 0);
+
+    // A length within the expansion limit that the data doesn't produce
+    let mut payload = vec![0x00, 0xc3, 0x02, 0x40, 0x64, 0x00, b'x'];
+    payload.extend_from_slice(&rdb::RDB_VERSION.to_le_bytes());
+    payload.extend_from_slice(&crc64(0, &payload).to_le_bytes());
+    let reply = run(&db, &[b"RESTORE", b"k", b"0", &payload]).await;
+    assert_eq!(&reply[..], b"-ERR Bad data format\r\n");
+}
+
+#[tokio::test]
+async fn test_migrate_to_second_instance() {
+    let (target, addr) = start_target().await;
+    let port = addr.port().to_string();
+    let source = Database::new_shared(16);
+    run(&source, &[b"SET", b"a", b"1"]).await;
+    run(&source, &[b"SET", b"b", b"2"]).await;
+    run(&source, &[b"SET", b"c", b"3"]).await;
+    source.expire(&Bytes::from("a"), 100).unwrap();
+
+    let reply = run(
+        &source,
+        &[
+            b"MIGRATE",
+            b"127.0.0.1",
+            port.as_bytes(),
+            b"a",
+            b"2",
+            b"1000",
+        ],
+    )
+    .await;
+    assert_eq!(&reply[..], b"+OK\r\n");
+    assert_eq!(source.exist(&[Bytes::from("a")]), 0);
+    target.select(2);
+    assert_eq!(
+        target.get(&Bytes::from("a")).unwrap(),
+        Some(Bytes::from("1"))
+    );
+    let ttl = target.ttl(&Bytes::from("a"));
+    assert!(ttl > 90 && ttl <= 100);
+
+    // KEYS with COPY keeps the source, missing keys are skipped
+    let reply = run(
+        &source,
+        &[
+            b"MIGRATE",
+            b"127.0.0.1",
+            port.as_bytes(),
+            b"",
+            b"2",
+            b"1000",
+            b"COPY",
+            b"KEYS",
+            b"b",
+            b"missing",
+            b"c",
+        ],
+    )
+    .await;
+    assert_eq!(&reply[..], b"+OK\r\n");
+    assert_eq!(source.exist(&[Bytes::from("b"), Bytes::from("c")]), 2);
+    assert_eq!(
+        target.get(&Bytes::from("c")).unwrap(),
+        Some(Bytes::from("3"))
+    );
+
+    // The target already holds "b": the error is forwarded and "b" stays put
+    run(&source, &[b"SET", b"b", b"new"]).await;
+    let reply = run(
+        &source,
+        &[
+            b"MIGRATE",
+            b"127.0.0.1",
+            port.as_bytes(),
+            b"b",
+            b"2",
+            b"1000",
+        ],
+    )
+    .await;
+    let reply = String::from_utf8_lossy(&reply).to_string();
+    assert!(
+        reply.contains("Target instance replied with error"),
+        "{}",
+        reply
+    );
+    assert!(reply.contains("BUSYKEY"));
+    assert_eq!(
+        source.get(&Bytes::from("b")).unwrap(),
+        Some(Bytes::from("new"))
+    );
+
+    let reply = run(
+        &source,
+        &[
+            b"MIGRATE",
+            b"127.0.0.1",
+            port.as_bytes(),
+            b"b",
+            b"2",
+            b"1000",
+            b"REPLACE",
+        ],
+    )
+    .await;
+    assert_eq!(&reply[..], b"+OK\r\n");
+    assert_eq!(
+        target.get(&Bytes::from("b")).unwrap(),
+        Some(Bytes::from("new"))
+    );
+
+    let reply = run(
+        &source,
+        &[
+            b"MIGRATE",
+            b"127.0.0.1",
+            port.as_bytes(),
+            b"nothing",
+            b"2",
+            b"1000",
+        ],
+    )
+    .await;
+    assert_eq!(&reply[..], b"+NOKEY\r\n");
+}
+
+#[tokio::test]
+async fn test_migrate_keeps_keys_written_during_the_exchange() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port().to_string();
+    let source = Database::new_shared(16);
+    run(&source, &[b"SET", b"a", b"1"]).await;
+    run(&source, &[b"SET", b"b", b"2"]).await;
+
+    // A target that accepts both keys, but only after "a" changed locally
+    let writer = source.clone();
+    let target = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = Vec::new();
+        let restores = |request: &[u8]| request.windows(7).filter(|w| w == b"RESTORE").count();
+        while restores(&request) < 2 || !request.ends_with(b"\r\n") {
+            let mut chunk = [0u8; 1024];
+            let n = stream.read(&mut chunk).await.unwrap();
+            assert!(n > 0, "MIGRATE closed the connection early");
+            request.extend_from_slice(&chunk[..n]);
+        }
+        run(&writer, &[b"SET", b"a", b"changed"]).await;
+        stream.write_all(b"+OK\r\n+OK\r\n+OK\r\n").await.unwrap();
+    });
+
+    let reply = run(
+        &source,
+        &[
+            b"MIGRATE",
+            b"127.0.0.1",
+            port.as_bytes(),
+            b"",
+            b"0",
+            b"1000",
+            b"KEYS",
+            b"a",
+            b"b",
+        ],
+    )
+    .await;
+    target.await.unwrap();
+    assert_eq!(&reply[..], b"+OK\r\n");
+    assert_eq!(
+        source.get(&Bytes::from("a")).unwrap(),
+        Some(Bytes::from("changed"))
+    );
+    assert_eq!(source.exist(&[Bytes::from("b")]), 0);
+}
+
+#[tokio::test]
+async fn test_migrate_unreachable_target() {
+    // Bind and drop a listener to get a port nothing listens on
+    let port = {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().port().to_string()
+    };
+    let source = Database::new_shared(16);
+    run(&source, &[b"SET", b"a", b"1"]).await;
+    let reply = run(
+        &source,
+        &[
+            b"MIGRATE",
+            b"127.0.0.1",
+            port.as_bytes(),
+            b"a",
+            b"0",
+            b"200",
+        ],
+    )
+    .await;
+    assert!(String::from_utf8_lossy(&reply).contains("IOERR"));
+    assert_eq!(
+        source.get(&Bytes::from("a")).unwrap(),
+        Some(Bytes::from("1"))
+    );
+}
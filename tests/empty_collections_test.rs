@@ -0,0 +1,81 @@
+// Tests that collections emptied by a command are deleted with their expiry
+
+use bytes::Bytes;
+use rudis::commands::Command;
+use rudis::data_structures::RedisList;
+use rudis::database::traits::KeyOp;
+use rudis::database::{Database, RedisValue, SharedDatabase};
+use rudis::networking::resp::{encode, Protocol, RespValue};
+use rudis::persistence::rdb;
+
+async fn run(db: &SharedDatabase, args: &[&str]) -> String {
+    let resp = RespValue::Array(
+        args.iter()
+            .map(|a| RespValue::BulkString(Bytes::from(a.to_string())))
+            .collect(),
+    );
+    let reply = Command::parse(&resp)
+        .unwrap_or_else(|| panic!("{:?} should parse", args))
+        .execute(db)
+        .await;
+    String::from_utf8_lossy(&encode(&reply, Protocol::Resp2)).to_string()
+}
+
+async fn assert_gone(db: &SharedDatabase, key: &str) {
+    assert_eq!(run(db, &["EXISTS", key]).await, ":0\r\n", "{}", key);
+    assert_eq!(run(db, &["TYPE", key]).await, "+none\r\n", "{}", key);
+    assert_eq!(run(db, &["TTL", key]).await, ":-2\r\n", "{}", key);
+}
+
+#[tokio::test]
+async fn test_removing_the_last_element_deletes_the_key() {
+    // (command creating a one element collection, command removing it)
+    let cases: &[(&[&str], &[&str])] = &[
+        (&["RPUSH", "k", "a"], &["LPOP", "k"]),
+        (&["RPUSH", "k", "a"], &["RPOP", "k"]),
+        (&["RPUSH", "k", "a"], &["LTRIM", "k", "1", "0"]),
+        (&["SADD", "k", "a"], &["SREM", "k", "a", "b"]),
+        (&["HSET", "k", "f", "v"], &["HDEL", "k", "f"]),
+        (&["ZADD", "k", "1", "a"], &["ZREM", "k", "a"]),
+    ];
+    for (create, remove) in cases {
+        let db = Database::new_shared(16);
+        run(&db, create).await;
+        run(&db, &["EXPIRE", "k", "100"]).await;
+        assert_ne!(run(&db, &["TTL", "k"]).await, ":-1\r\n");
+        assert!(!run(&db, remove).await.starts_with('-'), "{:?}", remove);
+        assert_gone(&db, "k").await;
+
+        // A new value under the same name does not inherit the old TTL
+        run(&db, create).await;
+        assert_eq!(run(&db, &["TTL", "k"]).await, ":-1\r\n", "{:?}", remove);
+    }
+}
+
+#[tokio::test]
+async fn test_partial_removal_keeps_the_key() {
+    let db = Database::new_shared(16);
+    run(&db, &["RPUSH", "l", "a", "b"]).await;
+    run(&db, &["EXPIRE", "l", "100"]).await;
+    assert_eq!(run(&db, &["LPOP", "l"]).await, "$1\r\na\r\n");
+    assert_eq!(run(&db, &["EXISTS", "l"]).await, ":1\r\n");
+    assert_ne!(run(&db, &["TTL", "l"]).await, ":-1\r\n");
+
+    // A failed removal leaves other types alone
+    run(&db, &["SET", "s", "v"]).await;
+    assert!(run(&db, &["LPOP", "s"]).await.starts_with("-WRONGTYPE"));
+    assert_eq!(run(&db, &["GET", "s"]).await, "$1\r\nv\r\n");
+
+    // Empty strings are values, not collections
+    run(&db, &["SET", "e", ""]).await;
+    assert_eq!(run(&db, &["EXISTS", "e"]).await, ":1\r\n");
+}
+
+#[tokio::test]
+async fn test_empty_collections_are_not_restored() {
+    let db = Database::new_shared(16);
+    let payload = rdb::dump_value(&RedisValue::List(RedisList::new()));
+    let value = rdb::restore_value(&payload).unwrap();
+    db.restore(&Bytes::from("k"), value, None, false).unwrap();
+    assert_gone(&db, "k").await;
+}
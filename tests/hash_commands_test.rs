@@ -0,0 +1,301 @@
+// Tests for the hash commands: multi-field writes, counters, HRANDFIELD and HSCAN
+
+use bytes::Bytes;
+use rudis::commands::{CommandError, Reply};
+use rudis::database::Database;
+use rudis::Client;
+use std::collections::{HashMap, HashSet};
+
+fn client() -> Client {
+    Client::new(&Database::new_shared(16))
+}
+
+fn bulks(reply: Reply) -> Vec<Bytes> {
+    match reply {
+        Reply::Array(items) => items
+            .into_iter()
+            .map(|item| match item {
+                Reply::Bulk(value) => value,
+                other => panic!("expected a bulk string, got {:?}", other),
+            })
+            .collect(),
+        other => panic!("expected an array, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_multi_field_writes() {
+    let client = client();
+    assert_eq!(
+        client.call(&[b"HSET", b"h", b"a", b"1", b"b", b"2"]).await,
+        Reply::Integer(2)
+    );
+    // Only new fields count
+    assert_eq!(
+        client.call(&[b"HSET", b"h", b"a", b"9", b"c", b"3"]).await,
+        Reply::Integer(1)
+    );
+    assert_eq!(
+        client.call(&[b"HSET", b"h", b"a", b"1", b"b"]).await,
+        CommandError::WrongArity("hset".into()).into()
+    );
+    assert_eq!(
+        client.call(&[b"HMSET", b"h", b"d", b"4"]).await,
+        Reply::ok()
+    );
+    assert_eq!(
+        client.call(&[b"HMSET", b"h", b"d"]).await,
+        CommandError::WrongArity("hmset".into()).into()
+    );
+    assert_eq!(
+        client.call(&[b"HMGET", b"h", b"a", b"missing", b"d"]).await,
+        Reply::Array(vec![Reply::bulk("9"), Reply::Null, Reply::bulk("4")])
+    );
+    assert_eq!(
+        client.call(&[b"HMGET", b"nokey", b"a"]).await,
+        Reply::Array(vec![Reply::Null])
+    );
+
+    assert_eq!(
+        client.call(&[b"HSETNX", b"h", b"a", b"x"]).await,
+        Reply::Integer(0)
+    );
+    assert_eq!(
+        client.call(&[b"HSETNX", b"h", b"e", b"hello"]).await,
+        Reply::Integer(1)
+    );
+    assert_eq!(
+        client.call(&[b"HSTRLEN", b"h", b"e"]).await,
+        Reply::Integer(5)
+    );
+    assert_eq!(
+        client.call(&[b"HSTRLEN", b"h", b"missing"]).await,
+        Reply::Integer(0)
+    );
+
+    client.call(&[b"RPUSH", b"list", b"x"]).await;
+    for command in [
+        &[&b"HMGET"[..], b"list", b"a"][..],
+        &[b"HSETNX", b"list", b"a", b"1"],
+        &[b"HSTRLEN", b"list", b"a"],
+        &[b"HRANDFIELD", b"list"],
+        &[b"HSCAN", b"list", b"0"],
+    ] {
+        assert_eq!(
+            client.call(command).await,
+            CommandError::WrongType.into(),
+            "{:?}",
+            command
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_hash_counters() {
+    let client = client();
+    client
+        .call(&[b"HSET", b"h", b"n", i64::MAX.to_string().as_bytes()])
+        .await;
+    assert_eq!(
+        client.call(&[b"HINCRBY", b"h", b"n", b"1"]).await,
+        CommandError::Overflow.into()
+    );
+    assert_eq!(
+        client.call(&[b"HGET", b"h", b"n"]).await,
+        Reply::bulk(i64::MAX.to_string())
+    );
+    assert_eq!(
+        client.call(&[b"HINCRBY", b"h", b"n", b"-10"]).await,
+        Reply::Integer(i64::MAX - 10)
+    );
+    assert_eq!(
+        client.call(&[b"HINCRBY", b"h", b"n", b"ten"]).await,
+        CommandError::InvalidInteger.into()
+    );
+    client.call(&[b"HSET", b"h", b"s", b"abc"]).await;
+    assert_eq!(
+        client.call(&[b"HINCRBY", b"h", b"s", b"1"]).await,
+        CommandError::HashValueNotInteger.into()
+    );
+
+    assert_eq!(
+        client.call(&[b"HINCRBYFLOAT", b"h", b"f", b"1.5"]).await,
+        Reply::bulk("1.5")
+    );
+    assert_eq!(
+        client.call(&[b"HINCRBYFLOAT", b"h", b"s", b"1"]).await,
+        CommandError::HashValueNotFloat.into()
+    );
+    assert_eq!(
+        client.call(&[b"HINCRBYFLOAT", b"h", b"f", b"nan"]).await,
+        CommandError::InvalidFloat.into()
+    );
+    assert_eq!(
+        client.call(&[b"HINCRBYFLOAT", b"h", b"f", b"inf"]).await,
+        CommandError::NanOrInfinity.into()
+    );
+    assert_eq!(
+        client.call(&[b"HGET", b"h", b"f"]).await,
+        Reply::bulk("1.5")
+    );
+    // A failed increment does not create the key
+    assert_eq!(
+        client.call(&[b"HINCRBYFLOAT", b"new", b"f", b"-inf"]).await,
+        CommandError::NanOrInfinity.into()
+    );
+    assert_eq!(client.call(&[b"EXISTS", b"new"]).await, Reply::Integer(0));
+}
+
+#[tokio::test]
+async fn test_hrandfield() {
+    let client = client();
+    client
+        .call(&[b"HSET", b"h", b"a", b"1", b"b", b"2", b"c", b"3"])
+        .await;
+    let fields: HashSet<Bytes> = ["a", "b", "c"].into_iter().map(Bytes::from).collect();
+
+    match client.call(&[b"HRANDFIELD", b"h"]).await {
+        Reply::Bulk(field) => assert!(fields.contains(&field)),
+        other => panic!("expected a field, got {:?}", other),
+    }
+    assert_eq!(client.call(&[b"HRANDFIELD", b"missing"]).await, Reply::Null);
+
+    // A positive count picks different fields, at most all of them
+    let picked = bulks(client.call(&[b"HRANDFIELD", b"h", b"2"]).await);
+    assert_eq!(picked.len(), 2);
+    assert_eq!(picked.iter().collect::<HashSet<_>>().len(), 2);
+    let picked = bulks(client.call(&[b"HRANDFIELD", b"h", b"10"]).await);
+    assert_eq!(picked.into_iter().collect::<HashSet<_>>(), fields);
+    // A negative one may repeat them
+    let picked = bulks(client.call(&[b"HRANDFIELD", b"h", b"-10"]).await);
+    assert_eq!(picked.len(), 10);
+    assert!(picked.iter().all(|field| fields.contains(field)));
+    assert_eq!(
+        client.call(&[b"HRANDFIELD", b"h", b"0"]).await,
+        Reply::Array(vec![])
+    );
+    assert_eq!(
+        client.call(&[b"HRANDFIELD", b"missing", b"-3"]).await,
+        Reply::Array(vec![])
+    );
+
+    match client
+        .call(&[b"HRANDFIELD", b"h", b"-5", b"WITHVALUES"])
+        .await
+    {
+        Reply::Pairs(pairs) => {
+            assert_eq!(pairs.len(), 5);
+            for (field, value) in pairs {
+                let expected = match field {
+                    Reply::Bulk(ref f) if f == "a" => "1",
+                    Reply::Bulk(ref f) if f == "b" => "2",
+                    _ => "3",
+                };
+                assert_eq!(value, Reply::bulk(expected));
+            }
+        }
+        other => panic!("expected pairs, got {:?}", other),
+    }
+
+    assert_eq!(
+        client.call(&[b"HRANDFIELD", b"h", b"one"]).await,
+        CommandError::InvalidInteger.into()
+    );
+    assert_eq!(
+        client
+            .call(&[b"HRANDFIELD", b"h", b"1", b"WITHSCORES"])
+            .await,
+        CommandError::SyntaxError.into()
+    );
+    assert_eq!(
+        client
+            .call(&[b"HRANDFIELD", b"h", b"-9223372036854775807", b"WITHVALUES"])
+            .await,
+        CommandError::Custom("value is out of range".into()).into()
+    );
+}
+
+#[tokio::test]
+async fn test_hscan() {
+    let client = client();
+    for i in 0..100 {
+        let field = format!("field:{}", i);
+        client
+            .call(&[b"HSET", b"h", field.as_bytes(), i.to_string().as_bytes()])
+            .await;
+    }
+
+    // Every field comes back exactly once, even with fields removed and
+    // added while scanning
+    let mut seen = HashMap::new();
+    let mut cursor = Bytes::from("0");
+    let mut steps = 0;
+    loop {
+        let reply = client
+            .call(&[b"HSCAN", b"h", &cursor, b"COUNT", b"7"])
+            .await;
+        let Reply::Array(mut parts) = reply else {
+            panic!("expected an array, got {:?}", reply);
+        };
+        let items = bulks(parts.pop().unwrap());
+        for pair in items.chunks(2) {
+            assert!(seen.insert(pair[0].clone(), pair[1].clone()).is_none());
+        }
+        steps += 1;
+        if steps == 3 {
+            client.call(&[b"HDEL", b"h", b"field:0", b"field:1"]).await;
+            client.call(&[b"HSET", b"h", b"extra", b"x"]).await;
+        }
+        cursor = bulks(Reply::Array(parts)).remove(0);
+        if cursor == "0" {
+            break;
+        }
+    }
+    assert!(steps > 10);
+    for i in 2..100 {
+        assert_eq!(
+            seen.get(format!("field:{}", i).as_bytes()),
+            Some(&Bytes::from(i.to_string()))
+        );
+    }
+
+    // MATCH filters what a step returns; NOVALUES leaves out the values
+    let reply = client
+        .call(&[
+            b"HSCAN",
+            b"h",
+            b"0",
+            b"MATCH",
+            b"field:1?",
+            b"COUNT",
+            b"1000",
+            b"NOVALUES",
+        ])
+        .await;
+    let Reply::Array(mut parts) = reply else {
+        panic!("expected an array, got {:?}", reply);
+    };
+    let fields: HashSet<Bytes> = bulks(parts.pop().unwrap()).into_iter().collect();
+    let expected: HashSet<Bytes> = (10..20)
+        .map(|i| Bytes::from(format!("field:{}", i)))
+        .collect();
+    assert_eq!(fields, expected);
+    assert_eq!(parts, vec![Reply::bulk("0")]);
+
+    assert_eq!(
+        client.call(&[b"HSCAN", b"missing", b"0"]).await,
+        Reply::Array(vec![Reply::bulk("0"), Reply::Array(vec![])])
+    );
+    assert_eq!(
+        client.call(&[b"HSCAN", b"h", b"abc"]).await,
+        CommandError::InvalidCursor.into()
+    );
+    assert_eq!(
+        client.call(&[b"HSCAN", b"h", b"0", b"COUNT", b"0"]).await,
+        CommandError::SyntaxError.into()
+    );
+    assert_eq!(
+        client.call(&[b"HSCAN", b"h", b"0", b"COUNT"]).await,
+        CommandError::SyntaxError.into()
+    );
+}
@@ -1,7 +1,11 @@
 // Test for HDEL functionality fix
 
 use bytes::Bytes;
-use rudis::database::{Database, traits::{HashOp, StringOp}};
+use rudis::commands::CommandError;
+use rudis::database::{
+    traits::{HashOp, StringOp},
+    Database,
+};
 
 #[test]
 fn test_hdel_multiple_fields() {
@@ -42,14 +46,16 @@ fn test_hdel_multiple_fields() {
     );
 
     // Delete multiple fields at once
-    let deleted_count = db.hdel_multiple(
-        &Bytes::from("user:1"),
-        &[
-            Bytes::from("age"),
-            Bytes::from("city"),
-            Bytes::from("nonexistent"),
-        ],
-    );
+    let deleted_count = db
+        .hdel_multiple(
+            &Bytes::from("user:1"),
+            &[
+                Bytes::from("age"),
+                Bytes::from("city"),
+                Bytes::from("nonexistent"),
+            ],
+        )
+        .unwrap();
     assert_eq!(deleted_count, 2); // Should delete 2 fields (age and city), nonexistent doesn't count
 
     // Verify the correct fields were deleted
@@ -76,10 +82,12 @@ fn test_hdel_nonexistent_hash() {
     let db = Database::new(16);
 
     // Try to delete from non-existent hash
-    let deleted_count = db.hdel_multiple(
-        &Bytes::from("nonexistent"),
-        &[Bytes::from("field1"), Bytes::from("field2")],
-    );
+    let deleted_count = db
+        .hdel_multiple(
+            &Bytes::from("nonexistent"),
+            &[Bytes::from("field1"), Bytes::from("field2")],
+        )
+        .unwrap();
     assert_eq!(deleted_count, 0);
 }
 
@@ -90,7 +98,9 @@ fn test_hdel_wrong_type() {
     // Set a string value
     db.set(&Bytes::from("mystring"), Bytes::from("value"));
 
-    // Try HDEL on string - should return 0 (no fields deleted)
-    let deleted_count = db.hdel_multiple(&Bytes::from("mystring"), &[Bytes::from("field1")]);
-    assert_eq!(deleted_count, 0);
+    // Try HDEL on string - should fail with WRONGTYPE
+    assert_eq!(
+        db.hdel_multiple(&Bytes::from("mystring"), &[Bytes::from("field1")]),
+        Err(CommandError::WrongType)
+    );
 }
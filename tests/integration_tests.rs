@@ -2,9 +2,13 @@
 // Tests the full server functionality with command parsing and execution
 
 use bytes::Bytes;
-use rudis::commands::Command;
-use rudis::database::Database;
-use rudis::networking::resp::RespValue;
+use rudis::client::{Options, Pool};
+use rudis::commands::{Command, CommandError, Reply};
+use rudis::config::Config;
+use rudis::database::{traits::StringOp, Database};
+use rudis::networking::resp::{encode, Protocol, RespValue};
+use rudis::persistence::Persistence;
+use rudis::server::Server;
 
 #[test]
 fn test_command_parsing_and_execution_integration() {
@@ -20,7 +24,7 @@ fn test_command_parsing_and_execution_integration() {
 
     if let Some(cmd) = Command::parse(&set_cmd) {
         let rt = tokio::runtime::Runtime::new().unwrap();
-        let result_bytes = rt.block_on(cmd.execute(&db));
+        let result_bytes = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         let result = String::from_utf8_lossy(&result_bytes);
         assert_eq!(result, "+OK\r\n");
     } else {
@@ -35,7 +39,7 @@ fn test_command_parsing_and_execution_integration() {
 
     if let Some(cmd) = Command::parse(&get_cmd) {
         let rt = tokio::runtime::Runtime::new().unwrap();
-        let result_bytes = rt.block_on(cmd.execute(&db));
+        let result_bytes = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         let result = String::from_utf8_lossy(&result_bytes);
         assert_eq!(result, "$17\r\nintegration_value\r\n");
     } else {
@@ -58,7 +62,7 @@ fn test_hash_operations_integration() {
 
     if let Some(cmd) = Command::parse(&hset_cmd) {
         let rt = tokio::runtime::Runtime::new().unwrap();
-        let result_bytes = rt.block_on(cmd.execute(&db));
+        let result_bytes = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         let result = String::from_utf8_lossy(&result_bytes);
         assert_eq!(result, ":1\r\n");
     }
@@ -72,7 +76,7 @@ fn test_hash_operations_integration() {
 
     if let Some(cmd) = Command::parse(&hget_cmd) {
         let rt = tokio::runtime::Runtime::new().unwrap();
-        let result_bytes = rt.block_on(cmd.execute(&db));
+        let result_bytes = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         let result = String::from_utf8_lossy(&result_bytes);
         assert_eq!(result, "$5\r\nAlice\r\n");
     }
@@ -85,7 +89,7 @@ fn test_hash_operations_integration() {
 
     if let Some(cmd) = Command::parse(&hgetall_cmd) {
         let rt = tokio::runtime::Runtime::new().unwrap();
-        let result_bytes = rt.block_on(cmd.execute(&db));
+        let result_bytes = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         let result = String::from_utf8_lossy(&result_bytes);
         assert!(result.contains("$4\r\nname\r\n"));
         assert!(result.contains("$5\r\nAlice\r\n"));
@@ -119,7 +123,7 @@ fn test_multiple_operations_integration() {
         let resp_value = RespValue::Array(resp_parts);
 
         if let Some(cmd) = Command::parse(&resp_value) {
-            let result_bytes = rt.block_on(cmd.execute(&db));
+            let result_bytes = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
             let result = String::from_utf8_lossy(&result_bytes);
             assert_eq!(result, expected, "Command '{}' failed", cmd_str);
         } else {
@@ -151,9 +155,9 @@ fn test_error_handling_integration() {
     ]);
 
     if let Some(cmd) = Command::parse(&incr_cmd) {
-        let result_bytes = rt.block_on(cmd.execute(&db));
+        let result_bytes = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         let result = String::from_utf8_lossy(&result_bytes);
-        assert!(result.contains("-ERR"));
+        assert_eq!(result, "-ERR value is not an integer or out of range\r\n");
     }
 }
 
@@ -170,14 +174,14 @@ fn test_numeric_operations_integration() {
     ]);
 
     if let Some(cmd) = Command::parse(&incr_cmd) {
-        let result_bytes = rt.block_on(cmd.execute(&db));
+        let result_bytes = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         let result = String::from_utf8_lossy(&result_bytes);
         assert_eq!(result, ":1\r\n");
     }
 
     // Test INCR on existing value
     if let Some(cmd) = Command::parse(&incr_cmd) {
-        let result_bytes = rt.block_on(cmd.execute(&db));
+        let result_bytes = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         let result = String::from_utf8_lossy(&result_bytes);
         assert_eq!(result, ":2\r\n");
     }
@@ -189,7 +193,7 @@ fn test_numeric_operations_integration() {
     ]);
 
     if let Some(cmd) = Command::parse(&decr_cmd) {
-        let result_bytes = rt.block_on(cmd.execute(&db));
+        let result_bytes = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         let result = String::from_utf8_lossy(&result_bytes);
         assert_eq!(result, ":1\r\n");
     }
@@ -202,7 +206,7 @@ fn test_numeric_operations_integration() {
     ]);
 
     if let Some(cmd) = Command::parse(&incrby_cmd) {
-        let result_bytes = rt.block_on(cmd.execute(&db));
+        let result_bytes = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         let result = String::from_utf8_lossy(&result_bytes);
         assert_eq!(result, ":6\r\n");
     }
@@ -215,7 +219,7 @@ fn test_numeric_operations_integration() {
     ]);
 
     if let Some(cmd) = Command::parse(&decrby_cmd) {
-        let result = rt.block_on(cmd.execute(&db));
+        let result = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         assert_eq!(result, ":3\r\n");
     }
 }
@@ -234,7 +238,7 @@ fn test_string_operations_integration() {
     ]);
 
     if let Some(cmd) = Command::parse(&append_cmd1) {
-        let result = rt.block_on(cmd.execute(&db));
+        let result = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         assert_eq!(result, ":5\r\n");
     }
 
@@ -246,7 +250,7 @@ fn test_string_operations_integration() {
     ]);
 
     if let Some(cmd) = Command::parse(&append_cmd2) {
-        let result = rt.block_on(cmd.execute(&db));
+        let result = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         assert_eq!(result, ":11\r\n");
     }
 
@@ -257,7 +261,7 @@ fn test_string_operations_integration() {
     ]);
 
     if let Some(cmd) = Command::parse(&strlen_cmd) {
-        let result = rt.block_on(cmd.execute(&db));
+        let result = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         assert_eq!(result, ":11\r\n");
     }
 
@@ -268,7 +272,7 @@ fn test_string_operations_integration() {
     ]);
 
     if let Some(cmd) = Command::parse(&strlen_cmd2) {
-        let result = rt.block_on(cmd.execute(&db));
+        let result = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         assert_eq!(result, ":0\r\n");
     }
 }
@@ -295,7 +299,7 @@ fn test_del_operations_integration() {
         let resp_value = RespValue::Array(resp_parts);
 
         if let Some(cmd) = Command::parse(&resp_value) {
-            let result_bytes = rt.block_on(cmd.execute(&db));
+            let result_bytes = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
             let result = String::from_utf8_lossy(&result_bytes);
             assert_eq!(result, expected);
         }
@@ -310,7 +314,7 @@ fn test_del_operations_integration() {
     ]);
 
     if let Some(cmd) = Command::parse(&del_cmd) {
-        let result = rt.block_on(cmd.execute(&db));
+        let result = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         assert_eq!(result, ":2\r\n"); // 2 keys deleted, 1 didn't exist
     }
 
@@ -321,7 +325,7 @@ fn test_del_operations_integration() {
     ]);
 
     if let Some(cmd) = Command::parse(&get_cmd) {
-        let result = rt.block_on(cmd.execute(&db));
+        let result = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         assert_eq!(result, "$-1\r\n"); // Key doesn't exist
     }
 }
@@ -336,7 +340,7 @@ fn test_ping_variations_integration() {
     let ping_cmd1 = RespValue::Array(vec![RespValue::BulkString(Bytes::from("PING".to_string()))]);
 
     if let Some(cmd) = Command::parse(&ping_cmd1) {
-        let result = rt.block_on(cmd.execute(&db));
+        let result = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         assert_eq!(result, "+PONG\r\n");
     }
 
@@ -347,7 +351,7 @@ fn test_ping_variations_integration() {
     ]);
 
     if let Some(cmd) = Command::parse(&ping_cmd2) {
-        let result = rt.block_on(cmd.execute(&db));
+        let result = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         assert_eq!(result, "$11\r\nhello world\r\n");
     }
 }
@@ -374,7 +378,7 @@ fn test_hash_comprehensive_integration() {
         let resp_value = RespValue::Array(resp_parts);
 
         if let Some(cmd) = Command::parse(&resp_value) {
-            let result_bytes = rt.block_on(cmd.execute(&db));
+            let result_bytes = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
             let result = String::from_utf8_lossy(&result_bytes);
             assert_eq!(result, expected);
         }
@@ -387,7 +391,7 @@ fn test_hash_comprehensive_integration() {
     ]);
 
     if let Some(cmd) = Command::parse(&hgetall_cmd) {
-        let result_bytes = rt.block_on(cmd.execute(&db));
+        let result_bytes = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         let result = String::from_utf8_lossy(&result_bytes);
         // Should contain all key-value pairs
         assert!(result.contains("$4\r\nname\r\n"));
@@ -406,7 +410,7 @@ fn test_hash_comprehensive_integration() {
     ]);
 
     if let Some(cmd) = Command::parse(&hdel_cmd) {
-        let result_bytes = rt.block_on(cmd.execute(&db));
+        let result_bytes = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         let result = String::from_utf8_lossy(&result_bytes);
         assert_eq!(result, ":1\r\n"); // 1 field deleted
     }
@@ -419,7 +423,7 @@ fn test_hash_comprehensive_integration() {
     ]);
 
     if let Some(cmd) = Command::parse(&hget_cmd) {
-        let result_bytes = rt.block_on(cmd.execute(&db));
+        let result_bytes = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         let result = String::from_utf8_lossy(&result_bytes);
         assert_eq!(result, "$-1\r\n"); // Field doesn't exist
     }
@@ -450,9 +454,12 @@ fn test_type_conflicts_integration() {
     ]);
 
     if let Some(cmd) = Command::parse(&hget_cmd) {
-        let result_bytes = rt.block_on(cmd.execute(&db));
+        let result_bytes = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         let result = String::from_utf8_lossy(&result_bytes);
-        assert!(result.contains("WRONGTYPE"));
+        assert_eq!(
+            result,
+            "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"
+        );
     }
 
     let hset_cmd = RespValue::Array(vec![
@@ -463,9 +470,12 @@ fn test_type_conflicts_integration() {
     ]);
 
     if let Some(cmd) = Command::parse(&hset_cmd) {
-        let result_bytes = rt.block_on(cmd.execute(&db));
+        let result_bytes = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         let result = String::from_utf8_lossy(&result_bytes);
-        assert!(result.contains("WRONGTYPE"));
+        assert_eq!(
+            result,
+            "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"
+        );
     }
 
     let hgetall_cmd = RespValue::Array(vec![
@@ -474,9 +484,12 @@ fn test_type_conflicts_integration() {
     ]);
 
     if let Some(cmd) = Command::parse(&hgetall_cmd) {
-        let result_bytes = rt.block_on(cmd.execute(&db));
+        let result_bytes = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
         let result = String::from_utf8_lossy(&result_bytes);
-        assert!(result.contains("WRONGTYPE"));
+        assert_eq!(
+            result,
+            "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"
+        );
     }
 }
 
@@ -537,7 +550,7 @@ fn test_complex_sequence_integration() {
         let resp_value = RespValue::Array(resp_parts);
 
         if let Some(cmd) = Command::parse(&resp_value) {
-            let result_bytes = rt.block_on(cmd.execute(&db));
+            let result_bytes = encode(&rt.block_on(cmd.execute(&db)), Protocol::Resp2);
             let result = String::from_utf8_lossy(&result_bytes);
             assert_eq!(result, expected, "Command '{}' failed", cmd_str);
         } else {
@@ -546,12 +559,152 @@ fn test_complex_sequence_integration() {
     }
 }
 
-// Placeholder for future TCP server tests
-// These would require a running server instance
-/*
+fn parse_error(args: &[&str]) -> String {
+    let resp_value = RespValue::Array(
+        args.iter()
+            .map(|a| RespValue::BulkString(Bytes::from(a.to_string())))
+            .collect(),
+    );
+    let err = Command::try_parse(&resp_value).expect_err("command should be rejected");
+    String::from_utf8_lossy(&encode(&err.into(), Protocol::Resp2)).to_string()
+}
+
 #[test]
-fn test_tcp_server_integration() {
-    // This test would start the server and connect via TCP
-    // For now, it's commented out until server implementation is complete
+fn test_error_replies_are_exact() {
+    assert_eq!(
+        parse_error(&["GET"]),
+        "-ERR wrong number of arguments for 'get' command\r\n"
+    );
+    assert_eq!(
+        parse_error(&["config", "get"]),
+        "-ERR wrong number of arguments for 'config|get' command\r\n"
+    );
+    assert_eq!(
+        parse_error(&["FOO", "bar", "baz"]),
+        "-ERR unknown command 'FOO', with args beginning with: 'bar' 'baz' \r\n"
+    );
+    assert_eq!(
+        parse_error(&["foo"]),
+        "-ERR unknown command 'foo', with args beginning with: \r\n"
+    );
+    let long = "x".repeat(200);
+    assert_eq!(
+        parse_error(&["foo", &long, "next"]),
+        format!(
+            "-ERR unknown command 'foo', with args beginning with: '{}' \r\n",
+            "x".repeat(128)
+        )
+    );
+    assert_eq!(
+        parse_error(&["CONFIG", "nope"]),
+        "-ERR unknown subcommand 'nope'. Try CONFIG HELP.\r\n"
+    );
+    assert_eq!(
+        parse_error(&["SET", "k", "v", "BOGUS"]),
+        "-ERR syntax error\r\n"
+    );
+
+    let db = Database::new_shared(16);
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let run = |args: &[&str]| {
+        let resp_value = RespValue::Array(
+            args.iter()
+                .map(|a| RespValue::BulkString(Bytes::from(a.to_string())))
+                .collect(),
+        );
+        let reply = rt.block_on(Command::parse(&resp_value).unwrap().execute(&db));
+        String::from_utf8_lossy(&encode(&reply, Protocol::Resp2)).to_string()
+    };
+    run(&["LPUSH", "list", "a"]);
+    assert_eq!(
+        run(&["INCR", "list"]),
+        "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"
+    );
+    assert_eq!(
+        run(&["SELECT", "abc"]),
+        "-ERR value is not an integer or out of range\r\n"
+    );
+    assert_eq!(run(&["SELECT", "99"]), "-ERR DB index is out of range\r\n");
+    assert_eq!(run(&["LSET", "missing", "0", "x"]), "-ERR no such key\r\n");
+    assert_eq!(
+        run(&["COMMAND", "GETKEYS", "PING"]),
+        "-ERR The command has no key arguments\r\n"
+    );
+
+    // Messages without a code are sent as ERR
+    assert_eq!(
+        Reply::error("plain message"),
+        Reply::Error("ERR plain message".to_string())
+    );
+    assert!(
+        matches!(Reply::from(CommandError::OutOfMemory), Reply::Error(e) if e.starts_with("OOM "))
+    );
+    assert_eq!(CommandError::WrongType.code(), "WRONGTYPE");
+}
+
+#[tokio::test]
+async fn test_tcp_server_integration() {
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config {
+        bind: vec!["127.0.0.1".to_string()],
+        port,
+        dir: dir.path().to_string_lossy().to_string(),
+        save: Vec::new(),
+        ..Config::default()
+    };
+    let server = Server::new(config).await.unwrap();
+    tokio::spawn(async move { server.run().await });
+
+    let pool = Pool::new(Options::tcp(format!("127.0.0.1:{}", port)));
+    assert_eq!(pool.call(&["PING"]).await.unwrap(), Reply::status("PONG"));
+    assert_eq!(pool.call(&["SET", "k", "v"]).await.unwrap(), Reply::ok());
+    assert_eq!(pool.call(&["GET", "k"]).await.unwrap(), Reply::bulk("v"));
+    assert_eq!(
+        pool.call(&["INCR", "k"]).await.unwrap(),
+        Reply::from(CommandError::InvalidInteger)
+    );
+    assert_eq!(pool.call(&["DEL", "k"]).await.unwrap(), Reply::Integer(1));
+}
+
+#[tokio::test]
+async fn test_shutdown_saves_when_save_points_are_set() {
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config {
+        bind: vec!["127.0.0.1".to_string()],
+        port,
+        dir: dir.path().to_string_lossy().to_string(),
+        ..Config::default()
+    };
+    let server = Server::new(config.clone()).await.unwrap();
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let running = tokio::spawn(async move {
+        server
+            .run_until(async {
+                let _ = stopped.await;
+            })
+            .await
+    });
+
+    let pool = Pool::new(Options::tcp(format!("127.0.0.1:{}", port)));
+    assert_eq!(pool.call(&["SET", "k", "v"]).await.unwrap(), Reply::ok());
+    assert!(!config.rdb_path().exists());
+    stop.send(()).unwrap();
+    running.await.unwrap().unwrap();
+
+    let restored = Database::new(16);
+    assert_eq!(Persistence::new(&config).load(&restored).unwrap(), 1);
+    assert_eq!(
+        restored.get(&Bytes::from("k")).unwrap(),
+        Some(Bytes::from("v"))
+    );
 }
-*/
@@ -0,0 +1,206 @@
+// Tests for the JSON lines keyspace export/import and the rudis-jsonl binary
+
+use bytes::Bytes;
+use rudis::commands::Command;
+use rudis::database::traits::{HashOp, KeyOp, ListOp, SetOp, StringOp};
+use rudis::database::Database;
+use rudis::networking::resp::{encode, Protocol, RespValue};
+use rudis::persistence::{jsonl, rdb};
+use std::process;
+
+fn run(
+    db: &rudis::database::SharedDatabase,
+    rt: &tokio::runtime::Runtime,
+    args: &[&[u8]],
+) -> Bytes {
+    let resp = RespValue::Array(
+        args.iter()
+            .map(|p| RespValue::BulkString(Bytes::copy_from_slice(p)))
+            .collect(),
+    );
+    let cmd = Command::parse(&resp).expect("command should parse");
+    encode(&rt.block_on(cmd.execute(db)), Protocol::Resp2)
+}
+
+fn sample_db() -> Database {
+    let db = Database::new(16);
+    db.set(&Bytes::from("str"), Bytes::from("hello"));
+    db.set(
+        &Bytes::from_static(b"bin\xff"),
+        Bytes::from_static(b"\x00\x01\xfe"),
+    );
+    db.rpush(&Bytes::from("list"), &[Bytes::from("a"), Bytes::from("b")])
+        .unwrap();
+    db.sadd(&Bytes::from("set"), &[Bytes::from("y"), Bytes::from("x")])
+        .unwrap();
+    db.hset(&Bytes::from("hash"), Bytes::from("f"), Bytes::from("v"))
+        .unwrap();
+    db.expire(&Bytes::from("str"), 100).unwrap();
+    db.select(2);
+    db.set(&Bytes::from("other"), Bytes::from("db2"));
+    db.select(0);
+    db
+}
+
+#[test]
+fn test_jsonl_round_trip() {
+    let db = sample_db();
+    let mut out = Vec::new();
+    assert_eq!(jsonl::export(&db, None, &mut out).unwrap(), 6);
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.contains(r#"{"db":0,"key":"list","pttl":null,"type":"list","value":["a","b"]}"#));
+    assert!(text.contains(r#""type":"set","value":["x","y"]"#));
+    assert!(text.contains(
+        r#"{"db":0,"key":{"base64":"Ymlu/w=="},"pttl":null,"type":"string","value":{"base64":"AAH+"}}"#
+    ));
+    assert!(text
+        .lines()
+        .last()
+        .unwrap()
+        .starts_with(r#"{"db":2,"key":"other""#));
+
+    let restored = Database::new(16);
+    assert_eq!(
+        jsonl::import(&restored, jsonl::parse(&text).unwrap(), false).unwrap(),
+        6
+    );
+    assert_eq!(
+        restored.get(&Bytes::from_static(b"bin\xff")).unwrap(),
+        Some(Bytes::from_static(b"\x00\x01\xfe"))
+    );
+    assert_eq!(
+        restored.hget(&Bytes::from("hash"), &Bytes::from("f")),
+        Ok(Some(Bytes::from("v")))
+    );
+    let ttl = restored.ttl(&Bytes::from("str"));
+    assert!(ttl > 90 && ttl <= 100);
+    assert_eq!(restored.ttl(&Bytes::from("list")), -1);
+    restored.select(2);
+    assert_eq!(
+        restored.get(&Bytes::from("other")).unwrap(),
+        Some(Bytes::from("db2"))
+    );
+}
+
+#[test]
+fn test_jsonl_zset_infinite_scores() {
+    let text = r#"{"db":0,"key":"z","pttl":null,"type":"zset","value":[["low","-inf"],["mid",1.5],["high","inf"]]}"#;
+    let records = jsonl::parse(text).unwrap();
+    let value = records[0].to_redis_value().unwrap();
+    let again = jsonl::JsonRecord::new(0, &Bytes::from("z"), &value, None);
+    assert_eq!(again, records[0]);
+}
+
+#[test]
+fn test_jsonl_import_is_atomic() {
+    let db = Database::new(16);
+    db.set(&Bytes::from("taken"), Bytes::from("old"));
+
+    // Line 2 is invalid: nothing is written
+    let bad = "{\"db\":0,\"key\":\"a\",\"pttl\":null,\"type\":\"string\",\"value\":\"1\"}\n\
+               {\"db\":0,\"key\":\"b\",\"type\":\"bogus\"}\n";
+    let err = jsonl::parse(bad).unwrap_err();
+    assert!(err.to_string().contains("line 2"));
+
+    // Existing key without REPLACE: nothing is written
+    let clash = "{\"db\":0,\"key\":\"a\",\"pttl\":null,\"type\":\"string\",\"value\":\"1\"}\n\
+                 {\"db\":0,\"key\":\"taken\",\"pttl\":null,\"type\":\"string\",\"value\":\"new\"}\n";
+    assert!(jsonl::import(&db, jsonl::parse(clash).unwrap(), false).is_err());
+    assert_eq!(db.get(&Bytes::from("a")).unwrap(), None);
+
+    assert_eq!(
+        jsonl::import(&db, jsonl::parse(clash).unwrap(), true).unwrap(),
+        2
+    );
+    assert_eq!(
+        db.get(&Bytes::from("taken")).unwrap(),
+        Some(Bytes::from("new"))
+    );
+
+    let out_of_range = r#"{"db":99,"key":"a","pttl":null,"type":"string","value":"1"}"#;
+    assert!(jsonl::import(&db, jsonl::parse(out_of_range).unwrap(), true).is_err());
+}
+
+#[test]
+fn test_keyspace_command() {
+    let db = Database::new_shared(16);
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    run(&db, &rt, &[b"SET", b"k", b"v"]);
+    run(&db, &rt, &[b"SELECT", b"1"]);
+    run(&db, &rt, &[b"SET", b"k1", b"v1"]);
+
+    let line = r#"{"db":1,"key":"k1","pttl":null,"type":"string","value":"v1"}"#;
+    let reply = run(&db, &rt, &[b"KEYSPACE", b"EXPORT", b"DB", b"1"]);
+    assert_eq!(
+        reply,
+        Bytes::from(format!("${}\r\n{}\n\r\n", line.len() + 1, line))
+    );
+
+    let export = run(&db, &rt, &[b"KEYSPACE", b"EXPORT"]);
+    let payload = &export[export.iter().position(|&b| b == b'\n').unwrap() + 1..export.len() - 2];
+
+    let target = Database::new_shared(16);
+    assert_eq!(
+        &run(&target, &rt, &[b"KEYSPACE", b"IMPORT", payload])[..],
+        b":2\r\n"
+    );
+    assert!(run(&target, &rt, &[b"KEYSPACE", b"IMPORT", payload]).starts_with(b"-ERR"));
+    assert_eq!(
+        &run(&target, &rt, &[b"KEYSPACE", b"IMPORT", payload, b"REPLACE"])[..],
+        b":2\r\n"
+    );
+    assert_eq!(&run(&target, &rt, &[b"GET", b"k"])[..], b"$1\r\nv\r\n");
+    // Every imported key counts towards the save points
+    assert_eq!(target.save_state().dirty(), 4);
+
+    let resp = RespValue::Array(vec![
+        RespValue::BulkString(Bytes::from("KEYSPACE")),
+        RespValue::BulkString(Bytes::from("DROP")),
+    ]);
+    assert!(Command::parse(&resp).is_none());
+}
+
+#[test]
+fn test_jsonl_binary() {
+    let dir = tempfile::tempdir().unwrap();
+    let rdb_path = dir.path().join("dump.rdb");
+    let jsonl_path = dir.path().join("keys.jsonl");
+    let rebuilt_path = dir.path().join("rebuilt.rdb");
+    std::fs::write(&rdb_path, rdb::dump(&sample_db()).unwrap()).unwrap();
+
+    let status = process::Command::new(env!("CARGO_BIN_EXE_rudis-jsonl"))
+        .args([
+            "export".as_ref(),
+            rdb_path.as_os_str(),
+            jsonl_path.as_os_str(),
+        ])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let status = process::Command::new(env!("CARGO_BIN_EXE_rudis-jsonl"))
+        .args([
+            "import".as_ref(),
+            jsonl_path.as_os_str(),
+            rebuilt_path.as_os_str(),
+        ])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let restored = Database::new(16);
+    assert_eq!(
+        rdb::load(&restored, &std::fs::read(&rebuilt_path).unwrap()).unwrap(),
+        6
+    );
+    assert_eq!(
+        restored.lrange(&Bytes::from("list"), 0, -1).unwrap(),
+        vec![Bytes::from("a"), Bytes::from("b")]
+    );
+
+    let status = process::Command::new(env!("CARGO_BIN_EXE_rudis-jsonl"))
+        .arg("bogus")
+        .stderr(process::Stdio::null())
+        .status()
+        .unwrap();
+    assert!(!status.success());
+}
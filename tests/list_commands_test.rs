@@ -0,0 +1,284 @@
+// Tests for the list commands: counted pops, LPOS, LREM, LMOVE and LMPOP
+
+use rudis::commands::{CommandError, Reply};
+use rudis::database::Database;
+use rudis::Client;
+
+fn client() -> Client {
+    Client::new(&Database::new_shared(16))
+}
+
+fn bulks(values: &[&'static str]) -> Reply {
+    Reply::Array(values.iter().map(|&v| Reply::bulk(v)).collect())
+}
+
+fn ints(values: &[i64]) -> Reply {
+    Reply::Array(values.iter().map(|&v| Reply::Integer(v)).collect())
+}
+
+#[tokio::test]
+async fn test_pop_with_count() {
+    let client = client();
+    client
+        .call(&[b"RPUSH", b"l", b"a", b"b", b"c", b"d", b"e"])
+        .await;
+    assert_eq!(
+        client.call(&[b"LPOP", b"l", b"2"]).await,
+        bulks(&["a", "b"])
+    );
+    assert_eq!(
+        client.call(&[b"RPOP", b"l", b"2"]).await,
+        bulks(&["e", "d"])
+    );
+    assert_eq!(client.call(&[b"LPOP", b"l", b"0"]).await, bulks(&[]));
+    assert_eq!(client.call(&[b"LPOP", b"l", b"10"]).await, bulks(&["c"]));
+    assert_eq!(client.call(&[b"EXISTS", b"l"]).await, Reply::Integer(0));
+
+    // A missing key is a null array with a count and nil without
+    assert_eq!(client.call(&[b"LPOP", b"l", b"1"]).await, Reply::NullArray);
+    assert_eq!(client.call(&[b"RPOP", b"l"]).await, Reply::Null);
+    assert_eq!(
+        client.call(&[b"LPOP", b"l", b"-1"]).await,
+        CommandError::Custom("value is out of range, must be positive".into()).into()
+    );
+    assert_eq!(
+        client.call(&[b"RPOP", b"l", b"x"]).await,
+        CommandError::InvalidInteger.into()
+    );
+}
+
+#[tokio::test]
+async fn test_lpos() {
+    let client = client();
+    client
+        .call(&[
+            b"RPUSH", b"l", b"a", b"b", b"c", b"1", b"2", b"3", b"c", b"c",
+        ])
+        .await;
+    for (options, expected) in [
+        (&[][..], Reply::Integer(2)),
+        (&[&b"RANK"[..], b"2"], Reply::Integer(6)),
+        (&[b"RANK", b"-1"], Reply::Integer(7)),
+        (&[b"rank", b"4"], Reply::Null),
+        (&[b"COUNT", b"2"], ints(&[2, 6])),
+        (&[b"COUNT", b"0"], ints(&[2, 6, 7])),
+        (&[b"RANK", b"-1", b"COUNT", b"2"], ints(&[7, 6])),
+        (&[b"MAXLEN", b"2"], Reply::Null),
+        (&[b"COUNT", b"0", b"MAXLEN", b"7"], ints(&[2, 6])),
+    ] {
+        let mut command: Vec<&[u8]> = vec![b"LPOS", b"l", b"c"];
+        command.extend_from_slice(options);
+        assert_eq!(client.call(&command).await, expected, "{:?}", options);
+    }
+    assert_eq!(client.call(&[b"LPOS", b"l", b"x"]).await, Reply::Null);
+    assert_eq!(
+        client
+            .call(&[b"LPOS", b"missing", b"x", b"COUNT", b"1"])
+            .await,
+        ints(&[])
+    );
+
+    assert_eq!(
+        client.call(&[b"LPOS", b"l", b"c", b"RANK", b"0"]).await,
+        CommandError::Custom(
+            "RANK can't be zero: use 1 to start from the first match, 2 from the second ... or use negative to start from the end of the list".into()
+        )
+        .into()
+    );
+    assert_eq!(
+        client.call(&[b"LPOS", b"l", b"c", b"COUNT", b"-1"]).await,
+        CommandError::Custom("COUNT can't be negative".into()).into()
+    );
+    assert_eq!(
+        client.call(&[b"LPOS", b"l", b"c", b"MAXLEN", b"-1"]).await,
+        CommandError::Custom("MAXLEN can't be negative".into()).into()
+    );
+    assert_eq!(
+        client.call(&[b"LPOS", b"l", b"c", b"RANK"]).await,
+        CommandError::SyntaxError.into()
+    );
+    assert_eq!(
+        client.call(&[b"LPOS", b"l", b"c", b"FIRST", b"1"]).await,
+        CommandError::SyntaxError.into()
+    );
+}
+
+#[tokio::test]
+async fn test_lrem_and_pushx() {
+    let client = client();
+    client
+        .call(&[b"RPUSH", b"l", b"a", b"b", b"a", b"c", b"a"])
+        .await;
+    assert_eq!(
+        client.call(&[b"LREM", b"l", b"-2", b"a"]).await,
+        Reply::Integer(2)
+    );
+    assert_eq!(
+        client.call(&[b"LRANGE", b"l", b"0", b"-1"]).await,
+        bulks(&["a", "b", "c"])
+    );
+    assert_eq!(
+        client.call(&[b"LREM", b"l", b"0", b"x"]).await,
+        Reply::Integer(0)
+    );
+    client.call(&[b"LREM", b"l", b"0", b"a"]).await;
+    client.call(&[b"LREM", b"l", b"1", b"b"]).await;
+    assert_eq!(
+        client.call(&[b"LREM", b"l", b"0", b"c"]).await,
+        Reply::Integer(1)
+    );
+    // Removing the last element deletes the list
+    assert_eq!(client.call(&[b"EXISTS", b"l"]).await, Reply::Integer(0));
+
+    assert_eq!(
+        client.call(&[b"LPUSHX", b"l", b"x"]).await,
+        Reply::Integer(0)
+    );
+    assert_eq!(client.call(&[b"EXISTS", b"l"]).await, Reply::Integer(0));
+    client.call(&[b"RPUSH", b"l", b"m"]).await;
+    assert_eq!(
+        client.call(&[b"LPUSHX", b"l", b"b", b"a"]).await,
+        Reply::Integer(3)
+    );
+    assert_eq!(
+        client.call(&[b"RPUSHX", b"l", b"y", b"z"]).await,
+        Reply::Integer(5)
+    );
+    assert_eq!(
+        client.call(&[b"LRANGE", b"l", b"0", b"-1"]).await,
+        bulks(&["a", "b", "m", "y", "z"])
+    );
+
+    // LINSERT takes its direction in any case, and checks it first
+    assert_eq!(
+        client
+            .call(&[b"LINSERT", b"l", b"before", b"m", b"k"])
+            .await,
+        Reply::Integer(6)
+    );
+    assert_eq!(
+        client
+            .call(&[b"LINSERT", b"missing", b"SIDEWAYS", b"m", b"k"])
+            .await,
+        CommandError::SyntaxError.into()
+    );
+}
+
+#[tokio::test]
+async fn test_lmove() {
+    let client = client();
+    client.call(&[b"RPUSH", b"src", b"a", b"b", b"c"]).await;
+    assert_eq!(
+        client
+            .call(&[b"LMOVE", b"src", b"dst", b"LEFT", b"RIGHT"])
+            .await,
+        Reply::bulk("a")
+    );
+    assert_eq!(
+        client.call(&[b"RPOPLPUSH", b"src", b"dst"]).await,
+        Reply::bulk("c")
+    );
+    assert_eq!(
+        client.call(&[b"LRANGE", b"dst", b"0", b"-1"]).await,
+        bulks(&["c", "a"])
+    );
+    // Moving within one list rotates it
+    assert_eq!(
+        client
+            .call(&[b"LMOVE", b"dst", b"dst", b"left", b"right"])
+            .await,
+        Reply::bulk("c")
+    );
+    assert_eq!(
+        client.call(&[b"LRANGE", b"dst", b"0", b"-1"]).await,
+        bulks(&["a", "c"])
+    );
+    // The source goes away with its last element
+    assert_eq!(
+        client
+            .call(&[b"LMOVE", b"src", b"dst", b"RIGHT", b"LEFT"])
+            .await,
+        Reply::bulk("b")
+    );
+    assert_eq!(client.call(&[b"EXISTS", b"src"]).await, Reply::Integer(0));
+    assert_eq!(
+        client
+            .call(&[b"LMOVE", b"src", b"dst", b"LEFT", b"LEFT"])
+            .await,
+        Reply::Null
+    );
+
+    // Nothing moves when the destination is not a list
+    client.call(&[b"SET", b"str", b"x"]).await;
+    assert_eq!(
+        client
+            .call(&[b"LMOVE", b"dst", b"str", b"LEFT", b"LEFT"])
+            .await,
+        CommandError::WrongType.into()
+    );
+    assert_eq!(client.call(&[b"LLEN", b"dst"]).await, Reply::Integer(3));
+    assert_eq!(
+        client.call(&[b"RPOPLPUSH", b"str", b"dst"]).await,
+        CommandError::WrongType.into()
+    );
+    assert_eq!(
+        client
+            .call(&[b"LMOVE", b"dst", b"new", b"UP", b"LEFT"])
+            .await,
+        CommandError::SyntaxError.into()
+    );
+}
+
+#[tokio::test]
+async fn test_lmpop() {
+    let client = client();
+    assert_eq!(
+        client.call(&[b"LMPOP", b"2", b"a", b"b", b"LEFT"]).await,
+        Reply::NullArray
+    );
+    client.call(&[b"RPUSH", b"b", b"1", b"2", b"3"]).await;
+    assert_eq!(
+        client
+            .call(&[b"LMPOP", b"2", b"a", b"b", b"RIGHT", b"COUNT", b"2"])
+            .await,
+        Reply::Array(vec![Reply::bulk("b"), bulks(&["3", "2"])])
+    );
+    assert_eq!(
+        client
+            .call(&[b"LMPOP", b"2", b"a", b"b", b"left", b"COUNT", b"5"])
+            .await,
+        Reply::Array(vec![Reply::bulk("b"), bulks(&["1"])])
+    );
+    assert_eq!(client.call(&[b"EXISTS", b"b"]).await, Reply::Integer(0));
+
+    for (args, error) in [
+        (
+            &[&b"0"[..], b"a", b"LEFT"][..],
+            CommandError::Custom("numkeys should be greater than 0".into()),
+        ),
+        (&[b"x", b"a", b"LEFT"], CommandError::InvalidInteger),
+        (&[b"3", b"a", b"LEFT"], CommandError::SyntaxError),
+        (&[b"1", b"a", b"UP"], CommandError::SyntaxError),
+        (
+            &[b"1", b"a", b"LEFT", b"COUNT", b"0"],
+            CommandError::Custom("count should be greater than 0".into()),
+        ),
+        (&[b"1", b"a", b"LEFT", b"COUNT"], CommandError::SyntaxError),
+    ] {
+        let mut command: Vec<&[u8]> = vec![b"LMPOP"];
+        command.extend_from_slice(args);
+        assert_eq!(client.call(&command).await, error.into(), "{:?}", args);
+    }
+    client.call(&[b"SET", b"str", b"x"]).await;
+    assert_eq!(
+        client.call(&[b"LMPOP", b"2", b"str", b"b", b"LEFT"]).await,
+        CommandError::WrongType.into()
+    );
+
+    assert_eq!(
+        client
+            .call(&[b"COMMAND", b"GETKEYS", b"LMPOP", b"2", b"a", b"b", b"LEFT"])
+            .await,
+        bulks(&["a", "b"])
+    );
+}
@@ -0,0 +1,312 @@
+// Tests for server-side modules: commands, value types and their hooks
+
+use bytes::Bytes;
+use rudis::commands::table::Flag;
+use rudis::commands::{CommandError, Reply};
+use rudis::database::Database;
+use rudis::error::Result as AppResult;
+use rudis::modules::{self, Module, ModuleCommand, ModuleType, ModuleValue};
+use rudis::persistence::{jsonl, rdb};
+use rudis::Client;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Once};
+
+/// Intervals by member; a stand-in for a real interval tree
+#[derive(Debug, Default)]
+struct Intervals(BTreeMap<Bytes, (i64, i64)>);
+
+impl ModuleValue for Intervals {
+    fn type_name(&self) -> &'static str {
+        "intervals"
+    }
+
+    fn save(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for (member, (start, end)) in &self.0 {
+            buf.extend_from_slice(&start.to_le_bytes());
+            buf.extend_from_slice(&end.to_le_bytes());
+            buf.extend_from_slice(&(member.len() as u32).to_le_bytes());
+            buf.extend_from_slice(member);
+        }
+        buf
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.0.keys().map(|member| member.len() + 16).sum()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+struct IntervalsType;
+
+impl ModuleType for IntervalsType {
+    fn name(&self) -> &'static str {
+        "intervals"
+    }
+
+    fn load(&self, mut payload: &[u8], _encoding_version: u16) -> AppResult<Box<dyn ModuleValue>> {
+        let mut value = Intervals::default();
+        while !payload.is_empty() {
+            let corrupt = || rudis::error::AppError::Serialization("short payload".into());
+            let header = payload.get(..20).ok_or_else(corrupt)?;
+            let start = i64::from_le_bytes(header[..8].try_into().unwrap());
+            let end = i64::from_le_bytes(header[8..16].try_into().unwrap());
+            let len = u32::from_le_bytes(header[16..].try_into().unwrap()) as usize;
+            let member = payload.get(20..20 + len).ok_or_else(corrupt)?;
+            value.0.insert(Bytes::copy_from_slice(member), (start, end));
+            payload = &payload[20 + len..];
+        }
+        Ok(Box::new(value))
+    }
+}
+
+fn integer(arg: &Bytes) -> Result<i64, CommandError> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or(CommandError::InvalidInteger)
+}
+
+struct IntervalsModule;
+
+impl Module for IntervalsModule {
+    fn name(&self) -> &str {
+        "itree"
+    }
+
+    fn commands(&self) -> Vec<ModuleCommand> {
+        vec![
+            ModuleCommand::new("itree.add", 5, |db, args| {
+                let (start, end) = (integer(&args[1])?, integer(&args[2])?);
+                let added = db.upsert_module_value(&args[0], Intervals::default, |value| {
+                    Ok(value.0.insert(args[3].clone(), (start, end)).is_none())
+                })?;
+                Ok(added.into())
+            })
+            .flags(&[Flag::Write, Flag::DenyOom])
+            .keys(1, 1, 1)
+            .summary("Adds an interval."),
+            ModuleCommand::new("itree.del", 3, |db, args| {
+                let removed =
+                    db.update_module_value(&args[0], false, |value: &mut Intervals| {
+                        Ok(value.0.remove(&args[1]).is_some())
+                    })?;
+                Ok(removed.into())
+            })
+            .flags(&[Flag::Write, Flag::Fast])
+            .keys(1, 1, 1),
+            ModuleCommand::new("itree.stab", 3, |db, args| {
+                let point = integer(&args[1])?;
+                let members = db.read_module_value(&args[0], |value: &Intervals| {
+                    value
+                        .0
+                        .iter()
+                        .filter(|(_, (start, end))| (*start..=*end).contains(&point))
+                        .map(|(member, _)| member.clone())
+                        .collect()
+                })?;
+                Ok(Reply::bulk_array(members.unwrap_or_default()))
+            })
+            .flags(&[Flag::ReadOnly])
+            .keys(1, 1, 1)
+            .summary("Returns the members whose interval contains a point."),
+        ]
+    }
+
+    fn types(&self) -> Vec<Arc<dyn ModuleType>> {
+        vec![Arc::new(IntervalsType)]
+    }
+}
+
+fn setup() {
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| modules::register(IntervalsModule).unwrap());
+}
+
+async fn populated() -> (Arc<Database>, Client) {
+    setup();
+    let db = Database::new_shared(16);
+    let client = Client::new(&db);
+    for (start, end, member) in [("1", "5", "a"), ("3", "9", "b"), ("10", "12", "c")] {
+        client
+            .call(&[
+                b"ITREE.ADD",
+                b"spans",
+                start.as_bytes(),
+                end.as_bytes(),
+                member.as_bytes(),
+            ])
+            .await;
+    }
+    (db, client)
+}
+
+#[tokio::test]
+async fn test_module_commands() {
+    let (db, client) = populated().await;
+    assert_eq!(
+        client.call(&[b"itree.stab", b"spans", b"4"]).await,
+        Reply::bulk_array(vec![Bytes::from("a"), Bytes::from("b")])
+    );
+    assert_eq!(
+        client.call(&[b"ITREE.STAB", b"nothing", b"4"]).await,
+        Reply::Array(Vec::new())
+    );
+    assert_eq!(
+        client.call(&[b"TYPE", b"spans"]).await,
+        Reply::status("intervals")
+    );
+    assert_eq!(db.save_state().dirty(), 3);
+
+    // Arity comes from the table, argument errors from the handler
+    assert_eq!(
+        client.call(&[b"ITREE.STAB", b"spans"]).await,
+        CommandError::WrongArity("itree.stab".to_string()).into()
+    );
+    assert_eq!(
+        client.call(&[b"ITREE.STAB", b"spans", b"x"]).await,
+        CommandError::InvalidInteger.into()
+    );
+
+    // Removing the last interval deletes the key
+    for member in ["a", "b", "c"] {
+        assert_eq!(
+            client
+                .call(&[b"ITREE.DEL", b"spans", member.as_bytes()])
+                .await,
+            Reply::Integer(1)
+        );
+    }
+    assert_eq!(client.call(&[b"EXISTS", b"spans"]).await, Reply::Integer(0));
+    assert_eq!(
+        client.call(&[b"ITREE.DEL", b"spans", b"a"]).await,
+        Reply::Integer(0)
+    );
+}
+
+#[tokio::test]
+async fn test_module_values_and_builtin_types_do_not_mix() {
+    let (_db, client) = populated().await;
+    client.set("plain", "v").await.unwrap();
+    let wrong_type: Reply = CommandError::WrongType.into();
+    assert_eq!(
+        client
+            .call(&[b"ITREE.ADD", b"plain", b"1", b"2", b"m"])
+            .await,
+        wrong_type
+    );
+    assert_eq!(
+        client.call(&[b"ITREE.STAB", b"plain", b"1"]).await,
+        wrong_type
+    );
+    assert_eq!(client.call(&[b"GET", b"spans"]).await, wrong_type);
+    assert_eq!(client.call(&[b"LPUSH", b"spans", b"x"]).await, wrong_type);
+    assert_eq!(client.call(&[b"DEL", b"spans"]).await, Reply::Integer(1));
+}
+
+#[tokio::test]
+async fn test_module_commands_are_listed() {
+    setup();
+    let db = Database::new_shared(16);
+    let client = Client::new(&db);
+
+    let Reply::Array(info) = client.call(&[b"COMMAND", b"INFO", b"itree.add"]).await else {
+        panic!("COMMAND INFO must return an array");
+    };
+    let Reply::Array(entry) = &info[0] else {
+        panic!("itree.add must be described");
+    };
+    assert_eq!(entry[0], Reply::bulk("itree.add"));
+    assert_eq!(entry[1], Reply::Integer(5));
+    assert_eq!(
+        entry[2],
+        Reply::Set(vec![Reply::status("write"), Reply::status("denyoom")])
+    );
+
+    assert_eq!(
+        client
+            .call(&[b"COMMAND", b"LIST", b"FILTERBY", b"MODULE", b"itree"])
+            .await,
+        Reply::bulk_array(vec![
+            Bytes::from("itree.add"),
+            Bytes::from("itree.del"),
+            Bytes::from("itree.stab"),
+        ])
+    );
+    assert_eq!(
+        client
+            .call(&[b"COMMAND", b"GETKEYS", b"ITREE.ADD", b"k", b"1", b"2", b"m"])
+            .await,
+        Reply::bulk_array(vec![Bytes::from("k")])
+    );
+    let Reply::Array(loaded) = client.call(&[b"MODULE", b"LIST"]).await else {
+        panic!("MODULE LIST must return an array");
+    };
+    assert!(loaded.iter().any(|module| matches!(
+        module,
+        Reply::Map(fields) if fields[0].1 == Reply::bulk("itree")
+    )));
+}
+
+#[test]
+fn test_registration_conflicts() {
+    setup();
+    struct Clash(&'static str, &'static str);
+    impl Module for Clash {
+        fn name(&self) -> &str {
+            "clash"
+        }
+        fn commands(&self) -> Vec<ModuleCommand> {
+            vec![ModuleCommand::new(self.0, 1, |_, _| Ok(Reply::ok()))]
+        }
+        fn types(&self) -> Vec<Arc<dyn ModuleType>> {
+            struct Named(&'static str);
+            impl ModuleType for Named {
+                fn name(&self) -> &'static str {
+                    self.0
+                }
+                fn load(&self, _: &[u8], _: u16) -> AppResult<Box<dyn ModuleValue>> {
+                    Ok(Box::new(Intervals::default()))
+                }
+            }
+            vec![Arc::new(Named(self.1))]
+        }
+    }
+
+    // A builtin command, another module's command or type, or a bad type name
+    assert!(modules::register(Clash("GET", "clashtype")).is_err());
+    assert!(modules::register(Clash("itree.add", "clashtype")).is_err());
+    assert!(modules::register(Clash("clash.cmd", "intervals")).is_err());
+    assert!(modules::register(Clash("clash.cmd", "short")).is_err());
+    // Nothing was registered by the failed attempts
+    assert!(modules::lookup("clash.cmd").is_none());
+    assert!(modules::find_type("clashtype").is_none());
+}
+
+#[tokio::test]
+async fn test_module_values_persist() {
+    let (db, _client) = populated().await;
+
+    // DUMP payloads and RDB snapshots go through the type's save/load hooks
+    let snapshot = rdb::dump(&db).unwrap();
+    let restored = Database::new_shared(16);
+    assert_eq!(rdb::load(&restored, &snapshot).unwrap(), 1);
+    let client = Client::new(&restored);
+    assert_eq!(
+        client.call(&[b"ITREE.STAB", b"spans", b"11"]).await,
+        Reply::bulk_array(vec![Bytes::from("c")])
+    );
+
+    let mut exported = Vec::new();
+    jsonl::export(&db, None, &mut exported).unwrap();
+    let imported = Database::new_shared(16);
+    let records = jsonl::parse(std::str::from_utf8(&exported).unwrap()).unwrap();
+    assert_eq!(jsonl::import(&imported, records, false).unwrap(), 1);
+    assert_eq!(imported.data_type(&Bytes::from("spans")), "intervals");
+
+    // Memory accounting uses the type's estimate
+    assert_eq!(db.refresh_used_memory(), ("spans".len() + 3 * 17) as u64);
+}
@@ -0,0 +1,240 @@
+// Tests for the network client against a spawned server, and against a
+// scripted peer over other kinds of stream
+
+use bytes::Bytes;
+use rudis::client::{Backoff, Connection, Error, Options, Pipeline, Pool, Protocol};
+use rudis::commands::{CommandError, Reply};
+use rudis::config::Config;
+use rudis::networking::resp;
+use rudis::server::Server;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// A port that was free a moment ago
+fn free_port() -> u16 {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().port()
+}
+
+async fn start_server(port: u16) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config {
+        bind: vec!["127.0.0.1".to_string()],
+        port,
+        dir: dir.path().to_string_lossy().to_string(),
+        save: Vec::new(),
+        ..Config::default()
+    };
+    let server = Server::new(config).await.unwrap();
+    tokio::spawn(async move { server.run().await });
+    dir
+}
+
+/// Options for a running server on a fresh port
+async fn spawn_server() -> (Options, tempfile::TempDir) {
+    let port = free_port();
+    let dir = start_server(port).await;
+    (Options::tcp(format!("127.0.0.1:{}", port)), dir)
+}
+
+#[tokio::test]
+async fn test_commands_and_typed_queries() {
+    let (options, _dir) = spawn_server().await;
+    let pool = Pool::new(options);
+
+    pool.query::<(), _>(&["SET", "k", "v"]).await.unwrap();
+    let value: Option<Bytes> = pool.query(&["GET", "k"]).await.unwrap();
+    assert_eq!(value, Some(Bytes::from("v")));
+    let missing: Option<Bytes> = pool.query(&["GET", "nope"]).await.unwrap();
+    assert_eq!(missing, None);
+    assert_eq!(
+        pool.query::<i64, _>(&["INCRBY", "n", "5"]).await.unwrap(),
+        5
+    );
+
+    // Error replies: as replies from `call`, as errors from `query`
+    assert_eq!(
+        pool.call(&["LPUSH", "k", "x"]).await.unwrap(),
+        Reply::Error("WRONGTYPE Operation against a key holding the wrong kind of value".into())
+    );
+    assert!(matches!(
+        pool.query::<i64, _>(&["LPUSH", "k", "x"]).await,
+        Err(Error::Server(CommandError::WrongType))
+    ));
+    assert!(matches!(
+        pool.query::<Reply, _>(&["NOPE"]).await,
+        Err(Error::Server(CommandError::Reply(m))) if m.starts_with("ERR unknown command 'NOPE'")
+    ));
+}
+
+#[tokio::test]
+async fn test_concurrent_requests_are_pipelined() {
+    let (options, _dir) = spawn_server().await;
+    let connection = Connection::connect(&options).await.unwrap();
+
+    let mut tasks = Vec::new();
+    for _ in 0..200 {
+        let connection = connection.clone();
+        tasks.push(tokio::spawn(async move {
+            connection.query::<i64, _>(&["INCR", "counter"]).await
+        }));
+    }
+    let mut seen = Vec::new();
+    for task in tasks {
+        seen.push(task.await.unwrap().unwrap());
+    }
+    seen.sort();
+    assert_eq!(seen, (1..=200).collect::<Vec<i64>>());
+
+    let mut pipeline = Pipeline::new();
+    pipeline
+        .cmd(&["SET", "a", "1"])
+        .cmd(&["INCR", "a"])
+        .cmd(&["LPUSH", "a", "x"])
+        .cmd(&["GET", "a"]);
+    let replies = connection.pipeline(&pipeline).await.unwrap();
+    assert_eq!(replies.len(), 4);
+    assert_eq!(replies[1], Reply::Integer(2));
+    assert!(replies[2].is_error());
+    assert_eq!(replies[3], Reply::bulk("2"));
+}
+
+#[tokio::test]
+async fn test_resp3_and_select() {
+    let (mut options, _dir) = spawn_server().await;
+    options.protocol = Protocol::Resp3;
+    options.db = 2;
+    let resp3 = Connection::connect(&options).await.unwrap();
+    resp3.call(&["HSET", "h", "f", "v"]).await.unwrap();
+    resp3.call(&["ZADD", "z", "1.5", "m"]).await.unwrap();
+    assert_eq!(
+        resp3.call(&["HGETALL", "h"]).await.unwrap(),
+        Reply::Map(vec![(Reply::bulk("f"), Reply::bulk("v"))])
+    );
+    assert_eq!(
+        resp3.call(&["ZSCORE", "z", "m"]).await.unwrap(),
+        Reply::Double(1.5)
+    );
+    assert_eq!(resp3.call(&["GET", "nope"]).await.unwrap(), Reply::Null);
+
+    // RESP2 on the same database sees the same data, encoded the old way
+    options.protocol = Protocol::Resp2;
+    let resp2 = Connection::connect(&options).await.unwrap();
+    assert_eq!(
+        resp2.call(&["HGETALL", "h"]).await.unwrap(),
+        Reply::Array(vec![Reply::bulk("f"), Reply::bulk("v")])
+    );
+    assert_eq!(
+        resp2.call(&["ZSCORE", "z", "m"]).await.unwrap(),
+        Reply::bulk("1.5")
+    );
+
+    // Database 0 is untouched
+    options.db = 0;
+    let other = Connection::connect(&options).await.unwrap();
+    assert_eq!(
+        other.call(&["EXISTS", "h"]).await.unwrap(),
+        Reply::Integer(0)
+    );
+
+    let hello = other.call(&["HELLO", "4"]).await.unwrap();
+    assert_eq!(
+        hello,
+        Reply::Error("NOPROTO unsupported protocol version".into())
+    );
+}
+
+#[tokio::test]
+async fn test_pool_reconnects_with_backoff() {
+    let port = free_port();
+    let mut options = Options::tcp(format!("127.0.0.1:{}", port));
+    options.pool_size = 1;
+    options.backoff = Backoff {
+        initial: Duration::from_millis(20),
+        max: Duration::from_millis(100),
+        retries: 30,
+    };
+
+    // Nothing listens yet, and no retries means giving up at once
+    let impatient = Pool::new(Options {
+        backoff: Backoff {
+            retries: 0,
+            ..options.backoff
+        },
+        ..options.clone()
+    });
+    assert!(matches!(impatient.call(&["PING"]).await, Err(Error::Io(_))));
+
+    // The server comes up while the pool is retrying
+    let pool = Arc::new(Pool::new(options));
+    let waiting = tokio::spawn({
+        let pool = Arc::clone(&pool);
+        async move { pool.call(&["PING"]).await }
+    });
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    let _dir = start_server(port).await;
+    assert_eq!(waiting.await.unwrap().unwrap(), Reply::status("PONG"));
+
+    // The server drops idle clients; the pool replaces the connection
+    pool.call(&["CONFIG", "SET", "timeout", "1"]).await.unwrap();
+    let first = pool.get().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(1300)).await;
+    assert!(first.is_closed());
+    assert_eq!(pool.call(&["PING"]).await.unwrap(), Reply::status("PONG"));
+}
+
+/// Serve one scripted connection: `respond` maps each command received to
+/// the raw bytes to send back
+#[cfg(unix)]
+async fn serve_script<S, F>(mut stream: S, mut respond: F)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    F: FnMut(Vec<Bytes>) -> Vec<u8>,
+{
+    let mut buf = Vec::new();
+    loop {
+        while let Ok(Some((reply, used))) = resp::decode(&buf) {
+            buf.drain(..used);
+            let Reply::Array(items) = reply else { return };
+            let args = items
+                .into_iter()
+                .map(|item| match item {
+                    Reply::Bulk(arg) => arg,
+                    _ => Bytes::new(),
+                })
+                .collect();
+            stream.write_all(&respond(args)).await.unwrap();
+        }
+        let mut chunk = [0u8; 1024];
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+        }
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_unix_socket_and_caller_streams() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("rudis.sock");
+    let listener = tokio::net::UnixListener::bind(&path).unwrap();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        serve_script(stream, |_| b"+PONG\r\n".to_vec()).await
+    });
+    let connection = Connection::connect(&Options::unix(&path)).await.unwrap();
+    assert_eq!(
+        connection.call(&["PING"]).await.unwrap(),
+        Reply::status("PONG")
+    );
+
+    // Any stream works, e.g. a TLS session opened by the caller
+    let (ours, theirs) = tokio::io::duplex(1024);
+    tokio::spawn(serve_script(theirs, |_| b":7\r\n".to_vec()));
+    let connection = Connection::from_stream(ours, &Options::tcp("unused"))
+        .await
+        .unwrap();
+    assert_eq!(connection.query::<i64, _>(&["DBSIZE"]).await.unwrap(), 7);
+}
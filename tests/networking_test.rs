@@ -0,0 +1,92 @@
+// Tests for listening on several bind addresses
+
+use rudis::database::Database;
+use rudis::networking::{is_unavailable_address, Networking};
+use socket2::{Domain, Socket, Type};
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// A port that was free a moment ago
+fn free_port() -> u16 {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().port()
+}
+
+fn addresses(list: &[&str]) -> Vec<String> {
+    list.iter().map(|a| a.to_string()).collect()
+}
+
+async fn ping(addr: SocketAddr) -> Vec<u8> {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(b"*1\r\n$4\r\nPING\r\n").await.unwrap();
+    let mut reply = vec![0u8; 7];
+    stream.read_exact(&mut reply).await.unwrap();
+    reply
+}
+
+#[tokio::test]
+async fn test_listens_on_every_address() {
+    let port = free_port();
+    // ::1 is optional so the test also runs on hosts without IPv6
+    let networking = Networking::bind(&addresses(&["127.0.0.1", "-::1"]), port).unwrap();
+    let addrs = networking.local_addrs();
+    assert!(!addrs.is_empty() && addrs.len() <= 2);
+    assert!(addrs.iter().all(|a| a.port() == port));
+
+    let db = Database::new_shared(16);
+    tokio::spawn(async move { networking.listen(&db).await });
+    for addr in addrs {
+        assert_eq!(ping(addr).await, b"+PONG\r\n", "{}", addr);
+    }
+}
+
+#[tokio::test]
+async fn test_wildcards_bind_side_by_side() {
+    let port = free_port();
+    let networking = Networking::bind(&addresses(&["*", "-::*"]), port).unwrap();
+    let addrs = networking.local_addrs();
+    assert!(addrs[0].ip().is_unspecified() && addrs[0].is_ipv4());
+    if let Some(v6) = addrs.get(1) {
+        assert!(v6.ip().is_unspecified() && v6.is_ipv6());
+    }
+}
+
+#[tokio::test]
+async fn test_optional_and_missing_addresses() {
+    // TEST-NET-1 is never assigned to a local interface
+    let port = free_port();
+    let networking = Networking::bind(&addresses(&["-192.0.2.1", "127.0.0.1"]), port).unwrap();
+    assert_eq!(networking.local_addrs().len(), 1);
+
+    let err = Networking::bind(&addresses(&["192.0.2.1"]), free_port())
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("192.0.2.1"), "{}", err);
+    assert!(Networking::bind(&addresses(&["-192.0.2.1"]), free_port()).is_err());
+}
+
+#[test]
+fn test_unsupported_family_is_unavailable() {
+    // No address family has this number
+    let err = Socket::new(Domain::from(255), Type::STREAM, None).unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::EAFNOSUPPORT));
+    assert!(is_unavailable_address(&err));
+    assert!(is_unavailable_address(&std::io::Error::from_raw_os_error(
+        libc::EPROTONOSUPPORT
+    )));
+
+    assert!(!is_unavailable_address(&std::io::Error::from_raw_os_error(
+        libc::EADDRINUSE
+    )));
+    assert!(!is_unavailable_address(&std::io::Error::from(
+        std::io::ErrorKind::Unsupported
+    )));
+}
+
+#[tokio::test]
+async fn test_port_zero_disables_tcp() {
+    let networking = Networking::bind(&addresses(&["127.0.0.1"]), 0).unwrap();
+    assert!(networking.local_addrs().is_empty());
+    assert!(networking.local_addr().is_err());
+}
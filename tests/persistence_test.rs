@@ -0,0 +1,190 @@
+// Tests for RDB snapshots and save-point scheduling
+
+use bytes::Bytes;
+use rudis::commands::Command;
+use rudis::config::{Config, SavePoint};
+use rudis::database::traits::{HashOp, KeyOp, ListOp, SetOp, StringOp};
+use rudis::database::Database;
+use rudis::networking::resp::{encode, Protocol, RespValue};
+use rudis::persistence::{crc64::crc64, rdb, Persistence};
+
+fn run(db: &rudis::database::SharedDatabase, rt: &tokio::runtime::Runtime, cmd: &str) -> String {
+    let resp = RespValue::Array(
+        cmd.split_whitespace()
+            .map(|p| RespValue::BulkString(Bytes::from(p.to_string())))
+            .collect(),
+    );
+    let cmd = Command::parse(&resp).expect("command should parse");
+    let reply = rt.block_on(cmd.execute(db));
+    String::from_utf8_lossy(&encode(&reply, Protocol::Resp2)).to_string()
+}
+
+#[test]
+fn test_parse_save_points() {
+    let points = Config::parse_save_points(&["900", "1", "300", "10", "60", "10000"]).unwrap();
+    assert_eq!(
+        points,
+        vec![
+            SavePoint {
+                seconds: 900,
+                changes: 1
+            },
+            SavePoint {
+                seconds: 300,
+                changes: 10
+            },
+            SavePoint {
+                seconds: 60,
+                changes: 10000
+            },
+        ]
+    );
+
+    assert!(Config::parse_save_points(&[""]).unwrap().is_empty());
+    assert!(Config::parse_save_points(&["900"]).is_err());
+    assert!(Config::parse_save_points(&["900", "x"]).is_err());
+}
+
+#[test]
+fn test_crc64_check_value() {
+    // Reference value from the Redis crc64 test suite
+    assert_eq!(crc64(0, b"123456789"), 0xe9c6d914c4b8d9ca);
+}
+
+#[test]
+fn test_rdb_round_trip() {
+    let db = Database::new(16);
+    db.set(&Bytes::from("str"), Bytes::from("hello"));
+    db.set(&Bytes::from("num"), Bytes::from("12345"));
+    db.set(&Bytes::from("big"), Bytes::from("x".repeat(20000)));
+    db.rpush(&Bytes::from("list"), &[Bytes::from("a"), Bytes::from("b")])
+        .unwrap();
+    db.sadd(&Bytes::from("set"), &[Bytes::from("m1"), Bytes::from("m2")])
+        .unwrap();
+    db.hset(&Bytes::from("hash"), Bytes::from("f"), Bytes::from("v"))
+        .unwrap();
+    db.expire(&Bytes::from("str"), 100).unwrap();
+    db.select(3);
+    db.set(&Bytes::from("other"), Bytes::from("db3"));
+    db.select(0);
+
+    let payload = rdb::dump(&db).unwrap();
+    assert!(payload.starts_with(b"REDIS0009"));
+
+    let restored = Database::new(16);
+    assert_eq!(rdb::load(&restored, &payload).unwrap(), 7);
+    assert_eq!(
+        restored.get(&Bytes::from("str")).unwrap(),
+        Some(Bytes::from("hello"))
+    );
+    assert_eq!(
+        restored.get(&Bytes::from("num")).unwrap(),
+        Some(Bytes::from("12345"))
+    );
+    assert_eq!(restored.str_len(&Bytes::from("big")).unwrap(), 20000);
+    assert_eq!(
+        restored.lrange(&Bytes::from("list"), 0, -1).unwrap(),
+        vec![Bytes::from("a"), Bytes::from("b")]
+    );
+    assert_eq!(restored.scard(&Bytes::from("set")).unwrap(), 2);
+    assert_eq!(
+        restored.hget(&Bytes::from("hash"), &Bytes::from("f")),
+        Ok(Some(Bytes::from("v")))
+    );
+    let ttl = restored.ttl(&Bytes::from("str"));
+    assert!(ttl > 90 && ttl <= 100);
+    restored.select(3);
+    assert_eq!(
+        restored.get(&Bytes::from("other")).unwrap(),
+        Some(Bytes::from("db3"))
+    );
+}
+
+#[test]
+fn test_rdb_rejects_corruption() {
+    let db = Database::new(16);
+    db.set(&Bytes::from("key"), Bytes::from("value"));
+    let mut payload = rdb::dump(&db).unwrap();
+
+    // Flip a byte inside the body: the checksum no longer matches
+    let idx = payload.len() - 12;
+    payload[idx] ^= 0xff;
+    assert!(rdb::load(&Database::new(16), &payload).is_err());
+
+    // Truncated file
+    let payload = rdb::dump(&db).unwrap();
+    assert!(rdb::load(&Database::new(16), &payload[..payload.len() - 9]).is_err());
+
+    assert!(rdb::load(&Database::new(16), b"NOTREDIS").is_err());
+}
+
+#[test]
+fn test_write_commands_increment_dirty() {
+    let db = Database::new_shared(16);
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    assert_eq!(db.save_state().dirty(), 0);
+
+    run(&db, &rt, "SET a 1");
+    run(&db, &rt, "INCR a");
+    run(&db, &rt, "GET a");
+    run(&db, &rt, "LPUSH l x");
+    assert_eq!(db.save_state().dirty(), 3);
+
+    // Failed writes don't count
+    run(&db, &rt, "SET s notanumber");
+    run(&db, &rt, "INCR s");
+    assert_eq!(db.save_state().dirty(), 4);
+
+    // Writes count the keys they change, not the commands
+    run(&db, &rt, "MSET a 1 b 2 c 3");
+    assert_eq!(db.save_state().dirty(), 7);
+    run(&db, &rt, "DEL missing");
+    run(&db, &rt, "SADD set x");
+    run(&db, &rt, "SADD set x");
+    assert_eq!(db.save_state().dirty(), 8);
+
+    let info = run(&db, &rt, "INFO persistence");
+    assert!(info.contains("rdb_changes_since_last_save:8\r\n"));
+    assert!(!info.contains("redis_version"));
+}
+
+#[test]
+fn test_save_points_trigger() {
+    let db = Database::new(16);
+    let points = Config::parse_save_points(&["900", "1", "60", "100"]).unwrap();
+    let start = db.save_state().last_save();
+
+    // Nothing changed yet
+    assert!(!db.save_state().should_save(&points, start + 1000));
+
+    db.save_state().add_dirty(5);
+    assert!(!db.save_state().should_save(&points, start + 120));
+    assert!(db.save_state().should_save(&points, start + 900));
+
+    db.save_state().add_dirty(100);
+    assert!(db.save_state().should_save(&points, start + 60));
+}
+
+#[test]
+fn test_save_and_load_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config {
+        dir: dir.path().to_string_lossy().to_string(),
+        ..Config::default()
+    };
+    let persistence = Persistence::new(&config);
+
+    let db = Database::new(16);
+    db.set(&Bytes::from("key"), Bytes::from("value"));
+    db.save_state().add_dirty(1);
+    persistence.save(&db).unwrap();
+    assert_eq!(db.save_state().dirty(), 0);
+    assert!(config.rdb_path().exists());
+
+    let restored = Database::new(16);
+    assert_eq!(persistence.load(&restored).unwrap(), 1);
+    assert_eq!(
+        restored.get(&Bytes::from("key")).unwrap(),
+        Some(Bytes::from("value"))
+    );
+}
@@ -0,0 +1,167 @@
+// Tests for typed replies and their RESP2/RESP3 encodings
+
+use bytes::Bytes;
+use rudis::commands::{Command, CommandError, Reply};
+use rudis::database::{Database, SharedDatabase};
+use rudis::networking::resp::{decode, encode, Protocol, RespValue};
+
+async fn run(db: &SharedDatabase, args: &[&str]) -> Reply {
+    let resp = RespValue::Array(
+        args.iter()
+            .map(|a| RespValue::BulkString(Bytes::from(a.to_string())))
+            .collect(),
+    );
+    Command::parse(&resp)
+        .expect("command should parse")
+        .execute(db)
+        .await
+}
+
+fn resp2(reply: &Reply) -> String {
+    String::from_utf8_lossy(&encode(reply, Protocol::Resp2)).to_string()
+}
+
+fn resp3(reply: &Reply) -> String {
+    String::from_utf8_lossy(&encode(reply, Protocol::Resp3)).to_string()
+}
+
+#[test]
+fn test_scalar_encodings() {
+    assert_eq!(resp2(&Reply::ok()), "+OK\r\n");
+    assert_eq!(resp2(&Reply::Integer(-7)), ":-7\r\n");
+    assert_eq!(resp2(&Reply::bulk("hi")), "$2\r\nhi\r\n");
+    assert_eq!(resp2(&Reply::bulk("")), "$0\r\n\r\n");
+
+    assert_eq!(resp2(&Reply::Null), "$-1\r\n");
+    assert_eq!(resp2(&Reply::NullArray), "*-1\r\n");
+    assert_eq!(resp3(&Reply::Null), "_\r\n");
+    assert_eq!(resp3(&Reply::NullArray), "_\r\n");
+
+    assert_eq!(resp2(&Reply::Double(1.5)), "$3\r\n1.5\r\n");
+    assert_eq!(resp3(&Reply::Double(1.5)), ",1.5\r\n");
+    assert_eq!(resp3(&Reply::Double(3.0)), ",3\r\n");
+    assert_eq!(resp3(&Reply::Double(f64::NEG_INFINITY)), ",-inf\r\n");
+    assert_eq!(resp3(&Reply::Double(f64::NAN)), ",nan\r\n");
+}
+
+#[test]
+fn test_aggregate_encodings() {
+    let map = Reply::Map(vec![(Reply::bulk("f"), Reply::Integer(1))]);
+    assert_eq!(resp2(&map), "*2\r\n$1\r\nf\r\n:1\r\n");
+    assert_eq!(resp3(&map), "%1\r\n$1\r\nf\r\n:1\r\n");
+
+    let pairs = Reply::Pairs(vec![(Reply::bulk("f"), Reply::bulk("v"))]);
+    assert_eq!(resp2(&pairs), "*2\r\n$1\r\nf\r\n$1\r\nv\r\n");
+    assert_eq!(resp3(&pairs), "*1\r\n*2\r\n$1\r\nf\r\n$1\r\nv\r\n");
+
+    let set = Reply::bulk_set(vec![Bytes::from("a")]);
+    assert_eq!(resp2(&set), "*1\r\n$1\r\na\r\n");
+    assert_eq!(resp3(&set), "~1\r\n$1\r\na\r\n");
+
+    let push = Reply::Push(vec![Reply::bulk("message"), Reply::Null]);
+    assert_eq!(resp2(&push), "*2\r\n$7\r\nmessage\r\n$-1\r\n");
+    assert_eq!(resp3(&push), ">2\r\n$7\r\nmessage\r\n_\r\n");
+
+    // Nested values are encoded for the same protocol
+    let nested = Reply::Array(vec![Reply::Double(2.5), Reply::Array(Vec::new())]);
+    assert_eq!(resp2(&nested), "*2\r\n$3\r\n2.5\r\n*0\r\n");
+    assert_eq!(resp3(&nested), "*2\r\n,2.5\r\n*0\r\n");
+}
+
+#[test]
+fn test_error_replies() {
+    assert_eq!(
+        Reply::error("plain message"),
+        Reply::Error("ERR plain message".to_string())
+    );
+    assert_eq!(
+        Reply::error("WRONGTYPE already coded"),
+        Reply::Error("WRONGTYPE already coded".to_string())
+    );
+    assert_eq!(resp2(&Reply::error("two\r\nlines")), "-ERR two  lines\r\n");
+    assert!(Reply::from(CommandError::WrongType).is_error());
+    assert_eq!(
+        Reply::from(Err::<i64, _>(CommandError::InvalidInteger)),
+        Reply::Error("ERR value is not an integer or out of range".to_string())
+    );
+    assert_eq!(
+        Reply::from(Ok::<_, CommandError>(Some(3usize))),
+        Reply::Integer(3)
+    );
+    assert_eq!(Reply::from(None::<Bytes>), Reply::Null);
+}
+
+#[tokio::test]
+async fn test_handlers_return_typed_replies() {
+    let db = Database::new_shared(16);
+    assert_eq!(run(&db, &["SET", "k", "v"]).await, Reply::ok());
+    assert_eq!(run(&db, &["GET", "k"]).await, Reply::bulk("v"));
+    assert_eq!(run(&db, &["GET", "missing"]).await, Reply::Null);
+    assert!(run(&db, &["LPUSH", "k", "x"]).await.is_error());
+
+    run(&db, &["HSET", "h", "f", "v"]).await;
+    assert_eq!(
+        run(&db, &["HGETALL", "h"]).await,
+        Reply::Map(vec![(Reply::bulk("f"), Reply::bulk("v"))])
+    );
+
+    run(&db, &["ZADD", "z", "2.5", "m"]).await;
+    let score = run(&db, &["ZSCORE", "z", "m"]).await;
+    assert_eq!(score, Reply::Double(2.5));
+    assert_eq!(resp2(&score), "$3\r\n2.5\r\n");
+
+    run(&db, &["SADD", "s", "a"]).await;
+    assert_eq!(
+        run(&db, &["SMEMBERS", "s"]).await,
+        Reply::bulk_set(vec![Bytes::from("a")])
+    );
+
+    // CONFIG GET is a map, sent flat to RESP2 clients
+    let config = run(&db, &["CONFIG", "GET", "port"]).await;
+    assert!(matches!(&config, Reply::Map(pairs) if pairs.len() == 1));
+    assert!(resp2(&config).starts_with("*2\r\n$4\r\nport\r\n"));
+    assert!(resp3(&config).starts_with("%1\r\n$4\r\nport\r\n"));
+}
+
+#[test]
+fn test_decode_round_trips() {
+    let replies = [
+        Reply::ok(),
+        Reply::error("WRONGTYPE nope"),
+        Reply::Integer(-3),
+        Reply::bulk("a\r\nb"),
+        Reply::Null,
+        Reply::Array(vec![Reply::bulk("x"), Reply::Array(Vec::new())]),
+    ];
+    for reply in &replies {
+        for protocol in [Protocol::Resp2, Protocol::Resp3] {
+            let bytes = encode(reply, protocol);
+            assert_eq!(decode(&bytes).unwrap(), Some((reply.clone(), bytes.len())));
+            // A reply cut short is incomplete, not an error
+            assert_eq!(decode(&bytes[..bytes.len() - 1]).unwrap(), None);
+        }
+    }
+
+    let resp3 = [
+        Reply::Map(vec![(Reply::bulk("f"), Reply::Double(1.5))]),
+        Reply::bulk_set(vec![Bytes::from("m")]),
+        Reply::Push(vec![Reply::bulk("message")]),
+        Reply::Double(f64::NEG_INFINITY),
+    ];
+    for reply in &resp3 {
+        let bytes = encode(reply, Protocol::Resp3);
+        assert_eq!(decode(&bytes).unwrap().unwrap().0, *reply);
+    }
+
+    // RESP3 types without a `Reply` of their own
+    assert_eq!(decode(b"#t\r\n").unwrap().unwrap().0, Reply::Integer(1));
+    assert_eq!(
+        decode(b"=7\r\ntxt:abc\r\n").unwrap().unwrap().0,
+        Reply::bulk("abc")
+    );
+    assert_eq!(
+        decode(b"|1\r\n+key\r\n+value\r\n:5\r\n").unwrap().unwrap(),
+        (Reply::Integer(5), 22)
+    );
+    assert!(decode(b"?oops\r\n").is_err());
+}
@@ -0,0 +1,352 @@
+// Tests for the set commands: random members, SMOVE, SMISMEMBER, SINTERCARD,
+// the STORE variants and SSCAN
+
+use bytes::Bytes;
+use rudis::commands::{CommandError, Reply};
+use rudis::database::Database;
+use rudis::Client;
+use std::collections::HashSet;
+
+fn client() -> Client {
+    Client::new(&Database::new_shared(16))
+}
+
+fn members(reply: Reply) -> HashSet<Bytes> {
+    match reply {
+        Reply::Array(items) | Reply::Set(items) => items
+            .into_iter()
+            .map(|item| match item {
+                Reply::Bulk(value) => value,
+                other => panic!("expected a bulk string, got {:?}", other),
+            })
+            .collect(),
+        other => panic!("expected members, got {:?}", other),
+    }
+}
+
+fn set_of(values: &[&'static str]) -> HashSet<Bytes> {
+    values.iter().map(|&v| Bytes::from(v)).collect()
+}
+
+#[tokio::test]
+async fn test_spop_and_srandmember() {
+    let client = client();
+    client
+        .call(&[b"SADD", b"s", b"a", b"b", b"c", b"d", b"e"])
+        .await;
+    let all = set_of(&["a", "b", "c", "d", "e"]);
+
+    let popped = members(client.call(&[b"SPOP", b"s", b"2"]).await);
+    assert_eq!(popped.len(), 2);
+    assert_eq!(client.call(&[b"SCARD", b"s"]).await, Reply::Integer(3));
+    let Reply::Bulk(one) = client.call(&[b"SPOP", b"s"]).await else {
+        panic!("expected a member");
+    };
+    assert!(all.contains(&one) && !popped.contains(&one));
+    let rest = members(client.call(&[b"SPOP", b"s", b"10"]).await);
+    assert_eq!(rest.len(), 2);
+    assert_eq!(client.call(&[b"EXISTS", b"s"]).await, Reply::Integer(0));
+    assert_eq!(client.call(&[b"SPOP", b"s"]).await, Reply::Null);
+    assert!(members(client.call(&[b"SPOP", b"s", b"1"]).await).is_empty());
+    assert_eq!(
+        client.call(&[b"SPOP", b"s", b"-1"]).await,
+        CommandError::Custom("value is out of range, must be positive".into()).into()
+    );
+
+    client
+        .call(&[b"SADD", b"s", b"a", b"b", b"c", b"d", b"e"])
+        .await;
+    // A positive count picks different members, at most all of them
+    let Reply::Array(picked) = client.call(&[b"SRANDMEMBER", b"s", b"3"]).await else {
+        panic!("expected an array");
+    };
+    assert_eq!(members(Reply::Array(picked)).len(), 3);
+    assert_eq!(
+        members(client.call(&[b"SRANDMEMBER", b"s", b"10"]).await),
+        all
+    );
+    // A negative one may repeat them
+    let Reply::Array(picked) = client.call(&[b"SRANDMEMBER", b"s", b"-20"]).await else {
+        panic!("expected an array");
+    };
+    assert_eq!(picked.len(), 20);
+    assert!(members(Reply::Array(picked)).is_subset(&all));
+    assert_eq!(client.call(&[b"SCARD", b"s"]).await, Reply::Integer(5));
+    assert_eq!(client.call(&[b"SRANDMEMBER", b"none"]).await, Reply::Null);
+    assert_eq!(
+        client.call(&[b"SRANDMEMBER", b"none", b"-3"]).await,
+        Reply::Array(vec![])
+    );
+    assert_eq!(
+        client
+            .call(&[b"SRANDMEMBER", b"s", b"-9223372036854775808"])
+            .await,
+        CommandError::OutOfRange(-i64::MAX, i64::MAX).into()
+    );
+}
+
+#[tokio::test]
+async fn test_smove_and_smismember() {
+    let client = client();
+    client.call(&[b"SADD", b"src", b"a", b"b"]).await;
+    assert_eq!(
+        client.call(&[b"SMOVE", b"src", b"dst", b"a"]).await,
+        Reply::Integer(1)
+    );
+    assert_eq!(
+        client.call(&[b"SMOVE", b"src", b"dst", b"a"]).await,
+        Reply::Integer(0)
+    );
+    assert_eq!(
+        client.call(&[b"SMOVE", b"src", b"src", b"b"]).await,
+        Reply::Integer(1)
+    );
+    assert_eq!(
+        client.call(&[b"SMOVE", b"src", b"dst", b"b"]).await,
+        Reply::Integer(1)
+    );
+    // The source goes away with its last member
+    assert_eq!(client.call(&[b"EXISTS", b"src"]).await, Reply::Integer(0));
+    assert_eq!(
+        client
+            .call(&[b"SMISMEMBER", b"dst", b"a", b"x", b"b"])
+            .await,
+        Reply::Array(vec![
+            Reply::Integer(1),
+            Reply::Integer(0),
+            Reply::Integer(1)
+        ])
+    );
+    assert_eq!(
+        client.call(&[b"SMISMEMBER", b"none", b"a"]).await,
+        Reply::Array(vec![Reply::Integer(0)])
+    );
+
+    // Nothing moves when either key is not a set
+    client.call(&[b"SET", b"str", b"x"]).await;
+    assert_eq!(
+        client.call(&[b"SMOVE", b"dst", b"str", b"a"]).await,
+        CommandError::WrongType.into()
+    );
+    assert_eq!(
+        client.call(&[b"SMOVE", b"str", b"dst", b"a"]).await,
+        CommandError::WrongType.into()
+    );
+    assert_eq!(client.call(&[b"SCARD", b"dst"]).await, Reply::Integer(2));
+    assert_eq!(
+        client.call(&[b"SMISMEMBER", b"str", b"a"]).await,
+        CommandError::WrongType.into()
+    );
+}
+
+#[tokio::test]
+async fn test_set_algebra() {
+    let client = client();
+    client
+        .call(&[b"SADD", b"big", b"a", b"b", b"c", b"d", b"e"])
+        .await;
+    client.call(&[b"SADD", b"small", b"b", b"d", b"x"]).await;
+    client.call(&[b"SADD", b"other", b"d", b"e"]).await;
+
+    assert_eq!(
+        members(client.call(&[b"SINTER", b"big", b"small"]).await),
+        set_of(&["b", "d"])
+    );
+    assert_eq!(
+        members(client.call(&[b"SINTER", b"big", b"missing"]).await),
+        set_of(&[])
+    );
+    assert_eq!(
+        members(
+            client
+                .call(&[b"SUNION", b"small", b"other", b"missing"])
+                .await
+        ),
+        set_of(&["b", "d", "x", "e"])
+    );
+    assert_eq!(
+        members(client.call(&[b"SDIFF", b"big", b"small", b"other"]).await),
+        set_of(&["a", "c"])
+    );
+
+    for (command, expected) in [
+        (&b"SINTERSTORE"[..], set_of(&["d"])),
+        (b"SUNIONSTORE", set_of(&["a", "b", "c", "d", "e", "x"])),
+        (b"SDIFFSTORE", set_of(&["a", "c"])),
+    ] {
+        assert_eq!(
+            client
+                .call(&[command, b"dest", b"big", b"small", b"other"])
+                .await,
+            Reply::Integer(expected.len() as i64)
+        );
+        assert_eq!(
+            members(client.call(&[b"SMEMBERS", b"dest"]).await),
+            expected
+        );
+    }
+    // A source can also be the destination
+    assert_eq!(
+        client
+            .call(&[b"SINTERSTORE", b"big", b"big", b"small"])
+            .await,
+        Reply::Integer(2)
+    );
+    assert_eq!(
+        members(client.call(&[b"SMEMBERS", b"big"]).await),
+        set_of(&["b", "d"])
+    );
+    // An empty result deletes the destination, whatever it held
+    client.call(&[b"SET", b"str", b"x"]).await;
+    client.call(&[b"EXPIRE", b"dest", b"100"]).await;
+    assert_eq!(
+        client
+            .call(&[b"SINTERSTORE", b"dest", b"small", b"missing"])
+            .await,
+        Reply::Integer(0)
+    );
+    assert_eq!(client.call(&[b"EXISTS", b"dest"]).await, Reply::Integer(0));
+    assert_eq!(
+        client.call(&[b"SUNIONSTORE", b"str", b"small"]).await,
+        Reply::Integer(3)
+    );
+    assert_eq!(
+        client.call(&[b"TYPE", b"str"]).await,
+        Reply::Status("set".into())
+    );
+    client.call(&[b"RPUSH", b"list", b"x"]).await;
+    assert_eq!(
+        client
+            .call(&[b"SDIFFSTORE", b"dest", b"small", b"list"])
+            .await,
+        CommandError::WrongType.into()
+    );
+    assert_eq!(client.call(&[b"EXISTS", b"dest"]).await, Reply::Integer(0));
+}
+
+#[tokio::test]
+async fn test_sintercard() {
+    let client = client();
+    client.call(&[b"SADD", b"a", b"1", b"2", b"3", b"4"]).await;
+    client.call(&[b"SADD", b"b", b"2", b"3", b"4", b"5"]).await;
+    for (args, expected) in [
+        (&[&b"2"[..], b"a", b"b"][..], 3),
+        (&[b"2", b"a", b"b", b"LIMIT", b"2"], 2),
+        (&[b"2", b"a", b"b", b"limit", b"0"], 3),
+        (&[b"1", b"a"], 4),
+        (&[b"2", b"a", b"missing"], 0),
+    ] {
+        let mut command: Vec<&[u8]> = vec![b"SINTERCARD"];
+        command.extend_from_slice(args);
+        assert_eq!(
+            client.call(&command).await,
+            Reply::Integer(expected),
+            "{:?}",
+            args
+        );
+    }
+
+    for (args, error) in [
+        (
+            &[&b"0"[..], b"a"][..],
+            CommandError::Custom("numkeys should be greater than 0".into()),
+        ),
+        (
+            &[b"3", b"a", b"b"],
+            CommandError::Custom("Number of keys can't be greater than number of args".into()),
+        ),
+        (
+            &[b"2", b"a", b"b", b"LIMIT", b"-1"],
+            CommandError::Custom("LIMIT can't be negative".into()),
+        ),
+        (&[b"1", b"a", b"b"], CommandError::SyntaxError),
+        (&[b"1", b"a", b"LIMIT"], CommandError::SyntaxError),
+    ] {
+        let mut command: Vec<&[u8]> = vec![b"SINTERCARD"];
+        command.extend_from_slice(args);
+        assert_eq!(client.call(&command).await, error.into(), "{:?}", args);
+    }
+    assert_eq!(
+        client
+            .call(&[
+                b"COMMAND",
+                b"GETKEYS",
+                b"SINTERCARD",
+                b"2",
+                b"a",
+                b"b",
+                b"LIMIT",
+                b"1"
+            ])
+            .await,
+        Reply::Array(vec![Reply::bulk("a"), Reply::bulk("b")])
+    );
+}
+
+#[tokio::test]
+async fn test_sscan() {
+    let client = client();
+    for i in 0..100 {
+        client
+            .call(&[b"SADD", b"s", format!("member:{}", i).as_bytes()])
+            .await;
+    }
+
+    // Every member comes back exactly once
+    let mut seen = HashSet::new();
+    let mut cursor = Bytes::from("0");
+    loop {
+        let reply = client
+            .call(&[b"SSCAN", b"s", &cursor, b"COUNT", b"7"])
+            .await;
+        let Reply::Array(mut parts) = reply else {
+            panic!("expected an array, got {:?}", reply);
+        };
+        let Reply::Array(items) = parts.pop().unwrap() else {
+            panic!("expected the members");
+        };
+        for member in members(Reply::Array(items)) {
+            assert!(seen.insert(member));
+        }
+        let Some(Reply::Bulk(next)) = parts.pop() else {
+            panic!("expected a cursor");
+        };
+        cursor = next;
+        if cursor == "0" {
+            break;
+        }
+    }
+    assert_eq!(seen.len(), 100);
+
+    let reply = client
+        .call(&[
+            b"SSCAN",
+            b"s",
+            b"0",
+            b"MATCH",
+            b"member:9?",
+            b"COUNT",
+            b"1000",
+        ])
+        .await;
+    let Reply::Array(mut parts) = reply else {
+        panic!("expected an array, got {:?}", reply);
+    };
+    let expected: HashSet<Bytes> = (90..100)
+        .map(|i| Bytes::from(format!("member:{}", i)))
+        .collect();
+    assert_eq!(members(parts.pop().unwrap()), expected);
+
+    assert_eq!(
+        client.call(&[b"SSCAN", b"missing", b"0"]).await,
+        Reply::Array(vec![Reply::bulk("0"), Reply::Array(vec![])])
+    );
+    assert_eq!(
+        client.call(&[b"SSCAN", b"s", b"0", b"NOVALUES"]).await,
+        CommandError::SyntaxError.into()
+    );
+    assert_eq!(
+        client.call(&[b"SSCAN", b"s", b"x"]).await,
+        CommandError::InvalidCursor.into()
+    );
+}
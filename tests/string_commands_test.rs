@@ -0,0 +1,395 @@
+// Tests for the string commands: ranges, floats, overflow and multi-key sets
+
+use bytes::Bytes;
+use rudis::commands::{CommandError, Reply};
+use rudis::database::traits::{KeyOp, StringOp};
+use rudis::database::Database;
+use rudis::Client;
+use std::sync::Arc;
+
+fn client() -> Client {
+    Client::new(&Database::new_shared(16))
+}
+
+fn ints(values: &[i64]) -> Reply {
+    Reply::Array(values.iter().map(|&v| Reply::Integer(v)).collect())
+}
+
+#[tokio::test]
+async fn test_integer_overflow() {
+    let client = client();
+    client.set("max", i64::MAX.to_string()).await.unwrap();
+    client.set("min", i64::MIN.to_string()).await.unwrap();
+    let overflow: Reply = CommandError::Overflow.into();
+    assert_eq!(client.call(&[b"INCR", b"max"]).await, overflow);
+    assert_eq!(client.call(&[b"INCRBY", b"max", b"1"]).await, overflow);
+    assert_eq!(client.call(&[b"DECR", b"min"]).await, overflow);
+    assert_eq!(
+        client
+            .call(&[b"DECRBY", b"n", i64::MIN.to_string().as_bytes()])
+            .await,
+        CommandError::DecrementOverflow.into()
+    );
+    // Failed increments leave the values alone
+    assert_eq!(
+        client.call(&[b"GET", b"max"]).await,
+        Reply::bulk(i64::MAX.to_string())
+    );
+    assert_eq!(client.call(&[b"EXISTS", b"n"]).await, Reply::Integer(0));
+}
+
+#[tokio::test]
+async fn test_getrange_and_setrange() {
+    let client = client();
+    client.set("k", "This is a string").await.unwrap();
+    for (start, end, expected) in [
+        ("0", "3", "This"),
+        ("-3", "-1", "ing"),
+        ("0", "-1", "This is a string"),
+        ("10", "100", "string"),
+        ("-1", "-5", ""),
+        ("100", "200", ""),
+    ] {
+        assert_eq!(
+            client
+                .call(&[b"GETRANGE", b"k", start.as_bytes(), end.as_bytes()])
+                .await,
+            Reply::bulk(expected),
+            "{} {}",
+            start,
+            end
+        );
+    }
+    assert_eq!(
+        client.call(&[b"SUBSTR", b"k", b"0", b"3"]).await,
+        Reply::bulk("This")
+    );
+    assert_eq!(
+        client.call(&[b"GETRANGE", b"nothing", b"0", b"-1"]).await,
+        Reply::bulk("")
+    );
+
+    assert_eq!(
+        client.call(&[b"SETRANGE", b"k", b"10", b"STRING"]).await,
+        Reply::Integer(16)
+    );
+    assert_eq!(
+        client.call(&[b"GET", b"k"]).await,
+        Reply::bulk("This is a STRING")
+    );
+    // Writing past the end pads with zero bytes
+    assert_eq!(
+        client.call(&[b"SETRANGE", b"pad", b"3", b"x"]).await,
+        Reply::Integer(4)
+    );
+    assert_eq!(
+        client.call(&[b"GET", b"pad"]).await,
+        Reply::bulk(&b"\0\0\0x"[..])
+    );
+    // An empty value does not create the key
+    assert_eq!(
+        client.call(&[b"SETRANGE", b"empty", b"5", b""]).await,
+        Reply::Integer(0)
+    );
+    assert_eq!(client.call(&[b"EXISTS", b"empty"]).await, Reply::Integer(0));
+    assert_eq!(
+        client.call(&[b"SETRANGE", b"k", b"-1", b"x"]).await,
+        CommandError::OffsetOutOfRange.into()
+    );
+    assert_eq!(
+        client.call(&[b"SETRANGE", b"k", b"536870912", b"x"]).await,
+        CommandError::StringTooLong.into()
+    );
+}
+
+#[tokio::test]
+async fn test_incrbyfloat() {
+    let client = client();
+    client.set("f", "10.50").await.unwrap();
+    assert_eq!(
+        client.call(&[b"INCRBYFLOAT", b"f", b"0.1"]).await,
+        Reply::bulk("10.6")
+    );
+    assert_eq!(
+        client.call(&[b"INCRBYFLOAT", b"f", b"-5"]).await,
+        Reply::bulk("5.6")
+    );
+    assert_eq!(
+        client.call(&[b"INCRBYFLOAT", b"new", b"3"]).await,
+        Reply::bulk("3")
+    );
+    assert_eq!(
+        client.call(&[b"INCRBYFLOAT", b"f", b"abc"]).await,
+        CommandError::InvalidFloat.into()
+    );
+    assert_eq!(
+        client.call(&[b"INCRBYFLOAT", b"inf", b"inf"]).await,
+        CommandError::NanOrInfinity.into()
+    );
+    assert_eq!(client.call(&[b"EXISTS", b"inf"]).await, Reply::Integer(0));
+    client.set("text", "hello").await.unwrap();
+    assert_eq!(
+        client.call(&[b"INCRBYFLOAT", b"text", b"1"]).await,
+        CommandError::InvalidFloat.into()
+    );
+}
+
+#[tokio::test]
+async fn test_msetnx_sets_all_or_nothing() {
+    let client = client();
+    assert_eq!(
+        client.call(&[b"MSETNX", b"a", b"1", b"b", b"2"]).await,
+        Reply::Integer(1)
+    );
+    client.call(&[b"RPUSH", b"list", b"x"]).await;
+    assert_eq!(
+        client
+            .call(&[b"MSETNX", b"c", b"3", b"a", b"9", b"list", b"y"])
+            .await,
+        Reply::Integer(0)
+    );
+    assert_eq!(
+        client.call(&[b"MGET", b"a", b"b", b"c", b"list"]).await,
+        Reply::Array(vec![
+            Reply::bulk("1"),
+            Reply::bulk("2"),
+            Reply::Null,
+            Reply::Null
+        ])
+    );
+}
+
+#[test]
+fn test_mset_is_atomic() {
+    // Readers never see a mix of two MSETs over the same keys
+    let db = Database::new_shared(16);
+    let keys: Vec<Bytes> = (0..32).map(|i| Bytes::from(format!("k{}", i))).collect();
+    let batch = |value: &str| -> Vec<(Bytes, Bytes)> {
+        keys.iter()
+            .map(|key| (key.clone(), Bytes::from(value.to_string())))
+            .collect()
+    };
+    db.mset(&batch("a"));
+    let writers: Vec<_> = ["a", "b"]
+        .into_iter()
+        .map(|value| {
+            let (db, pairs) = (Arc::clone(&db), batch(value));
+            std::thread::spawn(move || {
+                for _ in 0..500 {
+                    db.mset(&pairs);
+                }
+            })
+        })
+        .collect();
+    for _ in 0..500 {
+        let values = db.mget(&keys);
+        assert!(values.windows(2).all(|pair| pair[0] == pair[1]));
+    }
+    for writer in writers {
+        writer.join().unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_getdel_getex_and_psetex() {
+    let client = client();
+    client.set("k", "v").await.unwrap();
+    assert_eq!(client.call(&[b"GETDEL", b"k"]).await, Reply::bulk("v"));
+    assert_eq!(client.call(&[b"GETDEL", b"k"]).await, Reply::Null);
+
+    client.set("k", "v").await.unwrap();
+    assert_eq!(
+        client.call(&[b"GETEX", b"k", b"EX", b"100"]).await,
+        Reply::bulk("v")
+    );
+    assert_eq!(client.call(&[b"TTL", b"k"]).await, Reply::Integer(100));
+    assert_eq!(
+        client.call(&[b"GETEX", b"k", b"PERSIST"]).await,
+        Reply::bulk("v")
+    );
+    assert_eq!(client.call(&[b"TTL", b"k"]).await, Reply::Integer(-1));
+    assert_eq!(
+        client.call(&[b"GETEX", b"k", b"EX", b"0"]).await,
+        CommandError::InvalidExpireTime("getex").into()
+    );
+    assert_eq!(
+        client
+            .call(&[b"GETEX", b"k", b"EX", b"1", b"PX", b"1"])
+            .await,
+        CommandError::SyntaxError.into()
+    );
+    // An absolute time in the past deletes the key
+    assert_eq!(
+        client.call(&[b"GETEX", b"k", b"PXAT", b"1"]).await,
+        Reply::bulk("v")
+    );
+    assert_eq!(client.call(&[b"EXISTS", b"k"]).await, Reply::Integer(0));
+    assert_eq!(client.call(&[b"GETEX", b"k"]).await, Reply::Null);
+
+    assert_eq!(
+        client.call(&[b"PSETEX", b"p", b"100000", b"v"]).await,
+        Reply::ok()
+    );
+    assert_eq!(client.call(&[b"TTL", b"p"]).await, Reply::Integer(100));
+    assert_eq!(
+        client.call(&[b"PSETEX", b"p", b"-1", b"v"]).await,
+        CommandError::InvalidExpireTime("psetex").into()
+    );
+    assert_eq!(
+        client.call(&[b"SETEX", b"s", b"0", b"v"]).await,
+        CommandError::InvalidExpireTime("setex").into()
+    );
+}
+
+#[tokio::test]
+async fn test_lcs() {
+    let client = client();
+    client.set("key1", "ohmytext").await.unwrap();
+    client.set("key2", "mynewtext").await.unwrap();
+    assert_eq!(
+        client.call(&[b"LCS", b"key1", b"key2"]).await,
+        Reply::bulk("mytext")
+    );
+    assert_eq!(
+        client.call(&[b"LCS", b"key1", b"key2", b"LEN"]).await,
+        Reply::Integer(6)
+    );
+    assert_eq!(
+        client.call(&[b"LCS", b"key1", b"key2", b"IDX"]).await,
+        Reply::Map(vec![
+            (
+                Reply::bulk("matches"),
+                Reply::Array(vec![
+                    Reply::Array(vec![ints(&[4, 7]), ints(&[5, 8])]),
+                    Reply::Array(vec![ints(&[2, 3]), ints(&[0, 1])]),
+                ])
+            ),
+            (Reply::bulk("len"), Reply::Integer(6)),
+        ])
+    );
+    assert_eq!(
+        client
+            .call(&[
+                b"LCS",
+                b"key1",
+                b"key2",
+                b"IDX",
+                b"MINMATCHLEN",
+                b"4",
+                b"WITHMATCHLEN"
+            ])
+            .await,
+        Reply::Map(vec![
+            (
+                Reply::bulk("matches"),
+                Reply::Array(vec![Reply::Array(vec![
+                    ints(&[4, 7]),
+                    ints(&[5, 8]),
+                    Reply::Integer(4)
+                ])])
+            ),
+            (Reply::bulk("len"), Reply::Integer(6)),
+        ])
+    );
+    assert_eq!(
+        client.call(&[b"LCS", b"key1", b"missing"]).await,
+        Reply::bulk("")
+    );
+    client.call(&[b"RPUSH", b"list", b"x"]).await;
+    assert_eq!(
+        client.call(&[b"LCS", b"key1", b"list"]).await,
+        CommandError::Custom("The specified keys must contain string values".into()).into()
+    );
+}
+
+#[test]
+fn test_pexpire_at_and_persist() {
+    let db = Database::new_shared(16);
+    let key = Bytes::from("k");
+    db.set(&key, Bytes::from("v"));
+    assert!(!db.persist(&key));
+    let later = std::time::SystemTime::now() + std::time::Duration::from_secs(60);
+    assert!(db.pexpire_at(&key, later));
+    assert!(db.persist(&key));
+    assert_eq!(db.ttl(&key), -1);
+    assert!(db.pexpire_at(&key, std::time::UNIX_EPOCH));
+    assert_eq!(db.exist(std::slice::from_ref(&key)), 0);
+}
+
+#[tokio::test]
+async fn test_object_encoding() {
+    let client = client();
+    client.set("n", "100").await.unwrap();
+    client.set("short", "hello").await.unwrap();
+    client.set("long", "x".repeat(100)).await.unwrap();
+    for (key, encoding) in [("n", "int"), ("short", "embstr"), ("long", "raw")] {
+        assert_eq!(
+            client.call(&[b"OBJECT", b"ENCODING", key.as_bytes()]).await,
+            Reply::bulk(encoding),
+            "{}",
+            key
+        );
+    }
+
+    // Counters keep the int encoding and read back as their digits
+    assert_eq!(
+        client.call(&[b"INCRBY", b"n", b"-150"]).await,
+        Reply::Integer(-50)
+    );
+    assert_eq!(client.call(&[b"INCR", b"c"]).await, Reply::Integer(1));
+    assert_eq!(
+        client.call(&[b"OBJECT", b"ENCODING", b"c"]).await,
+        Reply::bulk("int")
+    );
+    assert_eq!(client.call(&[b"GET", b"n"]).await, Reply::bulk("-50"));
+    assert_eq!(client.call(&[b"STRLEN", b"n"]).await, Reply::Integer(3));
+    assert_eq!(
+        client.call(&[b"APPEND", b"n", b"0"]).await,
+        Reply::Integer(4)
+    );
+    assert_eq!(client.call(&[b"GET", b"n"]).await, Reply::bulk("-500"));
+    assert_eq!(
+        client.call(&[b"GETRANGE", b"n", b"1", b"-1"]).await,
+        Reply::bulk("500")
+    );
+    // APPEND leaves a raw string, which still counts if it is canonical
+    assert_eq!(
+        client.call(&[b"OBJECT", b"ENCODING", b"n"]).await,
+        Reply::bulk("raw")
+    );
+    assert_eq!(client.call(&[b"INCR", b"n"]).await, Reply::Integer(-499));
+    for value in ["+5", "007", " 5", "-0"] {
+        client.set("odd", value).await.unwrap();
+        assert_eq!(
+            client.call(&[b"INCR", b"odd"]).await,
+            CommandError::InvalidInteger.into(),
+            "{}",
+            value
+        );
+        client.call(&[b"APPEND", b"odd", b""]).await;
+        assert_eq!(
+            client.call(&[b"DECRBY", b"odd", b"1"]).await,
+            CommandError::InvalidInteger.into(),
+            "{}",
+            value
+        );
+    }
+
+    client.call(&[b"RPUSH", b"list", b"x"]).await;
+    assert_eq!(
+        client.call(&[b"OBJECT", b"ENCODING", b"list"]).await,
+        Reply::bulk("quicklist")
+    );
+    assert_eq!(
+        client.call(&[b"OBJECT", b"ENCODING", b"missing"]).await,
+        Reply::Null
+    );
+    assert_eq!(
+        client.call(&[b"OBJECT", b"FREQ", b"n"]).await,
+        CommandError::UnknownSubcommand {
+            command: "OBJECT".into(),
+            subcommand: "FREQ".into()
+        }
+        .into()
+    );
+}
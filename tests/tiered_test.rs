@@ -0,0 +1,189 @@
+// Tests for tiered storage (spilling cold values to disk)
+
+use bytes::Bytes;
+use rudis::commands::Command;
+use rudis::config::Config;
+use rudis::data_structures::ListEnd;
+use rudis::database::tiered::TieredStore;
+use rudis::database::traits::{KeyOp, ListOp, SetOp, StringOp};
+use rudis::database::{Database, SharedDatabase};
+use rudis::networking::resp::{encode, Protocol, RespValue};
+use rudis::persistence::rdb;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn tiered_db(dir: &std::path::Path) -> SharedDatabase {
+    let config = Config {
+        dir: dir.to_string_lossy().to_string(),
+        tiered_storage: true,
+        tiered_max_memory: 2000,
+        tiered_min_value_size: 100,
+        ..Config::default()
+    };
+    let mut db = Database::new(16);
+    db.enable_tiered(TieredStore::open(&config).unwrap());
+    Arc::new(db)
+}
+
+fn info(db: &SharedDatabase) -> String {
+    let cmd = Command::parse(&RespValue::Array(vec![
+        RespValue::BulkString(Bytes::from("INFO")),
+        RespValue::BulkString(Bytes::from("tiered")),
+    ]))
+    .unwrap();
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let reply = rt.block_on(cmd.execute(db));
+    String::from_utf8_lossy(&encode(&reply, Protocol::Resp2)).to_string()
+}
+
+/// Spill every value large enough, once it has been idle long enough
+fn spill_all(db: &SharedDatabase) -> u64 {
+    std::thread::sleep(Duration::from_millis(1100));
+    let store = db.tiered().unwrap();
+    store.set_max_memory(1);
+    db.tiered_cycle().unwrap();
+    store.stats().spilled_keys.load(Ordering::Relaxed)
+}
+
+fn items(count: usize) -> Vec<Bytes> {
+    (0..count)
+        .map(|i| Bytes::from(format!("{:0>100}", i)))
+        .collect()
+}
+
+#[test]
+fn test_cold_values_spill_and_load_back() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = tiered_db(dir.path());
+    let big = Bytes::from("x".repeat(1500));
+    let items: Vec<Bytes> = (0..20)
+        .map(|i| Bytes::from(format!("{:0>100}", i)))
+        .collect();
+    db.set(&Bytes::from("cold"), big.clone());
+    db.rpush(&Bytes::from("hot"), &items).unwrap();
+    db.set(&Bytes::from("small"), Bytes::from("tiny"));
+    db.expire(&Bytes::from("cold"), 100).unwrap();
+
+    // Only keys idle for a while are spilled
+    std::thread::sleep(Duration::from_millis(1100));
+    assert_eq!(db.llen(&Bytes::from("hot")).unwrap(), 20);
+    db.tiered_cycle().unwrap();
+
+    let stats = db.tiered().unwrap().stats();
+    assert_eq!(stats.spilled_keys.load(Ordering::Relaxed), 1);
+    assert_eq!(stats.spills.load(Ordering::Relaxed), 1);
+    assert!(dir.path().join("tiered").join("tiered-0.seg").exists());
+
+    // Keys and metadata stay in memory
+    assert_eq!(db.data_type(&Bytes::from("cold")), "string");
+    assert_eq!(db.exist(&[Bytes::from("cold")]), 1);
+    assert!(db.ttl(&Bytes::from("cold")) > 90);
+    assert_eq!(stats.disk_hits.load(Ordering::Relaxed), 0);
+
+    // The value comes back transparently
+    assert_eq!(db.get(&Bytes::from("cold")).unwrap(), Some(big));
+    assert_eq!(stats.disk_hits.load(Ordering::Relaxed), 1);
+    assert!(info(&db).contains("tiered_disk_hits:1\r\n"));
+}
+
+#[test]
+fn test_snapshot_includes_spilled_values() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = tiered_db(dir.path());
+    let big = Bytes::from("y".repeat(3000));
+    db.set(&Bytes::from("cold"), big.clone());
+    std::thread::sleep(Duration::from_millis(1100));
+    db.tiered_cycle().unwrap();
+    assert_eq!(
+        db.tiered()
+            .unwrap()
+            .stats()
+            .spilled_keys
+            .load(Ordering::Relaxed),
+        1
+    );
+
+    let restored = Database::new(16);
+    rdb::load(&restored, &rdb::dump(&db).unwrap()).unwrap();
+    assert_eq!(restored.get(&Bytes::from("cold")).unwrap(), Some(big));
+    // Snapshotting reads the value without pulling it back into memory
+    assert_eq!(db.data_type(&Bytes::from("cold")), "string");
+    assert!(info(&db).contains("tiered_spilled_keys:1\r\n"));
+}
+
+#[test]
+fn test_tiered_requires_budget() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config {
+        dir: dir.path().to_string_lossy().to_string(),
+        tiered_storage: true,
+        ..Config::default()
+    };
+    assert!(TieredStore::open(&config).is_err());
+    assert!(info(&Database::new_shared(16)).contains("tiered_enabled:0\r\n"));
+}
+
+#[test]
+fn test_list_moves_load_spilled_lists() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = tiered_db(dir.path());
+    let items = items(5);
+    let (source, destination) = (Bytes::from("source"), Bytes::from("destination"));
+    db.rpush(&source, &items).unwrap();
+    db.rpush(&destination, &items).unwrap();
+    assert_eq!(spill_all(&db), 2);
+
+    // LMOVE and RPOPLPUSH, and BLMOVE and BRPOPLPUSH through them
+    assert_eq!(
+        db.lmove(&source, &destination, ListEnd::Left, ListEnd::Right)
+            .unwrap(),
+        Some(items[0].clone())
+    );
+    assert_eq!(db.llen(&source).unwrap(), 4);
+    assert_eq!(db.llen(&destination).unwrap(), 6);
+
+    assert_eq!(spill_all(&db), 2);
+    assert_eq!(
+        db.lmpop(
+            &[Bytes::from("missing"), destination.clone()],
+            ListEnd::Right,
+            2
+        )
+        .unwrap(),
+        Some((
+            destination.clone(),
+            vec![items[0].clone(), items[4].clone()]
+        ))
+    );
+    assert_eq!(db.lrange(&destination, 0, -1).unwrap(), items[..4]);
+}
+
+#[test]
+fn test_set_commands_load_spilled_sets() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = tiered_db(dir.path());
+    let items = items(4);
+    let (a, b) = (Bytes::from("a"), Bytes::from("b"));
+    db.sadd(&a, &items[..3]).unwrap();
+    db.sadd(&b, &items[1..]).unwrap();
+    assert_eq!(spill_all(&db), 2);
+
+    let mut inter = db.sinter(&[a.clone(), b.clone()]).unwrap();
+    inter.sort();
+    assert_eq!(inter, items[1..3]);
+
+    assert_eq!(spill_all(&db), 2);
+    assert_eq!(db.sintercard(&[a.clone(), b.clone()], 0).unwrap(), 2);
+    assert_eq!(spill_all(&db), 2);
+    assert_eq!(
+        db.sinterstore(&Bytes::from("c"), &[a.clone(), b.clone()])
+            .unwrap(),
+        2
+    );
+
+    assert_eq!(spill_all(&db), 3);
+    assert!(db.smove(&a, &b, &items[0]).unwrap());
+    assert_eq!(db.scard(&a).unwrap(), 2);
+    assert_eq!(db.scard(&b).unwrap(), 4);
+}
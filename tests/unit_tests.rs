@@ -3,19 +3,51 @@
 
 use bytes::Bytes;
 use rudis::commands::CommandError;
-use rudis::data_structures::{RedisHash, RedisList, RedisSet, RedisSortedSet, RedisString};
-use rudis::database::{Database, traits::{HashOp, SetOp, StringOp}};
+use rudis::data_structures::{RedisHash, RedisList, RedisSet, RedisString};
+use rudis::database::{
+    traits::{HashOp, SetOp, StringOp},
+    Database,
+};
 
 #[test]
 fn test_redis_string_operations() {
     // Test basic operations
     let rs = RedisString::new(Bytes::from("hello"));
-    // assert_eq!(rs.get(), "hello");
+    assert_eq!(rs.get(), Bytes::from("hello"));
 
     // Test mutable operations
     let mut rs_mut = RedisString::new(Bytes::from("world"));
     rs_mut.set(Bytes::from("updated"));
-    // assert_eq!(rs_mut.get(), "updated");
+    assert_eq!(rs_mut.get(), Bytes::from("updated"));
+}
+
+#[test]
+fn test_redis_string_int_encoding() {
+    // Only the canonical form of an i64 is stored as a number
+    for (value, encoding) in [
+        ("12345", "int"),
+        ("-9223372036854775808", "int"),
+        ("0", "int"),
+        ("012", "embstr"),
+        ("+1", "embstr"),
+        ("-0", "embstr"),
+        ("9223372036854775808", "embstr"),
+        (" 1", "embstr"),
+    ] {
+        let rs = RedisString::new(Bytes::from(value));
+        assert_eq!(rs.encoding(), encoding, "{}", value);
+        assert_eq!(rs.get(), Bytes::from(value));
+        // Counters accept exactly the values stored as numbers
+        assert_eq!(rs.integer().is_some(), encoding == "int", "{}", value);
+    }
+    assert_eq!(
+        RedisString::new(Bytes::from("x".repeat(45))).encoding(),
+        "raw"
+    );
+
+    let rs = RedisString::from_integer(-1200);
+    assert_eq!(rs.integer(), Some(-1200));
+    assert_eq!(rs.get(), Bytes::from("-1200"));
 }
 
 #[test]
@@ -41,6 +73,29 @@ fn test_redis_list_operations() {
     assert_eq!(list.pop(), None);
 }
 
+#[test]
+fn test_redis_list_trim_and_remove() {
+    let mut list = RedisList::new();
+    for item in ["a", "b", "a", "c", "a", "d"] {
+        list.push(Bytes::from(item));
+    }
+    let items = |list: &RedisList| list.iter().cloned().collect::<Vec<_>>();
+
+    assert_eq!(list.positions(&Bytes::from("a"), 1, 0, 0), vec![0, 2, 4]);
+    assert_eq!(list.positions(&Bytes::from("a"), -2, 1, 0), vec![2]);
+    assert_eq!(list.positions(&Bytes::from("a"), 1, 0, 2), vec![0]);
+
+    assert_eq!(list.remove(&Bytes::from("a"), -2), 2);
+    assert_eq!(items(&list), vec!["a", "b", "c", "d"]);
+
+    list.trim(1, -2);
+    assert_eq!(items(&list), vec!["b", "c"]);
+    list.trim(-100, 100);
+    assert_eq!(items(&list), vec!["b", "c"]);
+    list.trim(1, 0);
+    assert!(list.is_empty());
+}
+
 #[test]
 fn test_redis_hash_operations() {
     let mut hash = RedisHash::new();
@@ -103,17 +158,20 @@ fn test_redis_set_operations() {
 
 #[test]
 fn test_database_operations() {
-    let mut db = Database::new(16);
+    let db = Database::new(16);
 
     // Test string operations
     db.set(&Bytes::from("key1"), Bytes::from("value1"));
-    assert_eq!(db.get(&Bytes::from("key1")), Some(Bytes::from("value1")));
-    assert_eq!(db.get(&Bytes::from("nonexistent")), None);
+    assert_eq!(
+        db.get(&Bytes::from("key1")).unwrap(),
+        Some(Bytes::from("value1"))
+    );
+    assert_eq!(db.get(&Bytes::from("nonexistent")).unwrap(), None);
 
     // Test del
-    assert_eq!(db.del(&vec![Bytes::from("key1")]), 1);
-    assert_eq!(db.get(&Bytes::from("key1")), None);
-    assert_eq!(db.del(&vec![Bytes::from("nonexistent")]), 0);
+    assert_eq!(db.del(&[Bytes::from("key1")]), 1);
+    assert_eq!(db.get(&Bytes::from("key1")).unwrap(), None);
+    assert_eq!(db.del(&[Bytes::from("nonexistent")]), 0);
 
     // Test numeric operations
     assert_eq!(db.incr(&Bytes::from("counter")), Ok(1));
@@ -130,27 +188,29 @@ fn test_database_operations() {
 
     // Test append and str_len
     assert_eq!(
-        db.append(&Bytes::from("append_key"), Bytes::from("hello")),
+        db.append(&Bytes::from("append_key"), Bytes::from("hello"))
+            .unwrap(),
         5
     );
-    assert_eq!(db.str_len(&Bytes::from("append_key")), 5);
+    assert_eq!(db.str_len(&Bytes::from("append_key")).unwrap(), 5);
 
     assert_eq!(
-        db.append(&Bytes::from("append_key"), Bytes::from(" world")),
+        db.append(&Bytes::from("append_key"), Bytes::from(" world"))
+            .unwrap(),
         11
     );
-    assert_eq!(db.str_len(&Bytes::from("append_key")), 11);
+    assert_eq!(db.str_len(&Bytes::from("append_key")).unwrap(), 11);
     assert_eq!(
-        db.get(&Bytes::from("append_key")),
+        db.get(&Bytes::from("append_key")).unwrap(),
         Some(Bytes::from("hello world"))
     );
 
-    assert_eq!(db.str_len(&Bytes::from("nonexistent")), 0);
+    assert_eq!(db.str_len(&Bytes::from("nonexistent")).unwrap(), 0);
 }
 
 #[test]
 fn test_database_hash_operations() {
-    let mut db = Database::new(16);
+    let db = Database::new(16);
 
     // Test hset on new hash
     assert_eq!(
@@ -195,9 +255,11 @@ fn test_database_hash_operations() {
     );
 
     // Test hdel
-    assert!(db.hdel(&Bytes::from("user"), &Bytes::from("age")));
+    assert!(db.hdel(&Bytes::from("user"), &Bytes::from("age")).unwrap());
     assert_eq!(db.hget(&Bytes::from("user"), &Bytes::from("age")), Ok(None));
-    assert!(!db.hdel(&Bytes::from("user"), &Bytes::from("nonexistent")));
+    assert!(!db
+        .hdel(&Bytes::from("user"), &Bytes::from("nonexistent"))
+        .unwrap());
 
     // Test hget_all
     let all_fields = db.hget_all(&Bytes::from("user")).unwrap();
@@ -211,7 +273,7 @@ fn test_database_hash_operations() {
 
 #[test]
 fn test_database_type_conflicts() {
-    let mut db = Database::new(16);
+    let db = Database::new(16);
 
     // Set a string value
     db.set(&Bytes::from("mykey"), Bytes::from("string_value"));
@@ -249,11 +311,14 @@ fn test_database_type_conflicts() {
 
 #[test]
 fn test_database_edge_cases() {
-    let mut db = Database::new(16);
+    let db = Database::new(16);
 
     // Test operations on empty keys
     db.set(&Bytes::from(""), Bytes::from("empty_key"));
-    assert_eq!(db.get(&Bytes::from("")), Some(Bytes::from("empty_key")));
+    assert_eq!(
+        db.get(&Bytes::from("")).unwrap(),
+        Some(Bytes::from("empty_key"))
+    );
 
     // Test large numbers
     assert_eq!(
@@ -291,20 +356,29 @@ fn test_database_edge_cases() {
 
 #[test]
 fn test_database_set_operations() {
-    let mut db = Database::new(16);
+    let db = Database::new(16);
 
     // Test sadd on new set
-    assert_eq!(db.sadd(&Bytes::from("myset"), &[Bytes::from("member1")]), 1);
+    assert_eq!(
+        db.sadd(&Bytes::from("myset"), &[Bytes::from("member1")])
+            .unwrap(),
+        1
+    );
     assert_eq!(
         db.sadd(
             &Bytes::from("myset"),
             &[Bytes::from("member2"), Bytes::from("member3")]
-        ),
+        )
+        .unwrap(),
         2
     );
 
     // Test sadd on existing members
-    assert_eq!(db.sadd(&Bytes::from("myset"), &[Bytes::from("member1")]), 0);
+    assert_eq!(
+        db.sadd(&Bytes::from("myset"), &[Bytes::from("member1")])
+            .unwrap(),
+        0
+    );
 
     // Test smembers
     let members = db.smembers(&Bytes::from("myset")).unwrap();
@@ -314,28 +388,42 @@ fn test_database_set_operations() {
     assert!(members.contains(&Bytes::from("member3")));
 
     // Test scard
-    assert_eq!(db.scard(&Bytes::from("myset")), 3);
+    assert_eq!(db.scard(&Bytes::from("myset")).unwrap(), 3);
 
     // Test sismember
-    assert!(db.sismember(&Bytes::from("myset"), &Bytes::from("member1")));
-    assert!(!db.sismember(&Bytes::from("myset"), &Bytes::from("nonexistent")));
+    assert!(db
+        .sismember(&Bytes::from("myset"), &Bytes::from("member1"))
+        .unwrap());
+    assert!(!db
+        .sismember(&Bytes::from("myset"), &Bytes::from("nonexistent"))
+        .unwrap());
 
     // Test srem
-    assert_eq!(db.srem(&Bytes::from("myset"), &[Bytes::from("member2")]), 1);
-    assert_eq!(db.scard(&Bytes::from("myset")), 2);
-    assert!(!db.sismember(&Bytes::from("myset"), &Bytes::from("member2")));
+    assert_eq!(
+        db.srem(&Bytes::from("myset"), &[Bytes::from("member2")])
+            .unwrap(),
+        1
+    );
+    assert_eq!(db.scard(&Bytes::from("myset")).unwrap(), 2);
+    assert!(!db
+        .sismember(&Bytes::from("myset"), &Bytes::from("member2"))
+        .unwrap());
 
     // Test srem on non-existent members
     assert_eq!(
-        db.srem(&Bytes::from("myset"), &[Bytes::from("nonexistent")]),
+        db.srem(&Bytes::from("myset"), &[Bytes::from("nonexistent")])
+            .unwrap(),
         0
     );
 
     // Test operations on non-existent set
-    assert_eq!(db.scard(&Bytes::from("nonexistent")), 0);
-    assert!(!db.sismember(&Bytes::from("nonexistent"), &Bytes::from("anything")));
+    assert_eq!(db.scard(&Bytes::from("nonexistent")).unwrap(), 0);
+    assert!(!db
+        .sismember(&Bytes::from("nonexistent"), &Bytes::from("anything"))
+        .unwrap());
     assert_eq!(
-        db.srem(&Bytes::from("nonexistent"), &[Bytes::from("anything")]),
+        db.srem(&Bytes::from("nonexistent"), &[Bytes::from("anything")])
+            .unwrap(),
         0
     );
 